@@ -1,9 +1,21 @@
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
-#[derive(Copy, Clone, Debug)]
+/// Pressure of input devices that don't report any, like mice.
+pub const FULL_PRESSURE: f32 = 1.0;
+
+const fn full_pressure() -> f32 {
+    FULL_PRESSURE
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Dot2D {
     pub position: Point2<f32>,
     pub radius: f32,
+    /// Normalized pen or touch force, 0 to 1.
+    #[serde(default = "full_pressure")]
+    pub pressure: f32,
 }
 
 impl fmt::Display for Dot2D {
@@ -18,6 +30,7 @@ type Dot2Dx4 = [Dot2D; 4];
 pub struct StrokeDot2D {
     pub position: Point2<f32>,
     pub radius: f32,
+    pub pressure: f32,
     pub is_last: bool,
 }
 
@@ -32,6 +45,7 @@ impl From<StrokeDot2D> for Dot2D {
         Dot2D {
             position: value.position,
             radius: value.radius,
+            pressure: value.pressure,
         }
     }
 }
@@ -55,6 +69,7 @@ pub const fn catmull_rom_to_bezier(dots: Dot2Dx4) -> Dot2Dx4 {
                 y: p2.position.y * i6 + p1.position.y - p0.position.y * i6,
             },
             radius: p2.radius * i6 + p1.radius - p0.radius * i6,
+            pressure: p2.pressure * i6 + p1.pressure - p0.pressure * i6,
         },
         Dot2D {
             position: Point2 {
@@ -62,6 +77,7 @@ pub const fn catmull_rom_to_bezier(dots: Dot2Dx4) -> Dot2Dx4 {
                 y: p3.position.y * -i6 + p2.position.y - p1.position.y * -i6,
             },
             radius: p3.radius * -i6 + p2.radius - p1.radius * -i6,
+            pressure: p3.pressure * -i6 + p2.pressure - p1.pressure * -i6,
         },
         p2,
     ]
@@ -111,6 +127,15 @@ impl DistanceFilter {
         }
     }
 
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance;
+    }
+
+    /// Forgets the last allowed point, the next one always passes.
+    pub fn reset(&mut self) {
+        self.current_point = None;
+    }
+
     pub fn filter_by_distance(&mut self, next: Point2<f32>) -> Option<Point2<f32>> {
         if let Some(current_point_val) = self.current_point {
             let diff = current_point_val.sub_element_wise(next);
@@ -164,18 +189,22 @@ mod tests {
             Dot2D {
                 position: Point2 { x: 584.5, y: 630.0 },
                 radius: 2.0,
+                pressure: FULL_PRESSURE,
             },
             Dot2D {
                 position: Point2 { x: 582.5, y: 644.0 },
                 radius: 2.0,
+                pressure: FULL_PRESSURE,
             },
             Dot2D {
                 position: Point2 { x: 581.5, y: 649.0 },
                 radius: 2.0,
+                pressure: FULL_PRESSURE,
             },
             Dot2D {
                 position: Point2 { x: 581.5, y: 651.0 },
                 radius: 2.0,
+                pressure: FULL_PRESSURE,
             },
         ];
 
@@ -5,7 +5,7 @@ use crate::prelude::*;
 pub type Dimension2D = Vector2<f32>;
 
 /// Linearly interpolate between two `Dot2D`s.
-/// Interpolates the position and the radius.
+/// Interpolates the position, the radius and the pressure.
 #[must_use]
 pub const fn lerp_dot_2d(dot1: Dot2D, dot2: Dot2D, k: f32) -> Dot2D {
     Dot2D {
@@ -14,6 +14,7 @@ pub const fn lerp_dot_2d(dot1: Dot2D, dot2: Dot2D, k: f32) -> Dot2D {
             y: dot1.position.y + (dot2.position.y - dot1.position.y) * k,
         },
         radius: dot1.radius + (dot2.radius - dot1.radius) * k,
+        pressure: dot1.pressure + (dot2.pressure - dot1.pressure) * k,
     }
 }
 
@@ -20,7 +20,15 @@ impl PointProcessor {
         }
     }
 
-    pub fn process_point(&mut self, point: StrokeDot2D) -> Vec<Point2<f32>> {
+    /// Spaces the following dabs `step` apart.
+    pub fn set_step(&mut self, step: f32) {
+        self.step = step;
+        self.filter.set_distance(step);
+    }
+
+    /// The dabs spaced `step` apart along the curve through the last points,
+    /// with radius and pressure interpolated between them.
+    pub fn process_point(&mut self, point: StrokeDot2D) -> Vec<Dot2D> {
         if self.dots.len() < POINT_PROCESSOR_SIZE {
             self.dots.push_back(point);
             return vec![];
@@ -31,7 +39,7 @@ impl PointProcessor {
         let diff_from_last_point = self.dots[3].position.sub_element_wise(new_point.position);
         let diff_square_len = sqr_len(diff_from_last_point);
 
-        let mut out_dots: Vec<Point2<f32>> = vec![];
+        let mut out_dots: Vec<Dot2D> = vec![];
         if new_point.is_last || diff_square_len > self.step.powi(2) {
             let repeat: usize = if new_point.is_last { 3 } else { 1 };
 
@@ -56,9 +64,9 @@ impl PointProcessor {
                 #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
                 let dots = eval_bezier(bezier, dots_count.floor() as usize);
 
-                let dots: Vec<Point2<f32>> = dots
+                let dots: Vec<Dot2D> = dots
                     .into_iter()
-                    .filter_map(|point| self.filter.filter_by_distance(point.position))
+                    .filter(|dot| self.filter.filter_by_distance(dot.position).is_some())
                     .collect();
                 out_dots.extend(dots);
             }
@@ -67,7 +75,93 @@ impl PointProcessor {
         out_dots
     }
 
+    /// Ends the stroke, the first dab of the next one is never filtered out.
     pub fn clear(&mut self) {
         self.dots.clear();
+        self.filter.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke_dot(x: f32, pressure: f32, is_last: bool) -> StrokeDot2D {
+        StrokeDot2D {
+            position: Point2 { x, y: 0.0 },
+            radius: 10.0 * pressure,
+            pressure,
+            is_last,
+        }
+    }
+
+    #[test]
+    fn pressure_is_interpolated_along_the_curve() {
+        let mut processor = PointProcessor::new(1.0);
+        let mut dots = vec![];
+        for i in 0..8u8 {
+            let x = f32::from(i) * 10.0;
+            let pressure = 0.2 + f32::from(i) * 0.1;
+            dots.extend(processor.process_point(stroke_dot(x, pressure, false)));
+        }
+        dots.extend(processor.process_point(stroke_dot(80.0, 1.0, true)));
+
+        assert!(dots.len() > 20);
+        // between the pressure of the input points they lie between
+        for dot in &dots {
+            let expected = 0.2 + dot.position.x / 100.0;
+            assert!(
+                (dot.pressure - expected).abs() < 0.02,
+                "pressure {} at x {}",
+                dot.pressure,
+                dot.position.x
+            );
+            assert!((dot.radius - dot.pressure * 10.0).abs() < 0.2);
+        }
+        assert!(
+            dots.windows(2)
+                .all(|pair| pair[1].pressure >= pair[0].pressure - f32::EPSILON)
+        );
+    }
+
+    fn straight_stroke(processor: &mut PointProcessor) -> Vec<Dot2D> {
+        let mut dots = vec![];
+        for i in 0..10u8 {
+            dots.extend(processor.process_point(stroke_dot(f32::from(i) * 10.0, 1.0, false)));
+        }
+        dots.extend(processor.process_point(stroke_dot(100.0, 1.0, true)));
+        processor.clear();
+        dots
+    }
+
+    #[test]
+    fn dabs_are_spaced_a_step_apart() {
+        let mut processor = PointProcessor::new(1.0);
+        let dense = straight_stroke(&mut processor);
+        processor.set_step(8.0);
+        let sparse = straight_stroke(&mut processor);
+
+        assert!(
+            sparse.len() * 4 < dense.len(),
+            "{} vs {}",
+            sparse.len(),
+            dense.len()
+        );
+        for pair in sparse.windows(2) {
+            let gap = pair[1].position.x - pair[0].position.x;
+            assert!(gap > 8.0 && gap < 9.0, "gap {gap}");
+        }
+    }
+
+    #[test]
+    fn strokes_start_with_a_dab_wherever_the_last_ended() {
+        // wider than the stroke, each one is a single dab
+        let mut processor = PointProcessor::new(150.0);
+        let first = straight_stroke(&mut processor);
+        let second = straight_stroke(&mut processor);
+        assert_eq!(
+            first.first().map(|dot| dot.position),
+            second.first().map(|dot| dot.position)
+        );
     }
 }
@@ -1,3 +1,4 @@
+use batteries::prelude::Dot2D;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 use winit::{
@@ -11,18 +12,29 @@ use winit::{
 
 use crate::{
     constants::WINDOW_SIZE,
-    document::{Document, loader::LoadedDocument},
+    document::{
+        Artboard, ArtboardId, Document, Layer, LayerNode, NodeId,
+        artboard_size::{Anchor, new_artboard_position},
+        command::Command,
+        export::ExportOptions,
+        loader::LoadedDocument,
+    },
+    editor_state::BrushProperties,
     event_sender::EventSender,
-    events::CustomEvent,
+    events::{ArtboardEvent, CustomEvent},
     renderer::{
         egui_context::EguiContext, frame_context::FrameContext, render_context::RenderContext,
     },
     resource::{Res, ResMut, Resource, ResourceContext},
     resources::{
         brush_point_queue::{BrushPointData, BrushPointQueue},
+        brush_presets::{BrushPreset, BrushPresets},
         brush_preview_state::BrushPreviewState,
-        document_state::{DocumentState, GpuOp},
+        document_state::DocumentState,
+        history::{DEFAULT_HISTORY_BUDGET_MB, History},
         input_system::InputSystem,
+        launch_options::LaunchOptions,
+        layer_thumbnails::LayerThumbnails,
         scene_renderer::SceneRenderer,
         stroke_state::StrokeState,
     },
@@ -39,6 +51,21 @@ use std::{
 pub struct WindowResource(pub Arc<winit::window::Window>);
 impl Resource for WindowResource {}
 
+/// `History::undo` or `History::redo`.
+type HistoryStep =
+    fn(&mut History, &wgpu::Device, &wgpu::Queue, &mut SceneRenderer, &mut DocumentState) -> bool;
+
+/// Writes every artboard of a document into a dir in some layered format, see `openraster::export_openraster`.
+#[cfg(not(target_arch = "wasm32"))]
+type LayeredExport = fn(
+    &std::path::Path,
+    &str,
+    &wgpu::Device,
+    &wgpu::Queue,
+    &mut SceneRenderer,
+    &crate::document::Document,
+) -> anyhow::Result<Vec<std::path::PathBuf>>;
+
 pub struct App {
     resources: HashMap<TypeId, Arc<RwLock<dyn Any + Send + Sync>>>,
     startup_systems: Vec<Box<dyn System>>,
@@ -106,11 +133,846 @@ impl App {
         let mut app_state = State::new(window_size.0, window_size.1);
         app_state.camera.center_on(loaded.document.get_center());
 
+        let history_budget_mb = self
+            .read::<LaunchOptions>()
+            .map_or(DEFAULT_HISTORY_BUDGET_MB, |options| {
+                options.history_budget_mb
+            });
+
         self.insert_resource(scene_renderer)
             .insert_resource(DocumentState::new(loaded.document))
+            .insert_resource(History::new(history_budget_mb * 1024 * 1024))
+            .insert_resource(LayerThumbnails::new())
             .insert_resource(app_state)
             .insert_resource(FrameContext::new());
     }
+
+    /// Loads the launch document, or the session an interrupted run journaled for it when `--recovery restore` asks.
+    /// A journal nobody restored or discarded is kept and offered by `InterruptedSession`,
+    /// with autosave journaling next to it meanwhile.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_launch_document(&mut self, max_texture_dim: u32) -> LoadedDocument {
+        use crate::{
+            constants::AUTOSAVE_INTERVAL,
+            document::{
+                loader::load_document,
+                recovery::{
+                    Journal, discard_journal, has_journal, keep_later_journal, load_journal,
+                    next_recovery_dir, recovery_dir,
+                },
+            },
+            resources::{
+                autosave::Autosave,
+                launch_options::{InterruptedSession, Recovery},
+            },
+        };
+
+        let (document_name, recovery) = self.read::<LaunchOptions>().map_or_else(
+            || ("default".to_string(), Recovery::Ask),
+            |options| (options.document.clone(), options.recovery),
+        );
+        let journal_dir = recovery_dir(&document_name);
+        let next_dir = next_recovery_dir(&document_name);
+        match keep_later_journal(&journal_dir, &next_dir) {
+            Ok(true) => log::warn!(
+                "'{document_name}' was interrupted again before the earlier session was restored, \
+                keeping the later one"
+            ),
+            Ok(false) => {}
+            Err(error) => log::error!("failed to settle the recovery journals: {error:#}"),
+        }
+
+        let mut recovered = None;
+        match recovery {
+            Recovery::Ask => {}
+            Recovery::Restore => match load_journal(&journal_dir, max_texture_dim) {
+                Ok(Some(loaded)) => {
+                    log::info!("restored the interrupted session of '{document_name}'");
+                    recovered = Some(loaded);
+                }
+                Ok(None) => log::warn!("'{document_name}' has no interrupted session to restore"),
+                Err(error) => log::error!(
+                    "failed to restore the interrupted session of '{document_name}': {error:#}"
+                ),
+            },
+            Recovery::Discard => {
+                if let Err(error) = discard_journal(&journal_dir) {
+                    log::error!("failed to discard the recovery journal: {error:#}");
+                }
+            }
+        }
+
+        let interrupted = recovered.is_none() && has_journal(&journal_dir);
+        let autosave_dir = if interrupted {
+            log::warn!("a session of '{document_name}' was interrupted before it was saved");
+            next_dir
+        } else {
+            journal_dir
+        };
+        self.insert_resource(Autosave::new(Journal::new(autosave_dir), AUTOSAVE_INTERVAL))
+            .insert_resource(InterruptedSession(
+                interrupted.then(|| document_name.clone()),
+            ));
+
+        recovered.unwrap_or_else(|| {
+            load_document(&document_name, max_texture_dim).unwrap_or_else(|error| {
+                log::warn!(
+                    "failed to load document '{document_name}': {error:#}; \
+                    falling back to the default document"
+                );
+
+                LoadedDocument {
+                    document: Document::default_document(),
+                    layer_pixels: HashMap::new(),
+                }
+            })
+        })
+    }
+
+    /// Starts recording and playing back input sessions as `--record` and `--replay` ask.
+    /// Replayed events are relayed like live ones, so a replay can be recorded again.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_sessions(&self, window_size: (u32, u32)) {
+        use crate::session::{Recorder, SESSION_VERSION, SessionHeader, load_session, replay};
+
+        let (Some(options), Some(event_sender)) =
+            (self.read::<LaunchOptions>(), self.read::<EventSender>())
+        else {
+            return;
+        };
+        let header = SessionHeader {
+            version: SESSION_VERSION,
+            document: options.document.clone(),
+            window_size: [window_size.0, window_size.1],
+        };
+
+        if let Some(path) = &options.record {
+            match Recorder::create(path, &header) {
+                Ok(recorder) => {
+                    log::info!("recording the session to {}", path.display());
+                    event_sender.start_recording(recorder);
+                }
+                Err(error) => log::error!("failed to start recording the session: {error:#}"),
+            }
+        }
+
+        if let Some(path) = &options.replay {
+            match load_session(path) {
+                Ok(session) => {
+                    let recorded = &session.header;
+                    if recorded.document != header.document
+                        || recorded.window_size != header.window_size
+                    {
+                        log::warn!(
+                            "{} was recorded on '{}' in a {}x{} window, its strokes may land elsewhere",
+                            path.display(),
+                            recorded.document,
+                            recorded.window_size[0],
+                            recorded.window_size[1],
+                        );
+                    }
+                    replay(session.events, event_sender.clone());
+                }
+                Err(error) => log::error!("failed to replay the session: {error:#}"),
+            }
+        }
+    }
+
+    /// Reopens the interrupted session `InterruptedSession` offers in place of the open document.
+    /// Autosave carries on over its journal, which stays until the first snapshot replaces it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore_interrupted_session(&self) {
+        use crate::{
+            document::recovery::{load_journal, recovery_dir},
+            resources::{autosave::Autosave, launch_options::InterruptedSession},
+        };
+
+        let Some(document_name) = self
+            .read::<InterruptedSession>()
+            .and_then(|session| session.0.clone())
+        else {
+            return;
+        };
+        let Some(max_texture_dim) = self
+            .read::<RenderContext>()
+            .map(|render_ctx| render_ctx.device.limits().max_texture_dimension_2d)
+        else {
+            return;
+        };
+        let journal_dir = recovery_dir(&document_name);
+        match load_journal(&journal_dir, max_texture_dim) {
+            Ok(Some(loaded)) => {
+                self.replace_document(loaded);
+                if let Some(mut autosave) = self.write::<Autosave>() {
+                    autosave.take_over(journal_dir);
+                }
+                log::info!("restored the interrupted session of '{document_name}'");
+            }
+            Ok(None) => log::warn!("'{document_name}' has no interrupted session to restore"),
+            Err(error) => {
+                log::error!(
+                    "failed to restore the interrupted session of '{document_name}': {error:#}"
+                );
+                return;
+            }
+        }
+        if let Some(mut session) = self.write::<InterruptedSession>() {
+            session.0 = None;
+        }
+    }
+
+    /// Deletes the interrupted session `InterruptedSession` offers, autosave takes its place.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn discard_interrupted_session(&self) {
+        use crate::{
+            document::recovery::recovery_dir,
+            resources::{autosave::Autosave, launch_options::InterruptedSession},
+        };
+
+        let Some(mut session) = self.write::<InterruptedSession>() else {
+            return;
+        };
+        let Some(document_name) = session.0.take() else {
+            return;
+        };
+        if let Some(mut autosave) = self.write::<Autosave>() {
+            autosave.move_to(recovery_dir(&document_name));
+        }
+        log::info!("discarded the interrupted session of '{document_name}'");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn restore_interrupted_session(&self) {}
+
+    #[cfg(target_arch = "wasm32")]
+    fn discard_interrupted_session(&self) {}
+
+    /// Swaps the open document for `loaded`, dropping the undo history of the one it replaces.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn replace_document(&self, loaded: LoadedDocument) {
+        let (
+            Some(render_ctx),
+            Some(mut scene),
+            Some(mut doc),
+            Some(mut history),
+            Some(mut thumbnails),
+            Some(mut state),
+        ) = (
+            self.read::<RenderContext>(),
+            self.write::<SceneRenderer>(),
+            self.write::<DocumentState>(),
+            self.write::<History>(),
+            self.write::<LayerThumbnails>(),
+            self.write::<State>(),
+        )
+        else {
+            return;
+        };
+        scene.hydrate(&render_ctx.device, &render_ctx.queue, &loaded);
+        state.camera.center_on(loaded.document.get_center());
+        *doc = DocumentState::new(loaded.document);
+        history.clear();
+        *thumbnails = LayerThumbnails::new();
+    }
+
+    /// Drops the recovery journal on a clean exit, only interrupted sessions leave one behind.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn discard_recovery(&self) {
+        if let Some(mut autosave) = self.write::<crate::resources::autosave::Autosave>() {
+            autosave.discard();
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn discard_recovery(&self) {}
+
+    /// Undoes or redoes one step through `step`, leaving the layers it changed to autosave.
+    /// Edits applied since the last frame are recorded first, so they are what the step takes back.
+    fn step_history(&self, step: HistoryStep) {
+        let (Some(render_ctx), Some(mut scene), Some(mut doc), Some(mut history)) = (
+            self.read::<RenderContext>(),
+            self.write::<SceneRenderer>(),
+            self.write::<DocumentState>(),
+            self.write::<History>(),
+        ) else {
+            return;
+        };
+        let (device, queue) = (&render_ctx.device, &render_ctx.queue);
+
+        history.record_edits(device, queue, &scene, &mut doc);
+        doc.flush_gpu_ops(device, queue, &mut scene);
+        step(&mut history, device, queue, &mut scene, &mut doc);
+    }
+
+    fn move_camera(&self, position: cgmath::Point2<f32>) {
+        if let Some(mut state) = self.write::<State>() {
+            let delta = position - state.pan_offset;
+            state.pan_offset = position;
+            state.camera.pan_screen_delta(delta);
+        }
+    }
+
+    fn zoom_camera(&self, delta: f32) {
+        if let (Some(mut state), Some(mut preview_state)) =
+            (self.write::<State>(), self.write::<BrushPreviewState>())
+        {
+            state.camera.zoom_by(delta);
+            // Update brush preview scale to match viewport zoom
+            preview_state.update_scale(delta);
+        }
+    }
+
+    fn update_brush(&self, properties: BrushProperties) {
+        if let Some(mut state) = self.write::<State>() {
+            state.editor.update_brush(properties);
+        }
+    }
+
+    /// Keeps the current brush as the preset `name`, written back to the presets file.
+    fn save_brush_preset(&self, name: String) {
+        let (Some(state), Some(mut presets)) = (self.read::<State>(), self.write::<BrushPresets>())
+        else {
+            return;
+        };
+        presets.insert(BrushPreset {
+            name,
+            brush: state.editor.brush_properties,
+            settings: state.editor.brush_settings,
+        });
+        if let Err(error) = presets.persist() {
+            log::error!("failed to save brush presets: {error:#}");
+        }
+    }
+
+    fn delete_brush_preset(&self, name: &str) {
+        let Some(mut presets) = self.write::<BrushPresets>() else {
+            return;
+        };
+        if !presets.remove(name) {
+            log::warn!("there is no brush preset '{name}'");
+            return;
+        }
+        if let Err(error) = presets.persist() {
+            log::error!("failed to save brush presets: {error:#}");
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_brush_presets(&self, path: &std::path::Path) {
+        use crate::resources::brush_presets::read_presets;
+
+        let Some(mut presets) = self.write::<BrushPresets>() else {
+            return;
+        };
+        let imported = match read_presets(path) {
+            Ok(imported) => imported,
+            Err(error) => {
+                log::error!("failed to import brush presets: {error:#}");
+                return;
+            }
+        };
+        let count = imported.len();
+        for preset in imported {
+            presets.insert(preset);
+        }
+        log::info!("imported {count} brush presets from {}", path.display());
+        if let Err(error) = presets.persist() {
+            log::error!("failed to save brush presets: {error:#}");
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_brush_presets(&self, path: &std::path::Path) {
+        use crate::resources::brush_presets::write_presets;
+
+        let Some(presets) = self.read::<BrushPresets>() else {
+            return;
+        };
+        match write_presets(path, presets.presets()) {
+            Ok(()) => log::info!("exported brush presets to {}", path.display()),
+            Err(error) => log::error!("failed to export brush presets: {error:#}"),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn import_brush_presets(&self, _path: &std::path::Path) {
+        log::warn!("importing brush presets is not supported on the web yet");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_brush_presets(&self, _path: &std::path::Path) {
+        log::warn!("exporting brush presets is not supported on the web yet");
+    }
+
+    /// Queues a dab of the stroke, starting a begun one on the target its first dab picks.
+    fn queue_brush_point(&self, dot: Dot2D) {
+        if let (Some(mut state), Some(doc), Some(mut stroke_state), Some(mut queue)) = (
+            self.write::<State>(),
+            self.read::<DocumentState>(),
+            self.write::<StrokeState>(),
+            self.write::<BrushPointQueue>(),
+        ) {
+            if stroke_state.awaiting_first_dab() {
+                let world = state.camera.screen_to_world(dot.position);
+                match state.editor.stroke_target(&doc.document, world) {
+                    Some((_, layer_id)) if doc.document.is_layer_locked(layer_id) => {
+                        log::warn!("layer {layer_id:?} is locked");
+                        stroke_state.end();
+                    }
+                    Some(target) => {
+                        let brush = &state.editor.brush_properties;
+                        stroke_state.start(
+                            target,
+                            brush.mode,
+                            brush.tip,
+                            state.editor.brush_settings,
+                        );
+                    }
+                    None => {}
+                }
+            }
+            // Raw screen coordinates and camera state at enqueue time are enough for coordinate transformation later.
+            queue.write(BrushPointData {
+                dot,
+                color: state.editor.brush_properties.color.to_rgba_array(),
+                camera: state.camera,
+                target: stroke_state.active_target(),
+            });
+        }
+    }
+
+    /// Applies an undoable edit to the document, see `DocumentState::apply`.
+    fn apply(&self, command: Command) {
+        if let Some(mut doc) = self.write::<DocumentState>() {
+            doc.apply(command);
+        }
+    }
+
+    /// Applies an edit of `node` unless it is locked, see `DocumentState::apply_unless_locked`.
+    fn apply_unless_locked(&self, node: NodeId, command: Command) {
+        if let Some(mut doc) = self.write::<DocumentState>() {
+            doc.apply_unless_locked(node, command);
+        }
+    }
+
+    /// Deletes the node, the active layer for `None`, unless it is locked.
+    fn delete_node(&self, node: Option<NodeId>) {
+        if let (Some(mut doc), Some(state)) = (self.write::<DocumentState>(), self.read::<State>())
+            && let Some(node) = node.or_else(|| active_node(&state, &doc.document))
+        {
+            doc.apply_unless_locked(node, Command::RemoveNode { node });
+        }
+    }
+
+    /// Adds a blank layer, a vector one if `vector`, right above the active one or on top of the first artboard, and makes it active.
+    fn add_layer(&self, vector: bool) {
+        let (Some(mut doc), Some(mut state)) =
+            (self.write::<DocumentState>(), self.write::<State>())
+        else {
+            return;
+        };
+        let document = &doc.document;
+        let place = state
+            .editor
+            .active_layer(document)
+            .and_then(|(artboard_id, layer_id)| {
+                let artboard = document.artboard(artboard_id)?;
+                let (parent, index) = artboard.locate(NodeId::Layer(layer_id))?;
+                Some((artboard, parent, index + 1))
+            })
+            .or_else(|| {
+                let artboard = document.artboards.first()?;
+                Some((artboard, None, usize::MAX))
+            });
+        let Some((artboard, parent, index)) = place else {
+            log::warn!("no artboard to add a layer to");
+            return;
+        };
+        let artboard_id = artboard.id;
+        let number = artboard.iter_layers().count() + 1;
+
+        let layer_id = doc.document.alloc_layer_id();
+        let layer = if vector {
+            Layer::new_vector(layer_id, format!("Vector {number}"))
+        } else {
+            Layer::new(layer_id, format!("Layer {number}"))
+        };
+        doc.apply(Command::InsertNode {
+            artboard_id,
+            parent,
+            index,
+            node: layer.into(),
+        });
+        state.editor.select_layer(layer_id);
+    }
+
+    /// Copies the node, the active layer for `None`. A copied layer becomes the active one.
+    fn duplicate_node(&self, node: Option<NodeId>) {
+        let (Some(mut doc), Some(mut state)) =
+            (self.write::<DocumentState>(), self.write::<State>())
+        else {
+            return;
+        };
+        let Some(node) = node.or_else(|| active_node(&state, &doc.document)) else {
+            return;
+        };
+        doc.apply(Command::DuplicateNode { node });
+
+        // the copy sits right above
+        let copy = doc
+            .document
+            .locate(node)
+            .and_then(|(artboard_id, parent, index)| {
+                let children = doc.document.artboard(artboard_id)?.children(parent)?;
+                children.get(index + 1).map(LayerNode::id)
+            });
+        if let Some(NodeId::Layer(layer_id)) = copy {
+            state.editor.select_layer(layer_id);
+        }
+    }
+
+    fn select_adjacent_layer(&self, step: isize) {
+        if let (Some(doc), Some(mut state)) = (self.read::<DocumentState>(), self.write::<State>())
+        {
+            state.editor.select_adjacent_layer(&doc.document, step);
+        }
+    }
+
+    fn artboard_event(&self, event: ArtboardEvent) {
+        match event {
+            ArtboardEvent::Add { size } => self.add_artboard(size),
+            ArtboardEvent::Delete(artboard_id) => {
+                self.apply(Command::RemoveArtboard { artboard_id });
+            }
+            ArtboardEvent::Resize {
+                artboard_id,
+                size,
+                anchor,
+            } => self.resize_artboard(artboard_id, size, anchor),
+            ArtboardEvent::CropToContent(artboard_id) => self.crop_artboard(artboard_id),
+            ArtboardEvent::DragStart { position } => {
+                if let (Some(doc), Some(mut state)) =
+                    (self.read::<DocumentState>(), self.write::<State>())
+                {
+                    let world = state.camera.screen_to_world(position);
+                    state.editor.grab_artboard(&doc.document, world);
+                }
+            }
+            ArtboardEvent::DragMove { position } => {
+                if let (Some(mut doc), Some(state)) =
+                    (self.write::<DocumentState>(), self.read::<State>())
+                    && let Some(drag) = state.editor.artboard_drag
+                    && let Some(artboard) = doc.document.artboard_mut(drag.artboard_id)
+                {
+                    artboard.position = drag.position_at(state.camera.screen_to_world(position));
+                }
+            }
+            ArtboardEvent::DragEnd => self.drop_artboard(),
+        }
+    }
+
+    /// `size` clamped to what a layer texture can hold.
+    fn clamp_to_texture_limits(&self, size: [u32; 2]) -> [u32; 2] {
+        let max = self.read::<RenderContext>().map_or(u32::MAX, |render_ctx| {
+            render_ctx.device.limits().max_texture_dimension_2d
+        });
+        size.map(|dim| dim.clamp(1, max))
+    }
+
+    /// Adds an artboard with one blank layer right of the others, and makes its layer active.
+    fn add_artboard(&self, size: [u32; 2]) {
+        let size = self.clamp_to_texture_limits(size);
+        let (Some(mut doc), Some(mut state)) =
+            (self.write::<DocumentState>(), self.write::<State>())
+        else {
+            return;
+        };
+        let document = &mut doc.document;
+        let name = format!("Artboard {}", document.artboards.len() + 1);
+        let position = new_artboard_position(document);
+        let artboard_id = document.alloc_artboard_id();
+        let layer_id = document.alloc_layer_id();
+        #[allow(clippy::cast_precision_loss)]
+        let size = size.map(|dim| dim as f32);
+
+        doc.apply(Command::InsertArtboard {
+            index: usize::MAX,
+            artboard: Artboard {
+                id: artboard_id,
+                name,
+                position,
+                size,
+                layers: vec![Layer::new(layer_id, "Layer 1".to_string()).into()],
+            },
+        });
+        state.editor.select_layer(layer_id);
+    }
+
+    /// Resizes the artboard to `size` around `anchor`, see `Anchor::shift`.
+    fn resize_artboard(&self, artboard_id: ArtboardId, size: [u32; 2], anchor: Anchor) {
+        let size = self.clamp_to_texture_limits(size);
+        let Some(mut doc) = self.write::<DocumentState>() else {
+            return;
+        };
+        let Some(from) = doc.document.artboard(artboard_id).map(Artboard::pixel_size) else {
+            return;
+        };
+        let shift = anchor.shift(from, (size[0], size[1]));
+        #[allow(clippy::cast_precision_loss)]
+        let size = size.map(|dim| dim as f32);
+        doc.apply(Command::ResizeArtboard {
+            artboard_id,
+            size,
+            shift,
+        });
+    }
+
+    /// Shrinks the artboard to the tight bounds of its non-transparent pixels, read back from every layer.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn crop_artboard(&self, artboard_id: ArtboardId) {
+        use crate::document::artboard_size::content_bounds;
+
+        let (Some(render_ctx), Some(mut scene), Some(mut doc), Some(mut history)) = (
+            self.read::<RenderContext>(),
+            self.write::<SceneRenderer>(),
+            self.write::<DocumentState>(),
+            self.write::<History>(),
+        ) else {
+            return;
+        };
+        let (device, queue) = (&render_ctx.device, &render_ctx.queue);
+
+        // edits applied since the last frame may still be resizing the layers
+        history.record_edits(device, queue, &scene, &mut doc);
+        doc.flush_gpu_ops(device, queue, &mut scene);
+
+        let Some(artboard) = doc.document.artboard(artboard_id) else {
+            return;
+        };
+        let size = artboard.pixel_size();
+        let mut layers = Vec::new();
+        for layer in artboard.iter_layers() {
+            match scene.read_layer_pixels(device, queue, layer.id) {
+                Ok(pixels) => layers.extend(pixels),
+                Err(error) => {
+                    log::error!("failed to read back layer {}: {error:#}", layer.id.0);
+                    return;
+                }
+            }
+        }
+        let Some(bounds) = content_bounds(layers.iter().map(Vec::as_slice), size) else {
+            log::warn!("'{}' is empty, there is nothing to crop to", artboard.name);
+            return;
+        };
+        if bounds.origin == [0, 0] && bounds.size == [size.0, size.1] {
+            return;
+        }
+
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+        doc.apply(Command::ResizeArtboard {
+            artboard_id,
+            size: bounds.size.map(|dim| dim as f32),
+            shift: bounds.origin.map(|origin| -(origin as i32)),
+        });
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn crop_artboard(&self, _artboard_id: ArtboardId) {
+        log::warn!("cropping artboards is not supported on the web yet");
+    }
+
+    /// Puts the dragged artboard back where it was grabbed, and moves it from there as one undoable step.
+    fn drop_artboard(&self) {
+        let (Some(mut doc), Some(mut state)) =
+            (self.write::<DocumentState>(), self.write::<State>())
+        else {
+            return;
+        };
+        let Some(drag) = state.editor.artboard_drag.take() else {
+            return;
+        };
+        let Some(artboard) = doc.document.artboard_mut(drag.artboard_id) else {
+            return;
+        };
+        let position = std::mem::replace(&mut artboard.position, drag.from);
+        #[allow(clippy::float_cmp)]
+        if position != drag.from {
+            doc.apply(Command::MoveArtboard {
+                artboard_id: drag.artboard_id,
+                position,
+            });
+        }
+    }
+
+    /// Empties every unlocked layer, vector strokes included, as one undoable step.
+    fn clear_canvas(&self) {
+        if let Some(mut doc) = self.write::<DocumentState>() {
+            let layer_ids = doc
+                .document
+                .artboards
+                .iter()
+                .flat_map(|artboard| {
+                    artboard
+                        .iter_layers()
+                        .filter(|layer| !artboard.is_layer_locked(layer.id))
+                })
+                .map(|layer| layer.id)
+                .collect();
+            doc.apply(Command::ClearLayers(layer_ids));
+        }
+    }
+
+    /// Writes the open document back under its launch name.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_document(&self) {
+        use crate::{document::saver::save_document, resources::autosave::Autosave};
+
+        let (Some(render_ctx), Some(scene), Some(mut doc), Some(options)) = (
+            self.read::<RenderContext>(),
+            self.read::<SceneRenderer>(),
+            self.write::<DocumentState>(),
+            self.read::<LaunchOptions>(),
+        ) else {
+            return;
+        };
+
+        match save_document(
+            &options.document,
+            &doc.document,
+            &scene,
+            &render_ctx.device,
+            &render_ctx.queue,
+        ) {
+            Ok(saved) => {
+                log::info!("saved document '{}'", options.document);
+                doc.document = saved;
+                if let Some(mut autosave) = self.write::<Autosave>() {
+                    autosave.discard();
+                }
+            }
+            Err(error) => log::error!("failed to save document '{}': {error:#}", options.document),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save_document(&self) {
+        log::warn!("saving documents is not supported on the web yet");
+    }
+
+    /// Flattens the artboard at the center of the view, or the first one, into `exports/` under the asset dir
+    /// at the scale and background of `export_options`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_artboard(&self, export_options: ExportOptions) {
+        use crate::document::{
+            export::{export_artboard_png, export_file_name},
+            loader::asset_dir,
+        };
+
+        let (Some(render_ctx), Some(mut scene), Some(doc), Some(state), Some(options)) = (
+            self.read::<RenderContext>(),
+            self.write::<SceneRenderer>(),
+            self.read::<DocumentState>(),
+            self.read::<State>(),
+            self.read::<LaunchOptions>(),
+        ) else {
+            return;
+        };
+
+        let view = state.camera.viewport_world_rect();
+        let center = view.min + (view.max - view.min) / 2.0;
+        let artboard = doc
+            .document
+            .hit_test(center)
+            .and_then(|id| doc.document.artboard(id))
+            .or_else(|| doc.document.artboards.first());
+        let Some(artboard) = artboard else {
+            log::warn!("nothing to export, the document has no artboards");
+            return;
+        };
+
+        let path = asset_dir().join("exports").join(export_file_name(
+            &options.document,
+            &artboard.name,
+            "png",
+        ));
+        match export_artboard_png(
+            &path,
+            &render_ctx.device,
+            &render_ctx.queue,
+            &mut scene,
+            &doc.document,
+            artboard.id,
+            export_options,
+        ) {
+            Ok(()) => log::info!("exported '{}' to {}", artboard.name, path.display()),
+            Err(error) => log::error!("failed to export '{}': {error:#}", artboard.name),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_artboard(&self, _options: ExportOptions) {
+        log::warn!("exporting artboards is not supported on the web yet");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_openraster(&self) {
+        self.export_layered("OpenRaster", crate::document::openraster::export_openraster);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_psd(&self) {
+        self.export_layered("PSD", crate::document::psd::export_psd);
+    }
+
+    /// Writes one layered file per artboard into `exports/` under the asset dir, through `export`.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_layered(&self, format: &str, export: LayeredExport) {
+        use crate::document::loader::asset_dir;
+
+        let (Some(render_ctx), Some(mut scene), Some(doc), Some(options)) = (
+            self.read::<RenderContext>(),
+            self.write::<SceneRenderer>(),
+            self.read::<DocumentState>(),
+            self.read::<LaunchOptions>(),
+        ) else {
+            return;
+        };
+
+        match export(
+            &asset_dir().join("exports"),
+            &options.document,
+            &render_ctx.device,
+            &render_ctx.queue,
+            &mut scene,
+            &doc.document,
+        ) {
+            Ok(paths) => {
+                for path in paths {
+                    log::info!("exported {}", path.display());
+                }
+            }
+            Err(error) => log::error!(
+                "failed to export '{}' as {format}: {error:#}",
+                options.document
+            ),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_openraster(&self) {
+        log::warn!("exporting artboards is not supported on the web yet");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn export_psd(&self) {
+        log::warn!("exporting artboards is not supported on the web yet");
+    }
+}
+
+/// The active layer, standing in for a node an event leaves out.
+fn active_node(state: &State, document: &Document) -> Option<NodeId> {
+    let (_, layer_id) = state.editor.active_layer(document)?;
+    Some(NodeId::Layer(layer_id))
 }
 
 impl ResourceContext for App {
@@ -194,32 +1056,12 @@ impl ApplicationHandler<CustomEvent> for App {
 
             #[cfg(not(target_arch = "wasm32"))]
             {
-                use crate::{
-                    document::loader::load_document, resources::launch_options::LaunchOptions,
-                };
-
                 let window_size = window.inner_size();
                 let render_context = pollster::block_on(RenderContext::new(window.clone()))
                     .expect("Unable to create canvas!!!");
 
-                let document_name = self
-                    .read::<LaunchOptions>()
-                    .map_or_else(|| "default".to_string(), |options| options.document.clone());
                 let max_texture_dim = render_context.device.limits().max_texture_dimension_2d;
-                let loaded =
-                    load_document(&document_name, max_texture_dim).unwrap_or_else(|error| {
-                        use crate::document::Document;
-
-                        log::warn!(
-                            "failed to load document '{document_name}': {error:#}; \
-                        falling back to the default document"
-                        );
-
-                        LoadedDocument {
-                            document: Document::default_document(),
-                            layer_pixels: HashMap::new(),
-                        }
-                    });
+                let loaded = self.load_launch_document(max_texture_dim);
 
                 let egui_context = EguiContext::new(window, &render_context);
 
@@ -230,6 +1072,7 @@ impl ApplicationHandler<CustomEvent> for App {
                 );
                 self.insert_resource(render_context)
                     .insert_resource(egui_context);
+                self.start_sessions((window_size.width, window_size.height));
 
                 self.run_update_systems();
             }
@@ -243,71 +1086,73 @@ impl ApplicationHandler<CustomEvent> for App {
     ///
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: CustomEvent) {
         match event {
-            // FIXME: Cmd+R clears every layer
-            CustomEvent::ClearCanvas => {
-                if let Some(mut doc) = self.write::<DocumentState>() {
-                    let ops: Vec<GpuOp> = doc
-                        .document
-                        .artboards
-                        .iter()
-                        .flat_map(|artboard| artboard.layers.iter())
-                        .map(|layer| GpuOp::ClearLayer { layer_id: layer.id })
-                        .collect();
-                    doc.gpu_dirty.extend(ops);
-                }
-            }
+            CustomEvent::ClearCanvas => self.clear_canvas(),
+            CustomEvent::Undo => self.step_history(History::undo),
+            CustomEvent::Redo => self.step_history(History::redo),
+            CustomEvent::SaveDocument => self.save_document(),
+            CustomEvent::ExportArtboard(options) => self.export_artboard(options),
+            CustomEvent::ExportOpenRaster => self.export_openraster(),
+            CustomEvent::ExportPsd => self.export_psd(),
+            CustomEvent::RestoreInterruptedSession => self.restore_interrupted_session(),
+            CustomEvent::DiscardInterruptedSession => self.discard_interrupted_session(),
             // TODO: cleanup the transformation code
-            CustomEvent::CameraMove { position } => {
+            CustomEvent::CameraMove { position } => self.move_camera(position),
+            CustomEvent::CameraZoom { delta } => self.zoom_camera(delta),
+            CustomEvent::BrushPoint { dot } => self.queue_brush_point(dot),
+            CustomEvent::UpdateBrush(properties) => self.update_brush(properties),
+            CustomEvent::UpdateBrushSettings(settings) => {
                 if let Some(mut state) = self.write::<State>() {
-                    let delta = position - state.pan_offset;
-                    state.pan_offset = position;
-                    state.camera.pan_screen_delta(delta);
+                    state.editor.update_brush_settings(settings);
                 }
             }
-            CustomEvent::CameraZoom { delta } => {
-                if let (Some(mut state), Some(mut preview_state)) =
-                    (self.write::<State>(), self.write::<BrushPreviewState>())
-                {
-                    state.camera.zoom_by(delta);
-                    // Update brush preview scale to match viewport zoom
-                    preview_state.update_scale(delta);
+            CustomEvent::SaveBrushPreset(name) => self.save_brush_preset(name),
+            CustomEvent::DeleteBrushPreset(name) => self.delete_brush_preset(&name),
+            CustomEvent::ImportBrushPresets(path) => self.import_brush_presets(&path),
+            CustomEvent::ExportBrushPresets(path) => self.export_brush_presets(&path),
+            CustomEvent::SelectLayer(layer_id) => {
+                if let Some(mut state) = self.write::<State>() {
+                    state.editor.select_layer(layer_id);
                 }
             }
-            CustomEvent::BrushPoint { dot } => {
-                if let (Some(state), Some(stroke_state), Some(mut queue)) = (
-                    self.read::<State>(),
-                    self.read::<StrokeState>(),
-                    self.write::<BrushPointQueue>(),
-                ) {
-                    // Raw screen coordinates and camera state at enqueue time are enough for coordinate transformation later.
-                    queue.write(BrushPointData {
-                        dot,
-                        camera: state.camera,
-                        target: stroke_state.target,
-                    });
-                }
+            CustomEvent::AddLayer => self.add_layer(false),
+            CustomEvent::AddVectorLayer => self.add_layer(true),
+            CustomEvent::DuplicateNode(node) => self.duplicate_node(node),
+            CustomEvent::DeleteNode(node) => self.delete_node(node),
+            CustomEvent::RenameNode { node, name } => {
+                self.apply_unless_locked(node, Command::Rename { node, name });
             }
-            CustomEvent::UpdateBrush(properties) => {
-                if let (Some(render_ctx), Some(mut state), Some(mut scene)) = (
-                    self.read::<RenderContext>(),
-                    self.write::<State>(),
-                    self.write::<SceneRenderer>(),
-                ) {
-                    state.editor.update_brush(properties);
-                    scene.update_brush(&render_ctx.queue, properties.color.to_rgba_array());
+            CustomEvent::SetNodeVisible { node, visible } => {
+                self.apply(Command::SetVisible { node, visible });
+            }
+            CustomEvent::SetNodeLocked { node, locked } => {
+                self.apply(Command::SetLocked { node, locked });
+            }
+            CustomEvent::MoveNode {
+                node,
+                artboard_id,
+                parent,
+                index,
+            } => self.apply_unless_locked(
+                node,
+                Command::MoveNode {
+                    artboard_id,
+                    node,
+                    parent,
+                    index,
+                },
+            ),
+            CustomEvent::SelectLayerAbove => self.select_adjacent_layer(1),
+            CustomEvent::SelectLayerBelow => self.select_adjacent_layer(-1),
+            CustomEvent::PickArtboardAtStroke(pick) => {
+                if let Some(mut state) = self.write::<State>() {
+                    state.editor.pick_artboard_at_stroke = pick;
                 }
             }
+            CustomEvent::Artboard(event) => self.artboard_event(event),
             CustomEvent::StrokeStart => {
-                if let (Some(doc), Some(mut stroke_state)) =
-                    (self.read::<DocumentState>(), self.write::<StrokeState>())
-                {
-                    // TODO: replace hardcoded top layer with selected layer
-                    let target = doc.document.artboards.first().and_then(|artboard| {
-                        artboard.layers.last().map(|layer| (artboard.id, layer.id))
-                    });
-                    if let Some(target) = target {
-                        stroke_state.start(target);
-                    }
+                // the target is picked by the first brush point, see `EditorState::stroke_target`
+                if let Some(mut stroke_state) = self.write::<StrokeState>() {
+                    stroke_state.begin();
                 }
             }
             CustomEvent::StrokeEnd => {
@@ -358,7 +1203,10 @@ impl ApplicationHandler<CustomEvent> for App {
         event: WindowEvent,
     ) {
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => {
+                self.discard_recovery();
+                event_loop.exit();
+            }
             WindowEvent::Resized(new_size) => {
                 // Ignore zero-size resize events
                 if new_size.width == 0 || new_size.height == 0 {
@@ -417,7 +1265,11 @@ impl ApplicationHandler<CustomEvent> for App {
                 if let (Some(mut input_system), Some(state)) =
                     (self.write::<InputSystem>(), self.read::<State>())
                 {
-                    input_system.process_event(&event, state.editor.brush_properties.size);
+                    input_system.process_event(
+                        &event,
+                        &state.editor.brush_properties,
+                        &state.editor.brush_settings,
+                    );
                 }
 
                 if let WindowEvent::KeyboardInput {
@@ -431,6 +1283,7 @@ impl ApplicationHandler<CustomEvent> for App {
                 } = event
                     && let (KeyCode::Escape, true) = (code, key_state.is_pressed())
                 {
+                    self.discard_recovery();
                     event_loop.exit();
                 }
             }
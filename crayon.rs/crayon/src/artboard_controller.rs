@@ -0,0 +1,61 @@
+use cgmath::{EuclideanSpace, Point2};
+use winit::event::{ElementState, MouseButton, WindowEvent};
+
+use crate::{
+    event_sender::EventSender,
+    events::{ArtboardEvent, ControllerEvent},
+};
+
+/// Moves artboards around the canvas with an alt-drag.
+pub struct ArtboardController {
+    event_sender: EventSender,
+    is_dragging: bool,
+    cursor_position: Point2<f32>,
+}
+
+impl ArtboardController {
+    pub fn new(event_sender: EventSender) -> Self {
+        ArtboardController {
+            event_sender,
+            is_dragging: false,
+            cursor_position: Point2::origin(),
+        }
+    }
+
+    pub fn process_event(&mut self, event: &WindowEvent, is_alt_pressed: bool) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                #[allow(clippy::cast_possible_truncation)]
+                let position = Point2::new(position.x as f32, position.y as f32);
+                self.cursor_position = position;
+
+                if self.is_dragging {
+                    self.send(ArtboardEvent::DragMove { position });
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                // a drag started with alt held carries on if alt is let go
+                ElementState::Pressed if is_alt_pressed => {
+                    self.is_dragging = true;
+                    self.send(ArtboardEvent::DragStart {
+                        position: self.cursor_position,
+                    });
+                }
+                ElementState::Released if self.is_dragging => {
+                    self.is_dragging = false;
+                    self.send(ArtboardEvent::DragEnd);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn send(&self, event: ArtboardEvent) {
+        self.event_sender.send(ControllerEvent::Artboard(event));
+    }
+}
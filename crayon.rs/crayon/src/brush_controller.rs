@@ -1,110 +1,363 @@
-use batteries::prelude::{Dot2D, PointProcessor, StrokeDot2D};
+use batteries::prelude::{FULL_PRESSURE, PointProcessor, StrokeDot2D};
 use cgmath::{EuclideanSpace, Point2};
 use winit::{
-    event::{ElementState, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    event::{ElementState, Force, MouseButton, TouchPhase, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
 };
 
 use crate::{
-    event_sender::EventSender, events::ControllerEvent, renderer::brush::DEFAULT_BRUSH_SIZE,
+    editor_state::{BrushProperties, BrushSettings, PressureCurve},
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::brush::DEFAULT_BRUSH_SIZE,
 };
 
-const BRUSH_STEP_SIZE: f32 = 1.0;
-
 pub struct BrushController {
     event_sender: EventSender,
     is_mouse_down: bool,
     is_dragging: bool,
     brush_size: f32,
+    pressure_curve: PressureCurve,
     brush_position: cgmath::Point2<f32>,
+    /// Pressure of the latest point, the stroke ends with it.
+    brush_pressure: f32,
+    /// The finger or pen painting, other touches and the mouse wait until it lifts.
+    active_touch: Option<u64>,
     point_processor: PointProcessor,
 }
 
 impl BrushController {
     pub fn new(event_sender: EventSender) -> Self {
-        let point_processor = PointProcessor::new(BRUSH_STEP_SIZE);
+        let point_processor =
+            PointProcessor::new(BrushSettings::default().dab_step(DEFAULT_BRUSH_SIZE));
         BrushController {
             event_sender,
             is_dragging: false,
             is_mouse_down: false,
             brush_size: DEFAULT_BRUSH_SIZE,
+            pressure_curve: PressureCurve::default(),
             brush_position: Point2::origin(),
+            brush_pressure: FULL_PRESSURE,
+            active_touch: None,
             point_processor,
         }
     }
 
-    // TODO: threading brush_size isn't the cleanest approach
-    pub fn process_event(&mut self, event: &WindowEvent, brush_size: f32, is_super_pressed: bool) {
-        self.brush_size = brush_size;
+    // TODO: threading brush properties isn't the cleanest approach
+    pub fn process_event(
+        &mut self,
+        event: &WindowEvent,
+        brush: &BrushProperties,
+        settings: &BrushSettings,
+        modifiers: ModifiersState,
+    ) {
+        self.update_brush(brush, settings);
+        let is_super_pressed = modifiers.super_key();
+        // super pans the camera and alt drags artboards, neither paints
+        let is_painting_held_off = is_super_pressed || modifiers.alt_key();
         match event {
             WindowEvent::KeyboardInput { event, .. } => {
-                if is_super_pressed
-                    && event.physical_key == PhysicalKey::Code(KeyCode::KeyR)
-                    && event.state.is_pressed()
-                {
-                    self.event_sender.send(ControllerEvent::ClearCanvas);
+                if !is_super_pressed || !event.state.is_pressed() {
+                    return;
+                }
+                match event.physical_key {
+                    PhysicalKey::Code(KeyCode::KeyR) => {
+                        self.event_sender.send(ControllerEvent::ClearCanvas);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyZ) if modifiers.shift_key() => {
+                        self.event_sender.send(ControllerEvent::Redo);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyZ) => {
+                        self.event_sender.send(ControllerEvent::Undo);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyS) => {
+                        self.event_sender.send(ControllerEvent::SaveDocument);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyE) => {
+                        self.event_sender.send(ControllerEvent::ExportOpenRaster);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyP) => {
+                        self.event_sender.send(ControllerEvent::ExportPsd);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyN) if modifiers.shift_key() => {
+                        self.event_sender.send(ControllerEvent::AddLayer);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyV) if modifiers.shift_key() => {
+                        self.event_sender.send(ControllerEvent::AddVectorLayer);
+                    }
+                    PhysicalKey::Code(KeyCode::KeyJ) => {
+                        self.event_sender.send(ControllerEvent::DuplicateNode(None));
+                    }
+                    PhysicalKey::Code(KeyCode::Backspace | KeyCode::Delete) => {
+                        self.event_sender.send(ControllerEvent::DeleteNode(None));
+                    }
+                    PhysicalKey::Code(KeyCode::BracketRight) => {
+                        self.event_sender.send(ControllerEvent::SelectLayerAbove);
+                    }
+                    PhysicalKey::Code(KeyCode::BracketLeft) => {
+                        self.event_sender.send(ControllerEvent::SelectLayerBelow);
+                    }
+                    _ => {}
                 }
             }
             WindowEvent::CursorMoved { position, .. } => {
-                self.is_dragging = self.is_mouse_down;
-
-                if !self.is_dragging || is_super_pressed {
+                if self.active_touch.is_some() {
                     return;
                 }
+                self.is_dragging = self.is_mouse_down;
+                #[allow(clippy::cast_possible_truncation)]
+                let position = cgmath::Point2::new(position.x as f32, position.y as f32);
 
-                let points = self.point_processor.process_point(StrokeDot2D {
-                    #[allow(clippy::cast_possible_truncation)]
-                    position: cgmath::Point2::new(position.x as f32, position.y as f32),
-                    radius: self.brush_size,
-                    is_last: false,
-                });
-
-                for point in &points {
-                    self.event_sender.send(ControllerEvent::BrushPoint {
-                        dot: Dot2D {
-                            position: *point,
-                            radius: self.brush_size,
-                        },
-                    });
+                if !self.is_dragging {
+                    // where a click without a drag stamps its dab
+                    self.brush_position = position;
                 }
-
-                // Update internal position to last point for usage in mouse up
-                if let Some(last_point) = points.last() {
-                    self.brush_position = *last_point;
+                if !self.is_dragging || is_painting_held_off {
+                    return;
                 }
+
+                self.process_point(position, FULL_PRESSURE, false);
             }
             WindowEvent::MouseInput { state, button, .. } => {
-                if *button == MouseButton::Left {
+                if *button == MouseButton::Left && self.active_touch.is_none() {
                     let was_mouse_down = self.is_mouse_down;
                     self.is_mouse_down = *state == ElementState::Pressed;
 
-                    if !was_mouse_down && self.is_mouse_down && !is_super_pressed {
+                    if !was_mouse_down && self.is_mouse_down && !is_painting_held_off {
+                        self.brush_pressure = FULL_PRESSURE;
                         self.event_sender.send(ControllerEvent::StrokeStart);
                     }
 
                     if was_mouse_down && !self.is_mouse_down {
-                        // Process final point with is_last=true for stroke end
-                        let final_points = self.point_processor.process_point(StrokeDot2D {
-                            position: self.brush_position,
-                            radius: self.brush_size,
-                            is_last: true,
-                        });
-
-                        for point in final_points {
-                            self.event_sender.send(ControllerEvent::BrushPoint {
-                                dot: Dot2D {
-                                    position: point,
-                                    radius: self.brush_size,
-                                },
-                            });
-                        }
-
-                        self.point_processor.clear();
-                        self.event_sender.send(ControllerEvent::StrokeEnd);
+                        self.end_stroke();
                     }
                 }
             }
+            WindowEvent::Touch(touch) => {
+                #[allow(clippy::cast_possible_truncation)]
+                let position = Point2::new(touch.location.x as f32, touch.location.y as f32);
+                self.process_touch(
+                    touch.id,
+                    touch.phase,
+                    position,
+                    touch.force,
+                    is_painting_held_off,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn update_brush(&mut self, brush: &BrushProperties, settings: &BrushSettings) {
+        self.brush_size = brush.size;
+        self.pressure_curve = brush.pressure_curve;
+        self.point_processor.set_step(settings.dab_step(brush.size));
+    }
+
+    /// Paints with one touch at a time, its force as the pressure.
+    /// Touches without force, like most fingers, paint at full pressure.
+    fn process_touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: Point2<f32>,
+        force: Option<Force>,
+        is_painting_held_off: bool,
+    ) {
+        #[allow(clippy::cast_possible_truncation)]
+        let pressure = force.map_or(FULL_PRESSURE, |force| force.normalized() as f32);
+        match (phase, self.active_touch) {
+            (TouchPhase::Started, None) if !self.is_mouse_down && !is_painting_held_off => {
+                self.active_touch = Some(id);
+                self.brush_position = position;
+                self.brush_pressure = pressure;
+                self.event_sender.send(ControllerEvent::StrokeStart);
+            }
+            (TouchPhase::Moved, Some(active)) if active == id => {
+                self.process_point(position, pressure, false);
+            }
+            (TouchPhase::Ended | TouchPhase::Cancelled, Some(active)) if active == id => {
+                // lifting reports the force dropping off, the stroke keeps the last one
+                self.brush_position = position;
+                self.end_stroke();
+                self.active_touch = None;
+            }
             _ => {}
         }
     }
+
+    /// Feeds a point of the stroke through the `PointProcessor`, sending the dabs it spaces out.
+    fn process_point(&mut self, position: Point2<f32>, pressure: f32, is_last: bool) {
+        let dots = self.point_processor.process_point(StrokeDot2D {
+            position,
+            radius: self.pressure_curve.radius(self.brush_size, pressure),
+            pressure,
+            is_last,
+        });
+
+        for dot in &dots {
+            self.event_sender
+                .send(ControllerEvent::BrushPoint { dot: *dot });
+        }
+
+        // Update internal position to last point for usage in stroke end
+        self.brush_pressure = pressure;
+        if let Some(last) = dots.last() {
+            self.brush_position = last.position;
+        }
+    }
+
+    fn end_stroke(&mut self) {
+        // Process final point with is_last=true for stroke end
+        self.process_point(self.brush_position, self.brush_pressure, true);
+        self.point_processor.clear();
+        self.event_sender.send(ControllerEvent::StrokeEnd);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use batteries::prelude::Dot2D;
+
+    use cgmath::MetricSpace;
+
+    use super::*;
+    use crate::editor_state::EditorState;
+    use crate::testing::events::drain;
+
+    /// Drags touch `id` along x in 10px steps, one force per step, then lifts it.
+    fn touch_stroke(controller: &mut BrushController, id: u64, forces: &[Option<f64>]) {
+        let force = |force: Option<f64>| force.map(Force::Normalized);
+        let at = |step: usize| {
+            #[allow(clippy::cast_precision_loss)]
+            let x = step as f32 * 10.0;
+            Point2::new(x, 50.0)
+        };
+        controller.process_touch(id, TouchPhase::Started, at(0), force(forces[0]), false);
+        for (step, pressure) in forces.iter().enumerate().skip(1) {
+            controller.process_touch(id, TouchPhase::Moved, at(step), force(*pressure), false);
+        }
+        let last = forces.len() - 1;
+        controller.process_touch(
+            id,
+            TouchPhase::Ended,
+            at(last),
+            Some(Force::Normalized(0.0)),
+            false,
+        );
+    }
+
+    fn brush_dots(events: &[ControllerEvent]) -> Vec<Dot2D> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ControllerEvent::BrushPoint { dot } => Some(*dot),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn touch_force_drives_the_emitted_radii() {
+        let (sender, receiver) = EventSender::capturing();
+        let mut controller = BrushController::new(sender);
+        let forces: Vec<_> = (0..=10u8)
+            .map(|step| Some(f64::from(step) / 10.0))
+            .collect();
+        touch_stroke(&mut controller, 1, &forces);
+
+        let events = drain(&receiver);
+        assert!(matches!(events.first(), Some(ControllerEvent::StrokeStart)));
+        assert!(matches!(events.last(), Some(ControllerEvent::StrokeEnd)));
+        let dots = brush_dots(&events);
+        assert!(dots.len() > 20);
+
+        let curve = PressureCurve::default();
+        // the curve through the points overshoots them a little at the ends
+        for dot in &dots {
+            let expected = curve.radius(DEFAULT_BRUSH_SIZE, dot.pressure);
+            assert!(
+                (dot.radius - expected).abs() < 0.25,
+                "radius {} at pressure {}",
+                dot.radius,
+                dot.pressure
+            );
+            assert!(dot.radius >= curve.radius(DEFAULT_BRUSH_SIZE, 0.0) - 0.25);
+            assert!(dot.radius <= DEFAULT_BRUSH_SIZE + 0.25);
+        }
+        // pressing harder along the stroke widens it
+        let (first, last) = (dots[0], dots[dots.len() - 1]);
+        assert!(
+            last.radius > first.radius + 5.0,
+            "{} -> {}",
+            first.radius,
+            last.radius
+        );
+    }
+
+    #[test]
+    fn touches_without_force_paint_at_full_size() {
+        let (sender, receiver) = EventSender::capturing();
+        let mut controller = BrushController::new(sender);
+        touch_stroke(&mut controller, 1, &[None; 8]);
+
+        let dots = brush_dots(&drain(&receiver));
+        assert!(!dots.is_empty());
+        assert!(dots.iter().all(|dot| {
+            (dot.radius - DEFAULT_BRUSH_SIZE).abs() < f32::EPSILON
+                && (dot.pressure - FULL_PRESSURE).abs() < f32::EPSILON
+        }));
+    }
+
+    #[test]
+    fn dabs_are_spaced_by_the_brush_diameter() {
+        let (sender, receiver) = EventSender::capturing();
+        let mut controller = BrushController::new(sender);
+        let brush = EditorState::new().brush_properties;
+        let settings = BrushSettings {
+            spacing: 25.0,
+            ..BrushSettings::default()
+        };
+        controller.update_brush(&brush, &settings);
+        touch_stroke(&mut controller, 1, &[None; 11]);
+
+        let dots = brush_dots(&drain(&receiver));
+        let step = settings.dab_step(brush.size);
+        assert!(dots.len() > 5, "{}", dots.len());
+        for pair in dots.windows(2) {
+            let gap = pair[0].position.distance(pair[1].position);
+            assert!(
+                gap > step && gap < step + 1.0,
+                "gap {gap} for a {step} step"
+            );
+        }
+    }
+
+    #[test]
+    fn only_the_first_touch_paints() {
+        let (sender, receiver) = EventSender::capturing();
+        let mut controller = BrushController::new(sender);
+        let started = Point2::new(0.0, 0.0);
+        controller.process_touch(1, TouchPhase::Started, started, None, false);
+        controller.process_touch(2, TouchPhase::Started, started, None, false);
+        for step in 1..8u8 {
+            let position = Point2::new(0.0, f32::from(step) * 10.0);
+            controller.process_touch(2, TouchPhase::Moved, position, None, false);
+        }
+        controller.process_touch(2, TouchPhase::Ended, started, None, false);
+
+        let events = drain(&receiver);
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert!(matches!(events[0], ControllerEvent::StrokeStart));
+        assert_eq!(controller.active_touch, Some(1));
+    }
+
+    #[test]
+    fn held_off_touches_do_not_paint() {
+        let (sender, receiver) = EventSender::capturing();
+        let mut controller = BrushController::new(sender);
+        controller.process_touch(1, TouchPhase::Started, Point2::origin(), None, true);
+        controller.process_touch(1, TouchPhase::Ended, Point2::origin(), None, true);
+        assert!(drain(&receiver).is_empty());
+    }
 }
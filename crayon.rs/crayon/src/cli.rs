@@ -0,0 +1,347 @@
+//! Subcommands that run without a window, see `LaunchCommand`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    document::{
+        ArtboardId, Document,
+        export::{ExportOptions, export_artboard_png},
+        loader::{
+            LoadedDocument, asset_dir, check_document_file, check_document_from,
+            load_document_file, load_document_from,
+        },
+        validate::{Issue, Severity},
+    },
+    renderer::headless::{HEADLESS_FORMAT, headless_device},
+    resources::{
+        launch_options::{CheckArgs, LaunchCommand, RenderArgs},
+        scene_renderer::SceneRenderer,
+    },
+};
+
+pub fn run(command: &LaunchCommand) -> anyhow::Result<()> {
+    match command {
+        LaunchCommand::Render(args) => {
+            let (device, queue) = headless_device(args.software)?;
+            render(&device, &queue, args)
+        }
+        LaunchCommand::Check(args) => check(args, &mut std::io::stdout().lock()),
+    }
+}
+
+/// Writes every issue of the document to `out`, failing if there are errors,
+/// or warnings under `--deny-warnings`.
+fn check(args: &CheckArgs, out: &mut impl std::io::Write) -> anyhow::Result<()> {
+    let issues = document_location(&args.document)
+        .check(args.max_texture_dim)
+        .with_context(|| format!("checking '{}'", args.document))?;
+    for issue in &issues {
+        writeln!(out, "{issue}")?;
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    if errors > 0 || (args.deny_warnings && warnings > 0) {
+        bail!(
+            "'{}' has {errors} error(s) and {warnings} warning(s)",
+            args.document
+        );
+    }
+    Ok(())
+}
+
+/// Flattens the artboard `args` picks to a PNG.
+fn render(device: &wgpu::Device, queue: &wgpu::Queue, args: &RenderArgs) -> anyhow::Result<()> {
+    let loaded = document_location(&args.document)
+        .load(device.limits().max_texture_dimension_2d)
+        .with_context(|| format!("loading '{}'", args.document))?;
+    let artboard_id = pick_artboard(&loaded.document, args.artboard.as_deref())?;
+
+    let mut scene = SceneRenderer::new(device, queue, HEADLESS_FORMAT);
+    scene.hydrate(device, queue, &loaded);
+    export_artboard_png(
+        &args.out,
+        device,
+        queue,
+        &mut scene,
+        &loaded.document,
+        artboard_id,
+        ExportOptions {
+            scale: args.scale,
+            background: !args.transparent,
+        },
+    )?;
+    log::info!("rendered '{}' to {}", args.document, args.out.display());
+    Ok(())
+}
+
+/// What a document argument names, see `document_location`.
+#[derive(PartialEq, Debug)]
+enum DocumentLocation {
+    /// Exactly this file, read as its extension says.
+    File(PathBuf),
+    /// The document `load_document_from` picks by this name in `dir`.
+    Named { dir: PathBuf, name: String },
+}
+
+impl DocumentLocation {
+    fn load(&self, max_texture_dim: u32) -> anyhow::Result<LoadedDocument> {
+        match self {
+            Self::File(path) => load_document_file(path, max_texture_dim),
+            Self::Named { dir, name } => load_document_from(dir, name, max_texture_dim),
+        }
+    }
+
+    fn check(&self, max_texture_dim: u32) -> anyhow::Result<Vec<Issue>> {
+        match self {
+            Self::File(path) => check_document_file(path, max_texture_dim),
+            Self::Named { dir, name } => check_document_from(dir, name, max_texture_dim),
+        }
+    }
+}
+
+/// A path with an extension is that file, whatever else sits next to it.
+/// Without one it names a document, in the path's dir or under the asset dir as `--doc` does.
+fn document_location(document: &str) -> DocumentLocation {
+    let path = Path::new(document);
+    if path.extension().is_some() {
+        return DocumentLocation::File(path.to_path_buf());
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => DocumentLocation::Named {
+            dir: dir.to_path_buf(),
+            name: name.to_string_lossy().into_owned(),
+        },
+        _ => DocumentLocation::Named {
+            dir: asset_dir(),
+            name: document.to_string(),
+        },
+    }
+}
+
+fn pick_artboard(document: &Document, name: Option<&str>) -> anyhow::Result<ArtboardId> {
+    let artboard = match name {
+        Some(name) => document
+            .artboards
+            .iter()
+            .find(|artboard| artboard.name == name),
+        None => document.artboards.first(),
+    };
+    if let Some(artboard) = artboard {
+        return Ok(artboard.id);
+    }
+    let names: Vec<&str> = document
+        .artboards
+        .iter()
+        .map(|artboard| artboard.name.as_str())
+        .collect();
+    match name {
+        Some(name) => bail!("no artboard '{name}', the document has {names:?}"),
+        None => bail!("the document has no artboards"),
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::document::{
+        LayerId,
+        bundle::write_bundle,
+        saver::{save_bundle_to, save_document_to},
+    };
+    use crate::testing::fixtures::{doc_two_artboards, scratch_dir, solid_layer_pixels};
+    use crate::testing::gpu::headless_gpu;
+    use crate::testing::probe::sample;
+
+    fn render_args(document: &Path, artboard: Option<&str>, out: PathBuf) -> RenderArgs {
+        RenderArgs {
+            document: document.display().to_string(),
+            artboard: artboard.map(str::to_string),
+            scale: 2,
+            transparent: false,
+            software: false,
+            out,
+        }
+    }
+
+    #[test]
+    fn document_files_and_names_are_told_apart() {
+        assert_eq!(
+            document_location("art/foo.json"),
+            DocumentLocation::File(PathBuf::from("art/foo.json"))
+        );
+        assert_eq!(
+            document_location("foo.crayon"),
+            DocumentLocation::File(PathBuf::from("foo.crayon"))
+        );
+        assert_eq!(
+            document_location("art/foo"),
+            DocumentLocation::Named {
+                dir: PathBuf::from("art"),
+                name: "foo".to_string()
+            }
+        );
+        assert_eq!(
+            document_location("two-boards"),
+            DocumentLocation::Named {
+                dir: asset_dir(),
+                name: "two-boards".to_string()
+            }
+        );
+        let error = document_location("foo.png").check(2048).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("is not a .crayon, .json or .ora document"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn check_reports_every_issue_and_fails_on_errors() {
+        let dir = scratch_dir("cli-check");
+        let mut document = doc_two_artboards();
+        document.next_id = 1;
+        document.find_layer_mut(LayerId(2)).unwrap().content_path = Some("../x.png".to_string());
+        let json = serde_json::to_string(&document).unwrap();
+        std::fs::write(dir.join("doc.json"), json).unwrap();
+
+        let mut args = CheckArgs {
+            document: dir.join("doc.json").display().to_string(),
+            max_texture_dim: 8192,
+            deny_warnings: false,
+        };
+        let mut out = Vec::new();
+        let error = check(&args, &mut out).unwrap_err();
+        assert!(format!("{error:#}").contains("1 error(s) and 1 warning(s)"));
+        let report = String::from_utf8(out).unwrap();
+        assert_eq!(report.lines().count(), 2, "{report}");
+        assert!(report.contains("error: $.artboards[0].layers[0].content_path"));
+        assert!(report.contains("warning: $.next_id"));
+
+        document.find_layer_mut(LayerId(2)).unwrap().content_path = None;
+        let json = serde_json::to_string(&document).unwrap();
+        std::fs::write(dir.join("doc.json"), json).unwrap();
+        check(&args, &mut Vec::new()).unwrap();
+        args.deny_warnings = true;
+        assert!(check(&args, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn check_reads_the_named_file_rather_than_a_same_named_bundle() {
+        let dir = scratch_dir("cli-check-exact");
+        let clean = doc_two_artboards();
+        let mut bundle = Vec::new();
+        write_bundle(&mut bundle, &clean, []).unwrap();
+        std::fs::write(dir.join("doc.crayon"), bundle).unwrap();
+        let mut broken = clean;
+        broken.next_id = 1;
+        std::fs::write(
+            dir.join("doc.json"),
+            serde_json::to_string(&broken).unwrap(),
+        )
+        .unwrap();
+
+        let args = |file: &str| CheckArgs {
+            document: dir.join(file).display().to_string(),
+            max_texture_dim: 8192,
+            deny_warnings: true,
+        };
+        let mut out = Vec::new();
+        assert!(check(&args("doc.json"), &mut out).is_err());
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("warning: $.next_id")
+        );
+        let mut out = Vec::new();
+        check(&args("doc.crayon"), &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn renders_the_named_artboard_of_a_saved_document() {
+        let (device, queue) = headless_gpu();
+        let mut scene = SceneRenderer::new(&device, &queue, HEADLESS_FORMAT);
+        let mut layer_pixels = HashMap::new();
+        layer_pixels.insert(LayerId(4), solid_layer_pixels((400, 300), [255, 0, 0, 255]));
+        let document = doc_two_artboards();
+        scene.hydrate(
+            &device,
+            &queue,
+            &LoadedDocument {
+                document: document.clone(),
+                layer_pixels,
+            },
+        );
+        let dir = scratch_dir("cli-render");
+        save_document_to(&dir, "doc", &document, &scene, &device, &queue).unwrap();
+
+        let out = dir.join("out").join("right.png");
+        let args = render_args(&dir.join("doc.json"), Some("Right"), out.clone());
+        render(&device, &queue, &args).unwrap();
+
+        let png = image::open(&out).unwrap().to_rgba8();
+        assert_eq!(png.dimensions(), (800, 600), "scaled by 2");
+        assert_eq!(
+            sample(png.as_raw(), png.dimensions(), 400, 300),
+            [255, 0, 0, 255]
+        );
+
+        let missing = render_args(&dir.join("doc.json"), Some("Middle"), dir.join("x.png"));
+        let error = render(&device, &queue, &missing).unwrap_err();
+        assert!(format!("{error:#}").contains("\"Left\", \"Right\""));
+        assert!(!dir.join("x.png").exists());
+    }
+
+    #[test]
+    fn renders_the_named_file_rather_than_a_same_named_bundle() {
+        let (device, queue) = headless_gpu();
+        let mut scene = SceneRenderer::new(&device, &queue, HEADLESS_FORMAT);
+        let document = doc_two_artboards();
+        let dir = scratch_dir("cli-render-exact");
+        for (rgba, save_as_bundle) in [([255, 0, 0, 255], false), ([0, 0, 255, 255], true)] {
+            let layer_pixels = HashMap::from([(LayerId(4), solid_layer_pixels((400, 300), rgba))]);
+            scene.hydrate(
+                &device,
+                &queue,
+                &LoadedDocument {
+                    document: document.clone(),
+                    layer_pixels,
+                },
+            );
+            if save_as_bundle {
+                save_bundle_to(&dir.join("foo.crayon"), &document, &scene, &device, &queue)
+            } else {
+                save_document_to(&dir, "foo", &document, &scene, &device, &queue)
+            }
+            .unwrap();
+        }
+
+        for (file, rgba) in [
+            ("foo.json", [255, 0, 0, 255]),
+            ("foo.crayon", [0, 0, 255, 255]),
+        ] {
+            let out = dir.join(format!("{file}.png"));
+            render(
+                &device,
+                &queue,
+                &render_args(&dir.join(file), Some("Right"), out.clone()),
+            )
+            .unwrap();
+            let png = image::open(&out).unwrap().to_rgba8();
+            assert_eq!(
+                sample(png.as_raw(), png.dimensions(), 400, 300),
+                rgba,
+                "{file}"
+            );
+        }
+    }
+}
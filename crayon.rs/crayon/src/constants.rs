@@ -28,3 +28,9 @@ pub const WINDOW_SIZE: (u32, u32) = (1200, 880);
 pub const CAMERA_ZOOM_DELTA: f32 = 0.02;
 pub const CAMERA_ZOOM_MAX: f32 = 10.0;
 pub const CAMERA_ZOOM_MIN: f32 = 0.1;
+
+/// How often the open document is snapshotted for crash recovery.
+pub const AUTOSAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Highest texels per artboard pixel vector layers are re-rasterized at while zoomed in.
+pub const VECTOR_DISPLAY_SCALE_MAX: u32 = 4;
@@ -0,0 +1,205 @@
+//! Sizing artboards: presets to create them at, anchors to resize them around, and cropping to their content.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{document::Document, resources::history::TexelRect};
+
+/// World-space gap left between a new artboard and the ones already placed.
+const ARTBOARD_GAP: f32 = 100.0;
+
+/// A named size new artboards can be created at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ArtboardPreset {
+    pub name: &'static str,
+    pub size: [u32; 2],
+}
+
+pub const ARTBOARD_PRESETS: [ArtboardPreset; 5] = [
+    ArtboardPreset {
+        name: "Square",
+        size: [1080, 1080],
+    },
+    ArtboardPreset {
+        name: "HD",
+        size: [1920, 1080],
+    },
+    ArtboardPreset {
+        name: "Portrait",
+        size: [1080, 1920],
+    },
+    ArtboardPreset {
+        name: "A4 at 150 dpi",
+        size: [1240, 1754],
+    },
+    ArtboardPreset {
+        name: "Icon",
+        size: [512, 512],
+    },
+];
+
+/// The point of an artboard that stays put when it is resized.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Row by row, top left first, as laid out in a 3x3 grid.
+    pub const ALL: [Self; 9] = [
+        Self::TopLeft,
+        Self::Top,
+        Self::TopRight,
+        Self::Left,
+        Self::Center,
+        Self::Right,
+        Self::BottomLeft,
+        Self::Bottom,
+        Self::BottomRight,
+    ];
+
+    /// Where the old top left corner lands when resizing from `from` to `to` pixels around the anchor.
+    /// Centered anchors round towards the top left.
+    pub fn shift(self, from: (u32, u32), to: (u32, u32)) -> [i32; 2] {
+        // halves of the size difference along each axis
+        let (column, row) = match self {
+            Self::TopLeft => (0, 0),
+            Self::Top => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::Left => (0, 1),
+            Self::Center => (1, 1),
+            Self::Right => (2, 1),
+            Self::BottomLeft => (0, 2),
+            Self::Bottom => (1, 2),
+            Self::BottomRight => (2, 2),
+        };
+        let axis = |halves: i64, from: u32, to: u32| {
+            #[allow(clippy::cast_possible_truncation)]
+            let shift = ((i64::from(to) - i64::from(from)) * halves).div_euclid(2) as i32;
+            shift
+        };
+        [axis(column, from.0, to.0), axis(row, from.1, to.1)]
+    }
+}
+
+/// Where a new artboard goes: right of every other one, top aligned with the topmost.
+pub fn new_artboard_position(document: &Document) -> [f32; 2] {
+    let right = document
+        .artboards
+        .iter()
+        .map(|artboard| artboard.position[0] + artboard.size[0])
+        .reduce(f32::max);
+    let top = document
+        .artboards
+        .iter()
+        .map(|artboard| artboard.position[1])
+        .reduce(f32::min);
+    match (right, top) {
+        (Some(right), Some(top)) => [right + ARTBOARD_GAP, top],
+        _ => [0.0, 0.0],
+    }
+}
+
+/// The tight bounds of the pixels with any alpha across `layers`, RGBA8 pixels of `size` each.
+/// `None` when every layer is fully transparent.
+pub fn content_bounds<'a>(
+    layers: impl IntoIterator<Item = &'a [u8]>,
+    (width, height): (u32, u32),
+) -> Option<TexelRect> {
+    let row_bytes = width as usize * 4;
+    // [min_x, min_y, max_x, max_y], inclusive
+    let mut bounds: Option<[u32; 4]> = None;
+    for pixels in layers {
+        for (y, row) in (0..height).zip(pixels.chunks_exact(row_bytes)) {
+            let is_opaque = |pixel: &[u8]| pixel[3] != 0;
+            let Some(first) = row.chunks_exact(4).position(is_opaque) else {
+                continue;
+            };
+            let last = row.chunks_exact(4).rposition(is_opaque).unwrap_or(first);
+            #[allow(clippy::cast_possible_truncation)]
+            let (first, last) = (first as u32, last as u32);
+            bounds = Some(
+                bounds.map_or([first, y, last, y], |[min_x, min_y, max_x, max_y]| {
+                    [
+                        min_x.min(first),
+                        min_y.min(y),
+                        max_x.max(last),
+                        max_y.max(y),
+                    ]
+                }),
+            );
+        }
+    }
+    bounds.map(|[min_x, min_y, max_x, max_y]| TexelRect {
+        origin: [min_x, min_y],
+        size: [max_x - min_x + 1, max_y - min_y + 1],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::doc_two_artboards;
+
+    #[test]
+    fn anchors_keep_their_side_in_place() {
+        let (from, to) = ((100, 50), (140, 30));
+        assert_eq!(Anchor::TopLeft.shift(from, to), [0, 0]);
+        assert_eq!(Anchor::Center.shift(from, to), [20, -10]);
+        assert_eq!(Anchor::BottomRight.shift(from, to), [40, -20]);
+        assert_eq!(Anchor::Left.shift(from, to), [0, -10]);
+        assert_eq!(Anchor::Top.shift(from, to), [20, 0]);
+        assert_eq!(
+            Anchor::Center.shift((10, 10), (13, 7)),
+            [1, -2],
+            "odd differences round towards the top left"
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn new_artboards_go_right_of_the_others() {
+        let mut document = doc_two_artboards();
+        assert_eq!(new_artboard_position(&document), [1200.0, 0.0]);
+        document.artboards.clear();
+        assert_eq!(new_artboard_position(&document), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn content_bounds_span_every_layer() {
+        let size = (8, 6);
+        let pixel = |pixels: &mut Vec<u8>, x: usize, y: usize| {
+            pixels[(y * 8 + x) * 4 + 3] = 1;
+        };
+        let blank = vec![0; 8 * 6 * 4];
+        assert_eq!(content_bounds([blank.as_slice()], size), None);
+
+        let mut lower = blank.clone();
+        pixel(&mut lower, 2, 1);
+        let mut upper = blank.clone();
+        pixel(&mut upper, 5, 4);
+        pixel(&mut upper, 3, 3);
+        assert_eq!(
+            content_bounds([lower.as_slice()], size),
+            Some(TexelRect {
+                origin: [2, 1],
+                size: [1, 1]
+            })
+        );
+        assert_eq!(
+            content_bounds([lower.as_slice(), upper.as_slice(), &blank], size),
+            Some(TexelRect {
+                origin: [2, 1],
+                size: [4, 4]
+            })
+        );
+    }
+}
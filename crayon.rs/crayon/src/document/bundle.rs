@@ -0,0 +1,388 @@
+//! Single-file `.crayon` document bundle.
+//!
+//! A chunk stream in the spirit of PNG, so it can be written and consumed front to back without seeking:
+//!
+//! ```text
+//! magic   b"CRAYONB\n"
+//! version u32 LE
+//! chunk*  tag [u8; 4] | len u32 LE | payload | crc32(tag ++ payload) u32 LE
+//! ```
+//!
+//! Chunks:
+//! - `DOC ` the `Document` manifest as JSON, always first. Thumbhashes ride along on its layers.
+//! - `PNG ` a named layer image: `name_len u16 LE | name | png bytes`, matched by `Layer::content_path`.
+//! - `END ` empty terminator, so a stream cut at a chunk boundary still reads as truncated.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+use crate::document::{Document, migrations::migrate_document};
+
+pub const BUNDLE_EXTENSION: &str = "crayon";
+
+const MAGIC: &[u8; 8] = b"CRAYONB\n";
+const BUNDLE_VERSION: u32 = 1;
+
+const TAG_DOCUMENT: [u8; 4] = *b"DOC ";
+const TAG_PNG: [u8; 4] = *b"PNG ";
+const TAG_END: [u8; 4] = *b"END ";
+
+/// Guards allocations against a corrupt length field.
+const MAX_CHUNK_LEN: u32 = 512 * 1024 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BundleError {
+    /// The stream doesn't start with the bundle magic.
+    NotABundle,
+    UnsupportedVersion(u32),
+    /// The stream ended before the `END ` chunk.
+    Truncated,
+    /// A chunk failed its checksum or is malformed.
+    Corrupt(String),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotABundle => write!(f, "not a .crayon bundle"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported .crayon bundle version {version}")
+            }
+            Self::Truncated => write!(f, ".crayon bundle is truncated"),
+            Self::Corrupt(reason) => write!(f, ".crayon bundle is corrupt: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+/// One decoded chunk, in stream order.
+pub enum BundleEntry {
+    Document(Document),
+    Png { name: String, bytes: Vec<u8> },
+}
+
+/// Streams `BundleEntry`s out of `reader` one chunk at a time.
+pub struct BundleReader<R: Read> {
+    reader: R,
+    seen_document: bool,
+    finished: bool,
+}
+
+impl<R: Read> BundleReader<R> {
+    /// Checks the magic and container version.
+    pub fn new(mut reader: R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(BundleError::NotABundle.into());
+        }
+        let version = read_u32(&mut reader)?;
+        if version != BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(version).into());
+        }
+
+        Ok(Self {
+            reader,
+            seen_document: false,
+            finished: false,
+        })
+    }
+
+    /// The next entry, or `None` once the `END ` chunk has been read.
+    pub fn next_entry(&mut self) -> anyhow::Result<Option<BundleEntry>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        loop {
+            let mut tag = [0u8; 4];
+            read_exact(&mut self.reader, &mut tag)?;
+            let len = read_u32(&mut self.reader)?;
+            if len > MAX_CHUNK_LEN {
+                return Err(corrupt(format!("chunk length {len} exceeds the limit")));
+            }
+            let mut payload = vec![0u8; len as usize];
+            read_exact(&mut self.reader, &mut payload)?;
+            let crc = read_u32(&mut self.reader)?;
+            if crc != chunk_crc(tag, &payload) {
+                return Err(corrupt(format!(
+                    "checksum mismatch in '{}' chunk",
+                    tag.escape_ascii()
+                )));
+            }
+
+            if !self.seen_document && tag != TAG_DOCUMENT {
+                return Err(corrupt("manifest is not the first chunk"));
+            }
+
+            let entry = match tag {
+                TAG_DOCUMENT => {
+                    if self.seen_document {
+                        return Err(corrupt("duplicate manifest"));
+                    }
+                    self.seen_document = true;
+                    let value = serde_json::from_slice(&payload)
+                        .map_err(|error| corrupt(format!("manifest: {error}")))?;
+                    let document = migrate_document(value)?;
+                    Some(BundleEntry::Document(document))
+                }
+                TAG_PNG => {
+                    let (name, bytes) = split_named(payload)?;
+                    Some(BundleEntry::Png { name, bytes })
+                }
+                TAG_END => {
+                    self.finished = true;
+                    None
+                }
+                // Unknown chunks are skipped so newer writers can add optional data.
+                _ => continue,
+            };
+            return Ok(entry);
+        }
+    }
+}
+
+/// A fully read bundle.
+pub struct Bundle {
+    pub document: Document,
+    /// Layer PNGs keyed by their `content_path`.
+    pub pngs: HashMap<String, Vec<u8>>,
+}
+
+pub fn read_bundle(reader: impl Read) -> anyhow::Result<Bundle> {
+    let mut reader = BundleReader::new(reader)?;
+    let mut document = None;
+    let mut pngs = HashMap::new();
+    while let Some(entry) = reader.next_entry()? {
+        match entry {
+            BundleEntry::Document(doc) => document = Some(doc),
+            BundleEntry::Png { name, bytes } => {
+                if pngs.insert(name.clone(), bytes).is_some() {
+                    return Err(corrupt(format!("duplicate entry '{name}'")));
+                }
+            }
+        }
+    }
+
+    let document = document.ok_or_else(|| corrupt("missing manifest"))?;
+    Ok(Bundle { document, pngs })
+}
+
+/// Writes `document` followed by the named PNGs.
+pub fn write_bundle<'a>(
+    mut writer: impl Write,
+    document: &Document,
+    pngs: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+) -> anyhow::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&BUNDLE_VERSION.to_le_bytes())?;
+    write_chunk(&mut writer, TAG_DOCUMENT, &serde_json::to_vec(document)?)?;
+
+    for (name, bytes) in pngs {
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| anyhow::anyhow!("bundle entry name is too long: {name}"))?;
+        let mut payload = Vec::with_capacity(2 + name.len() + bytes.len());
+        payload.extend_from_slice(&name_len.to_le_bytes());
+        payload.extend_from_slice(name.as_bytes());
+        payload.extend_from_slice(bytes);
+        write_chunk(&mut writer, TAG_PNG, &payload)?;
+    }
+
+    write_chunk(&mut writer, TAG_END, &[])?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_chunk(writer: &mut impl Write, tag: [u8; 4], payload: &[u8]) -> anyhow::Result<()> {
+    let len = u32::try_from(payload.len())
+        .ok()
+        .filter(|len| *len <= MAX_CHUNK_LEN)
+        .ok_or_else(|| anyhow::anyhow!("bundle chunk of {} bytes is too large", payload.len()))?;
+    writer.write_all(&tag)?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.write_all(&chunk_crc(tag, payload).to_le_bytes())?;
+    Ok(())
+}
+
+fn chunk_crc(tag: [u8; 4], payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&tag);
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn split_named(mut payload: Vec<u8>) -> anyhow::Result<(String, Vec<u8>)> {
+    let Some(name_len) = payload
+        .first_chunk::<2>()
+        .map(|len| u16::from_le_bytes(*len))
+    else {
+        return Err(corrupt("entry without a name"));
+    };
+    let name_end = 2 + name_len as usize;
+    if payload.len() < name_end {
+        return Err(corrupt("entry name runs past the chunk"));
+    }
+    let name = std::str::from_utf8(&payload[2..name_end])
+        .map_err(|_| corrupt("entry name is not utf-8"))?
+        .to_string();
+    payload.drain(..name_end);
+    Ok((name, payload))
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> anyhow::Result<()> {
+    reader.read_exact(buf).map_err(|error| {
+        if error.kind() == std::io::ErrorKind::UnexpectedEof {
+            BundleError::Truncated.into()
+        } else {
+            anyhow::Error::from(error)
+        }
+    })
+}
+
+fn read_u32(reader: &mut impl Read) -> anyhow::Result<u32> {
+    let mut bytes = [0u8; 4];
+    read_exact(reader, &mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn corrupt(reason: impl Into<String>) -> anyhow::Error {
+    BundleError::Corrupt(reason.into()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixtures::doc_two_artboards;
+
+    fn sample_bundle() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_bundle(
+            &mut bytes,
+            &doc_two_artboards(),
+            [
+                ("layer-2.png", [1u8, 2, 3].as_slice()),
+                ("layer-4.png", [4u8; 10].as_slice()),
+            ],
+        )
+        .unwrap();
+        bytes
+    }
+
+    fn bundle_error(result: anyhow::Result<Bundle>) -> BundleError {
+        let error = result.err().expect("bundle should be rejected");
+        error
+            .downcast::<BundleError>()
+            .expect("a specific bundle error")
+    }
+
+    #[test]
+    fn round_trip() {
+        let bundle = read_bundle(sample_bundle().as_slice()).unwrap();
+        assert_eq!(bundle.document, doc_two_artboards());
+        assert_eq!(bundle.pngs.len(), 2);
+        assert_eq!(bundle.pngs["layer-2.png"], [1, 2, 3]);
+        assert_eq!(bundle.pngs["layer-4.png"], [4; 10]);
+    }
+
+    #[test]
+    fn streams_manifest_before_layers() {
+        let bytes = sample_bundle();
+        let mut reader = BundleReader::new(bytes.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next_entry().unwrap(),
+            Some(BundleEntry::Document(_))
+        ));
+        assert!(matches!(
+            reader.next_entry().unwrap(),
+            Some(BundleEntry::Png { name, .. }) if name == "layer-2.png"
+        ));
+        assert!(reader.next_entry().unwrap().is_some());
+        assert!(reader.next_entry().unwrap().is_none());
+        assert!(reader.next_entry().unwrap().is_none(), "stays finished");
+    }
+
+    #[test]
+    fn rejects_truncation_at_every_length() {
+        let bytes = sample_bundle();
+        for len in 0..bytes.len() {
+            let error = bundle_error(read_bundle(&bytes[..len]));
+            assert!(
+                matches!(error, BundleError::Truncated | BundleError::NotABundle),
+                "cut at {len}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_flipped_payload_byte() {
+        let mut bytes = sample_bundle();
+        // `END ` is 12 bytes, preceded by the last PNG chunk's crc.
+        let last_png_byte = bytes.len() - 12 - 4 - 1;
+        bytes[last_png_byte] ^= 0xff;
+        assert!(matches!(
+            bundle_error(read_bundle(bytes.as_slice())),
+            BundleError::Corrupt(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_foreign_files_and_versions() {
+        assert_eq!(
+            bundle_error(read_bundle(b"{\"version\": 1}".as_slice())),
+            BundleError::NotABundle
+        );
+
+        let mut bytes = sample_bundle();
+        bytes[MAGIC.len()] = 99;
+        assert_eq!(
+            bundle_error(read_bundle(bytes.as_slice())),
+            BundleError::UnsupportedVersion(99)
+        );
+    }
+
+    #[test]
+    fn rejects_layers_before_manifest() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        write_chunk(&mut bytes, TAG_PNG, &[0, 0]).unwrap();
+        assert!(matches!(
+            bundle_error(read_bundle(bytes.as_slice())),
+            BundleError::Corrupt(_)
+        ));
+    }
+
+    #[test]
+    fn skips_unknown_chunks() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        write_chunk(
+            &mut bytes,
+            TAG_DOCUMENT,
+            &serde_json::to_vec(&doc_two_artboards()).unwrap(),
+        )
+        .unwrap();
+        write_chunk(&mut bytes, *b"XTRA", b"from the future").unwrap();
+        write_chunk(&mut bytes, TAG_END, &[]).unwrap();
+        assert!(read_bundle(bytes.as_slice()).unwrap().pngs.is_empty());
+    }
+
+    #[test]
+    fn skips_long_runs_of_unknown_chunks() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        write_chunk(
+            &mut bytes,
+            TAG_DOCUMENT,
+            &serde_json::to_vec(&doc_two_artboards()).unwrap(),
+        )
+        .unwrap();
+        for _ in 0..200_000 {
+            write_chunk(&mut bytes, *b"PADD", &[]).unwrap();
+        }
+        write_chunk(&mut bytes, TAG_END, &[]).unwrap();
+        assert!(read_bundle(bytes.as_slice()).unwrap().pngs.is_empty());
+    }
+}
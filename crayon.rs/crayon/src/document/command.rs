@@ -0,0 +1,897 @@
+//! Undoable structural edits.
+//!
+//! Every edit to the document's structure is a `Command`. Applying one returns the command that reverts it,
+//! so undo and redo are both just applying whatever the last step returned.
+//! The GPU side of an edit is queued as `GpuOp`s, pixels it destroys are kept by `History`.
+
+use crate::{
+    document::{
+        Artboard, ArtboardId, Document, GroupId, Layer, LayerGroup, LayerId, LayerNode, NodeId,
+        VectorStroke,
+    },
+    resources::document_state::GpuOp,
+};
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    /// Empties the layers, pixels and vector strokes alike.
+    ClearLayers(Vec<LayerId>),
+    /// Replaces a layer's vector strokes, `None` making it a raster layer.
+    SetStrokes {
+        layer_id: LayerId,
+        strokes: Option<Vec<VectorStroke>>,
+    },
+    /// Inserts a stroke into a vector layer at `index`.
+    InsertStroke {
+        layer_id: LayerId,
+        index: usize,
+        stroke: VectorStroke,
+    },
+    RemoveStroke {
+        layer_id: LayerId,
+        index: usize,
+    },
+    SetVisible {
+        node: NodeId,
+        visible: bool,
+    },
+    SetLocked {
+        node: NodeId,
+        locked: bool,
+    },
+    Rename {
+        node: NodeId,
+        name: String,
+    },
+    /// Inserts a node at `index` among `parent`'s children, the artboard's own stack for `None`.
+    /// Its layers get blank textures. `index` is clamped to the children.
+    InsertNode {
+        artboard_id: ArtboardId,
+        parent: Option<GroupId>,
+        index: usize,
+        node: LayerNode,
+    },
+    /// Takes a node out of whichever artboard holds it, freeing its layers' textures.
+    RemoveNode {
+        node: NodeId,
+    },
+    /// Inserts a copy of a node right above it, with fresh ids and its layers' pixels copied.
+    DuplicateNode {
+        node: NodeId,
+    },
+    /// Moves a node from whichever artboard holds it to `index` among `parent`'s children in `artboard_id`,
+    /// the artboard's own stack for `None`.
+    /// `index` counts the children once the node is taken out, and is clamped to them.
+    /// Layers moving to an artboard of another size have their textures resized.
+    MoveNode {
+        artboard_id: ArtboardId,
+        node: NodeId,
+        parent: Option<GroupId>,
+        index: usize,
+    },
+    MoveArtboard {
+        artboard_id: ArtboardId,
+        position: [f32; 2],
+    },
+    /// Inserts an artboard at `index` in the drawing order, clamped to the artboards.
+    /// Its layers get blank textures.
+    InsertArtboard {
+        index: usize,
+        artboard: Artboard,
+    },
+    /// Takes an artboard out of the document, freeing its layers' textures.
+    RemoveArtboard {
+        artboard_id: ArtboardId,
+    },
+    /// Resizes an artboard to `size`, its content moving by `shift` pixels within it and staying put in the world.
+    /// Layer textures are reallocated, keeping the pixels that still fit.
+    ResizeArtboard {
+        artboard_id: ArtboardId,
+        size: [f32; 2],
+        shift: [i32; 2],
+    },
+    /// Several commands as one step, reverted back to front.
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    /// Applies the command to `document`, queueing the `GpuOp`s it needs on `gpu_ops`.
+    /// Returns the command reverting it, `None` when there was nothing to apply it to.
+    pub fn apply(self, document: &mut Document, gpu_ops: &mut Vec<GpuOp>) -> Option<Command> {
+        match self {
+            Self::ClearLayers(layer_ids) => clear_layers(document, layer_ids, gpu_ops),
+            Self::SetStrokes { layer_id, strokes } => {
+                let layer = document.find_layer_mut(layer_id)?;
+                let previous = std::mem::replace(&mut layer.strokes, strokes);
+                Some(Self::SetStrokes {
+                    layer_id,
+                    strokes: previous,
+                })
+            }
+            Self::InsertStroke {
+                layer_id,
+                index,
+                stroke,
+            } => {
+                let strokes = document.find_layer_mut(layer_id)?.strokes.as_mut()?;
+                let index = index.min(strokes.len());
+                strokes.insert(index, stroke);
+                Some(Self::RemoveStroke { layer_id, index })
+            }
+            Self::RemoveStroke { layer_id, index } => remove_stroke(document, layer_id, index),
+            Self::SetVisible { node, visible } => {
+                let previous = replace_flag(
+                    document,
+                    node,
+                    visible,
+                    |layer| &mut layer.visible,
+                    |group| &mut group.visible,
+                )?;
+                Some(Self::SetVisible {
+                    node,
+                    visible: previous,
+                })
+            }
+            Self::SetLocked { node, locked } => {
+                let previous = replace_flag(
+                    document,
+                    node,
+                    locked,
+                    |layer| &mut layer.locked,
+                    |group| &mut group.locked,
+                )?;
+                Some(Self::SetLocked {
+                    node,
+                    locked: previous,
+                })
+            }
+            Self::Rename { node, name } => {
+                let previous = match node {
+                    NodeId::Layer(id) => {
+                        std::mem::replace(&mut document.find_layer_mut(id)?.name, name)
+                    }
+                    NodeId::Group(id) => {
+                        std::mem::replace(&mut document.find_group_mut(id)?.name, name)
+                    }
+                };
+                Some(Self::Rename {
+                    node,
+                    name: previous,
+                })
+            }
+            Self::InsertNode {
+                artboard_id,
+                parent,
+                index,
+                node,
+            } => insert_node(document, artboard_id, parent, index, node, gpu_ops),
+            Self::RemoveNode { node } => remove_node(document, node, gpu_ops),
+            Self::DuplicateNode { node } => duplicate_node(document, node, gpu_ops),
+            Self::MoveNode {
+                artboard_id,
+                node,
+                parent,
+                index,
+            } => move_node(document, artboard_id, node, parent, index, gpu_ops),
+            Self::MoveArtboard {
+                artboard_id,
+                position,
+            } => move_artboard(document, artboard_id, position),
+            Self::InsertArtboard { index, artboard } => {
+                Some(insert_artboard(document, index, artboard, gpu_ops))
+            }
+            Self::RemoveArtboard { artboard_id } => remove_artboard(document, artboard_id, gpu_ops),
+            Self::ResizeArtboard {
+                artboard_id,
+                size,
+                shift,
+            } => resize_artboard(document, artboard_id, size, shift, gpu_ops),
+            Self::Batch(commands) => {
+                let reverts = commands
+                    .into_iter()
+                    .filter_map(|command| command.apply(document, gpu_ops))
+                    .collect();
+                batch(reverts)
+            }
+        }
+    }
+}
+
+fn remove_stroke(document: &mut Document, layer_id: LayerId, index: usize) -> Option<Command> {
+    let strokes = document.find_layer_mut(layer_id)?.strokes.as_mut()?;
+    if index >= strokes.len() {
+        return None;
+    }
+    let stroke = strokes.remove(index);
+    Some(Command::InsertStroke {
+        layer_id,
+        index,
+        stroke,
+    })
+}
+
+/// The pixels come back from what `History` kept, only vector strokes need reverting.
+fn clear_layers(
+    document: &mut Document,
+    layer_ids: Vec<LayerId>,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let mut reverts = Vec::new();
+    let mut cleared = false;
+    for layer_id in layer_ids {
+        let Some(layer) = document.find_layer_mut(layer_id) else {
+            continue;
+        };
+        cleared = true;
+        gpu_ops.push(GpuOp::ClearLayer { layer_id });
+        if let Some(strokes) = layer.strokes.as_mut().map(std::mem::take) {
+            reverts.push(Command::SetStrokes {
+                layer_id,
+                strokes: Some(strokes),
+            });
+        }
+    }
+    reverts.reverse();
+    cleared.then_some(Command::Batch(reverts))
+}
+
+fn insert_node(
+    document: &mut Document,
+    artboard_id: ArtboardId,
+    parent: Option<GroupId>,
+    index: usize,
+    node: LayerNode,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let artboard = document.artboard_mut(artboard_id)?;
+    let size = artboard.pixel_size();
+    let children = artboard.children_mut(parent)?;
+    gpu_ops.extend(node.iter_layers().map(|layer| GpuOp::CreateLayer {
+        layer_id: layer.id,
+        size,
+    }));
+    let id = node.id();
+    children.insert(index.min(children.len()), node);
+    Some(Command::RemoveNode { node: id })
+}
+
+fn remove_node(document: &mut Document, node: NodeId, gpu_ops: &mut Vec<GpuOp>) -> Option<Command> {
+    let (artboard_id, parent, index) = document.locate(node)?;
+    let taken = document
+        .artboard_mut(artboard_id)?
+        .children_mut(parent)?
+        .remove(index);
+    gpu_ops.extend(
+        taken
+            .iter_layers()
+            .map(|layer| GpuOp::DeleteLayer { layer_id: layer.id }),
+    );
+    Some(Command::InsertNode {
+        artboard_id,
+        parent,
+        index,
+        node: taken,
+    })
+}
+
+/// The copy sits right above the node, its name marked as a copy at the top level only.
+fn duplicate_node(
+    document: &mut Document,
+    node: NodeId,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let (artboard_id, parent, index) = document.locate(node)?;
+    let mut copy = document
+        .artboard_mut(artboard_id)?
+        .children_mut(parent)?
+        .get(index)?
+        .clone();
+    let sources: Vec<LayerId> = copy.iter_layers().map(|layer| layer.id).collect();
+    reassign_ids(document, &mut copy);
+    match &mut copy {
+        LayerNode::Layer(layer) => layer.name.push_str(" copy"),
+        LayerNode::Group(group) => group.name.push_str(" copy"),
+    }
+    gpu_ops.extend(
+        sources
+            .into_iter()
+            .zip(copy.iter_layers())
+            .map(|(source, layer)| GpuOp::CopyLayer {
+                source,
+                layer_id: layer.id,
+            }),
+    );
+
+    let copy_id = copy.id();
+    document
+        .artboard_mut(artboard_id)?
+        .children_mut(parent)?
+        .insert(index + 1, copy);
+    Some(Command::RemoveNode { node: copy_id })
+}
+
+/// Gives the node and everything in it ids of its own.
+fn reassign_ids(document: &mut Document, node: &mut LayerNode) {
+    match node {
+        LayerNode::Layer(layer) => layer.id = document.alloc_layer_id(),
+        LayerNode::Group(group) => {
+            group.id = document.alloc_group_id();
+            for child in &mut group.children {
+                reassign_ids(document, child);
+            }
+        }
+    }
+}
+
+fn move_node(
+    document: &mut Document,
+    artboard_id: ArtboardId,
+    node: NodeId,
+    parent: Option<GroupId>,
+    index: usize,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let size = document.artboard(artboard_id)?.pixel_size();
+    let (from_artboard, from_parent, from_index) = document.locate(node)?;
+    let from = document.artboard_mut(from_artboard)?;
+    let from_size = from.pixel_size();
+    let taken = from.children_mut(from_parent)?.remove(from_index);
+    // a missing parent, or one inside the moved group itself
+    let Some(children) = document
+        .artboard_mut(artboard_id)
+        .and_then(|artboard| artboard.children_mut(parent))
+    else {
+        document
+            .artboard_mut(from_artboard)
+            .and_then(|artboard| artboard.children_mut(from_parent))
+            .expect("the node was just taken from there")
+            .insert(from_index, taken);
+        return None;
+    };
+    if size != from_size {
+        gpu_ops.extend(taken.iter_layers().map(|layer| GpuOp::ResizeLayer {
+            layer_id: layer.id,
+            size,
+            shift: [0, 0],
+        }));
+    }
+    children.insert(index.min(children.len()), taken);
+    Some(Command::MoveNode {
+        artboard_id: from_artboard,
+        node,
+        parent: from_parent,
+        index: from_index,
+    })
+}
+
+/// Sets one of a layer's or group's flags, picked by `layer_flag` or `group_flag`. Returns what it was.
+fn replace_flag(
+    document: &mut Document,
+    node: NodeId,
+    value: bool,
+    layer_flag: fn(&mut Layer) -> &mut bool,
+    group_flag: fn(&mut LayerGroup) -> &mut bool,
+) -> Option<bool> {
+    let flag = match node {
+        NodeId::Layer(id) => layer_flag(document.find_layer_mut(id)?),
+        NodeId::Group(id) => group_flag(document.find_group_mut(id)?),
+    };
+    Some(std::mem::replace(flag, value))
+}
+
+fn move_artboard(
+    document: &mut Document,
+    artboard_id: ArtboardId,
+    position: [f32; 2],
+) -> Option<Command> {
+    let artboard = document.artboard_mut(artboard_id)?;
+    let previous = std::mem::replace(&mut artboard.position, position);
+    Some(Command::MoveArtboard {
+        artboard_id,
+        position: previous,
+    })
+}
+
+fn insert_artboard(
+    document: &mut Document,
+    index: usize,
+    artboard: Artboard,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Command {
+    let artboard_id = artboard.id;
+    let size = artboard.pixel_size();
+    gpu_ops.extend(artboard.iter_layers().map(|layer| GpuOp::CreateLayer {
+        layer_id: layer.id,
+        size,
+    }));
+    let index = index.min(document.artboards.len());
+    document.artboards.insert(index, artboard);
+    Command::RemoveArtboard { artboard_id }
+}
+
+fn remove_artboard(
+    document: &mut Document,
+    artboard_id: ArtboardId,
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let index = document
+        .artboards
+        .iter()
+        .position(|artboard| artboard.id == artboard_id)?;
+    let artboard = document.artboards.remove(index);
+    gpu_ops.extend(
+        artboard
+            .iter_layers()
+            .map(|layer| GpuOp::DeleteLayer { layer_id: layer.id }),
+    );
+    Some(Command::InsertArtboard { index, artboard })
+}
+
+/// Vector strokes move along with the pixels, so they rasterize to what the layer holds.
+fn resize_artboard(
+    document: &mut Document,
+    artboard_id: ArtboardId,
+    size: [f32; 2],
+    shift: [i32; 2],
+    gpu_ops: &mut Vec<GpuOp>,
+) -> Option<Command> {
+    let artboard = document.artboard_mut(artboard_id)?;
+    let previous = std::mem::replace(&mut artboard.size, size);
+    #[allow(clippy::cast_precision_loss)]
+    let shift_px = [shift[0] as f32, shift[1] as f32];
+    artboard.position[0] -= shift_px[0];
+    artboard.position[1] -= shift_px[1];
+
+    let pixel_size = artboard.pixel_size();
+    for layer in artboard.layers_mut() {
+        let points = layer
+            .strokes
+            .iter_mut()
+            .flatten()
+            .flat_map(|stroke| &mut stroke.points);
+        for point in points {
+            point.position[0] += shift_px[0];
+            point.position[1] += shift_px[1];
+        }
+        gpu_ops.push(GpuOp::ResizeLayer {
+            layer_id: layer.id,
+            size: pixel_size,
+            shift,
+        });
+    }
+    Some(Command::ResizeArtboard {
+        artboard_id,
+        size: previous,
+        shift: [-shift[0], -shift[1]],
+    })
+}
+
+/// Reverts `applied` back to front, `None` when nothing was applied.
+fn batch(mut applied: Vec<Command>) -> Option<Command> {
+    if applied.is_empty() {
+        return None;
+    }
+    applied.reverse();
+    Some(Command::Batch(applied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{BrushTip, LayerGroup, LayerNode, StrokeMode, VectorPoint};
+    use crate::testing::fixtures::doc_two_artboards;
+
+    fn stroke(x: f32) -> VectorStroke {
+        VectorStroke {
+            color: [0.0, 0.0, 0.0, 1.0],
+            mode: StrokeMode::Paint,
+            tip: BrushTip::Round,
+            hardness: 0.4,
+            opacity: 1.0,
+            points: vec![VectorPoint {
+                position: [x, 10.0],
+                radius: 4.0,
+                opacity: 1.0,
+                rotation: 0.0,
+            }],
+        }
+    }
+
+    /// Applies `command` and then what reverts it, checking the document round trips.
+    fn assert_reverts(document: &mut Document, command: Command) -> Document {
+        let before = document.clone();
+        let mut gpu_ops = Vec::new();
+        let revert = command.apply(document, &mut gpu_ops).expect("applies");
+        let applied = document.clone();
+        assert_ne!(applied, before);
+        revert.apply(document, &mut gpu_ops).expect("reverts");
+        assert_eq!(*document, before);
+        applied
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn visibility_locks_renames_and_artboard_moves_revert() {
+        let mut document = doc_two_artboards();
+        let applied = assert_reverts(
+            &mut document,
+            Command::SetVisible {
+                node: NodeId::Layer(LayerId(2)),
+                visible: false,
+            },
+        );
+        assert!(!applied.find_layer(LayerId(2)).unwrap().1.visible);
+
+        let applied = assert_reverts(
+            &mut document,
+            Command::SetLocked {
+                node: NodeId::Layer(LayerId(2)),
+                locked: true,
+            },
+        );
+        assert!(applied.is_layer_locked(LayerId(2)));
+
+        let applied = assert_reverts(
+            &mut document,
+            Command::Rename {
+                node: NodeId::Layer(LayerId(4)),
+                name: "Ink".to_string(),
+            },
+        );
+        assert_eq!(applied.find_layer(LayerId(4)).unwrap().1.name, "Ink");
+
+        let applied = assert_reverts(
+            &mut document,
+            Command::MoveArtboard {
+                artboard_id: ArtboardId(3),
+                position: [-50.0, 20.0],
+            },
+        );
+        assert_eq!(applied.artboards[1].position, [-50.0, 20.0]);
+    }
+
+    #[test]
+    fn moving_nodes_reverts_into_place() {
+        let mut document = doc_two_artboards();
+        let group_id = document.alloc_group_id();
+        let layer_id = document.alloc_layer_id();
+        let mut layer = document.find_layer(LayerId(2)).unwrap().1.clone();
+        layer.id = layer_id;
+        let artboard = &mut document.artboards[0];
+        artboard.layers.push(layer.into());
+        artboard.layers.push(
+            LayerGroup {
+                id: group_id,
+                name: "Group".to_string(),
+                visible: true,
+                locked: false,
+                opacity: 1.0,
+                blend_mode: crate::document::BlendMode::Normal,
+                children: Vec::new(),
+            }
+            .into(),
+        );
+
+        let applied = assert_reverts(
+            &mut document,
+            Command::MoveNode {
+                artboard_id: ArtboardId(1),
+                node: NodeId::Layer(LayerId(2)),
+                parent: Some(group_id),
+                index: 0,
+            },
+        );
+        assert_eq!(
+            applied.artboards[0].locate(NodeId::Layer(LayerId(2))),
+            Some((Some(group_id), 0))
+        );
+
+        let applied = assert_reverts(
+            &mut document,
+            Command::MoveNode {
+                artboard_id: ArtboardId(1),
+                node: NodeId::Layer(LayerId(2)),
+                parent: None,
+                index: usize::MAX,
+            },
+        );
+        let ids: Vec<NodeId> = applied.artboards[0]
+            .layers
+            .iter()
+            .map(LayerNode::id)
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                NodeId::Layer(layer_id),
+                NodeId::Group(group_id),
+                NodeId::Layer(LayerId(2))
+            ]
+        );
+
+        let into_itself = Command::MoveNode {
+            artboard_id: ArtboardId(1),
+            node: NodeId::Group(group_id),
+            parent: Some(group_id),
+            index: 0,
+        };
+        let before = document.clone();
+        assert_eq!(into_itself.apply(&mut document, &mut Vec::new()), None);
+        assert_eq!(document, before);
+    }
+
+    #[test]
+    fn clearing_queues_gpu_ops_and_keeps_vector_strokes_to_revert() {
+        let mut document = doc_two_artboards();
+        document.find_layer_mut(LayerId(4)).unwrap().strokes = Some(vec![stroke(1.0), stroke(2.0)]);
+        let before = document.clone();
+
+        let mut gpu_ops = Vec::new();
+        let revert = Command::ClearLayers(vec![LayerId(2), LayerId(4), LayerId(99)])
+            .apply(&mut document, &mut gpu_ops)
+            .unwrap();
+        assert!(matches!(
+            gpu_ops.as_slice(),
+            [
+                GpuOp::ClearLayer {
+                    layer_id: LayerId(2)
+                },
+                GpuOp::ClearLayer {
+                    layer_id: LayerId(4)
+                }
+            ]
+        ));
+        assert_eq!(
+            document.find_layer(LayerId(4)).unwrap().1.strokes,
+            Some(Vec::new())
+        );
+
+        // pixels come back from `History`, only the strokes from the revert
+        let mut revert_ops = Vec::new();
+        let redo = revert.apply(&mut document, &mut revert_ops).unwrap();
+        assert!(revert_ops.is_empty());
+        assert_eq!(document, before);
+        redo.apply(&mut document, &mut revert_ops).unwrap();
+        assert!(revert_ops.is_empty(), "redone from the pixels too");
+        assert_eq!(
+            document.find_layer(LayerId(4)).unwrap().1.strokes,
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn strokes_insert_and_remove_by_index() {
+        let mut document = doc_two_artboards();
+        document.find_layer_mut(LayerId(2)).unwrap().strokes = Some(vec![stroke(1.0), stroke(3.0)]);
+        assert_reverts(
+            &mut document,
+            Command::InsertStroke {
+                layer_id: LayerId(2),
+                index: 1,
+                stroke: stroke(2.0),
+            },
+        );
+        assert_reverts(
+            &mut document,
+            Command::RemoveStroke {
+                layer_id: LayerId(2),
+                index: 0,
+            },
+        );
+        assert_eq!(
+            Command::RemoveStroke {
+                layer_id: LayerId(2),
+                index: 2
+            }
+            .apply(&mut document, &mut Vec::new()),
+            None
+        );
+        assert_eq!(
+            Command::InsertStroke {
+                layer_id: LayerId(4),
+                index: 0,
+                stroke: stroke(0.0)
+            }
+            .apply(&mut document, &mut Vec::new()),
+            None,
+            "raster layers have no strokes"
+        );
+    }
+
+    #[test]
+    fn inserted_and_removed_layers_queue_their_textures() {
+        let mut document = doc_two_artboards();
+        let layer_id = document.alloc_layer_id();
+        let insert = Command::InsertNode {
+            artboard_id: ArtboardId(3),
+            parent: None,
+            index: 0,
+            node: crate::document::Layer::new(layer_id, "New".to_string()).into(),
+        };
+        let before = document.clone();
+        let mut gpu_ops = Vec::new();
+        let remove = insert.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::CreateLayer {
+                layer_id,
+                size: (400, 300)
+            }]
+        );
+        assert_eq!(
+            document.locate(NodeId::Layer(layer_id)),
+            Some((ArtboardId(3), None, 0))
+        );
+
+        gpu_ops.clear();
+        remove.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(gpu_ops, [GpuOp::DeleteLayer { layer_id }]);
+        assert_eq!(document, before);
+    }
+
+    #[test]
+    fn duplicated_groups_get_fresh_ids_and_copied_layers() {
+        let mut document = doc_two_artboards();
+        let group_id = document.alloc_group_id();
+        let layer = document.artboards[0].layers.remove(0);
+        document.artboards[0].layers.push(
+            LayerGroup {
+                id: group_id,
+                name: "Group".to_string(),
+                visible: true,
+                locked: false,
+                opacity: 1.0,
+                blend_mode: crate::document::BlendMode::Normal,
+                children: vec![layer],
+            }
+            .into(),
+        );
+
+        let mut gpu_ops = Vec::new();
+        let revert = Command::DuplicateNode {
+            node: NodeId::Group(group_id),
+        }
+        .apply(&mut document, &mut gpu_ops)
+        .unwrap();
+        let LayerNode::Group(copy) = &document.artboards[0].layers[1] else {
+            panic!("the copy sits above the group");
+        };
+        assert_eq!(copy.name, "Group copy");
+        assert_eq!(copy.id, GroupId(6));
+        assert_eq!(copy.children[0].id(), NodeId::Layer(LayerId(7)));
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::CopyLayer {
+                source: LayerId(2),
+                layer_id: LayerId(7)
+            }]
+        );
+        assert_eq!(
+            revert,
+            Command::RemoveNode {
+                node: NodeId::Group(GroupId(6))
+            }
+        );
+    }
+
+    #[test]
+    fn moving_across_artboards_resizes_the_layers() {
+        let mut document = doc_two_artboards();
+        let before = document.clone();
+        let mut gpu_ops = Vec::new();
+        let revert = Command::MoveNode {
+            artboard_id: ArtboardId(3),
+            node: NodeId::Layer(LayerId(2)),
+            parent: None,
+            index: 0,
+        }
+        .apply(&mut document, &mut gpu_ops)
+        .unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::ResizeLayer {
+                layer_id: LayerId(2),
+                size: (400, 300),
+                shift: [0, 0]
+            }]
+        );
+        assert!(document.artboards[0].layers.is_empty());
+        assert_eq!(
+            document.locate(NodeId::Layer(LayerId(2))),
+            Some((ArtboardId(3), None, 0))
+        );
+
+        gpu_ops.clear();
+        revert.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::ResizeLayer {
+                layer_id: LayerId(2),
+                size: (600, 400),
+                shift: [0, 0]
+            }]
+        );
+        assert_eq!(document, before);
+    }
+
+    #[test]
+    fn removed_artboards_come_back_in_place() {
+        let mut document = doc_two_artboards();
+        let before = document.clone();
+        let mut gpu_ops = Vec::new();
+        let revert = Command::RemoveArtboard {
+            artboard_id: ArtboardId(1),
+        }
+        .apply(&mut document, &mut gpu_ops)
+        .unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::DeleteLayer {
+                layer_id: LayerId(2)
+            }]
+        );
+        assert_eq!(document.artboards.len(), 1);
+
+        gpu_ops.clear();
+        revert.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::CreateLayer {
+                layer_id: LayerId(2),
+                size: (600, 400)
+            }]
+        );
+        assert_eq!(document, before);
+        assert_eq!(
+            Command::RemoveArtboard {
+                artboard_id: ArtboardId(99)
+            }
+            .apply(&mut document, &mut gpu_ops),
+            None
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn resized_artboards_shift_their_content_and_stay_put() {
+        let mut document = doc_two_artboards();
+        document.find_layer_mut(LayerId(4)).unwrap().strokes = Some(vec![stroke(30.0)]);
+        let resize = Command::ResizeArtboard {
+            artboard_id: ArtboardId(3),
+            size: [500.0, 200.0],
+            shift: [50, -20],
+        };
+
+        let before = document.clone();
+        let mut gpu_ops = Vec::new();
+        let revert = resize.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(
+            gpu_ops,
+            [GpuOp::ResizeLayer {
+                layer_id: LayerId(4),
+                size: (500, 200),
+                shift: [50, -20]
+            }]
+        );
+        assert_eq!(
+            revert,
+            Command::ResizeArtboard {
+                artboard_id: ArtboardId(3),
+                size: [400.0, 300.0],
+                shift: [-50, 20]
+            }
+        );
+        let artboard = document.artboard(ArtboardId(3)).unwrap();
+        assert_eq!(artboard.position, [650.0, 120.0]);
+        let strokes = artboard
+            .layer(LayerId(4))
+            .unwrap()
+            .strokes
+            .as_ref()
+            .unwrap();
+        assert_eq!(strokes[0].points[0].position, [80.0, -10.0]);
+
+        revert.apply(&mut document, &mut gpu_ops).unwrap();
+        assert_eq!(document, before);
+    }
+}
@@ -0,0 +1,412 @@
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    document::{Artboard, ArtboardId, Document, Layer, LayerId, saver::unpremultiply_alpha},
+    resources::scene_renderer::SceneRenderer,
+    texture::CRTexture,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportOptions {
+    /// Output pixels per artboard pixel.
+    pub scale: u32,
+    /// Flatten onto the white artboard background, else keep the layers' transparency.
+    pub background: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            background: true,
+        }
+    }
+}
+
+/// Flattens one artboard offscreen, returning premultiplied RGBA8 and its `(width, height)`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_artboard_pixels(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut SceneRenderer,
+    document: &Document,
+    artboard_id: ArtboardId,
+    options: ExportOptions,
+) -> anyhow::Result<(Vec<u8>, (u32, u32))> {
+    let artboard = document
+        .artboard(artboard_id)
+        .with_context(|| format!("no artboard {} in document", artboard_id.0))?;
+    if options.scale == 0 {
+        bail!("export scale must be at least 1");
+    }
+
+    let (width, height) = artboard.pixel_size();
+    let max_dim = device.limits().max_texture_dimension_2d;
+    let size = match (
+        width.checked_mul(options.scale),
+        height.checked_mul(options.scale),
+    ) {
+        (Some(scaled_width), Some(scaled_height))
+            if scaled_width <= max_dim && scaled_height <= max_dim =>
+        {
+            (scaled_width, scaled_height)
+        }
+        _ => bail!(
+            "{width}x{height} export at scale {} exceeds the {max_dim}px texture limit",
+            options.scale
+        ),
+    };
+
+    // vector layers are re-rasterized at the export scale rather than magnified, then put back as they were shown
+    let shown_scales: Vec<(&Layer, u32)> = artboard
+        .iter_layers()
+        .filter(|layer| layer.is_vector())
+        .map(|layer| (layer, scene.vector_display_scale(layer.id)))
+        .collect();
+    for (layer, _) in &shown_scales {
+        scene.set_vector_display_scale(device, queue, layer, options.scale);
+    }
+
+    let target = CRTexture::create_render_texture(device, size, scene.format(), "Export Target");
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Export Encoder"),
+    });
+    scene.render_artboard(
+        device,
+        queue,
+        &mut encoder,
+        &target.view,
+        artboard,
+        options.scale,
+        options.background,
+    );
+    queue.submit([encoder.finish()]);
+
+    for (layer, scale) in shown_scales {
+        scene.set_vector_display_scale(device, queue, layer, scale);
+    }
+
+    let pixels = scene.read_pixels(device, queue, &target.texture, size)?;
+    Ok((pixels, size))
+}
+
+/// The flattened artboard in straight alpha without its background, as layered formats store their composite.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_straight_composite(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut SceneRenderer,
+    document: &Document,
+    artboard_id: ArtboardId,
+) -> anyhow::Result<(Vec<u8>, (u32, u32))> {
+    let options = ExportOptions {
+        background: false,
+        ..ExportOptions::default()
+    };
+    let (mut pixels, size) =
+        render_artboard_pixels(device, queue, scene, document, artboard_id, options)?;
+    unpremultiply_alpha(&mut pixels);
+    Ok((pixels, size))
+}
+
+/// Reads every layer of `artboard` back as artboard sized, straight-alpha RGBA8.
+/// Layers without a texture read as transparent.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_straight_layers(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &SceneRenderer,
+    artboard: &Artboard,
+) -> anyhow::Result<std::collections::HashMap<LayerId, Vec<u8>>> {
+    let (width, height) = artboard.pixel_size();
+    let mut layers = std::collections::HashMap::new();
+    for layer in artboard.iter_layers() {
+        let mut pixels = scene
+            .read_layer_pixels(device, queue, layer.id)
+            .with_context(|| format!("reading back layer {}", layer.id.0))?
+            .unwrap_or_else(|| vec![0; width as usize * height as usize * 4]);
+        unpremultiply_alpha(&mut pixels);
+        layers.insert(layer.id, pixels);
+    }
+    Ok(layers)
+}
+
+/// Flattens one artboard and writes it to `path` as a straight-alpha PNG.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_artboard_png(
+    path: &std::path::Path,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut SceneRenderer,
+    document: &Document,
+    artboard_id: ArtboardId,
+    options: ExportOptions,
+) -> anyhow::Result<()> {
+    use image::ImageEncoder;
+
+    let (mut pixels, (width, height)) =
+        render_artboard_pixels(device, queue, scene, document, artboard_id, options)?;
+    unpremultiply_alpha(&mut pixels);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let file =
+        std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    image::codecs::png::PngEncoder::new(std::io::BufWriter::new(file))
+        .write_image(&pixels, width, height, image::ExtendedColorType::Rgba8)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+/// Export file name for an artboard, `<document>-<artboard name>.<extension>` with anything unusual replaced.
+pub fn export_file_name(document_name: &str, artboard_name: &str, extension: &str) -> String {
+    let slug: String = artboard_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{document_name}-{slug}.{extension}")
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::constants::WHITE;
+    use crate::document::{
+        BlendMode, BrushTip, LayerId, StrokeMode, VectorPoint, VectorStroke, loader::LoadedDocument,
+    };
+    use crate::testing::fixtures::{doc_two_artboards, hydrated_scene, scratch_dir};
+    use crate::testing::gpu::headless_gpu;
+    use crate::testing::probe::{assert_pixel, sample};
+
+    #[test]
+    fn flattens_onto_the_white_background() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(1),
+            ExportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(size, (600, 400));
+        for (x, y) in [(0, 0), (300, 200), (599, 399)] {
+            assert_pixel(&pixels, size, x, y, [255, 127, 127, 255], 1);
+        }
+
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(3),
+            ExportOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(size, (400, 300));
+        assert_pixel(&pixels, size, 0, 0, WHITE, 0);
+    }
+
+    #[test]
+    fn scaled_export_re_rasterizes_vector_layers() {
+        // The right artboard as a vector layer holding one dab, both shrunk by `1 / scale`.
+        let vector_doc = |scale: f32| {
+            let mut document = doc_two_artboards();
+            document.artboards[1].size = [400.0 * scale, 300.0 * scale];
+            document.find_layer_mut(LayerId(4)).unwrap().strokes = Some(vec![VectorStroke {
+                color: [0.0, 0.0, 1.0, 1.0],
+                mode: StrokeMode::Paint,
+                tip: BrushTip::Round,
+                hardness: 0.4,
+                opacity: 1.0,
+                points: vec![VectorPoint {
+                    position: [100.0 * scale, 80.0 * scale],
+                    radius: 12.0 * scale,
+                    opacity: 1.0,
+                    rotation: 0.0,
+                }],
+            }]);
+            document
+        };
+        let (device, queue) = headless_gpu();
+        let export = |document: &Document, scale: u32| {
+            let mut scene = SceneRenderer::new(&device, &queue, wgpu::TextureFormat::Rgba8Unorm);
+            scene.hydrate(
+                &device,
+                &queue,
+                &LoadedDocument {
+                    document: document.clone(),
+                    layer_pixels: HashMap::new(),
+                },
+            );
+            let options = ExportOptions {
+                scale,
+                ..ExportOptions::default()
+            };
+            let exported = render_artboard_pixels(
+                &device,
+                &queue,
+                &mut scene,
+                document,
+                ArtboardId(3),
+                options,
+            )
+            .unwrap();
+            assert_eq!(
+                scene.vector_display_scale(LayerId(4)),
+                1,
+                "display restored"
+            );
+            exported
+        };
+
+        // At 4x the dab is drawn at 4x its radius, exactly as if it had been painted that large.
+        let (scaled, size) = export(&vector_doc(0.25), 4);
+        let (native, native_size) = export(&vector_doc(1.0), 1);
+        assert_eq!(size, native_size);
+        assert!(scaled == native);
+        assert_pixel(&scaled, size, 100, 80, [0, 0, 255, 255], 0);
+    }
+
+    #[test]
+    fn integer_scale_multiplies_the_size() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let options = ExportOptions {
+            scale: 2,
+            ..ExportOptions::default()
+        };
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(3),
+            options,
+        )
+        .unwrap();
+        assert_eq!(size, (800, 600));
+        assert_pixel(&pixels, size, 799, 599, WHITE, 0);
+    }
+
+    #[test]
+    fn transparent_export_keeps_layer_alpha() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        let options = ExportOptions {
+            background: false,
+            ..ExportOptions::default()
+        };
+        let dir = scratch_dir("export-transparent");
+        let path = dir.join("left.png");
+        export_artboard_png(
+            &path,
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(1),
+            options,
+        )
+        .unwrap();
+        let png = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(png.dimensions(), (600, 400));
+        assert_eq!(png.get_pixel(10, 10).0, [255, 0, 0, 128]);
+
+        // blend modes have nothing to blend with, the isolated path keeps the alpha too
+        document.find_layer_mut(LayerId(2)).unwrap().blend_mode = BlendMode::Multiply;
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(1),
+            options,
+        )
+        .unwrap();
+        assert_pixel(&pixels, size, 10, 10, [128, 0, 0, 128], 1);
+
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(3),
+            options,
+        )
+        .unwrap();
+        assert_eq!(sample(&pixels, size, 200, 150), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn export_does_not_disturb_the_other_artboards() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        // overlap the right artboard with the left one: only the exported one may show
+        document.artboard_mut(ArtboardId(3)).unwrap().position = [100.0, 100.0];
+        let (pixels, size) = render_artboard_pixels(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            ArtboardId(1),
+            ExportOptions::default(),
+        )
+        .unwrap();
+        assert_pixel(&pixels, size, 300, 200, [255, 127, 127, 255], 1);
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+        assert!(
+            render_artboard_pixels(
+                &device,
+                &queue,
+                &mut scene,
+                &document,
+                ArtboardId(99),
+                ExportOptions::default(),
+            )
+            .is_err()
+        );
+        // 2^30 + 1 wraps 600x400 around to itself in unchecked u32 math
+        for scale in [0, 1000, (1 << 30) + 1] {
+            let options = ExportOptions {
+                scale,
+                ..ExportOptions::default()
+            };
+            assert!(
+                render_artboard_pixels(
+                    &device,
+                    &queue,
+                    &mut scene,
+                    &document,
+                    ArtboardId(1),
+                    options
+                )
+                .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn file_names_are_slugged() {
+        assert_eq!(
+            export_file_name("default", "Artboard 1", "png"),
+            "default-artboard-1.png"
+        );
+        assert_eq!(export_file_name("doc", "a/b", "png"), "doc-a-b.png");
+        assert_eq!(export_file_name("doc", "a/b", "ora"), "doc-a-b.ora");
+    }
+}
@@ -1,10 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-#[cfg(not(target_arch = "wasm32"))]
-use anyhow::Context;
-use anyhow::bail;
+use anyhow::{Context, bail};
 
-use crate::document::{Document, LayerId};
+use crate::document::{
+    Document, LayerId,
+    bundle::{Bundle, read_bundle},
+    migrations::migrate_document,
+    openraster::read_openraster,
+    validate::{ContentSize, Issue, Severity, repair, validate},
+};
 
 pub struct LoadedDocument {
     pub document: Document,
@@ -15,29 +19,214 @@ pub struct LoadedDocument {
 
 #[cfg(not(target_arch = "wasm32"))]
 pub fn load_document(name: &str, max_texture_dim: u32) -> anyhow::Result<LoadedDocument> {
-    let dir = asset_dir();
+    load_document_from(&asset_dir(), name, max_texture_dim)
+}
+
+/// Loads `<dir>/<name>.crayon` if present, else `<dir>/<name>.json` and the layer PNGs it references, relative to `dir`,
+/// else an OpenRaster `<dir>/<name>.ora`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_document_from(
+    dir: &std::path::Path,
+    name: &str,
+    max_texture_dim: u32,
+) -> anyhow::Result<LoadedDocument> {
+    let (document, source, path) = read_document_from(dir, name)?;
+    decode_layers(document, max_texture_dim, |content| source.open(content))
+        .with_context(|| format!("loading {}", path.display()))
+}
+
+/// Every issue of the document `load_document_from` would load, layer images included.
+/// Fails only when the document can't be read at all.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_document_from(
+    dir: &std::path::Path,
+    name: &str,
+    max_texture_dim: u32,
+) -> anyhow::Result<Vec<Issue>> {
+    let (document, source, _) = read_document_from(dir, name)?;
+    Ok(validate(&document, max_texture_dim, &mut |content| {
+        Ok(Some(source.open(content)?.dimensions()))
+    }))
+}
+
+/// Loads exactly the document at `path`, read as its extension says:
+/// a `.crayon` bundle, `.json` and the layer PNGs it references relative to its dir, or an OpenRaster `.ora`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_document_file(
+    path: &std::path::Path,
+    max_texture_dim: u32,
+) -> anyhow::Result<LoadedDocument> {
+    let (document, source) = read_document_file(path)?;
+    decode_layers(document, max_texture_dim, |content| source.open(content))
+        .with_context(|| format!("loading {}", path.display()))
+}
+
+/// Every issue of the document `load_document_file` would load, layer images included.
+/// Fails only when the document can't be read at all.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_document_file(
+    path: &std::path::Path,
+    max_texture_dim: u32,
+) -> anyhow::Result<Vec<Issue>> {
+    let (document, source) = read_document_file(path)?;
+    Ok(validate(&document, max_texture_dim, &mut |content| {
+        Ok(Some(source.open(content)?.dimensions()))
+    }))
+}
+
+/// The undecoded document `load_document_from` picks, where its layer images come from, and its path.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_document_from<'a>(
+    dir: &'a std::path::Path,
+    name: &str,
+) -> anyhow::Result<(Document, LayerSource<'a>, std::path::PathBuf)> {
+    let bundle_path = dir.join(format!("{name}.{}", super::bundle::BUNDLE_EXTENSION));
     let json_path = dir.join(format!("{name}.json"));
-    let json = std::fs::read_to_string(&json_path)
-        .with_context(|| format!("reading {}", json_path.display()))?;
-    let mut document: Document =
-        serde_json::from_str(&json).with_context(|| format!("parsing {}", json_path.display()))?;
-    validate(&mut document, max_texture_dim)?;
+    let ora_path = dir.join(format!("{name}.{}", super::openraster::ORA_EXTENSION));
+    let path = if bundle_path.is_file() {
+        bundle_path
+    } else if !json_path.is_file() && ora_path.is_file() {
+        ora_path
+    } else {
+        json_path
+    };
+    let (document, source) = read_document_in(dir, &path, name)?;
+    Ok((document, source, path))
+}
+
+/// The undecoded document at `path` and where its layer images come from.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_document_file(path: &std::path::Path) -> anyhow::Result<(Document, LayerSource<'_>)> {
+    let dir = path.parent().unwrap_or(std::path::Path::new(""));
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    read_document_in(dir, path, &name)
+}
+
+/// Reads the document at `path` in `dir` by its extension, an OpenRaster one as a document named `name`.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_document_in<'a>(
+    dir: &'a std::path::Path,
+    path: &std::path::Path,
+    name: &str,
+) -> anyhow::Result<(Document, LayerSource<'a>)> {
+    use super::{bundle::BUNDLE_EXTENSION, openraster::ORA_EXTENSION};
+
+    let open = || {
+        std::fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .with_context(|| format!("reading {}", path.display()))
+    };
+    match path.extension().and_then(std::ffi::OsStr::to_str) {
+        Some(BUNDLE_EXTENSION) => {
+            let Bundle { document, pngs } =
+                read_bundle(open()?).with_context(|| format!("loading {}", path.display()))?;
+            Ok((document, LayerSource::Entries(pngs)))
+        }
+        Some(ORA_EXTENSION) => {
+            let Bundle { document, pngs } = read_openraster(open()?, name)
+                .with_context(|| format!("loading {}", path.display()))?;
+            Ok((document, LayerSource::Entries(pngs)))
+        }
+        Some("json") => {
+            let json = std::fs::read_to_string(path)
+                .with_context(|| format!("reading {}", path.display()))?;
+            let value: serde_json::Value = serde_json::from_str(&json)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            let document =
+                migrate_document(value).with_context(|| format!("reading {}", path.display()))?;
+            Ok((document, LayerSource::Dir(dir)))
+        }
+        _ => bail!(
+            "{} is not a .{BUNDLE_EXTENSION}, .json or .{ORA_EXTENSION} document",
+            path.display()
+        ),
+    }
+}
+
+/// Where the layer images of a document being read come from.
+enum LayerSource<'a> {
+    /// PNG files relative to the document's dir.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    Dir(&'a std::path::Path),
+    /// Entries of a bundle or an OpenRaster archive, keyed by `content_path`.
+    Entries(HashMap<String, Vec<u8>>),
+}
+
+impl LayerSource<'_> {
+    fn open(&self, content: &str) -> anyhow::Result<image::RgbaImage> {
+        match self {
+            Self::Dir(dir) => {
+                let png_path = dir.join(content);
+                Ok(image::open(&png_path)
+                    .with_context(|| format!("decoding {}", png_path.display()))?
+                    .to_rgba8())
+            }
+            Self::Entries(pngs) => {
+                let bytes = pngs
+                    .get(content)
+                    .ok_or_else(|| anyhow::anyhow!("bundle has no entry for '{content}'"))?;
+                Ok(
+                    image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+                        .with_context(|| format!("decoding bundle entry '{content}'"))?
+                        .to_rgba8(),
+                )
+            }
+        }
+    }
+}
+
+/// Loads a `.crayon` bundle from any byte stream.
+pub fn load_bundle(
+    reader: impl std::io::Read,
+    max_texture_dim: u32,
+) -> anyhow::Result<LoadedDocument> {
+    decode_bundle(read_bundle(reader)?, max_texture_dim)
+}
+
+/// Loads an OpenRaster `.ora` as a single artboard document named `name`.
+pub fn load_openraster(
+    reader: impl std::io::Read + std::io::Seek,
+    name: &str,
+    max_texture_dim: u32,
+) -> anyhow::Result<LoadedDocument> {
+    decode_bundle(read_openraster(reader, name)?, max_texture_dim)
+}
+
+fn decode_bundle(
+    Bundle { document, pngs }: Bundle,
+    max_texture_dim: u32,
+) -> anyhow::Result<LoadedDocument> {
+    let source = LayerSource::Entries(pngs);
+    decode_layers(document, max_texture_dim, |content| source.open(content))
+}
+
+/// Validates `document`, decoding the content of every layer through `open_layer`, which resolves a `content_path`.
+/// Vector layers are left to be rasterized from their strokes.
+fn decode_layers(
+    mut document: Document,
+    max_texture_dim: u32,
+    mut open_layer: impl FnMut(&str) -> anyhow::Result<image::RgbaImage>,
+) -> anyhow::Result<LoadedDocument> {
+    let mut images = HashMap::new();
+    check(&mut document, max_texture_dim, &mut |content| {
+        if !images.contains_key(content) {
+            images.insert(content.to_string(), open_layer(content)?);
+        }
+        Ok(images.get(content).map(image::RgbaImage::dimensions))
+    })?;
 
     let mut layer_pixels = HashMap::new();
     for artboard in &document.artboards {
         let size = artboard.pixel_size();
-        for layer in &artboard.layers {
-            use anyhow::Context;
-
-            let Some(content) = &layer.content_path else {
+        for layer in artboard.iter_layers().filter(|layer| !layer.is_vector()) {
+            let Some(img) = layer.content_path.as_ref().and_then(|c| images.get(c)) else {
                 continue;
             };
-            let png_path = dir.join(content);
-            let img = image::open(&png_path)
-                .with_context(|| format!("decoding {}", png_path.display()))?
-                .to_rgba8();
 
-            let mut pixels = artboard_sized(&img, size);
+            let mut pixels = artboard_sized(img, size);
             premultiply_alpha(&mut pixels);
             layer_pixels.insert(layer.id, pixels);
         }
@@ -54,32 +243,31 @@ pub async fn load_document(_name: &str, _max_texture_dim: u32) -> anyhow::Result
     todo!("WASM document fetch is slated for later")
 }
 
-/// Validates the document to be loaded with the following constraints:
-/// - element ids are unique
-/// - elements have valid sizes
-/// - artboard dimensions are clamped to device specific max texture dims
-fn validate(document: &mut Document, max_texture_dim: u32) -> anyhow::Result<()> {
-    let max_dim = max_texture_dim as f32;
-    let mut seen = HashSet::new();
-    for artboard in &mut document.artboards {
-        if !seen.insert(artboard.id.0) {
-            bail!("duplicate id {} in document", artboard.id.0);
-        }
-        for (axis, extent) in artboard.size.iter_mut().enumerate() {
-            if !extent.is_finite() || *extent < 1.0 {
-                bail!(
-                    "artboard {} has invalid size on axis {axis}: {extent}",
-                    artboard.id.0
-                );
-            }
-            *extent = extent.min(max_dim);
-        }
-        for layer in &artboard.layers {
-            if !seen.insert(layer.id.0) {
-                bail!("duplicate id {} in document", layer.id.0);
-            }
-        }
+/// Validates the document to be loaded without looking at layer content, see `check`.
+fn validate_document(document: &mut Document, max_texture_dim: u32) -> anyhow::Result<()> {
+    check(document, max_texture_dim, &mut |_| Ok(None))
+}
+
+/// Refuses the document on any `validate` error. Warnings are logged, then `repair`ed,
+/// e.g. artboard dimensions are clamped to device specific max texture dims.
+fn check(
+    document: &mut Document,
+    max_texture_dim: u32,
+    content_size: &mut ContentSize,
+) -> anyhow::Result<()> {
+    let issues = validate(document, max_texture_dim, content_size);
+    let errors: Vec<String> = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(ToString::to_string)
+        .collect();
+    if !errors.is_empty() {
+        bail!("invalid document: {}", errors.join("; "));
     }
+    for issue in &issues {
+        log::warn!("{issue}");
+    }
+    repair(document, max_texture_dim);
     Ok(())
 }
 
@@ -114,7 +302,7 @@ pub fn premultiply_alpha(rgba: &mut [u8]) {
 
 /// Returns the asset dir for bundled assets.
 #[cfg(not(target_arch = "wasm32"))]
-fn asset_dir() -> std::path::PathBuf {
+pub(crate) fn asset_dir() -> std::path::PathBuf {
     if let Ok(exe) = std::env::current_exe()
         && let Some(exe_dir) = exe.parent()
     {
@@ -129,7 +317,10 @@ fn asset_dir() -> std::path::PathBuf {
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
-    use super::super::{Artboard, ArtboardId, DOCUMENT_VERSION, Layer};
+    use super::super::{
+        Artboard, ArtboardId, BlendMode, BrushTip, DOCUMENT_VERSION, GroupId, Layer, LayerGroup,
+        LayerNode, StrokeMode, VectorPoint, VectorStroke, migrations::MigrationError,
+    };
     use super::*;
 
     fn two_layer_doc() -> Document {
@@ -141,14 +332,18 @@ mod tests {
                 name: "A".to_string(),
                 position: [0.0, 0.0],
                 size: [100.0, 100.0],
-                layers: vec![Layer {
+                layers: vec![LayerNode::Layer(Layer {
                     id: LayerId(2),
                     name: "L".to_string(),
                     offset: [0.0, 0.0],
                     visible: true,
+                    locked: false,
+                    opacity: 1.0,
+                    blend_mode: BlendMode::Normal,
                     content_path: None,
                     thumbhash: None,
-                }],
+                    strokes: None,
+                })],
             }],
         }
     }
@@ -157,8 +352,27 @@ mod tests {
     fn validate_bails_on_duplicate_ids() {
         let mut document = two_layer_doc();
         // collides with artboard id
-        document.artboards[0].layers[0].id = LayerId(1);
-        assert!(validate(&mut document, 2048).is_err());
+        document.find_layer_mut(LayerId(2)).unwrap().id = LayerId(1);
+        assert!(validate_document(&mut document, 2048).is_err());
+    }
+
+    #[test]
+    fn validate_checks_ids_inside_groups() {
+        let mut document = two_layer_doc();
+        let layer = document.artboards[0].layers.pop().unwrap();
+        let group = LayerGroup {
+            id: GroupId(3),
+            name: "G".to_string(),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            children: vec![layer.clone()],
+        };
+        document.artboards[0].layers = vec![group.into(), layer];
+        document.next_id = 4;
+        let error = validate_document(&mut document, 2048).unwrap_err();
+        assert!(format!("{error}").contains("duplicate id 2"), "{error}");
     }
 
     #[test]
@@ -166,7 +380,7 @@ mod tests {
     fn validate_clamps_oversized_artboards() {
         let mut document = two_layer_doc();
         document.artboards[0].size = [5000.0, 1000.0];
-        validate(&mut document, 2048).unwrap();
+        validate_document(&mut document, 2048).unwrap();
         assert_eq!(document.artboards[0].size, [2048.0, 1000.0]);
     }
 
@@ -174,9 +388,30 @@ mod tests {
     fn validate_bails_on_degenerate_size() {
         let mut document = two_layer_doc();
         document.artboards[0].size = [0.0, 100.0];
-        assert!(validate(&mut document, 2048).is_err());
+        assert!(validate_document(&mut document, 2048).is_err());
         document.artboards[0].size = [f32::NAN, 100.0];
-        assert!(validate(&mut document, 2048).is_err());
+        assert!(validate_document(&mut document, 2048).is_err());
+    }
+
+    #[test]
+    fn validate_bails_on_non_finite_stroke_points() {
+        let mut document = two_layer_doc();
+        let layer = document.find_layer_mut(LayerId(2)).unwrap();
+        layer.strokes = Some(vec![VectorStroke {
+            color: [0.0, 0.0, 0.0, 1.0],
+            mode: StrokeMode::Paint,
+            tip: BrushTip::Round,
+            hardness: 0.4,
+            opacity: 1.0,
+            points: vec![VectorPoint {
+                position: [1.0, f32::INFINITY],
+                radius: 4.0,
+                opacity: 1.0,
+                rotation: 0.0,
+            }],
+        }]);
+        let error = validate_document(&mut document, 2048).unwrap_err();
+        assert!(format!("{error}").contains("layer 2"), "{error}");
     }
 
     #[test]
@@ -207,7 +442,7 @@ mod tests {
         let mut content_layers = 0;
         for artboard in &loaded.document.artboards {
             let (w, h) = artboard.pixel_size();
-            for layer in &artboard.layers {
+            for layer in artboard.iter_layers() {
                 if layer.content_path.is_some() {
                     content_layers += 1;
                     assert!(layer.thumbhash.is_some(), "content layers carry a hash");
@@ -244,6 +479,60 @@ mod tests {
         assert_ne!(positions[0], positions[1], "distinct world positions");
     }
 
+    /// Frozen documents from older (and newer) schema versions.
+    /// Never regenerate these, they pin down what old files on disk look like.
+    fn migration_fixtures() -> std::path::PathBuf {
+        std::path::PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/documents/migrations"
+        ))
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn load_v1_document_from_fixture() {
+        let loaded = load_document_from(&migration_fixtures(), "v1", 2048).unwrap();
+        let document = &loaded.document;
+        assert_eq!(document.version, DOCUMENT_VERSION);
+        assert_eq!(document.next_id, 4);
+        let layers: Vec<_> = document.artboards[0].iter_layers().collect();
+        assert_eq!(layers.len(), 2);
+        assert!(layers[0].visible);
+        assert!(!layers[1].visible);
+        assert_eq!(layers[1].offset, [12.0, -4.0]);
+        for layer in layers {
+            assert!((layer.opacity - 1.0).abs() < f32::EPSILON);
+            assert_eq!(layer.blend_mode, BlendMode::Normal);
+        }
+        assert!(loaded.layer_pixels.is_empty());
+    }
+
+    #[test]
+    fn refuses_newer_document_from_fixture() {
+        let error = load_document_from(&migration_fixtures(), "future", 2048)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast_ref::<MigrationError>(),
+            Some(&MigrationError::TooNew {
+                found: 999,
+                supported: DOCUMENT_VERSION
+            }),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn refuses_unversioned_document_from_fixture() {
+        let error = load_document_from(&migration_fixtures(), "unversioned", 2048)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.downcast_ref::<MigrationError>(),
+            Some(&MigrationError::Unversioned)
+        );
+    }
+
     /// Regenerates the committed test assets deterministically. Run manually:
     /// `cargo test -p crayon --lib generate_default_assets -- --ignored`
     #[test]
@@ -290,22 +579,30 @@ mod tests {
                     position: [0.0, 0.0],
                     size: [800.0, 600.0],
                     layers: vec![
-                        Layer {
+                        LayerNode::Layer(Layer {
                             id: LayerId(2),
                             name: "Background".to_string(),
                             offset: [0.0, 0.0],
                             visible: true,
+                            locked: false,
+                            opacity: 1.0,
+                            blend_mode: BlendMode::Normal,
                             content_path: Some("default.layer-2.png".to_string()),
                             thumbhash: Some(hash),
-                        },
-                        Layer {
+                            strokes: None,
+                        }),
+                        LayerNode::Layer(Layer {
                             id: LayerId(3),
                             name: "Sketch".to_string(),
                             offset: [0.0, 0.0],
                             visible: true,
+                            locked: false,
+                            opacity: 1.0,
+                            blend_mode: BlendMode::Normal,
                             content_path: None,
                             thumbhash: None,
-                        },
+                            strokes: None,
+                        }),
                     ],
                 },
                 Artboard {
@@ -333,28 +630,36 @@ mod tests {
                     name: "Left".to_string(),
                     position: [0.0, 0.0],
                     size: [600.0, 400.0],
-                    layers: vec![Layer {
+                    layers: vec![LayerNode::Layer(Layer {
                         id: LayerId(2),
                         name: "Layer 1".to_string(),
                         offset: [0.0, 0.0],
                         visible: true,
+                        locked: false,
+                        opacity: 1.0,
+                        blend_mode: BlendMode::Normal,
                         content_path: None,
                         thumbhash: None,
-                    }],
+                        strokes: None,
+                    })],
                 },
                 Artboard {
                     id: ArtboardId(3),
                     name: "Right".to_string(),
                     position: [700.0, 100.0],
                     size: [400.0, 300.0],
-                    layers: vec![Layer {
+                    layers: vec![LayerNode::Layer(Layer {
                         id: LayerId(4),
                         name: "Layer 1".to_string(),
                         offset: [0.0, 0.0],
                         visible: true,
+                        locked: false,
+                        opacity: 1.0,
+                        blend_mode: BlendMode::Normal,
                         content_path: None,
                         thumbhash: None,
-                    }],
+                        strokes: None,
+                    })],
                 },
             ],
         };
@@ -0,0 +1,493 @@
+//! On-disk document schema upgrades.
+//!
+//! Documents are parsed into a `serde_json::Value` first, so fields can be renamed, added or restructured
+//! before they ever meet the current `Document` type.
+//! `MIGRATIONS[i]` upgrades a version `i + 1` document to version `i + 2`.
+
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::document::{DOCUMENT_VERSION, Document};
+
+/// Upgrades a document in place by exactly one version.
+type Migration = fn(&mut Value) -> anyhow::Result<()>;
+
+const MIGRATIONS: &[Migration] = &[
+    v1_add_layer_compositing,
+    v2_tag_layer_nodes,
+    v3_add_vector_strokes,
+    v4_add_node_locks,
+    v5_add_stroke_modes,
+    v6_add_point_opacities,
+    v7_add_brush_tips,
+    v8_add_stroke_hardness_and_opacity,
+];
+
+// Every version bump needs a migration step.
+const _: () = assert!(MIGRATIONS.len() + 1 == DOCUMENT_VERSION as usize);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    /// No numeric top-level `version` field.
+    Unversioned,
+    /// Written by a newer Crayon than this one.
+    TooNew { found: u32, supported: u32 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unversioned => write!(f, "document has no version"),
+            Self::TooNew { found, supported } => write!(
+                f,
+                "document version {found} is newer than the supported version {supported}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Upgrades `value` to `DOCUMENT_VERSION` and deserializes it.
+pub fn migrate_document(value: Value) -> anyhow::Result<Document> {
+    let value = migrate_with(value, MIGRATIONS)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+fn migrate_with(mut value: Value, migrations: &[Migration]) -> anyhow::Result<Value> {
+    let supported = u32::try_from(migrations.len() + 1)?;
+    let found = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .and_then(|version| u32::try_from(version).ok())
+        .filter(|version| *version >= 1)
+        .ok_or(MigrationError::Unversioned)?;
+    if found > supported {
+        return Err(MigrationError::TooNew { found, supported }.into());
+    }
+
+    for (from, migration) in (found..).zip(&migrations[found as usize - 1..]) {
+        migration(&mut value).map_err(|error| {
+            error.context(format!("migrating document v{from} to v{}", from + 1))
+        })?;
+        value["version"] = Value::from(from + 1);
+    }
+
+    Ok(value)
+}
+
+/// v2: layers gained `opacity` and `blend_mode`; existing layers composite as before.
+fn v1_add_layer_compositing(value: &mut Value) -> anyhow::Result<()> {
+    for layer in layers_mut(value)? {
+        let layer = layer
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("layer is not an object"))?;
+        layer.insert("opacity".to_string(), Value::from(1.0));
+        layer.insert("blend_mode".to_string(), Value::from("normal"));
+    }
+    Ok(())
+}
+
+/// v3: artboard layer stacks can hold groups, so each entry is tagged with its `kind`.
+fn v2_tag_layer_nodes(value: &mut Value) -> anyhow::Result<()> {
+    for layer in layers_mut(value)? {
+        let layer = layer
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("layer is not an object"))?;
+        layer.insert("kind".to_string(), Value::from("layer"));
+    }
+    Ok(())
+}
+
+/// v4: layers can keep vector strokes; existing layers are raster layers.
+fn v3_add_vector_strokes(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        match node.get("kind").and_then(Value::as_str) {
+            Some("layer") => {
+                node.insert("strokes".to_string(), Value::Null);
+            }
+            Some("group") => {
+                if !node.get("children").is_some_and(Value::is_array) {
+                    anyhow::bail!("group without children");
+                }
+            }
+            _ => anyhow::bail!("layer node without a known kind"),
+        }
+        Ok(())
+    })
+}
+
+/// v5: layers and groups can be locked; existing ones are not.
+fn v4_add_node_locks(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        node.insert("locked".to_string(), Value::Bool(false));
+        Ok(())
+    })
+}
+
+/// v6: vector strokes can erase; existing ones paint.
+fn v5_add_stroke_modes(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        let Some(strokes) = node.get_mut("strokes").and_then(Value::as_array_mut) else {
+            return Ok(());
+        };
+        for stroke in strokes {
+            stroke
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("stroke is not an object"))?
+                .insert("mode".to_string(), Value::String("paint".to_string()));
+        }
+        Ok(())
+    })
+}
+
+/// v7: stroke dabs carry the opacity pen pressure gave them; existing ones are opaque.
+fn v6_add_point_opacities(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        let Some(strokes) = node.get_mut("strokes").and_then(Value::as_array_mut) else {
+            return Ok(());
+        };
+        for stroke in strokes {
+            let points = stroke
+                .get_mut("points")
+                .and_then(Value::as_array_mut)
+                .ok_or_else(|| anyhow::anyhow!("stroke without points"))?;
+            for point in points {
+                point
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("stroke point is not an object"))?
+                    .insert("opacity".to_string(), Value::from(1.0));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// v8: strokes stamp a brush tip, rotated per dab; existing ones are round and unrotated.
+fn v7_add_brush_tips(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        let Some(strokes) = node.get_mut("strokes").and_then(Value::as_array_mut) else {
+            return Ok(());
+        };
+        for stroke in strokes {
+            let stroke = stroke
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("stroke is not an object"))?;
+            stroke.insert("tip".to_string(), Value::from("round"));
+            let points = stroke
+                .get_mut("points")
+                .and_then(Value::as_array_mut)
+                .ok_or_else(|| anyhow::anyhow!("stroke without points"))?;
+            for point in points {
+                point
+                    .as_object_mut()
+                    .ok_or_else(|| anyhow::anyhow!("stroke point is not an object"))?
+                    .insert("rotation".to_string(), Value::from(0.0));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// v9: strokes keep the hardness and opacity they were painted with;
+/// existing ones had the fixed 0.4 hardness and full opacity.
+fn v8_add_stroke_hardness_and_opacity(value: &mut Value) -> anyhow::Result<()> {
+    visit_nodes(value, |node| {
+        let Some(strokes) = node.get_mut("strokes").and_then(Value::as_array_mut) else {
+            return Ok(());
+        };
+        for stroke in strokes {
+            let stroke = stroke
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("stroke is not an object"))?;
+            stroke.insert("hardness".to_string(), Value::from(0.4));
+            stroke.insert("opacity".to_string(), Value::from(1.0));
+        }
+        Ok(())
+    })
+}
+
+/// Calls `visit` on every layer and group object of every artboard, groups before their children.
+fn visit_nodes(
+    value: &mut Value,
+    mut visit: impl FnMut(&mut Map<String, Value>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let artboards = value
+        .get_mut("artboards")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow::anyhow!("missing artboards"))?;
+    let mut nodes = Vec::new();
+    for artboard in artboards {
+        let artboard_layers = artboard
+            .get_mut("layers")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| anyhow::anyhow!("artboard without layers"))?;
+        nodes.extend(artboard_layers);
+    }
+    while let Some(node) = nodes.pop() {
+        let node = node
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("layer is not an object"))?;
+        visit(node)?;
+        if let Some(children) = node.get_mut("children").and_then(Value::as_array_mut) {
+            nodes.extend(children);
+        }
+    }
+    Ok(())
+}
+
+/// Every layer object of every artboard, for versions without groups.
+fn layers_mut(value: &mut Value) -> anyhow::Result<Vec<&mut Value>> {
+    let artboards = value
+        .get_mut("artboards")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| anyhow::anyhow!("missing artboards"))?;
+    let mut layers = Vec::new();
+    for artboard in artboards {
+        let artboard_layers = artboard
+            .get_mut("layers")
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| anyhow::anyhow!("artboard without layers"))?;
+        layers.extend(artboard_layers);
+    }
+    Ok(layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rename_title(value: &mut Value) -> anyhow::Result<()> {
+        let title = value
+            .as_object_mut()
+            .and_then(|object| object.remove("title"))
+            .ok_or_else(|| anyhow::anyhow!("missing title"))?;
+        value["name"] = title;
+        Ok(())
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn add_tags(value: &mut Value) -> anyhow::Result<()> {
+        value["tags"] = json!([]);
+        Ok(())
+    }
+
+    const CHAIN: &[Migration] = &[rename_title, add_tags];
+
+    fn migration_error(result: anyhow::Result<Value>) -> MigrationError {
+        result
+            .expect_err("migration should fail")
+            .downcast::<MigrationError>()
+            .expect("a specific migration error")
+    }
+
+    #[test]
+    fn runs_every_step_from_the_found_version() {
+        let value = migrate_with(json!({ "version": 1, "title": "a" }), CHAIN).unwrap();
+        assert_eq!(value, json!({ "version": 3, "name": "a", "tags": [] }));
+    }
+
+    #[test]
+    fn resumes_partway_through_the_chain() {
+        let value = migrate_with(json!({ "version": 2, "name": "a" }), CHAIN).unwrap();
+        assert_eq!(value, json!({ "version": 3, "name": "a", "tags": [] }));
+    }
+
+    #[test]
+    fn current_version_is_untouched() {
+        let current = json!({ "version": 3, "name": "a", "tags": ["x"] });
+        assert_eq!(migrate_with(current.clone(), CHAIN).unwrap(), current);
+    }
+
+    #[test]
+    fn failing_step_names_the_versions() {
+        let error = migrate_with(json!({ "version": 1 }), CHAIN).unwrap_err();
+        assert!(format!("{error:#}").contains("v1 to v2"), "{error:#}");
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        assert_eq!(
+            migration_error(migrate_with(json!({ "version": 4 }), CHAIN)),
+            MigrationError::TooNew {
+                found: 4,
+                supported: 3
+            }
+        );
+    }
+
+    #[test]
+    fn refuses_missing_or_invalid_versions() {
+        for value in [
+            json!({}),
+            json!({ "version": "1" }),
+            json!({ "version": 0 }),
+            json!([]),
+        ] {
+            assert_eq!(
+                migration_error(migrate_with(value, CHAIN)),
+                MigrationError::Unversioned
+            );
+        }
+    }
+
+    #[test]
+    fn v1_layers_composite_normally_at_full_opacity() {
+        let mut value = json!({
+            "version": 1,
+            "artboards": [{ "layers": [{ "id": 2 }] }, { "layers": [] }],
+        });
+        v1_add_layer_compositing(&mut value).unwrap();
+        assert_eq!(
+            value["artboards"][0]["layers"][0],
+            json!({ "id": 2, "opacity": 1.0, "blend_mode": "normal" })
+        );
+    }
+
+    #[test]
+    fn v2_layers_become_tagged_nodes() {
+        let mut value = json!({
+            "version": 2,
+            "artboards": [{ "layers": [{ "id": 2 }] }],
+        });
+        v2_tag_layer_nodes(&mut value).unwrap();
+        assert_eq!(
+            value["artboards"][0]["layers"][0],
+            json!({ "kind": "layer", "id": 2 })
+        );
+    }
+
+    #[test]
+    fn v3_layers_become_raster_layers_inside_groups_too() {
+        let mut value = json!({
+            "version": 3,
+            "artboards": [{ "layers": [
+                { "kind": "layer", "id": 2 },
+                { "kind": "group", "id": 3, "children": [{ "kind": "layer", "id": 4 }] },
+            ] }],
+        });
+        v3_add_vector_strokes(&mut value).unwrap();
+        let layers = &value["artboards"][0]["layers"];
+        assert_eq!(
+            layers[0],
+            json!({ "kind": "layer", "id": 2, "strokes": null })
+        );
+        assert_eq!(
+            layers[1]["children"][0],
+            json!({ "kind": "layer", "id": 4, "strokes": null })
+        );
+        assert_eq!(layers[1].get("strokes"), None);
+
+        let mut unknown = json!({ "version": 3, "artboards": [{ "layers": [{ "id": 2 }] }] });
+        assert!(v3_add_vector_strokes(&mut unknown).is_err());
+    }
+
+    #[test]
+    fn v4_layers_and_groups_start_unlocked() {
+        let mut value = json!({
+            "version": 4,
+            "artboards": [{ "layers": [
+                { "kind": "layer", "id": 2 },
+                { "kind": "group", "id": 3, "children": [{ "kind": "layer", "id": 4 }] },
+            ] }],
+        });
+        v4_add_node_locks(&mut value).unwrap();
+        let layers = &value["artboards"][0]["layers"];
+        assert_eq!(
+            layers[0],
+            json!({ "kind": "layer", "id": 2, "locked": false })
+        );
+        assert_eq!(layers[1]["locked"], json!(false));
+        assert_eq!(layers[1]["children"][0]["locked"], json!(false));
+    }
+
+    #[test]
+    fn v5_strokes_paint() {
+        let mut value = json!({
+            "version": 5,
+            "artboards": [{ "layers": [
+                { "kind": "layer", "id": 2, "strokes": null },
+                { "kind": "group", "id": 3, "children": [
+                    { "kind": "layer", "id": 4, "strokes": [{ "color": [0.0, 0.0, 0.0, 1.0], "points": [] }] },
+                ] },
+            ] }],
+        });
+        v5_add_stroke_modes(&mut value).unwrap();
+        let layers = &value["artboards"][0]["layers"];
+        assert_eq!(layers[0]["strokes"], Value::Null);
+        assert_eq!(
+            layers[1]["children"][0]["strokes"][0]["mode"],
+            json!("paint")
+        );
+    }
+
+    #[test]
+    fn v6_points_are_opaque() {
+        let mut value = json!({
+            "version": 6,
+            "artboards": [{ "layers": [
+                { "kind": "layer", "id": 2, "strokes": null },
+                { "kind": "layer", "id": 4, "strokes": [{
+                    "color": [0.0, 0.0, 0.0, 1.0],
+                    "mode": "erase",
+                    "points": [{ "position": [1.0, 2.0], "radius": 3.0 }],
+                }] },
+            ] }],
+        });
+        v6_add_point_opacities(&mut value).unwrap();
+        let layers = &value["artboards"][0]["layers"];
+        assert_eq!(layers[0]["strokes"], Value::Null);
+        assert_eq!(layers[1]["strokes"][0]["points"][0]["opacity"], json!(1.0));
+    }
+
+    #[test]
+    fn v7_strokes_are_round() {
+        let mut value = json!({
+            "version": 7,
+            "artboards": [{ "layers": [
+                { "kind": "group", "id": 3, "children": [
+                    { "kind": "layer", "id": 4, "strokes": [{
+                        "color": [0.0, 0.0, 0.0, 1.0],
+                        "mode": "paint",
+                        "points": [{ "position": [1.0, 2.0], "radius": 3.0, "opacity": 0.5 }],
+                    }] },
+                ] },
+            ] }],
+        });
+        v7_add_brush_tips(&mut value).unwrap();
+        let stroke = &value["artboards"][0]["layers"][0]["children"][0]["strokes"][0];
+        assert_eq!(stroke["tip"], json!("round"));
+        assert_eq!(stroke["points"][0]["rotation"], json!(0.0));
+    }
+
+    #[test]
+    fn v8_strokes_keep_the_old_hardness() {
+        let mut value = json!({
+            "version": 8,
+            "artboards": [{ "layers": [
+                { "kind": "layer", "id": 2, "strokes": null },
+                { "kind": "layer", "id": 4, "strokes": [{
+                    "color": [0.0, 0.0, 0.0, 1.0],
+                    "mode": "paint",
+                    "tip": "round",
+                    "points": [],
+                }] },
+            ] }],
+        });
+        v8_add_stroke_hardness_and_opacity(&mut value).unwrap();
+        let layers = &value["artboards"][0]["layers"];
+        assert_eq!(layers[0]["strokes"], Value::Null);
+        assert_eq!(layers[1]["strokes"][0]["hardness"], json!(0.4));
+        assert_eq!(layers[1]["strokes"][0]["opacity"], json!(1.0));
+    }
+
+    #[test]
+    fn current_document_passes_through() {
+        let document = Document::default_document();
+        let value = serde_json::to_value(&document).unwrap();
+        assert_eq!(migrate_document(value).unwrap(), document);
+    }
+}
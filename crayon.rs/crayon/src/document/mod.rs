@@ -1,5 +1,16 @@
+pub mod artboard_size;
+pub mod bundle;
+pub mod command;
+pub mod export;
 pub mod loader;
+pub mod migrations;
+pub mod openraster;
+pub mod psd;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recovery;
+pub mod saver;
 pub mod thumbhash;
+pub mod validate;
 
 use batteries::prelude::{Rect, rects_to_center};
 use cgmath::Point2;
@@ -13,6 +24,17 @@ pub struct ArtboardId(pub u32);
 #[serde(transparent)]
 pub struct LayerId(pub u32);
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(transparent)]
+pub struct GroupId(pub u32);
+
+/// A layer or a group in a layer stack.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NodeId {
+    Layer(LayerId),
+    Group(GroupId),
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Document {
     pub version: u32,
@@ -30,8 +52,32 @@ pub struct Artboard {
     pub position: [f32; 2],
     /// Size in world-space clamped to device max texture dims on load.
     pub size: [f32; 2],
+    /// Layers and groups, drawn in ascending order of index
+    pub layers: Vec<LayerNode>,
+}
+
+/// An entry in a layer stack.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LayerNode {
+    Layer(Layer),
+    Group(LayerGroup),
+}
+
+/// A named folder of layers and other groups.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct LayerGroup {
+    pub id: GroupId,
+    pub name: String,
+    /// Hides every child.
+    pub visible: bool,
+    /// Keeps every child from being painted on or cleared.
+    pub locked: bool,
+    /// Applied to the group as a whole, after its children are flattened.
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
     /// Drawn in ascending order of index
-    pub layers: Vec<Layer>,
+    pub children: Vec<LayerNode>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -41,15 +87,104 @@ pub struct Layer {
     /// Artboard local top left corner.
     pub offset: [f32; 2],
     pub visible: bool,
+    /// Keeps the layer from being painted on or cleared.
+    pub locked: bool,
+    /// 0.0 (invisible) to 1.0 (opaque), applied when compositing.
+    pub opacity: f32,
+    /// How the layer combines with everything drawn below it in the artboard.
+    pub blend_mode: BlendMode,
     /// Relative path for bundled assets, or web url
     /// None signifies an empty layer.
     pub content_path: Option<String>,
     /// Base64 thumbhash of the layer content for instant previews.
     /// None for empty layer.
     pub thumbhash: Option<String>,
+    /// Some for vector layers, which keep their strokes as data and are rasterized from them
+    /// rather than from `content_path`.
+    pub strokes: Option<Vec<VectorStroke>>,
+}
+
+/// A brush stroke kept on a vector layer, replayed through the same accumulate pipeline it was painted with.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct VectorStroke {
+    /// Straight-alpha RGBA brush color. Erasing strokes only use its alpha.
+    pub color: [f32; 4],
+    pub mode: StrokeMode,
+    pub tip: BrushTip,
+    /// Where the round tip's edge starts fading, as a fraction of its radius.
+    pub hardness: f32,
+    /// The most the stroke covers once its dabs have built up.
+    pub opacity: f32,
+    /// The dabs the `PointProcessor` produced, in stamping order.
+    pub points: Vec<VectorPoint>,
 }
 
-pub const DOCUMENT_VERSION: u32 = 1;
+/// What a stroke does to the layer it lands in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum StrokeMode {
+    #[default]
+    Paint,
+    /// Removes coverage from the layer where the stroke's dabs land.
+    Erase,
+}
+
+/// The shape every dab of a stroke stamps, see `renderer::brush` for the bundled tip images.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BrushTip {
+    /// The procedural soft circle.
+    #[default]
+    Round,
+    Chalk,
+    Pencil,
+    Bristle,
+}
+
+impl BrushTip {
+    pub const ALL: [Self; 4] = [Self::Round, Self::Chalk, Self::Pencil, Self::Bristle];
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct VectorPoint {
+    /// Layer local center, in artboard pixels.
+    pub position: [f32; 2],
+    /// In artboard pixels.
+    pub radius: f32,
+    /// Scales the dab's coverage, pen pressure makes it vary along the stroke.
+    pub opacity: f32,
+    /// Of the tip around the center, in radians.
+    pub rotation: f32,
+}
+
+/// Separable blend modes, as defined by the W3C Compositing and Blending spec.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    /// Linear dodge, clamped to white.
+    Add,
+    Darken,
+    Lighten,
+}
+
+impl BlendMode {
+    pub const ALL: [Self; 7] = [
+        Self::Normal,
+        Self::Multiply,
+        Self::Screen,
+        Self::Overlay,
+        Self::Add,
+        Self::Darken,
+        Self::Lighten,
+    ];
+}
+
+pub const DOCUMENT_VERSION: u32 = 9;
 
 impl Default for Document {
     fn default() -> Self {
@@ -67,14 +202,7 @@ impl Default for Document {
             name: "Artboard 1".to_string(),
             position: [0.0, 0.0],
             size: [800.0, 600.0],
-            layers: vec![Layer {
-                id: layer_id,
-                name: "Layer 1".to_string(),
-                offset: [0.0, 0.0],
-                visible: true,
-                content_path: None,
-                thumbhash: None,
-            }],
+            layers: vec![Layer::new(layer_id, "Layer 1".to_string()).into()],
         });
         doc
     }
@@ -97,6 +225,12 @@ impl Document {
         LayerId(id)
     }
 
+    pub fn alloc_group_id(&mut self) -> GroupId {
+        let id = self.next_id;
+        self.next_id += 1;
+        GroupId(id)
+    }
+
     pub fn artboard(&self, id: ArtboardId) -> Option<&Artboard> {
         self.artboards.iter().find(|artboard| artboard.id == id)
     }
@@ -105,16 +239,77 @@ impl Document {
         self.artboards.iter_mut().find(|artboard| artboard.id == id)
     }
 
+    /// Searches every artboard, including nested groups.
     pub fn find_layer(&self, id: LayerId) -> Option<(ArtboardId, &Layer)> {
+        self.artboards
+            .iter()
+            .find_map(|artboard| artboard.layer(id).map(|layer| (artboard.id, layer)))
+    }
+
+    pub fn find_layer_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.artboards
+            .iter_mut()
+            .find_map(|artboard| artboard.layer_mut(id))
+    }
+
+    /// Where `node` sits: its artboard, then its parent and index as `Artboard::locate` has them.
+    pub fn locate(&self, node: NodeId) -> Option<(ArtboardId, Option<GroupId>, usize)> {
         self.artboards.iter().find_map(|artboard| {
             artboard
-                .layers
-                .iter()
-                .find(|layer| layer.id == id)
-                .map(|layer| (artboard.id, layer))
+                .locate(node)
+                .map(|(parent, index)| (artboard.id, parent, index))
         })
     }
 
+    /// Whether the layer, or a group it sits in, is locked.
+    pub fn is_layer_locked(&self, id: LayerId) -> bool {
+        self.artboards
+            .iter()
+            .any(|artboard| artboard.is_layer_locked(id))
+    }
+
+    /// Whether the node, a group it sits in, or anything inside it is locked.
+    pub fn is_node_locked(&self, node: NodeId) -> bool {
+        self.artboards
+            .iter()
+            .any(|artboard| artboard.is_node_locked(node))
+    }
+
+    /// Where `MoveNode` puts `dragged` to sit right above `target`, among `target`'s siblings.
+    /// `None` when dropped on itself or `target` is gone.
+    pub fn slot_above(
+        &self,
+        dragged: NodeId,
+        target: NodeId,
+    ) -> Option<(ArtboardId, Option<GroupId>, usize)> {
+        if dragged == target {
+            return None;
+        }
+        let (artboard_id, parent, mut index) = self.locate(target)?;
+        // the index counts the siblings once the dragged node is taken out
+        if self
+            .locate(dragged)
+            .is_some_and(|(from_artboard, from_parent, from_index)| {
+                from_artboard == artboard_id && from_parent == parent && from_index < index
+            })
+        {
+            index -= 1;
+        }
+        Some((artboard_id, parent, index + 1))
+    }
+
+    pub fn find_group(&self, id: GroupId) -> Option<(ArtboardId, &LayerGroup)> {
+        self.artboards
+            .iter()
+            .find_map(|artboard| artboard.group(id).map(|group| (artboard.id, group)))
+    }
+
+    pub fn find_group_mut(&mut self, id: GroupId) -> Option<&mut LayerGroup> {
+        self.artboards
+            .iter_mut()
+            .find_map(|artboard| artboard.group_mut(id))
+    }
+
     pub fn hit_test(&self, world_position: cgmath::Point2<f32>) -> Option<ArtboardId> {
         self.artboards
             .iter()
@@ -149,8 +344,220 @@ impl Artboard {
         )
     }
 
+    /// Every layer bottom to top, descending into groups.
+    pub fn iter_layers(&self) -> Layers<'_> {
+        Layers {
+            stack: vec![self.layers.iter()],
+        }
+    }
+
+    /// Whether the layer, or a group it sits in, is locked. `false` for layers elsewhere.
+    pub fn is_layer_locked(&self, layer_id: LayerId) -> bool {
+        fn find(nodes: &[LayerNode], layer_id: LayerId, locked: bool) -> Option<bool> {
+            nodes.iter().find_map(|node| match node {
+                LayerNode::Layer(layer) => (layer.id == layer_id).then_some(locked || layer.locked),
+                LayerNode::Group(group) => find(&group.children, layer_id, locked || group.locked),
+            })
+        }
+        find(&self.layers, layer_id, false).unwrap_or(false)
+    }
+
+    /// Whether the node, a group it sits in, or anything inside it is locked. `false` for nodes elsewhere.
+    pub fn is_node_locked(&self, node: NodeId) -> bool {
+        fn holds_locked(nodes: &[LayerNode]) -> bool {
+            nodes.iter().any(|node| match node {
+                LayerNode::Layer(layer) => layer.locked,
+                LayerNode::Group(group) => group.locked || holds_locked(&group.children),
+            })
+        }
+        fn find(nodes: &[LayerNode], node: NodeId, locked: bool) -> Option<bool> {
+            nodes.iter().find_map(|candidate| match candidate {
+                LayerNode::Layer(layer) => {
+                    (candidate.id() == node).then_some(locked || layer.locked)
+                }
+                LayerNode::Group(group) if candidate.id() == node => {
+                    Some(locked || group.locked || holds_locked(&group.children))
+                }
+                LayerNode::Group(group) => find(&group.children, node, locked || group.locked),
+            })
+        }
+        find(&self.layers, node, false).unwrap_or(false)
+    }
+
+    /// Every layer bottom to top, descending into groups.
+    pub fn layers_mut(&mut self) -> Vec<&mut Layer> {
+        fn collect<'a>(nodes: &'a mut [LayerNode], out: &mut Vec<&'a mut Layer>) {
+            for node in nodes {
+                match node {
+                    LayerNode::Layer(layer) => out.push(layer),
+                    LayerNode::Group(group) => collect(&mut group.children, out),
+                }
+            }
+        }
+
+        let mut layers = Vec::new();
+        collect(&mut self.layers, &mut layers);
+        layers
+    }
+
     pub fn layer(&self, layer_id: LayerId) -> Option<&Layer> {
-        self.layers.iter().find(|layer| layer.id == layer_id)
+        self.iter_layers().find(|layer| layer.id == layer_id)
+    }
+
+    pub fn layer_mut(&mut self, layer_id: LayerId) -> Option<&mut Layer> {
+        self.layers_mut()
+            .into_iter()
+            .find(|layer| layer.id == layer_id)
+    }
+
+    pub fn group(&self, group_id: GroupId) -> Option<&LayerGroup> {
+        fn find(nodes: &[LayerNode], id: GroupId) -> Option<&LayerGroup> {
+            nodes.iter().find_map(|node| match node {
+                LayerNode::Layer(_) => None,
+                LayerNode::Group(group) if group.id == id => Some(group),
+                LayerNode::Group(group) => find(&group.children, id),
+            })
+        }
+        find(&self.layers, group_id)
+    }
+
+    pub fn group_mut(&mut self, group_id: GroupId) -> Option<&mut LayerGroup> {
+        fn find(nodes: &mut [LayerNode], id: GroupId) -> Option<&mut LayerGroup> {
+            nodes.iter_mut().find_map(|node| match node {
+                LayerNode::Layer(_) => None,
+                LayerNode::Group(group) => {
+                    if group.id == id {
+                        Some(group)
+                    } else {
+                        find(&mut group.children, id)
+                    }
+                }
+            })
+        }
+        find(&mut self.layers, group_id)
+    }
+
+    /// Where `node` sits: its parent group, `None` at the top of the stack, and its index among the parent's children.
+    pub fn locate(&self, node: NodeId) -> Option<(Option<GroupId>, usize)> {
+        fn find(
+            nodes: &[LayerNode],
+            parent: Option<GroupId>,
+            node: NodeId,
+        ) -> Option<(Option<GroupId>, usize)> {
+            nodes.iter().enumerate().find_map(|(index, child)| {
+                if child.id() == node {
+                    return Some((parent, index));
+                }
+                match child {
+                    LayerNode::Layer(_) => None,
+                    LayerNode::Group(group) => find(&group.children, Some(group.id), node),
+                }
+            })
+        }
+        find(&self.layers, None, node)
+    }
+
+    /// The children of `parent`, the artboard's own stack for `None`.
+    pub fn children(&self, parent: Option<GroupId>) -> Option<&[LayerNode]> {
+        match parent {
+            None => Some(&self.layers),
+            Some(group_id) => self.group(group_id).map(|group| group.children.as_slice()),
+        }
+    }
+
+    /// The children of `parent`, the artboard's own stack for `None`.
+    pub fn children_mut(&mut self, parent: Option<GroupId>) -> Option<&mut Vec<LayerNode>> {
+        match parent {
+            None => Some(&mut self.layers),
+            Some(group_id) => self.group_mut(group_id).map(|group| &mut group.children),
+        }
+    }
+}
+
+impl Layer {
+    /// An empty, visible raster layer.
+    pub fn new(id: LayerId, name: String) -> Self {
+        Self {
+            id,
+            name,
+            offset: [0.0, 0.0],
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            content_path: None,
+            thumbhash: None,
+            strokes: None,
+        }
+    }
+
+    /// An empty, visible vector layer, keeping the strokes painted on it.
+    pub fn new_vector(id: LayerId, name: String) -> Self {
+        Self {
+            strokes: Some(Vec::new()),
+            ..Self::new(id, name)
+        }
+    }
+
+    pub fn is_vector(&self) -> bool {
+        self.strokes.is_some()
+    }
+}
+
+impl LayerGroup {
+    /// Whether the group has to be flattened on its own before it is composited.
+    /// Groups at full opacity with normal blending draw their children straight into the parent.
+    pub fn is_isolated(&self) -> bool {
+        self.opacity < 1.0 || self.blend_mode != BlendMode::Normal
+    }
+}
+
+impl LayerNode {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Self::Layer(layer) => NodeId::Layer(layer.id),
+            Self::Group(group) => NodeId::Group(group.id),
+        }
+    }
+
+    /// The node's layers bottom to top, itself if it is one.
+    pub fn iter_layers(&self) -> Layers<'_> {
+        Layers {
+            stack: vec![std::slice::from_ref(self).iter()],
+        }
+    }
+}
+
+impl From<Layer> for LayerNode {
+    fn from(layer: Layer) -> Self {
+        Self::Layer(layer)
+    }
+}
+
+impl From<LayerGroup> for LayerNode {
+    fn from(group: LayerGroup) -> Self {
+        Self::Group(group)
+    }
+}
+
+/// Depth-first iterator over the layers of a layer stack, see `Artboard::iter_layers`.
+pub struct Layers<'a> {
+    stack: Vec<std::slice::Iter<'a, LayerNode>>,
+}
+
+impl<'a> Iterator for Layers<'a> {
+    type Item = &'a Layer;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(LayerNode::Layer(layer)) => return Some(layer),
+                Some(LayerNode::Group(group)) => self.stack.push(group.children.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
     }
 }
 
@@ -188,7 +595,9 @@ mod tests {
         assert_eq!(artboard.position, [0.0, 0.0]);
         assert_eq!(artboard.size, [800.0, 600.0]);
         assert_eq!(artboard.layers.len(), 1);
-        let layer = &artboard.layers[0];
+        let LayerNode::Layer(layer) = &artboard.layers[0] else {
+            panic!("default document starts with a plain layer");
+        };
         assert!(layer.visible);
         assert!(layer.content_path.is_none());
         assert!(layer.thumbhash.is_none());
@@ -250,10 +659,157 @@ mod tests {
     fn find_layer_reports_owning_artboard() {
         let document = Document::default_document();
         let artboard = &document.artboards[0];
-        let layer_id = artboard.layers[0].id;
+        let layer_id = artboard.iter_layers().next().unwrap().id;
         let (owner, layer) = document.find_layer(layer_id).unwrap();
         assert_eq!(owner, artboard.id);
         assert_eq!(layer.id, layer_id);
         assert!(document.find_layer(LayerId(9999)).is_none());
     }
+
+    fn layer(id: u32) -> LayerNode {
+        LayerNode::Layer(Layer {
+            id: LayerId(id),
+            name: format!("Layer {id}"),
+            offset: [0.0, 0.0],
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            content_path: None,
+            thumbhash: None,
+            strokes: None,
+        })
+    }
+
+    fn group(id: u32, children: Vec<LayerNode>) -> LayerNode {
+        LayerNode::Group(LayerGroup {
+            id: GroupId(id),
+            name: format!("Group {id}"),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            children,
+        })
+    }
+
+    /// Artboard 1: layer 2, group 3 { layer 4, group 5 { layer 6 } }, layer 7
+    fn nested_document() -> Document {
+        let mut board = artboard(1, [0.0, 0.0], [100.0, 100.0]);
+        board.layers = vec![
+            layer(2),
+            group(3, vec![layer(4), group(5, vec![layer(6)])]),
+            layer(7),
+        ];
+        Document {
+            version: DOCUMENT_VERSION,
+            next_id: 8,
+            artboards: vec![board],
+        }
+    }
+
+    #[test]
+    fn iter_layers_descends_depth_first() {
+        let document = nested_document();
+        let ids: Vec<u32> = document.artboards[0]
+            .iter_layers()
+            .map(|layer| layer.id.0)
+            .collect();
+        assert_eq!(ids, [2, 4, 6, 7]);
+
+        let mut document = document;
+        let ids: Vec<u32> = document.artboards[0]
+            .layers_mut()
+            .into_iter()
+            .map(|layer| layer.id.0)
+            .collect();
+        assert_eq!(ids, [2, 4, 6, 7]);
+    }
+
+    #[test]
+    fn find_layer_and_group_recurse() {
+        let mut document = nested_document();
+        assert_eq!(document.find_layer(LayerId(6)).unwrap().0, ArtboardId(1));
+        assert_eq!(document.find_group(GroupId(5)).unwrap().1.children.len(), 1);
+        assert!(
+            document.find_group(GroupId(6)).is_none(),
+            "layers are not groups"
+        );
+
+        document.find_layer_mut(LayerId(6)).unwrap().visible = false;
+        assert!(!document.find_layer(LayerId(6)).unwrap().1.visible);
+        document.find_group_mut(GroupId(3)).unwrap().opacity = 0.5;
+        assert!(document.find_group(GroupId(3)).unwrap().1.is_isolated());
+    }
+
+    #[test]
+    fn nested_groups_round_trip_tagged() {
+        let document = nested_document();
+        let value = serde_json::to_value(&document).unwrap();
+        let nodes = &value["artboards"][0]["layers"];
+        assert_eq!(nodes[0]["kind"], "layer");
+        assert_eq!(nodes[1]["kind"], "group");
+        assert_eq!(nodes[1]["children"][1]["children"][0]["id"], 6);
+
+        let parsed: Document = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, document);
+    }
+
+    #[test]
+    fn locked_groups_lock_every_layer_in_them() {
+        let mut document = nested_document();
+        assert!(!document.is_layer_locked(LayerId(6)));
+
+        document.find_group_mut(GroupId(5)).unwrap().locked = true;
+        assert!(document.is_layer_locked(LayerId(6)));
+        assert!(!document.is_layer_locked(LayerId(4)), "outside the group");
+
+        document.find_layer_mut(LayerId(7)).unwrap().locked = true;
+        assert!(document.is_layer_locked(LayerId(7)));
+        assert!(!document.is_layer_locked(LayerId(99)));
+    }
+
+    #[test]
+    fn nodes_are_locked_by_their_groups_and_contents() {
+        let mut document = nested_document();
+        let group = |id| NodeId::Group(GroupId(id));
+        assert!(!document.is_node_locked(group(3)));
+
+        document.find_layer_mut(LayerId(6)).unwrap().locked = true;
+        assert!(document.is_node_locked(NodeId::Layer(LayerId(6))));
+        assert!(document.is_node_locked(group(5)), "holds a locked layer");
+        assert!(
+            document.is_node_locked(group(3)),
+            "holds a locked layer deeper down"
+        );
+        assert!(!document.is_node_locked(NodeId::Layer(LayerId(4))));
+
+        document.find_layer_mut(LayerId(6)).unwrap().locked = false;
+        document.find_group_mut(GroupId(3)).unwrap().locked = true;
+        assert!(document.is_node_locked(NodeId::Layer(LayerId(4))));
+        assert!(document.is_node_locked(group(5)), "sits in a locked group");
+        assert!(!document.is_node_locked(NodeId::Layer(LayerId(7))));
+        assert!(!document.is_node_locked(group(99)));
+    }
+
+    #[test]
+    fn slots_above_skip_the_dragged_node() {
+        let document = nested_document();
+        let at = |dragged, target| document.slot_above(NodeId::Layer(LayerId(dragged)), target);
+        assert_eq!(
+            at(2, NodeId::Layer(LayerId(7))),
+            Some((ArtboardId(1), None, 2)),
+            "taken from below, the target moves down one"
+        );
+        assert_eq!(
+            at(7, NodeId::Layer(LayerId(2))),
+            Some((ArtboardId(1), None, 1))
+        );
+        assert_eq!(
+            at(2, NodeId::Layer(LayerId(6))),
+            Some((ArtboardId(1), Some(GroupId(5)), 1))
+        );
+        assert_eq!(at(2, NodeId::Layer(LayerId(2))), None);
+        assert_eq!(at(2, NodeId::Group(GroupId(99))), None);
+    }
 }
@@ -0,0 +1,737 @@
+//! OpenRaster (`.ora`) interchange, one artboard per file.
+//!
+//! A zip archive, readable by Krita, GIMP and MyPaint:
+//!
+//! ```text
+//! mimetype                 "image/openraster", first and stored
+//! stack.xml                <image w h><stack> of <layer>s and nested <stack>s, topmost first
+//! data/layer-<n>.png       one straight-alpha PNG per layer
+//! mergedimage.png          the flattened artboard
+//! Thumbnails/thumbnail.png the flattened artboard, at most 256px
+//! ```
+//!
+//! Nested stacks map to `LayerGroup`s. `composite-op` maps to `BlendMode`, unknown ops fall back to normal.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek, Write};
+
+use anyhow::Context;
+
+use crate::document::{
+    Artboard, BlendMode, DOCUMENT_VERSION, Document, Layer, LayerGroup, LayerId, LayerNode,
+    bundle::Bundle,
+};
+
+pub const ORA_EXTENSION: &str = "ora";
+
+const MIMETYPE: &str = "image/openraster";
+const STACK_XML: &str = "stack.xml";
+const MERGED_IMAGE: &str = "mergedimage.png";
+const THUMBNAIL: &str = "Thumbnails/thumbnail.png";
+/// Longest edge of `THUMBNAIL`, as the spec requires.
+pub const THUMBNAIL_MAX_DIM: u32 = 256;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpenRasterError {
+    /// Not a zip, or the `mimetype` entry is missing or wrong.
+    NotOpenRaster,
+    /// `stack.xml` is missing or doesn't describe a usable image.
+    InvalidStack(String),
+    /// A layer references an entry the archive doesn't have.
+    MissingEntry(String),
+}
+
+impl fmt::Display for OpenRasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotOpenRaster => write!(f, "not an OpenRaster file"),
+            Self::InvalidStack(reason) => write!(f, "invalid OpenRaster stack.xml: {reason}"),
+            Self::MissingEntry(name) => write!(f, "OpenRaster file has no entry for '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for OpenRasterError {}
+
+/// Reads an `.ora` into a single artboard document, with every layer PNG keyed by its `content_path`.
+/// `name` names the artboard when the root stack doesn't.
+pub fn read_openraster(reader: impl Read + Seek, name: &str) -> anyhow::Result<Bundle> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|_| OpenRasterError::NotOpenRaster)?;
+    let mimetype =
+        read_entry(&mut archive, "mimetype").map_err(|_| OpenRasterError::NotOpenRaster)?;
+    if mimetype.trim_ascii() != MIMETYPE.as_bytes() {
+        return Err(OpenRasterError::NotOpenRaster.into());
+    }
+
+    let stack_xml = read_entry(&mut archive, STACK_XML)?;
+    let stack_xml = std::str::from_utf8(&stack_xml)
+        .map_err(|_| OpenRasterError::InvalidStack("not UTF-8".to_string()))?;
+    let document = parse_stack(stack_xml, name)?;
+
+    let mut pngs = HashMap::new();
+    for artboard in &document.artboards {
+        for layer in artboard.iter_layers() {
+            let Some(src) = &layer.content_path else {
+                continue;
+            };
+            if !pngs.contains_key(src) {
+                pngs.insert(src.clone(), read_entry(&mut archive, src)?);
+            }
+        }
+    }
+
+    Ok(Bundle { document, pngs })
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| OpenRasterError::MissingEntry(name.to_string()))?;
+    let mut bytes = Vec::with_capacity(usize::try_from(entry.size()).unwrap_or_default());
+    entry
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("reading OpenRaster entry '{name}'"))?;
+    Ok(bytes)
+}
+
+fn parse_stack(xml: &str, name: &str) -> anyhow::Result<Document> {
+    let invalid = |reason: String| OpenRasterError::InvalidStack(reason);
+
+    let xml = roxmltree::Document::parse(xml).map_err(|error| invalid(error.to_string()))?;
+    let image = xml.root_element();
+    if !image.has_tag_name("image") {
+        return Err(invalid(format!("root element is <{}>", image.tag_name().name())).into());
+    }
+    let extent = |attribute: &str| -> Result<f32, OpenRasterError> {
+        image
+            .attribute(attribute)
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .filter(|value| *value > 0)
+            // clamped to the texture limit on load
+            .map(|value| {
+                #[allow(clippy::cast_precision_loss)]
+                let value = value as f32;
+                value
+            })
+            .ok_or_else(|| invalid(format!("<image> has no valid '{attribute}'")))
+    };
+    let size = [extent("w")?, extent("h")?];
+    let root = image
+        .children()
+        .find(|node| node.has_tag_name("stack"))
+        .ok_or_else(|| invalid("<image> has no <stack>".to_string()))?;
+
+    let mut document = Document {
+        version: DOCUMENT_VERSION,
+        next_id: 1,
+        artboards: vec![],
+    };
+    let id = document.alloc_artboard_id();
+    let layers = parse_children(root, &mut document)?;
+    document.artboards.push(Artboard {
+        id,
+        name: root.attribute("name").unwrap_or(name).to_string(),
+        position: [0.0, 0.0],
+        size,
+        layers,
+    });
+    Ok(document)
+}
+
+/// Layer nodes of a `<stack>`, reversed into drawing order.
+fn parse_children(
+    stack: roxmltree::Node,
+    document: &mut Document,
+) -> anyhow::Result<Vec<LayerNode>> {
+    let mut nodes = Vec::new();
+    for node in stack.children().filter(roxmltree::Node::is_element) {
+        let name = node.attribute("name").unwrap_or_default().to_string();
+        let visible = node.attribute("visibility") != Some("hidden");
+        let opacity = node
+            .attribute("opacity")
+            .and_then(|value| value.trim().parse::<f32>().ok())
+            .filter(|value| value.is_finite())
+            .map_or(1.0, |value| value.clamp(0.0, 1.0));
+        let blend_mode = node
+            .attribute("composite-op")
+            .map_or(BlendMode::Normal, blend_mode_from_op);
+
+        match node.tag_name().name() {
+            "layer" => {
+                let src = node.attribute("src").ok_or_else(|| {
+                    OpenRasterError::InvalidStack(format!("layer '{name}' has no src"))
+                })?;
+                let coordinate = |attribute: &str| {
+                    node.attribute(attribute)
+                        .and_then(|value| value.trim().parse::<f32>().ok())
+                        .filter(|value| value.is_finite())
+                        .map_or(0.0, f32::round)
+                };
+                nodes.push(LayerNode::Layer(Layer {
+                    id: document.alloc_layer_id(),
+                    name,
+                    offset: [coordinate("x"), coordinate("y")],
+                    visible,
+                    locked: false,
+                    opacity,
+                    blend_mode,
+                    content_path: Some(src.to_string()),
+                    thumbhash: None,
+                    strokes: None,
+                }));
+            }
+            "stack" => {
+                let id = document.alloc_group_id();
+                nodes.push(LayerNode::Group(LayerGroup {
+                    id,
+                    name,
+                    visible,
+                    locked: false,
+                    opacity,
+                    blend_mode,
+                    children: parse_children(node, document)?,
+                }));
+            }
+            // text and filter nodes from other editors have no pixels we could load
+            other => log::warn!("skipping unsupported OpenRaster element <{other}>"),
+        }
+    }
+    nodes.reverse();
+    Ok(nodes)
+}
+
+fn blend_mode_from_op(op: &str) -> BlendMode {
+    match op {
+        "svg:src-over" => BlendMode::Normal,
+        "svg:multiply" => BlendMode::Multiply,
+        "svg:screen" => BlendMode::Screen,
+        "svg:overlay" => BlendMode::Overlay,
+        "svg:plus" => BlendMode::Add,
+        "svg:darken" => BlendMode::Darken,
+        "svg:lighten" => BlendMode::Lighten,
+        other => {
+            log::warn!("unsupported OpenRaster composite-op '{other}', using normal");
+            BlendMode::Normal
+        }
+    }
+}
+
+fn composite_op(blend_mode: BlendMode) -> &'static str {
+    match blend_mode {
+        BlendMode::Normal => "svg:src-over",
+        BlendMode::Multiply => "svg:multiply",
+        BlendMode::Screen => "svg:screen",
+        BlendMode::Overlay => "svg:overlay",
+        BlendMode::Add => "svg:plus",
+        BlendMode::Darken => "svg:darken",
+        BlendMode::Lighten => "svg:lighten",
+    }
+}
+
+/// PNGs making up one `.ora`, all straight alpha.
+pub struct OpenRasterImages<'a> {
+    /// Artboard sized, one per layer of the artboard.
+    pub layers: &'a HashMap<LayerId, Vec<u8>>,
+    pub merged: &'a [u8],
+    pub thumbnail: &'a [u8],
+}
+
+/// Writes `artboard` as an `.ora` archive to `writer`.
+pub fn write_openraster(
+    writer: impl Write + Seek,
+    artboard: &Artboard,
+    images: &OpenRasterImages,
+) -> anyhow::Result<()> {
+    use zip::{CompressionMethod, write::SimpleFileOptions};
+
+    // PNGs are compressed already
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut zip = zip::ZipWriter::new(writer);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+
+    zip.start_file(STACK_XML, deflated)?;
+    zip.write_all(stack_xml(artboard).as_bytes())?;
+
+    for layer in artboard.iter_layers() {
+        let png = images
+            .layers
+            .get(&layer.id)
+            .with_context(|| format!("no pixels for layer {}", layer.id.0))?;
+        zip.start_file(layer_src(layer.id), stored)?;
+        zip.write_all(png)?;
+    }
+
+    zip.start_file(MERGED_IMAGE, stored)?;
+    zip.write_all(images.merged)?;
+    zip.start_file(THUMBNAIL, stored)?;
+    zip.write_all(images.thumbnail)?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn layer_src(id: LayerId) -> String {
+    format!("data/layer-{}.png", id.0)
+}
+
+fn stack_xml(artboard: &Artboard) -> String {
+    let (width, height) = artboard.pixel_size();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <image version=\"0.0.5\" w=\"{width}\" h=\"{height}\">\n\
+         \x20 <stack name=\"{}\">\n",
+        escape(&artboard.name)
+    );
+    write_nodes(&mut xml, &artboard.layers, 2);
+    xml.push_str("  </stack>\n</image>\n");
+    xml
+}
+
+/// Appends `nodes` topmost first, as OpenRaster orders them.
+fn write_nodes(xml: &mut String, nodes: &[LayerNode], depth: usize) {
+    use std::fmt::Write as _;
+
+    let indent = "  ".repeat(depth);
+    for node in nodes.iter().rev() {
+        match node {
+            LayerNode::Layer(layer) => {
+                let _ = writeln!(
+                    xml,
+                    "{indent}<layer name=\"{}\" src=\"{}\" x=\"{}\" y=\"{}\" visibility=\"{}\" opacity=\"{}\" composite-op=\"{}\"/>",
+                    escape(&layer.name),
+                    layer_src(layer.id),
+                    layer.offset[0].round(),
+                    layer.offset[1].round(),
+                    visibility(layer.visible),
+                    layer.opacity,
+                    composite_op(layer.blend_mode),
+                );
+            }
+            LayerNode::Group(group) => {
+                let isolation = if group.is_isolated() {
+                    "isolate"
+                } else {
+                    "auto"
+                };
+                let _ = writeln!(
+                    xml,
+                    "{indent}<stack name=\"{}\" visibility=\"{}\" opacity=\"{}\" composite-op=\"{}\" isolation=\"{isolation}\">",
+                    escape(&group.name),
+                    visibility(group.visible),
+                    group.opacity,
+                    composite_op(group.blend_mode),
+                );
+                write_nodes(xml, &group.children, depth + 1);
+                let _ = writeln!(xml, "{indent}</stack>");
+            }
+        }
+    }
+}
+
+fn visibility(visible: bool) -> &'static str {
+    if visible { "visible" } else { "hidden" }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes one `<document>-<artboard name>.ora` per artboard into `dir`, returning their paths.
+///
+/// Layers are read back from the `SceneRenderer` like a save, the merged image is flattened without the artboard background.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_openraster(
+    dir: &std::path::Path,
+    document_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut crate::resources::scene_renderer::SceneRenderer,
+    document: &Document,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    use crate::document::export::{
+        export_file_name, read_straight_layers, render_straight_composite,
+    };
+
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+    let mut paths = Vec::new();
+    for artboard in &document.artboards {
+        let size = artboard.pixel_size();
+        let layers = read_straight_layers(device, queue, scene, artboard)?
+            .into_iter()
+            .map(|(id, pixels)| Ok((id, encode_png(&pixels, size)?)))
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+
+        let (merged, size) =
+            render_straight_composite(device, queue, scene, document, artboard.id)?;
+        let merged_image = image::RgbaImage::from_raw(size.0, size.1, merged)
+            .context("flattened artboard has the wrong size")?;
+        let thumbnail = image::DynamicImage::ImageRgba8(merged_image.clone())
+            .thumbnail(THUMBNAIL_MAX_DIM, THUMBNAIL_MAX_DIM)
+            .to_rgba8();
+
+        let path = dir.join(export_file_name(
+            document_name,
+            &artboard.name,
+            ORA_EXTENSION,
+        ));
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        write_openraster(
+            std::io::BufWriter::new(file),
+            artboard,
+            &OpenRasterImages {
+                layers: &layers,
+                merged: &encode_png(merged_image.as_raw(), size)?,
+                thumbnail: &encode_png(thumbnail.as_raw(), thumbnail.dimensions())?,
+            },
+        )
+        .with_context(|| format!("writing {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_png(rgba: &[u8], (width, height): (u32, u32)) -> anyhow::Result<Vec<u8>> {
+    use image::ImageEncoder;
+
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png).write_image(
+        rgba,
+        width,
+        height,
+        image::ExtendedColorType::Rgba8,
+    )?;
+    Ok(png)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::document::loader::{LoadedDocument, load_document_from, load_openraster};
+    use crate::document::{ArtboardId, GroupId};
+    use crate::resources::scene_renderer::SceneRenderer;
+    use crate::testing::fixtures::{doc_two_artboards, scratch_dir, solid_layer_pixels};
+    use crate::testing::gpu::headless_gpu;
+
+    fn layer(id: u32, name: &str) -> Layer {
+        Layer {
+            id: LayerId(id),
+            name: name.to_string(),
+            offset: [0.0, 0.0],
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            content_path: None,
+            thumbhash: None,
+            strokes: None,
+        }
+    }
+
+    /// `Bottom`, then `Shading` (multiply, in an isolated group with `Inner`), then a hidden `Top <&>`.
+    fn layered_artboard() -> Artboard {
+        Artboard {
+            id: ArtboardId(1),
+            name: "Cover \"A\"".to_string(),
+            position: [40.0, 40.0],
+            size: [4.0, 2.0],
+            layers: vec![
+                layer(2, "Bottom").into(),
+                LayerGroup {
+                    id: GroupId(3),
+                    name: "Group".to_string(),
+                    visible: true,
+                    locked: false,
+                    opacity: 0.5,
+                    blend_mode: BlendMode::Normal,
+                    children: vec![
+                        layer(4, "Inner").into(),
+                        Layer {
+                            offset: [3.0, -2.0],
+                            blend_mode: BlendMode::Multiply,
+                            ..layer(5, "Shading")
+                        }
+                        .into(),
+                    ],
+                }
+                .into(),
+                Layer {
+                    visible: false,
+                    opacity: 0.25,
+                    blend_mode: BlendMode::Lighten,
+                    ..layer(6, "Top <&>")
+                }
+                .into(),
+            ],
+        }
+    }
+
+    /// Everything but ids, in drawing order, depth first.
+    fn outline(nodes: &[LayerNode], depth: usize, lines: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                LayerNode::Layer(layer) => lines.push(format!(
+                    "{depth} layer {} {:?} {} {} {:?}",
+                    layer.name, layer.offset, layer.visible, layer.opacity, layer.blend_mode
+                )),
+                LayerNode::Group(group) => {
+                    lines.push(format!(
+                        "{depth} group {} {} {} {:?}",
+                        group.name, group.visible, group.opacity, group.blend_mode
+                    ));
+                    outline(&group.children, depth + 1, lines);
+                }
+            }
+        }
+    }
+
+    fn outline_of(artboard: &Artboard) -> Vec<String> {
+        let mut lines = Vec::new();
+        outline(&artboard.layers, 0, &mut lines);
+        lines
+    }
+
+    fn png(rgba: [u8; 4], size: (u32, u32)) -> Vec<u8> {
+        encode_png(&rgba.repeat(size.0 as usize * size.1 as usize), size).unwrap()
+    }
+
+    fn ora_bytes(artboard: &Artboard) -> Vec<u8> {
+        let layers = artboard
+            .iter_layers()
+            .map(|layer| (layer.id, png([0, 0, 255, 255], artboard.pixel_size())))
+            .collect();
+        let merged = png([0, 0, 255, 255], artboard.pixel_size());
+        let mut bytes = Cursor::new(Vec::new());
+        write_openraster(
+            &mut bytes,
+            artboard,
+            &OpenRasterImages {
+                layers: &layers,
+                merged: &merged,
+                thumbnail: &merged,
+            },
+        )
+        .unwrap();
+        bytes.into_inner()
+    }
+
+    /// Zips `entries` in order, with the OpenRaster mimetype first.
+    fn handmade_ora(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn expect_ora_error(result: anyhow::Result<Bundle>) -> OpenRasterError {
+        result
+            .err()
+            .expect("reading should fail")
+            .downcast::<OpenRasterError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn stack_round_trips_through_read() {
+        let artboard = layered_artboard();
+        let Bundle { document, pngs } =
+            read_openraster(Cursor::new(ora_bytes(&artboard)), "fallback").unwrap();
+
+        let read = &document.artboards[0];
+        assert_eq!(read.name, artboard.name);
+        assert_eq!(read.pixel_size(), artboard.pixel_size());
+        assert_eq!(outline_of(read), outline_of(&artboard));
+        assert_eq!(pngs.len(), 4);
+        for layer in read.iter_layers() {
+            assert!(pngs.contains_key(layer.content_path.as_ref().unwrap()));
+        }
+    }
+
+    #[test]
+    fn read_allocates_fresh_unique_ids() {
+        let Bundle { document, .. } =
+            read_openraster(Cursor::new(ora_bytes(&layered_artboard())), "fallback").unwrap();
+
+        let artboard = &document.artboards[0];
+        let mut ids: Vec<u32> = artboard.iter_layers().map(|layer| layer.id.0).collect();
+        ids.push(artboard.id.0);
+        ids.extend(artboard.layers.iter().filter_map(|node| match node {
+            LayerNode::Group(group) => Some(group.id.0),
+            LayerNode::Layer(_) => None,
+        }));
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 6);
+        assert!(ids.iter().all(|id| *id < document.next_id));
+    }
+
+    #[test]
+    fn stack_lists_the_topmost_layer_first() {
+        let xml = stack_xml(&layered_artboard());
+        let top = xml.find("Top &lt;&amp;&gt;").unwrap();
+        let group = xml.find("name=\"Group\"").unwrap();
+        let bottom = xml.find("name=\"Bottom\"").unwrap();
+        assert!(top < group && group < bottom);
+        assert!(xml.contains("name=\"Cover &quot;A&quot;\""));
+        assert!(xml.contains("isolation=\"isolate\""));
+        assert!(xml.contains("x=\"3\" y=\"-2\""));
+    }
+
+    #[test]
+    fn mimetype_comes_first_and_stored() {
+        let bytes = ora_bytes(&layered_artboard());
+        // readers may sniff the type at a fixed offset, which only works without an extra field
+        assert_eq!(&bytes[30..38], b"mimetype");
+        assert_eq!(&bytes[38..54], MIMETYPE.as_bytes());
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+        drop(mimetype);
+        for name in [STACK_XML, MERGED_IMAGE, THUMBNAIL] {
+            assert!(archive.by_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn reads_stacks_written_by_other_editors() {
+        let xml = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <image w="3" h="2" version="0.0.3">
+              <stack>
+                <text name="Caption"/>
+                <layer name="Glow" src="data/glow.png" composite-op="krita:color-dodge" opacity="7"/>
+                <layer src="data/paint.png" x="1.6" y="nope" visibility="hidden"/>
+              </stack>
+            </image>"#;
+        let bytes = handmade_ora(&[
+            ("mimetype", MIMETYPE.as_bytes()),
+            (STACK_XML, xml),
+            ("data/paint.png", &png([255, 0, 0, 255], (3, 2))),
+            ("data/glow.png", &png([0, 255, 0, 128], (5, 5))),
+        ]);
+
+        let loaded = load_openraster(Cursor::new(bytes), "sketch", 4096).unwrap();
+        let artboard = &loaded.document.artboards[0];
+        assert_eq!(artboard.name, "sketch");
+        assert_eq!(
+            outline_of(artboard),
+            [
+                "0 layer  [2.0, 0.0] false 1 Normal",
+                "0 layer Glow [0.0, 0.0] true 1 Normal",
+            ]
+        );
+
+        // oversized layer PNGs are cropped to the artboard and premultiplied
+        let glow = artboard.iter_layers().nth(1).unwrap().id;
+        assert_eq!(
+            loaded.layer_pixels[&glow],
+            solid_layer_pixels((3, 2), [0, 255, 0, 128])
+        );
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_openraster() {
+        assert_eq!(
+            expect_ora_error(read_openraster(Cursor::new(b"not a zip".to_vec()), "x")),
+            OpenRasterError::NotOpenRaster
+        );
+
+        let wrong_type = handmade_ora(&[("mimetype", b"image/png")]);
+        assert_eq!(
+            expect_ora_error(read_openraster(Cursor::new(wrong_type), "x")),
+            OpenRasterError::NotOpenRaster
+        );
+
+        let no_size = handmade_ora(&[
+            ("mimetype", MIMETYPE.as_bytes()),
+            (STACK_XML, b"<image><stack/></image>"),
+        ]);
+        assert!(matches!(
+            expect_ora_error(read_openraster(Cursor::new(no_size), "x")),
+            OpenRasterError::InvalidStack(_)
+        ));
+
+        let missing_png = handmade_ora(&[
+            ("mimetype", MIMETYPE.as_bytes()),
+            (
+                STACK_XML,
+                br#"<image w="1" h="1"><stack><layer src="data/gone.png"/></stack></image>"#,
+            ),
+        ]);
+        assert_eq!(
+            expect_ora_error(read_openraster(Cursor::new(missing_png), "x")),
+            OpenRasterError::MissingEntry("data/gone.png".to_string())
+        );
+    }
+
+    #[test]
+    fn export_round_trips_through_load() {
+        let (device, queue) = headless_gpu();
+        let mut scene = SceneRenderer::new(&device, &queue, wgpu::TextureFormat::Rgba8Unorm);
+        let mut document = doc_two_artboards();
+        {
+            let left = document.find_layer_mut(LayerId(2)).unwrap();
+            left.name = "Paint".to_string();
+            left.offset = [5.0, 7.0];
+            left.opacity = 0.5;
+            left.blend_mode = BlendMode::Screen;
+        }
+        let paint = solid_layer_pixels((600, 400), [255, 0, 0, 128]);
+        scene.hydrate(
+            &device,
+            &queue,
+            &LoadedDocument {
+                document: document.clone(),
+                layer_pixels: HashMap::from([(LayerId(2), paint.clone())]),
+            },
+        );
+
+        let dir = scratch_dir("openraster-export");
+        let paths = export_openraster(&dir, "doc", &device, &queue, &mut scene, &document).unwrap();
+        assert_eq!(paths, [dir.join("doc-left.ora"), dir.join("doc-right.ora")]);
+
+        let loaded = load_document_from(&dir, "doc-left", 4096).unwrap();
+        let artboard = &loaded.document.artboards[0];
+        assert_eq!(artboard.name, "Left");
+        assert_eq!(artboard.pixel_size(), (600, 400));
+        assert_eq!(outline_of(artboard), outline_of(&document.artboards[0]));
+        let layer = artboard.iter_layers().next().unwrap().id;
+        assert_eq!(loaded.layer_pixels[&layer], paint);
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&paths[1]).unwrap()).unwrap();
+        let merged = image::load_from_memory(&read_entry(&mut archive, MERGED_IMAGE).unwrap())
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(merged.dimensions(), (400, 300));
+        assert_eq!(merged.get_pixel(0, 0).0, [0, 0, 0, 0], "no background");
+        let thumbnail = image::load_from_memory(&read_entry(&mut archive, THUMBNAIL).unwrap())
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(thumbnail.dimensions(), (256, 192));
+    }
+}
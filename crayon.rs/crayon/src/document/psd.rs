@@ -0,0 +1,927 @@
+//! Layered Photoshop (`.psd`) export, one artboard per file.
+//!
+//! 8-bit RGB with transparency, big-endian throughout:
+//!
+//! ```text
+//! header          b"8BPS" | version 1 | 6 reserved | channels 4 | height | width | depth 8 | mode RGB
+//! color mode data empty
+//! resources       empty
+//! layers          one record per layer and group, bottom first, then every record's channel data
+//! composite       the flattened artboard, RLE planes R, G, B, A
+//! ```
+//!
+//! Layers are trimmed to their painted bounds and placed at their offsets. Groups become folders:
+//! a divider record below their children and the folder record above, as Photoshop writes them.
+//! Channel rows are PackBits compressed and stored straight alpha.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::bail;
+
+use crate::document::{Artboard, BlendMode, LayerId, LayerNode};
+
+pub const PSD_EXTENSION: &str = "psd";
+
+/// Largest width or height a version 1 PSD can describe.
+pub const PSD_MAX_DIM: u32 = 30_000;
+
+const SIGNATURE: &[u8; 4] = b"8BPS";
+const RESOURCE_SIGNATURE: &[u8; 4] = b"8BIM";
+const COLOR_MODE_RGB: u16 = 3;
+const COMPRESSION_RAW: u16 = 0;
+const COMPRESSION_RLE: u16 = 1;
+/// Layer flag hiding the layer.
+const FLAG_HIDDEN: u8 = 0b10;
+const CHANNEL_COUNT: u16 = 4;
+/// Channel ids in the order they're written.
+const CHANNEL_IDS: [i16; CHANNEL_COUNT as usize] = [-1, 0, 1, 2];
+/// `lsct` section divider types.
+const SECTION_OPEN_FOLDER: u32 = 1;
+const SECTION_BOUNDING_DIVIDER: u32 = 3;
+const PASS_THROUGH: &[u8; 4] = b"pass";
+
+/// Artboard pixels for one PSD, all straight alpha RGBA8.
+pub struct PsdImages<'a> {
+    /// Artboard sized, one per layer of the artboard.
+    pub layers: &'a HashMap<LayerId, Vec<u8>>,
+    pub composite: &'a [u8],
+}
+
+/// Writes `artboard` as a layered PSD to `writer`.
+pub fn write_psd(
+    mut writer: impl Write,
+    artboard: &Artboard,
+    images: &PsdImages,
+) -> anyhow::Result<()> {
+    let (width, height) = artboard.pixel_size();
+    if width > PSD_MAX_DIM || height > PSD_MAX_DIM {
+        bail!("{width}x{height} exceeds the {PSD_MAX_DIM}px PSD limit");
+    }
+
+    let mut records = Vec::new();
+    push_records(&mut records, &artboard.layers, images, (width, height))?;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(SIGNATURE);
+    put_u16(&mut out, 1);
+    out.extend_from_slice(&[0; 6]);
+    put_u16(&mut out, CHANNEL_COUNT);
+    put_u32(&mut out, height);
+    put_u32(&mut out, width);
+    put_u16(&mut out, 8);
+    put_u16(&mut out, COLOR_MODE_RGB);
+    // color mode data, image resources
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 0);
+
+    let layer_info = layer_info(&records)?;
+    put_u32(&mut out, section_len(layer_info.len() + 4 + 4)?);
+    put_u32(&mut out, section_len(layer_info.len())?);
+    out.extend_from_slice(&layer_info);
+    // global layer mask info
+    put_u32(&mut out, 0);
+
+    put_u16(&mut out, COMPRESSION_RLE);
+    let planes: Vec<(Vec<u8>, Vec<u8>)> = [0, 1, 2, 3]
+        .into_iter()
+        .map(|channel| {
+            pack_rows(
+                &plane(images.composite, width, channel, (0, 0, width, height)),
+                width,
+            )
+        })
+        .collect();
+    for (counts, _) in &planes {
+        out.extend_from_slice(counts);
+    }
+    for (_, rows) in &planes {
+        out.extend_from_slice(rows);
+    }
+
+    writer.write_all(&out)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// One layer record and its encoded channels, alpha first.
+struct Record {
+    /// `(top, left, bottom, right)` in canvas pixels.
+    bounds: [i32; 4],
+    name: String,
+    visible: bool,
+    opacity: f32,
+    blend_key: [u8; 4],
+    /// `lsct` divider type and blend key, for folders.
+    section: Option<(u32, [u8; 4])>,
+    channels: [Vec<u8>; 4],
+}
+
+impl Record {
+    /// A folder record or divider, which carries no pixels.
+    fn section(name: &str, kind: u32, visible: bool, opacity: f32, blend_key: [u8; 4]) -> Self {
+        Self {
+            bounds: [0; 4],
+            name: name.to_string(),
+            visible,
+            opacity,
+            blend_key,
+            section: Some((kind, blend_key)),
+            channels: std::array::from_fn(|_| COMPRESSION_RAW.to_be_bytes().to_vec()),
+        }
+    }
+}
+
+/// Appends `nodes` bottom first, folders wrapped in their divider and folder records.
+fn push_records(
+    records: &mut Vec<Record>,
+    nodes: &[LayerNode],
+    images: &PsdImages,
+    size: (u32, u32),
+) -> anyhow::Result<()> {
+    for node in nodes {
+        match node {
+            LayerNode::Layer(layer) => {
+                let Some(pixels) = images.layers.get(&layer.id) else {
+                    bail!("no pixels for layer {}", layer.id.0);
+                };
+                #[allow(clippy::cast_possible_truncation)]
+                let offset = [
+                    layer.offset[0].round() as i32,
+                    layer.offset[1].round() as i32,
+                ];
+                let (bounds, channels) = match painted_bounds(pixels, size) {
+                    Some(rect @ (left, top, right, bottom)) => (
+                        [
+                            offset[1] + top.cast_signed(),
+                            offset[0] + left.cast_signed(),
+                            offset[1] + bottom.cast_signed(),
+                            offset[0] + right.cast_signed(),
+                        ],
+                        [3, 0, 1, 2].map(|channel| {
+                            rle_channel(&plane(pixels, size.0, channel, rect), right - left)
+                        }),
+                    ),
+                    None => (
+                        [0; 4],
+                        std::array::from_fn(|_| COMPRESSION_RAW.to_be_bytes().to_vec()),
+                    ),
+                };
+                records.push(Record {
+                    bounds,
+                    name: layer.name.clone(),
+                    visible: layer.visible,
+                    opacity: layer.opacity,
+                    blend_key: blend_key(layer.blend_mode),
+                    section: None,
+                    channels,
+                });
+            }
+            LayerNode::Group(group) => {
+                records.push(Record::section(
+                    "</Layer group>",
+                    SECTION_BOUNDING_DIVIDER,
+                    true,
+                    1.0,
+                    blend_key(BlendMode::Normal),
+                ));
+                push_records(records, &group.children, images, size)?;
+                let blend_key = if group.is_isolated() {
+                    blend_key(group.blend_mode)
+                } else {
+                    *PASS_THROUGH
+                };
+                records.push(Record::section(
+                    &group.name,
+                    SECTION_OPEN_FOLDER,
+                    group.visible,
+                    group.opacity,
+                    blend_key,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn blend_key(blend_mode: BlendMode) -> [u8; 4] {
+    *match blend_mode {
+        BlendMode::Normal => b"norm",
+        BlendMode::Multiply => b"mul ",
+        BlendMode::Screen => b"scrn",
+        BlendMode::Overlay => b"over",
+        BlendMode::Add => b"lddg",
+        BlendMode::Darken => b"dark",
+        BlendMode::Lighten => b"lite",
+    }
+}
+
+/// The layer info block: record count, records, then their channel data in the same order.
+fn layer_info(records: &[Record]) -> anyhow::Result<Vec<u8>> {
+    let Ok(count) = i16::try_from(records.len()) else {
+        bail!("{} layers exceed the PSD limit", records.len());
+    };
+
+    let mut out = Vec::new();
+    // negative: the composite's alpha channel holds the merged transparency
+    put_u16(&mut out, count.wrapping_neg().cast_unsigned());
+    for record in records {
+        for edge in record.bounds {
+            put_u32(&mut out, edge.cast_unsigned());
+        }
+        put_u16(&mut out, CHANNEL_COUNT);
+        for (id, data) in CHANNEL_IDS.iter().zip(&record.channels) {
+            put_u16(&mut out, id.cast_unsigned());
+            put_u32(&mut out, section_len(data.len())?);
+        }
+        out.extend_from_slice(RESOURCE_SIGNATURE);
+        out.extend_from_slice(&record.blend_key);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        out.push((record.opacity.clamp(0.0, 1.0) * 255.0).round() as u8);
+        // clipping, flags, filler
+        out.push(0);
+        out.push(if record.visible { 0 } else { FLAG_HIDDEN });
+        out.push(0);
+
+        let extra = extra_data(record)?;
+        put_u32(&mut out, section_len(extra.len())?);
+        out.extend_from_slice(&extra);
+    }
+    for record in records {
+        for data in &record.channels {
+            out.extend_from_slice(data);
+        }
+    }
+    pad_to(&mut out, 2);
+    Ok(out)
+}
+
+/// Mask and blending ranges (both empty), the legacy name, and the `luni` and `lsct` blocks.
+fn extra_data(record: &Record) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 0);
+
+    // the Pascal name is MacRoman and at most 255 bytes, `luni` keeps the real one
+    let legacy: Vec<u8> = record
+        .name
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .take(255)
+        .collect();
+    out.push(u8::try_from(legacy.len()).unwrap_or(u8::MAX));
+    out.extend_from_slice(&legacy);
+    pad_to(&mut out, 4);
+
+    let utf16: Vec<u16> = record.name.encode_utf16().collect();
+    let mut unicode = Vec::new();
+    put_u32(&mut unicode, section_len(utf16.len())?);
+    for unit in utf16 {
+        put_u16(&mut unicode, unit);
+    }
+    pad_to(&mut unicode, 4);
+    additional_info(&mut out, *b"luni", &unicode)?;
+
+    if let Some((kind, blend_key)) = record.section {
+        let mut section = Vec::new();
+        put_u32(&mut section, kind);
+        section.extend_from_slice(RESOURCE_SIGNATURE);
+        section.extend_from_slice(&blend_key);
+        additional_info(&mut out, *b"lsct", &section)?;
+    }
+    Ok(out)
+}
+
+fn additional_info(out: &mut Vec<u8>, key: [u8; 4], data: &[u8]) -> anyhow::Result<()> {
+    out.extend_from_slice(RESOURCE_SIGNATURE);
+    out.extend_from_slice(&key);
+    put_u32(out, section_len(data.len())?);
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+/// `(left, top, right, bottom)` of the texels with any coverage, None for a blank layer.
+fn painted_bounds(rgba: &[u8], (width, height): (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for row in 0..height {
+        let covered = |column: &u32| rgba[((row * width + column) * 4 + 3) as usize] != 0;
+        let Some(first) = (0..width).find(covered) else {
+            continue;
+        };
+        let last = (0..width).rev().find(covered).unwrap_or(first);
+        bounds = Some(match bounds {
+            None => (first, row, last + 1, row + 1),
+            Some((left, top, right, _)) => (left.min(first), top, right.max(last + 1), row + 1),
+        });
+    }
+    bounds
+}
+
+/// One channel of `rect` (`left, top, right, bottom`) of an RGBA8 image `width` wide.
+fn plane(
+    rgba: &[u8],
+    width: u32,
+    channel: usize,
+    (left, top, right, bottom): (u32, u32, u32, u32),
+) -> Vec<u8> {
+    let mut plane = Vec::with_capacity(((right - left) * (bottom - top)) as usize);
+    for row in top..bottom {
+        let start = (row * width + left) as usize * 4;
+        let end = (row * width + right) as usize * 4;
+        plane.extend(rgba[start..end].chunks_exact(4).map(|px| px[channel]));
+    }
+    plane
+}
+
+/// Compression marker, row byte counts, then the PackBits rows.
+fn rle_channel(plane: &[u8], width: u32) -> Vec<u8> {
+    let (counts, rows) = pack_rows(plane, width);
+    let mut out = COMPRESSION_RLE.to_be_bytes().to_vec();
+    out.extend_from_slice(&counts);
+    out.extend_from_slice(&rows);
+    out
+}
+
+/// PackBits each row of `plane`, returning the big-endian u16 byte counts and the packed rows.
+fn pack_rows(plane: &[u8], width: u32) -> (Vec<u8>, Vec<u8>) {
+    let mut counts = Vec::new();
+    let mut rows = Vec::new();
+    for row in plane.chunks_exact(width as usize) {
+        let packed = pack_bits(row);
+        // rows of at most `PSD_MAX_DIM` texels always pack below 64 KiB
+        #[allow(clippy::cast_possible_truncation)]
+        put_u16(&mut counts, packed.len() as u16);
+        rows.extend_from_slice(&packed);
+    }
+    (counts, rows)
+}
+
+/// PackBits: runs of 2..=128 equal bytes as `1 - n, byte`, anything else as `n - 1, bytes` literals.
+#[allow(clippy::cast_possible_truncation)]
+fn pack_bits(row: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(row.len() + row.len() / 128 + 1);
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..]
+            .iter()
+            .take(128)
+            .take_while(|byte| **byte == row[i])
+            .count();
+        if run >= 2 {
+            out.push(((run - 1) as u8).wrapping_neg());
+            out.push(row[i]);
+            i += run;
+            continue;
+        }
+
+        // literals until a run of three or more is worth breaking for
+        let start = i;
+        while i < row.len() && i - start < 128 {
+            if i + 2 < row.len() && row[i] == row[i + 1] && row[i] == row[i + 2] {
+                break;
+            }
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&row[start..i]);
+    }
+    out
+}
+
+fn section_len(len: usize) -> anyhow::Result<u32> {
+    u32::try_from(len).map_err(|_| anyhow::anyhow!("PSD section exceeds 4 GiB"))
+}
+
+fn pad_to(out: &mut Vec<u8>, multiple: usize) {
+    while !out.len().is_multiple_of(multiple) {
+        out.push(0);
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Writes one `<document>-<artboard name>.psd` per artboard into `dir`, returning their paths.
+///
+/// Layers are read back from the `SceneRenderer` like a save, the composite is flattened without the artboard background.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_psd(
+    dir: &std::path::Path,
+    document_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut crate::resources::scene_renderer::SceneRenderer,
+    document: &crate::document::Document,
+) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    use anyhow::Context;
+
+    use crate::document::export::{
+        export_file_name, read_straight_layers, render_straight_composite,
+    };
+
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+    let mut paths = Vec::new();
+    for artboard in &document.artboards {
+        let layers = read_straight_layers(device, queue, scene, artboard)?;
+        let (composite, _) =
+            render_straight_composite(device, queue, scene, document, artboard.id)?;
+
+        let path = dir.join(export_file_name(
+            document_name,
+            &artboard.name,
+            PSD_EXTENSION,
+        ));
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        write_psd(
+            std::io::BufWriter::new(file),
+            artboard,
+            &PsdImages {
+                layers: &layers,
+                composite: &composite,
+            },
+        )
+        .with_context(|| format!("writing {}", path.display()))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::document::{ArtboardId, GroupId, Layer, LayerGroup};
+    use crate::testing::fixtures::{hydrated_scene, scratch_dir};
+
+    /// Enough of a PSD reader to check what `write_psd` produced.
+    struct Cursor<'a> {
+        bytes: &'a [u8],
+        at: usize,
+    }
+
+    impl<'a> Cursor<'a> {
+        fn take(&mut self, len: usize) -> &'a [u8] {
+            let slice = &self.bytes[self.at..self.at + len];
+            self.at += len;
+            slice
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.take(1)[0]
+        }
+
+        fn u16(&mut self) -> u16 {
+            u16::from_be_bytes(self.take(2).try_into().unwrap())
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_be_bytes(self.take(4).try_into().unwrap())
+        }
+
+        fn i32(&mut self) -> i32 {
+            self.u32().cast_signed()
+        }
+    }
+
+    #[derive(Debug)]
+    struct ParsedLayer {
+        /// `(top, left, bottom, right)`
+        bounds: [i32; 4],
+        name: String,
+        hidden: bool,
+        opacity: u8,
+        blend_key: [u8; 4],
+        section: Option<u32>,
+        /// Decoded planes by channel id
+        channels: HashMap<i16, Vec<u8>>,
+    }
+
+    impl ParsedLayer {
+        fn bounds_width(&self) -> i32 {
+            self.bounds[3] - self.bounds[1]
+        }
+
+        fn width(&self) -> usize {
+            usize::try_from(self.bounds_width()).unwrap()
+        }
+
+        /// Straight RGBA at canvas `(x, y)`.
+        fn pixel(&self, x: i32, y: i32) -> [u8; 4] {
+            let index =
+                usize::try_from((y - self.bounds[0]) * self.bounds_width() + x - self.bounds[1])
+                    .unwrap();
+            [0, 1, 2, -1].map(|id| self.channels[&id][index])
+        }
+    }
+
+    struct ParsedPsd {
+        size: (u32, u32),
+        layer_count: i16,
+        layers: Vec<ParsedLayer>,
+        /// Straight RGBA8
+        composite: Vec<u8>,
+    }
+
+    fn unpack_bits(mut packed: &[u8], len: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let header = packed[0].cast_signed();
+            if header >= 0 {
+                let count = usize::from(header.unsigned_abs()) + 1;
+                out.extend_from_slice(&packed[1..=count]);
+                packed = &packed[count + 1..];
+            } else {
+                let count = usize::from(header.unsigned_abs()) + 1;
+                out.extend(std::iter::repeat_n(packed[1], count));
+                packed = &packed[2..];
+            }
+        }
+        assert!(packed.is_empty(), "trailing packed bytes");
+        assert_eq!(out.len(), len);
+        out
+    }
+
+    fn read_channel(cursor: &mut Cursor, len: usize, (width, height): (usize, usize)) -> Vec<u8> {
+        let data = cursor.take(len);
+        let mut channel = Cursor { bytes: data, at: 0 };
+        match channel.u16() {
+            COMPRESSION_RAW => channel.take(width * height).to_vec(),
+            COMPRESSION_RLE => {
+                let counts: Vec<usize> = (0..height).map(|_| channel.u16() as usize).collect();
+                let mut plane = Vec::new();
+                for count in counts {
+                    plane.extend(unpack_bits(channel.take(count), width));
+                }
+                assert_eq!(channel.at, data.len(), "channel length");
+                plane
+            }
+            other => panic!("unexpected compression {other}"),
+        }
+    }
+
+    /// One layer record, with its `(channel id, data length)`s.
+    fn read_record(cursor: &mut Cursor) -> (ParsedLayer, Vec<(i16, usize)>) {
+        let bounds = [cursor.i32(), cursor.i32(), cursor.i32(), cursor.i32()];
+        let channels: Vec<(i16, usize)> = (0..cursor.u16())
+            .map(|_| (cursor.u16().cast_signed(), cursor.u32() as usize))
+            .collect();
+        assert_eq!(cursor.take(4), RESOURCE_SIGNATURE);
+        let blend_key: [u8; 4] = cursor.take(4).try_into().unwrap();
+        let opacity = cursor.u8();
+        cursor.u8();
+        let hidden = cursor.u8() & FLAG_HIDDEN != 0;
+        cursor.u8();
+
+        let extra_len = cursor.u32() as usize;
+        let extra_end = cursor.at + extra_len;
+        assert_eq!(cursor.u32(), 0, "mask data");
+        assert_eq!(cursor.u32(), 0, "blending ranges");
+        let name_start = cursor.at;
+        let legacy_len = cursor.u8() as usize;
+        cursor.take(legacy_len);
+        cursor.take((4 - (cursor.at - name_start) % 4) % 4);
+
+        let (mut name, mut section) = (None, None);
+        while cursor.at < extra_end {
+            assert_eq!(cursor.take(4), RESOURCE_SIGNATURE);
+            let key = cursor.take(4);
+            let len = cursor.u32() as usize;
+            let mut block = Cursor {
+                bytes: cursor.take(len),
+                at: 0,
+            };
+            match key {
+                b"luni" => {
+                    let units: Vec<u16> = (0..block.u32()).map(|_| block.u16()).collect();
+                    name = Some(String::from_utf16(&units).unwrap());
+                }
+                b"lsct" => section = Some(block.u32()),
+                _ => {}
+            }
+        }
+        (
+            ParsedLayer {
+                bounds,
+                name: name.expect("luni name"),
+                hidden,
+                opacity,
+                blend_key,
+                section,
+                channels: HashMap::new(),
+            },
+            channels,
+        )
+    }
+
+    fn parse_psd(bytes: &[u8]) -> ParsedPsd {
+        let mut cursor = Cursor { bytes, at: 0 };
+        assert_eq!(cursor.take(4), SIGNATURE);
+        assert_eq!(cursor.u16(), 1);
+        cursor.take(6);
+        assert_eq!(cursor.u16(), 4, "channels");
+        let height = cursor.u32();
+        let width = cursor.u32();
+        assert_eq!(cursor.u16(), 8, "depth");
+        assert_eq!(cursor.u16(), COLOR_MODE_RGB);
+        let color_mode_len = cursor.u32() as usize;
+        cursor.take(color_mode_len);
+        let resources_len = cursor.u32() as usize;
+        cursor.take(resources_len);
+
+        let layer_mask_len = cursor.u32() as usize;
+        let layer_mask_end = cursor.at + layer_mask_len;
+        let layer_info_len = cursor.u32() as usize;
+        assert_eq!(layer_info_len % 2, 0, "layer info is padded");
+        let layer_info_end = cursor.at + layer_info_len;
+        let layer_count = cursor.u16().cast_signed();
+
+        let mut records = Vec::new();
+        for _ in 0..layer_count.unsigned_abs() {
+            records.push(read_record(&mut cursor));
+        }
+
+        let mut layers = Vec::new();
+        for (mut layer, channels) in records {
+            let size = (
+                layer.width(),
+                usize::try_from(layer.bounds[2] - layer.bounds[0]).unwrap(),
+            );
+            for (id, len) in channels {
+                layer
+                    .channels
+                    .insert(id, read_channel(&mut cursor, len, size));
+            }
+            layers.push(layer);
+        }
+        assert!(layer_info_end - cursor.at <= 1, "layer info length");
+        cursor.at = layer_info_end;
+        assert_eq!(cursor.u32(), 0, "global mask info");
+        assert_eq!(cursor.at, layer_mask_end, "layer and mask length");
+
+        assert_eq!(cursor.u16(), COMPRESSION_RLE);
+        let rows = height as usize;
+        let counts: Vec<usize> = (0..rows * 4).map(|_| cursor.u16() as usize).collect();
+        let planes: Vec<Vec<u8>> = counts
+            .chunks_exact(rows)
+            .map(|counts| {
+                counts
+                    .iter()
+                    .flat_map(|count| unpack_bits(cursor.take(*count), width as usize))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(cursor.at, bytes.len(), "trailing bytes");
+        let composite = (0..width as usize * rows)
+            .flat_map(|index| [0, 1, 2, 3].map(|channel| planes[channel][index]))
+            .collect();
+
+        ParsedPsd {
+            size: (width, height),
+            layer_count,
+            layers,
+            composite,
+        }
+    }
+
+    fn layer(id: u32, name: &str) -> Layer {
+        Layer {
+            id: LayerId(id),
+            name: name.to_string(),
+            offset: [0.0, 0.0],
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            content_path: None,
+            thumbhash: None,
+            strokes: None,
+        }
+    }
+
+    /// Straight RGBA8 of `size` with `rgba` in the texels of `rect` (`left, top, right, bottom`).
+    fn painted(
+        (width, height): (u32, u32),
+        (left, top, right, bottom): (u32, u32, u32, u32),
+        rgba: [u8; 4],
+    ) -> Vec<u8> {
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        for y in top..bottom {
+            for x in left..right {
+                let index = (y * width + x) as usize * 4;
+                pixels[index..index + 4].copy_from_slice(&rgba);
+            }
+        }
+        pixels
+    }
+
+    fn write(artboard: &Artboard, layers: &HashMap<LayerId, Vec<u8>>) -> ParsedPsd {
+        let composite =
+            vec![9; artboard.pixel_size().0 as usize * artboard.pixel_size().1 as usize * 4];
+        let mut bytes = Vec::new();
+        write_psd(
+            &mut bytes,
+            artboard,
+            &PsdImages {
+                layers,
+                composite: &composite,
+            },
+        )
+        .unwrap();
+        parse_psd(&bytes)
+    }
+
+    #[test]
+    fn pack_bits_round_trips() {
+        let mut noise = Vec::new();
+        let mut state = 7u32;
+        for _ in 0..1000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            noise.push(u8::try_from((state >> 16) & 3).unwrap());
+        }
+        let rows: [Vec<u8>; 7] = [
+            vec![5],
+            vec![1, 2],
+            vec![4, 4],
+            vec![0; 300],
+            (0..=255).chain(0..=255).collect(),
+            [vec![1, 2, 3], vec![9; 129], vec![1, 1, 2]].concat(),
+            noise,
+        ];
+        for row in rows {
+            assert_eq!(unpack_bits(&pack_bits(&row), row.len()), row);
+        }
+        // long runs pack to two bytes per 128
+        assert_eq!(pack_bits(&[0; 256]), [129, 0, 129, 0]);
+    }
+
+    #[test]
+    fn layers_are_trimmed_and_placed_at_their_offsets() {
+        let artboard = Artboard {
+            id: ArtboardId(1),
+            name: "Board".to_string(),
+            position: [0.0, 0.0],
+            size: [8.0, 6.0],
+            layers: vec![
+                layer(2, "Bottom").into(),
+                Layer {
+                    offset: [-1.0, 2.0],
+                    ..layer(3, "Mark")
+                }
+                .into(),
+                layer(4, "Blank").into(),
+            ],
+        };
+        let layers = HashMap::from([
+            (LayerId(2), painted((8, 6), (0, 0, 8, 6), [0, 0, 255, 255])),
+            (LayerId(3), painted((8, 6), (2, 1, 5, 3), [255, 0, 0, 128])),
+            (LayerId(4), vec![0; 8 * 6 * 4]),
+        ]);
+
+        let psd = write(&artboard, &layers);
+        assert_eq!(psd.size, (8, 6));
+        assert_eq!(psd.layer_count, -3);
+        let names: Vec<&str> = psd.layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, ["Bottom", "Mark", "Blank"]);
+
+        let mark = &psd.layers[1];
+        assert_eq!(mark.bounds, [3, 1, 5, 4]);
+        assert_eq!(mark.pixel(1, 3), [255, 0, 0, 128]);
+        assert_eq!(mark.pixel(3, 4), [255, 0, 0, 128]);
+        assert_eq!(psd.layers[0].pixel(7, 5), [0, 0, 255, 255]);
+        assert_eq!(psd.layers[2].bounds, [0; 4]);
+        assert!(psd.composite.iter().all(|byte| *byte == 9));
+    }
+
+    #[test]
+    fn records_carry_visibility_opacity_blend_and_names() {
+        let artboard = Artboard {
+            id: ArtboardId(1),
+            name: "Board".to_string(),
+            position: [0.0, 0.0],
+            size: [2.0, 2.0],
+            layers: vec![
+                Layer {
+                    visible: false,
+                    opacity: 0.5,
+                    blend_mode: BlendMode::Multiply,
+                    ..layer(2, "Ombré ✏️")
+                }
+                .into(),
+            ],
+        };
+        let layers = HashMap::from([(LayerId(2), vec![255; 16])]);
+
+        let psd = write(&artboard, &layers);
+        let layer = &psd.layers[0];
+        assert_eq!(layer.name, "Ombré ✏️");
+        assert!(layer.hidden);
+        assert_eq!(layer.opacity, 128);
+        assert_eq!(&layer.blend_key, b"mul ");
+        assert_eq!(layer.section, None);
+    }
+
+    #[test]
+    fn groups_become_folders() {
+        let artboard = Artboard {
+            id: ArtboardId(1),
+            name: "Board".to_string(),
+            position: [0.0, 0.0],
+            size: [2.0, 2.0],
+            layers: vec![
+                LayerGroup {
+                    id: GroupId(2),
+                    name: "Outer".to_string(),
+                    visible: true,
+                    locked: false,
+                    opacity: 1.0,
+                    blend_mode: BlendMode::Normal,
+                    children: vec![
+                        layer(3, "Inside").into(),
+                        LayerGroup {
+                            id: GroupId(4),
+                            name: "Inner".to_string(),
+                            visible: false,
+                            locked: false,
+                            opacity: 0.5,
+                            blend_mode: BlendMode::Screen,
+                            children: vec![],
+                        }
+                        .into(),
+                    ],
+                }
+                .into(),
+                layer(5, "Top").into(),
+            ],
+        };
+        let layers = HashMap::from([(LayerId(3), vec![0; 16]), (LayerId(5), vec![0; 16])]);
+
+        let psd = write(&artboard, &layers);
+        let outline: Vec<(&str, Option<u32>, &[u8; 4])> = psd
+            .layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.section, &layer.blend_key))
+            .collect();
+        assert_eq!(
+            outline,
+            [
+                ("</Layer group>", Some(SECTION_BOUNDING_DIVIDER), b"norm"),
+                ("Inside", None, b"norm"),
+                ("</Layer group>", Some(SECTION_BOUNDING_DIVIDER), b"norm"),
+                ("Inner", Some(SECTION_OPEN_FOLDER), b"scrn"),
+                ("Outer", Some(SECTION_OPEN_FOLDER), PASS_THROUGH),
+                ("Top", None, b"norm"),
+            ]
+        );
+        assert!(psd.layers[3].hidden);
+        assert_eq!(psd.layers[3].opacity, 128);
+    }
+
+    #[test]
+    fn oversized_artboards_are_rejected() {
+        let artboard = Artboard {
+            id: ArtboardId(1),
+            name: "Wide".to_string(),
+            position: [0.0, 0.0],
+            size: [30_001.0, 1.0],
+            layers: vec![],
+        };
+        let result = write_psd(
+            Vec::new(),
+            &artboard,
+            &PsdImages {
+                layers: &HashMap::new(),
+                composite: &[],
+            },
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn export_reads_back_straight_alpha() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+
+        let dir = scratch_dir("psd-export");
+        let paths = export_psd(&dir, "doc", &device, &queue, &mut scene, &document).unwrap();
+        assert_eq!(paths, [dir.join("doc-left.psd"), dir.join("doc-right.psd")]);
+
+        let psd = parse_psd(&std::fs::read(&paths[0]).unwrap());
+        assert_eq!(psd.size, (600, 400));
+        assert_eq!(psd.layers.len(), 1);
+        let layer = &psd.layers[0];
+        assert_eq!(layer.name, "Layer 1");
+        assert_eq!(layer.bounds, [0, 0, 400, 600]);
+        assert_eq!(layer.pixel(300, 200), [255, 0, 0, 128]);
+        assert_eq!(&psd.composite[..4], [255, 0, 0, 128]);
+
+        let blank = parse_psd(&std::fs::read(&paths[1]).unwrap());
+        assert_eq!(blank.size, (400, 300));
+        assert_eq!(blank.layers[0].bounds, [0; 4]);
+        assert!(blank.composite.iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,435 @@
+//! Crash recovery journal.
+//!
+//! While a document is open its session is snapshotted into `recovery/<name>/` under the asset dir,
+//! as the loose `session.json` + `session.layer-<id>.png` that `save_document_to` writes, so recovering is a plain load.
+//! Snapshots are incremental: only layers touched since the previous one are read back and rewritten.
+//! Capturing only records the copies of those layers, reading them back and writing is left to `Snapshot::write`.
+//! Every file is written next to its target and renamed over it, the JSON last,
+//! so a snapshot interrupted halfway still leaves the previous one loadable.
+//!
+//! A clean exit or an explicit save discards the journal, one left behind means the session was interrupted.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::{
+    document::{
+        Document, LayerId,
+        loader::{LoadedDocument, asset_dir, load_document_from},
+        saver::{is_blank, unpremultiply_alpha},
+    },
+    resources::scene_renderer::{LayerReadback, SceneRenderer},
+};
+
+const JOURNAL_NAME: &str = "session";
+
+/// Where the session of the document launched as `document_name` is journaled.
+pub fn recovery_dir(document_name: &str) -> PathBuf {
+    asset_dir().join("recovery").join(document_name)
+}
+
+/// Where the session of `document_name` is journaled while an interrupted one is still in `recovery_dir`,
+/// so it neither overwrites that one nor goes unprotected.
+pub fn next_recovery_dir(document_name: &str) -> PathBuf {
+    asset_dir()
+        .join("recovery")
+        .join(format!("{document_name}.next"))
+}
+
+/// Whether `dir` holds a journal to recover from.
+pub fn has_journal(dir: &Path) -> bool {
+    dir.join(format!("{JOURNAL_NAME}.json")).is_file()
+}
+
+/// Loads the session journaled in `dir`, `None` if there is none.
+pub fn load_journal(dir: &Path, max_texture_dim: u32) -> anyhow::Result<Option<LoadedDocument>> {
+    if !has_journal(dir) {
+        return Ok(None);
+    }
+    load_document_from(dir, JOURNAL_NAME, max_texture_dim).map(Some)
+}
+
+/// Moves the journal in `next`, see `next_recovery_dir`, over the one in `dir`.
+/// Left behind, it is a session interrupted after the one in `dir` and the later of the two.
+/// Whether there was one to move.
+pub fn keep_later_journal(dir: &Path, next: &Path) -> anyhow::Result<bool> {
+    if !has_journal(next) {
+        discard_journal(next)?;
+        return Ok(false);
+    }
+    discard_journal(dir)?;
+    std::fs::rename(next, dir).with_context(|| format!("moving {}", next.display()))?;
+    Ok(true)
+}
+
+/// Deletes the journal in `dir`, if any.
+pub fn discard_journal(dir: &Path) -> anyhow::Result<()> {
+    match std::fs::remove_dir_all(dir) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("removing {}", dir.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// What the journal in `dir` holds, so each snapshot writes only what changed since the last.
+pub struct Journal {
+    dir: PathBuf,
+    /// `content_path` of every journaled layer, `None` for blank ones.
+    written: HashMap<LayerId, Option<String>>,
+    document: Option<Document>,
+}
+
+/// A captured snapshot, written by `Snapshot::write` off the frame loop.
+pub struct Snapshot {
+    dir: PathBuf,
+    document: Document,
+    /// `(layer, content_path, copy, size)` of the layers to rewrite.
+    layers: Vec<(LayerId, String, LayerReadback, (u32, u32))>,
+}
+
+impl Journal {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            written: HashMap::new(),
+            document: None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Forgets what was written, so the next snapshot rewrites every layer.
+    pub fn reset(&mut self) {
+        self.written.clear();
+        self.document = None;
+    }
+
+    /// Records on `encoder` the copies of the layers in `dirty` and any not journaled yet,
+    /// `None` when nothing changed since the last snapshot.
+    /// Vector layers are never read back, their strokes travel in the JSON.
+    pub fn capture(
+        &mut self,
+        document: &Document,
+        dirty: &HashSet<LayerId>,
+        scene: &SceneRenderer,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Option<Snapshot> {
+        if dirty.is_empty() && self.document.as_ref() == Some(document) {
+            return None;
+        }
+
+        let mut journaled = document.clone();
+        let mut layers = Vec::new();
+        for artboard in &mut journaled.artboards {
+            let size = artboard.pixel_size();
+            for layer in artboard.layers_mut() {
+                layer.thumbhash = None;
+                if layer.is_vector() {
+                    layer.content_path = None;
+                    continue;
+                }
+                if !dirty.contains(&layer.id)
+                    && let Some(content_path) = self.written.get(&layer.id)
+                {
+                    layer.content_path.clone_from(content_path);
+                    continue;
+                }
+
+                let copy = scene.copy_layer(device, encoder, layer.id);
+                let content_path = copy.map(|copy| {
+                    let file_name = format!("{JOURNAL_NAME}.layer-{}.png", layer.id.0);
+                    layers.push((layer.id, file_name.clone(), copy, size));
+                    file_name
+                });
+                self.written.insert(layer.id, content_path.clone());
+                layer.content_path = content_path;
+            }
+        }
+
+        self.document = Some(document.clone());
+        Some(Snapshot {
+            dir: self.dir.clone(),
+            document: journaled,
+            layers,
+        })
+    }
+
+    /// Takes in the layers a written snapshot found blank, whose PNGs it left out.
+    pub fn written_blank(&mut self, blank: &[LayerId]) {
+        for id in blank {
+            self.written.insert(*id, None);
+        }
+    }
+}
+
+impl Snapshot {
+    /// Number of layers this snapshot reads back.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Starts mapping the layer copies, once the encoder they were recorded on is submitted.
+    pub fn map(&mut self) {
+        for (_, _, copy, _) in &mut self.layers {
+            copy.map();
+        }
+    }
+
+    /// Reads back, encodes and writes the snapshot into the journal dir.
+    /// Blocks until the copies are mapped, which takes polling the device elsewhere.
+    /// Returns the layers that read back blank, journaled without a PNG, see `Journal::written_blank`.
+    pub fn write(mut self) -> anyhow::Result<Vec<LayerId>> {
+        use image::ImageEncoder;
+
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("creating {}", self.dir.display()))?;
+        let mut blank = Vec::new();
+        for (id, file_name, copy, (width, height)) in self.layers {
+            let mut pixels = copy
+                .into_pixels()
+                .with_context(|| format!("reading back layer {}", id.0))?;
+            if is_blank(&pixels) {
+                blank.push(id);
+                continue;
+            }
+            unpremultiply_alpha(&mut pixels);
+            let mut png = Vec::new();
+            image::codecs::png::PngEncoder::new(&mut png)
+                .write_image(&pixels, width, height, image::ExtendedColorType::Rgba8)
+                .with_context(|| format!("encoding {file_name}"))?;
+            write_replacing(&self.dir.join(file_name), &png)?;
+        }
+
+        for artboard in &mut self.document.artboards {
+            for layer in artboard.layers_mut() {
+                if blank.contains(&layer.id) {
+                    layer.content_path = None;
+                }
+            }
+        }
+        let json = serde_json::to_string_pretty(&self.document)?;
+        write_replacing(
+            &self.dir.join(format!("{JOURNAL_NAME}.json")),
+            json.as_bytes(),
+        )?;
+        Ok(blank)
+    }
+}
+
+/// Writes `bytes` next to `path` and renames them over it, so `path` is never seen half written.
+pub(crate) fn write_replacing(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    std::fs::write(&partial, bytes).with_context(|| format!("writing {}", partial.display()))?;
+    std::fs::rename(&partial, path).with_context(|| format!("replacing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor_state::DEFAULT_BRUSH_COLOR;
+    use crate::resources::scene_renderer::PointInstance;
+    use crate::testing::fixtures::{hydrated_scene, scratch_dir};
+
+    /// `Journal::capture` submitted and mapped, with the device polled so the snapshot writes straight away.
+    fn capture(
+        journal: &mut Journal,
+        document: &Document,
+        dirty: &HashSet<LayerId>,
+        scene: &SceneRenderer,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<Snapshot> {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Recovery Test Encoder"),
+        });
+        let mut snapshot = journal.capture(document, dirty, scene, device, &mut encoder);
+        queue.submit([encoder.finish()]);
+        if let Some(snapshot) = &mut snapshot {
+            snapshot.map();
+        }
+        device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+        snapshot
+    }
+
+    /// A dab merged into the middle of `layer`.
+    fn paint_dab(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        layer: LayerId,
+    ) {
+        let layer_size = scene.layers[&layer].size;
+        scene.begin_points().push(PointInstance {
+            center: [0.0, 0.0],
+            radius_px: 25.0,
+            opacity: 1.0,
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            rotation: 0.0,
+        });
+        let count = scene.upload_points(queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Recovery Test Encoder"),
+        });
+        scene.accumulate_stroke(queue, &mut encoder, true, count, layer_size);
+        scene.merge_stroke_into_layer(queue, &mut encoder, layer);
+        queue.submit([encoder.finish()]);
+    }
+
+    #[test]
+    fn interrupted_session_is_recovered() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        let dir = scratch_dir("recovery-interrupted");
+        let mut journal = Journal::new(dir.clone());
+
+        // The first snapshot journals every layer, the blank one without a PNG.
+        let first = capture(
+            &mut journal,
+            &document,
+            &HashSet::new(),
+            &scene,
+            &device,
+            &queue,
+        )
+        .unwrap();
+        assert_eq!(first.layer_count(), 2);
+        assert_eq!(first.write().unwrap(), [LayerId(4)]);
+        assert!(!dir.join("session.layer-4.png").exists());
+        journal.written_blank(&[LayerId(4)]);
+
+        paint_dab(&device, &queue, &mut scene, LayerId(4));
+        document.artboards[1].name = "Renamed".to_string();
+        let second = capture(
+            &mut journal,
+            &document,
+            &HashSet::from([LayerId(4)]),
+            &scene,
+            &device,
+            &queue,
+        )
+        .unwrap();
+        assert_eq!(
+            second.layer_count(),
+            1,
+            "only the touched layer is rewritten"
+        );
+        assert!(second.write().unwrap().is_empty());
+
+        // The session ends without discarding its journal.
+        drop(journal);
+        let recovered = load_journal(&dir, 2048).unwrap().unwrap();
+        assert_eq!(recovered.document.artboards[1].name, "Renamed");
+        for id in [LayerId(2), LayerId(4)] {
+            let gpu = scene
+                .read_layer_pixels(&device, &queue, id)
+                .unwrap()
+                .unwrap();
+            assert!(
+                recovered.layer_pixels[&id] == gpu,
+                "layer {} recovered",
+                id.0
+            );
+        }
+    }
+
+    #[test]
+    fn unchanged_session_is_not_snapshotted_again() {
+        let (device, queue, scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let mut journal = Journal::new(scratch_dir("recovery-unchanged"));
+        let none = HashSet::new();
+        assert!(capture(&mut journal, &document, &none, &scene, &device, &queue).is_some());
+        assert!(capture(&mut journal, &document, &none, &scene, &device, &queue).is_none());
+
+        journal.reset();
+        let full = capture(&mut journal, &document, &none, &scene, &device, &queue).unwrap();
+        assert_eq!(full.layer_count(), 2, "a reset journal is written in full");
+    }
+
+    #[test]
+    fn half_written_snapshot_leaves_the_previous_one() {
+        let (device, queue, scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let dir = scratch_dir("recovery-half-written");
+        let mut journal = Journal::new(dir.clone());
+        capture(
+            &mut journal,
+            &document,
+            &HashSet::new(),
+            &scene,
+            &device,
+            &queue,
+        )
+        .unwrap()
+        .write()
+        .unwrap();
+
+        // killed while writing the next snapshot
+        std::fs::write(dir.join("session.json.partial"), "{ \"version\":").unwrap();
+        std::fs::write(dir.join("session.layer-4.png.partial"), [0x89, b'P']).unwrap();
+
+        let recovered = load_journal(&dir, 2048).unwrap().unwrap();
+        assert_eq!(recovered.document.artboards.len(), 2);
+        assert!(recovered.layer_pixels.contains_key(&LayerId(2)));
+        assert!(!recovered.layer_pixels.contains_key(&LayerId(4)));
+    }
+
+    #[test]
+    fn discarded_journal_is_gone() {
+        let (device, queue, scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let dir = scratch_dir("recovery-discard");
+        let mut journal = Journal::new(dir.clone());
+        capture(
+            &mut journal,
+            &document,
+            &HashSet::new(),
+            &scene,
+            &device,
+            &queue,
+        )
+        .unwrap()
+        .write()
+        .unwrap();
+        assert!(has_journal(&dir));
+
+        discard_journal(&dir).unwrap();
+        assert!(!dir.exists());
+        assert!(load_journal(&dir, 2048).unwrap().is_none());
+        discard_journal(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_later_journal_replaces_the_unrestored_one() {
+        let (device, queue, scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        let root = scratch_dir("recovery-later");
+        let (dir, next) = (root.join("doc"), root.join("doc.next"));
+        assert!(!keep_later_journal(&dir, &next).unwrap(), "nothing to keep");
+
+        for (journal_dir, name) in [(&dir, "Earlier"), (&next, "Later")] {
+            document.artboards[0].name = name.to_string();
+            let mut journal = Journal::new(journal_dir.clone());
+            capture(
+                &mut journal,
+                &document,
+                &HashSet::new(),
+                &scene,
+                &device,
+                &queue,
+            )
+            .unwrap()
+            .write()
+            .unwrap();
+        }
+        assert!(keep_later_journal(&dir, &next).unwrap());
+        assert!(!next.exists());
+        let kept = load_journal(&dir, 2048).unwrap().unwrap();
+        assert_eq!(kept.document.artboards[0].name, "Later");
+    }
+}
@@ -0,0 +1,343 @@
+#[cfg(not(target_arch = "wasm32"))]
+use anyhow::Context;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    document::{
+        Document,
+        bundle::{BUNDLE_EXTENSION, write_bundle},
+        loader::asset_dir,
+        thumbhash::generate_thumbhash,
+    },
+    resources::scene_renderer::SceneRenderer,
+};
+
+/// `(content_path, png bytes)`
+#[cfg(not(target_arch = "wasm32"))]
+type NamedPng = (String, Vec<u8>);
+
+/// Saves under the bundled asset dir, as a `.crayon` bundle if the document was opened from one, else as loose JSON + PNGs.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_document(
+    name: &str,
+    document: &Document,
+    scene: &SceneRenderer,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Document> {
+    let dir = asset_dir();
+    let bundle_path = dir.join(format!("{name}.{BUNDLE_EXTENSION}"));
+    if bundle_path.is_file() {
+        save_bundle_to(&bundle_path, document, scene, device, queue)
+    } else {
+        save_document_to(&dir, name, document, scene, device, queue)
+    }
+}
+
+/// Writes `<dir>/<name>.json` plus one `<name>.layer-<id>.png` per painted layer.
+///
+/// Layer textures are read back from the `SceneRenderer` and stored straight-alpha, as `load_document` expects.
+/// Fully transparent layers are saved as blank (`content_path: None`) without a PNG.
+/// Vector layers get no PNG either, their strokes in the JSON are the content.
+/// Returns the document as written, with refreshed `content_path`s and thumbhashes.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_document_to(
+    dir: &std::path::Path,
+    name: &str,
+    document: &Document,
+    scene: &SceneRenderer,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Document> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+    let (saved, pngs) = encode_layers(&format!("{name}."), document, scene, device, queue)?;
+    for (file_name, png) in &pngs {
+        let png_path = dir.join(file_name);
+        std::fs::write(&png_path, png)
+            .with_context(|| format!("writing {}", png_path.display()))?;
+    }
+
+    let json_path = dir.join(format!("{name}.json"));
+    let json = serde_json::to_string_pretty(&saved)?;
+    std::fs::write(&json_path, json).with_context(|| format!("writing {}", json_path.display()))?;
+
+    Ok(saved)
+}
+
+/// Writes the document and its layer PNGs into the single `.crayon` file at `path`.
+/// Same layer handling as `save_document_to`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_bundle_to(
+    path: &std::path::Path,
+    document: &Document,
+    scene: &SceneRenderer,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<Document> {
+    let (saved, pngs) = encode_layers("", document, scene, device, queue)?;
+
+    // written next to the target and renamed over it, so a failed save never leaves a truncated bundle behind
+    let partial_path = path.with_extension(format!("{BUNDLE_EXTENSION}.partial"));
+    let file = std::fs::File::create(&partial_path)
+        .with_context(|| format!("creating {}", partial_path.display()))?;
+    write_bundle(
+        std::io::BufWriter::new(file),
+        &saved,
+        pngs.iter()
+            .map(|(name, png)| (name.as_str(), png.as_slice())),
+    )
+    .with_context(|| format!("writing {}", partial_path.display()))?;
+    std::fs::rename(&partial_path, path)
+        .with_context(|| format!("replacing {}", path.display()))?;
+
+    Ok(saved)
+}
+
+/// Reads every layer back and encodes the painted raster ones as straight-alpha PNGs named `<prefix>layer-<id>.png`.
+/// Returns the document updated to reference them, and the `(content_path, png)` pairs.
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_layers(
+    prefix: &str,
+    document: &Document,
+    scene: &SceneRenderer,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> anyhow::Result<(Document, Vec<NamedPng>)> {
+    use image::ImageEncoder;
+
+    let mut saved = document.clone();
+    let mut pngs = Vec::new();
+    for artboard in &mut saved.artboards {
+        let (width, height) = artboard.pixel_size();
+        for layer in artboard.layers_mut() {
+            let pixels = scene
+                .read_layer_pixels(device, queue, layer.id)
+                .with_context(|| format!("reading back layer {}", layer.id.0))?;
+
+            let Some(mut pixels) = pixels.filter(|pixels| !is_blank(pixels)) else {
+                layer.content_path = None;
+                layer.thumbhash = None;
+                continue;
+            };
+
+            unpremultiply_alpha(&mut pixels);
+            layer.thumbhash = Some(generate_thumbhash(&pixels, width, height)?);
+            if layer.is_vector() {
+                layer.content_path = None;
+                continue;
+            }
+
+            let file_name = format!("{prefix}layer-{}.png", layer.id.0);
+            let mut png = Vec::new();
+            image::codecs::png::PngEncoder::new(&mut png)
+                .write_image(&pixels, width, height, image::ExtendedColorType::Rgba8)
+                .with_context(|| format!("encoding {file_name}"))?;
+
+            layer.content_path = Some(file_name.clone());
+            pngs.push((file_name, png));
+        }
+    }
+
+    Ok((saved, pngs))
+}
+
+/// A layer without a single covered texel.
+pub fn is_blank(rgba: &[u8]) -> bool {
+    rgba.chunks_exact(4).all(|px| px[3] == 0)
+}
+
+/// Convert premultiplied RGBA8 to straight alpha in place.
+/// Exact inverse of `premultiply_alpha` for every premultiplied input.
+pub fn unpremultiply_alpha(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let alpha = u16::from(px[3]);
+        if alpha == 0 {
+            continue;
+        }
+        for channel in &mut px[..3] {
+            *channel = ((u16::from(*channel) * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+
+    use super::*;
+    use crate::constants::RED;
+    use crate::document::loader::{load_document_from, premultiply_alpha};
+    use crate::document::{BrushTip, LayerId, StrokeMode, VectorPoint, VectorStroke};
+    use crate::editor_state::DEFAULT_BRUSH_COLOR;
+    use crate::resources::scene_renderer::PointInstance;
+    use crate::testing::fixtures::{hydrated_scene, scratch_dir};
+
+    #[test]
+    fn unpremultiply_inverts_premultiply_exhaustively() {
+        for alpha in 0..=255u8 {
+            for channel in 0..=alpha {
+                let mut px = [channel, channel, channel, alpha];
+                unpremultiply_alpha(&mut px);
+                premultiply_alpha(&mut px);
+                assert_eq!(px, [channel, channel, channel, alpha]);
+            }
+        }
+    }
+
+    #[test]
+    fn unpremultiply_leaves_transparent_pixels() {
+        let mut px = [0, 0, 0, 0];
+        unpremultiply_alpha(&mut px);
+        assert_eq!(px, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn save_round_trips_through_load() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+
+        // A soft dab on the blank right layer covers the full range of edge alphas.
+        let layer = LayerId(4);
+        let layer_size = scene.layers[&layer].size;
+        scene.begin_points().push(PointInstance {
+            center: [0.0, 0.0],
+            radius_px: 40.0,
+            opacity: 1.0,
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            rotation: 0.0,
+        });
+        let count = scene.upload_points(&queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Save Test Encoder"),
+        });
+        scene.accumulate_stroke(&queue, &mut encoder, true, count, layer_size);
+        scene.merge_stroke_into_layer(&queue, &mut encoder, layer);
+        queue.submit([encoder.finish()]);
+
+        let dir = scratch_dir("save-round-trip");
+        let saved = save_document_to(&dir, "doc", &document, &scene, &device, &queue).unwrap();
+        let loaded = load_document_from(&dir, "doc", 2048).unwrap();
+        assert_eq!(loaded.document, saved);
+
+        for id in [LayerId(2), LayerId(4)] {
+            let (_, layer) = saved.find_layer(id).unwrap();
+            assert_eq!(
+                layer.content_path.as_deref(),
+                Some(format!("doc.layer-{}.png", id.0).as_str())
+            );
+            assert!(layer.thumbhash.is_some(), "painted layers carry a hash");
+
+            let gpu = scene
+                .read_layer_pixels(&device, &queue, id)
+                .unwrap()
+                .unwrap();
+            assert!(
+                loaded.layer_pixels[&id] == gpu,
+                "layer {} is lossless",
+                id.0
+            );
+        }
+    }
+
+    #[test]
+    fn bundle_round_trips_through_load() {
+        let (device, queue, scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let dir = scratch_dir("save-bundle");
+        let path = dir.join("doc.crayon");
+        let saved = save_bundle_to(&path, &document, &scene, &device, &queue).unwrap();
+        assert_eq!(
+            saved
+                .find_layer(LayerId(2))
+                .unwrap()
+                .1
+                .content_path
+                .as_deref(),
+            Some("layer-2.png")
+        );
+        assert!(!dir.join("doc.crayon.partial").exists());
+
+        // A bundle takes precedence over loose files of the same name.
+        let loaded = load_document_from(&dir, "doc", 2048).unwrap();
+        assert_eq!(loaded.document, saved);
+        let gpu = scene
+            .read_layer_pixels(&device, &queue, LayerId(2))
+            .unwrap()
+            .unwrap();
+        assert!(loaded.layer_pixels[&LayerId(2)] == gpu);
+        assert!(!loaded.layer_pixels.contains_key(&LayerId(4)));
+    }
+
+    #[test]
+    fn blank_layers_save_without_content() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        // A stale path on a layer that has since been cleared.
+        document.find_layer_mut(LayerId(2)).unwrap().content_path = Some("stale.png".to_string());
+        scene.clear_layer(&device, &queue, LayerId(2));
+
+        let dir = scratch_dir("save-blank");
+        let saved = save_document_to(&dir, "doc", &document, &scene, &device, &queue).unwrap();
+        for artboard in &saved.artboards {
+            for layer in artboard.iter_layers() {
+                assert!(layer.content_path.is_none());
+                assert!(layer.thumbhash.is_none());
+            }
+        }
+
+        let loaded = load_document_from(&dir, "doc", 2048).unwrap();
+        assert!(loaded.layer_pixels.is_empty());
+    }
+
+    #[test]
+    fn vector_layers_save_strokes_without_a_png() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([255, 0, 0, 128]);
+        let strokes = vec![VectorStroke {
+            color: [0.0, 0.5, 1.0, 1.0],
+            mode: StrokeMode::Paint,
+            tip: BrushTip::Round,
+            hardness: 0.4,
+            opacity: 1.0,
+            points: vec![
+                VectorPoint {
+                    position: [120.0, 90.0],
+                    radius: 16.0,
+                    opacity: 1.0,
+                    rotation: 0.0,
+                },
+                VectorPoint {
+                    position: [140.0, 95.0],
+                    radius: 12.5,
+                    opacity: 1.0,
+                    rotation: 0.0,
+                },
+            ],
+        }];
+        let layer = document.find_layer_mut(LayerId(4)).unwrap();
+        layer.strokes = Some(strokes.clone());
+        scene.rasterize_vector_layer(&device, &queue, layer);
+
+        let dir = scratch_dir("save-vector");
+        let saved = save_document_to(&dir, "doc", &document, &scene, &device, &queue).unwrap();
+        let layer = saved.find_layer(LayerId(4)).unwrap().1;
+        assert_eq!(layer.strokes.as_ref(), Some(&strokes));
+        assert!(layer.content_path.is_none());
+        assert!(
+            layer.thumbhash.is_some(),
+            "vector layers still get a preview"
+        );
+        assert!(!dir.join("doc.layer-4.png").exists());
+
+        let loaded = load_document_from(&dir, "doc", 2048).unwrap();
+        assert_eq!(loaded.document, saved);
+        assert!(!loaded.layer_pixels.contains_key(&LayerId(4)));
+    }
+
+    #[test]
+    fn saved_png_is_straight_alpha() {
+        let (device, queue, scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let dir = scratch_dir("save-straight");
+        save_document_to(&dir, "doc", &document, &scene, &device, &queue).unwrap();
+
+        let png = image::open(dir.join("doc.layer-2.png")).unwrap().to_rgba8();
+        assert_eq!(png.dimensions(), (600, 400));
+        assert_eq!(png.get_pixel(10, 10).0, [RED[0], 0, 0, 128]);
+    }
+}
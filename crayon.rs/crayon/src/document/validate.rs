@@ -0,0 +1,391 @@
+//! Document validation.
+//!
+//! `validate` walks a whole document and collects every issue instead of stopping at the first,
+//! each with its `Severity` and the JSON path of the offending value, as `crayon check` reports them.
+//! Errors make a document unloadable. Warnings are fixed by `repair` when the document is loaded.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Component, Path},
+};
+
+use crate::document::{Document, LayerNode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// JSON path of the value at fault, e.g. `$.artboards[0].layers[1].id`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+/// Size of the image a `content_path` resolves to, `Ok(None)` to leave layer content unchecked.
+pub type ContentSize<'a> = dyn FnMut(&str) -> anyhow::Result<Option<(u32, u32)>> + 'a;
+
+/// Every issue of `document`:
+/// - element ids are unique, groups included, and below `next_id`
+/// - artboards have valid sizes, within `max_texture_dim`
+/// - vector stroke points are finite
+/// - `content_path`s stay inside the document's dir, and their images match the artboard size
+pub fn validate(
+    document: &Document,
+    max_texture_dim: u32,
+    content_size: &mut ContentSize,
+) -> Vec<Issue> {
+    let mut validator = Validator {
+        issues: Vec::new(),
+        first_use: HashMap::new(),
+        content_size,
+    };
+    for (index, artboard) in document.artboards.iter().enumerate() {
+        let path = format!("$.artboards[{index}]");
+        validator.id(artboard.id.0, &path);
+
+        let mut valid_size = true;
+        for (axis, extent) in artboard.size.iter().enumerate() {
+            let path = format!("{path}.size[{axis}]");
+            if !extent.is_finite() || *extent < 1.0 {
+                validator.error(
+                    path,
+                    format!(
+                        "artboard {} has invalid size on axis {axis}: {extent}",
+                        artboard.id.0
+                    ),
+                );
+                valid_size = false;
+            } else if *extent > max_dim(max_texture_dim) {
+                validator.warning(
+                    path,
+                    format!("{extent} exceeds the {max_texture_dim}px texture limit, clamped"),
+                );
+            }
+        }
+
+        let mut clamped = artboard.clone();
+        clamp_size(&mut clamped.size, max_texture_dim);
+        let pixel_size = valid_size.then(|| clamped.pixel_size());
+        validator.nodes(&artboard.layers, &format!("{path}.layers"), pixel_size);
+    }
+
+    if let Some(max_id) = validator.first_use.keys().max()
+        && document.next_id <= *max_id
+    {
+        validator.warning(
+            "$.next_id".to_string(),
+            format!(
+                "{} is not above the largest id {max_id}, new elements would reuse ids",
+                document.next_id
+            ),
+        );
+    }
+    validator.issues
+}
+
+/// Fixes what `validate` warns about: clamps oversized artboards and moves `next_id` past every id.
+pub fn repair(document: &mut Document, max_texture_dim: u32) {
+    let mut max_id = 0;
+    for artboard in &mut document.artboards {
+        clamp_size(&mut artboard.size, max_texture_dim);
+        max_id = max_id.max(artboard.id.0);
+        let mut nodes: Vec<&LayerNode> = artboard.layers.iter().collect();
+        while let Some(node) = nodes.pop() {
+            match node {
+                LayerNode::Layer(layer) => max_id = max_id.max(layer.id.0),
+                LayerNode::Group(group) => {
+                    max_id = max_id.max(group.id.0);
+                    nodes.extend(&group.children);
+                }
+            }
+        }
+    }
+    document.next_id = document.next_id.max(max_id + 1);
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn max_dim(max_texture_dim: u32) -> f32 {
+    max_texture_dim as f32
+}
+
+fn clamp_size(size: &mut [f32; 2], max_texture_dim: u32) {
+    for extent in size {
+        *extent = extent.min(max_dim(max_texture_dim));
+    }
+}
+
+/// Whether `content_path` is a plain relative path that can't leave the dir it's resolved against.
+fn stays_inside(content_path: &str) -> bool {
+    let path = Path::new(content_path);
+    path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+struct Validator<'a, 'b> {
+    issues: Vec<Issue>,
+    /// Path of the element each id was first seen on.
+    first_use: HashMap<u32, String>,
+    content_size: &'a mut ContentSize<'b>,
+}
+
+impl Validator<'_, '_> {
+    fn error(&mut self, path: String, message: String) {
+        self.issues.push(Issue {
+            severity: Severity::Error,
+            path,
+            message,
+        });
+    }
+
+    fn warning(&mut self, path: String, message: String) {
+        self.issues.push(Issue {
+            severity: Severity::Warning,
+            path,
+            message,
+        });
+    }
+
+    fn id(&mut self, id: u32, element_path: &str) {
+        if let Some(first) = self.first_use.get(&id) {
+            let message = format!("duplicate id {id}, first used by {first}");
+            self.error(format!("{element_path}.id"), message);
+        } else {
+            self.first_use.insert(id, element_path.to_string());
+        }
+    }
+
+    /// `pixel_size` is the artboard's once clamped, `None` when it has no valid size.
+    fn nodes(&mut self, nodes: &[LayerNode], path: &str, pixel_size: Option<(u32, u32)>) {
+        for (index, node) in nodes.iter().enumerate() {
+            let path = format!("{path}[{index}]");
+            match node {
+                LayerNode::Layer(layer) => {
+                    self.id(layer.id.0, &path);
+                    for (s, stroke) in layer.strokes.iter().flatten().enumerate() {
+                        if !(0.0..=1.0).contains(&stroke.hardness)
+                            || !(0.0..=1.0).contains(&stroke.opacity)
+                        {
+                            self.error(
+                                format!("{path}.strokes[{s}]"),
+                                format!("layer {} has a stroke setting out of range", layer.id.0),
+                            );
+                        }
+                        for (p, point) in stroke.points.iter().enumerate() {
+                            if !point.position.iter().all(|v| v.is_finite())
+                                || !point.radius.is_finite()
+                                || !(0.0..=1.0).contains(&point.opacity)
+                                || !point.rotation.is_finite()
+                            {
+                                self.error(
+                                    format!("{path}.strokes[{s}].points[{p}]"),
+                                    format!("layer {} has a stroke point out of range", layer.id.0),
+                                );
+                            }
+                        }
+                    }
+                    if let Some(content) = layer.content_path.as_deref()
+                        && !layer.is_vector()
+                    {
+                        self.content(content, &format!("{path}.content_path"), pixel_size);
+                    }
+                }
+                LayerNode::Group(group) => {
+                    self.id(group.id.0, &path);
+                    self.nodes(&group.children, &format!("{path}.children"), pixel_size);
+                }
+            }
+        }
+    }
+
+    fn content(&mut self, content: &str, path: &str, pixel_size: Option<(u32, u32)>) {
+        if !stays_inside(content) {
+            let message = format!("'{content}' points outside the document's dir");
+            self.error(path.to_string(), message);
+            return;
+        }
+        match (self.content_size)(content) {
+            Ok(Some((width, height))) => {
+                if let Some((expected_width, expected_height)) = pixel_size
+                    && (width, height) != (expected_width, expected_height)
+                {
+                    self.warning(
+                        path.to_string(),
+                        format!(
+                            "image is {width}x{height} but the artboard {expected_width}x{expected_height}, \
+                            it is cropped or padded"
+                        ),
+                    );
+                }
+            }
+            Ok(None) => {}
+            Err(error) => self.error(path.to_string(), format!("{error:#}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{
+        BrushTip, GroupId, LayerGroup, LayerId, StrokeMode, VectorPoint, VectorStroke,
+    };
+    use crate::testing::fixtures::doc_two_artboards;
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn unchecked(_: &str) -> anyhow::Result<Option<(u32, u32)>> {
+        Ok(None)
+    }
+
+    fn paths(issues: &[Issue], severity: Severity) -> Vec<&str> {
+        issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn a_well_formed_document_has_no_issues() {
+        assert_eq!(validate(&doc_two_artboards(), 2048, &mut unchecked), []);
+    }
+
+    #[test]
+    fn every_issue_is_collected_with_its_path() {
+        let mut document = doc_two_artboards();
+        document.next_id = 3;
+        document.artboards[0].size = [f32::NAN, 5000.0];
+        let right = &mut document.artboards[1];
+        let mut copy = right.layers[0].clone();
+        if let LayerNode::Layer(layer) = &mut copy {
+            layer.strokes = Some(vec![VectorStroke {
+                color: [0.0; 4],
+                mode: StrokeMode::Paint,
+                tip: BrushTip::Round,
+                hardness: 0.4,
+                opacity: 1.0,
+                points: vec![VectorPoint {
+                    position: [0.0, f32::INFINITY],
+                    radius: 1.0,
+                    opacity: 1.0,
+                    rotation: 0.0,
+                }],
+            }]);
+        }
+        right.layers.push(
+            LayerGroup {
+                id: GroupId(1),
+                name: "G".to_string(),
+                visible: true,
+                locked: false,
+                opacity: 1.0,
+                blend_mode: crate::document::BlendMode::Normal,
+                children: vec![copy],
+            }
+            .into(),
+        );
+
+        let issues = validate(&document, 2048, &mut unchecked);
+        assert_eq!(
+            paths(&issues, Severity::Error),
+            [
+                "$.artboards[0].size[0]",
+                "$.artboards[1].layers[1].id",
+                "$.artboards[1].layers[1].children[0].id",
+                "$.artboards[1].layers[1].children[0].strokes[0].points[0]",
+            ]
+        );
+        assert_eq!(
+            paths(&issues, Severity::Warning),
+            ["$.artboards[0].size[1]", "$.next_id"]
+        );
+        assert!(
+            issues[3]
+                .message
+                .contains("first used by $.artboards[1].layers[0]"),
+            "{}",
+            issues[3]
+        );
+    }
+
+    #[test]
+    fn content_paths_must_stay_inside_the_document_dir() {
+        let mut document = doc_two_artboards();
+        let mut looked_up = Vec::new();
+        for (layer_id, content) in [(LayerId(2), "../secrets.png"), (LayerId(4), "/etc/passwd")] {
+            document.find_layer_mut(layer_id).unwrap().content_path = Some(content.to_string());
+        }
+        let issues = validate(&document, 2048, &mut |content| {
+            looked_up.push(content.to_string());
+            Ok(None)
+        });
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Error));
+        assert!(looked_up.is_empty(), "never opened");
+
+        assert!(stays_inside("doc.layer-2.png"));
+        assert!(stays_inside("layers/2.png"));
+        assert!(!stays_inside("layers/../../2.png"));
+        assert!(!stays_inside(""));
+    }
+
+    #[test]
+    fn content_size_is_checked_against_the_clamped_artboard() {
+        let mut document = doc_two_artboards();
+        document.artboards[0].size = [600.0, 3000.0];
+        for layer_id in [LayerId(2), LayerId(4)] {
+            let content = format!("{}.png", layer_id.0);
+            document.find_layer_mut(layer_id).unwrap().content_path = Some(content);
+        }
+        let issues = validate(&document, 2048, &mut |content| match content {
+            "2.png" => Ok(Some((600, 2048))),
+            _ => Err(anyhow::anyhow!("decoding {content}")),
+        });
+        assert_eq!(issues.len(), 2, "{issues:?}");
+        assert_eq!(issues[0].path, "$.artboards[0].size[1]");
+        assert_eq!(
+            issues[1].to_string(),
+            "error: $.artboards[1].layers[0].content_path: decoding 4.png"
+        );
+
+        let issues = validate(&document, 2048, &mut |_| Ok(Some((10, 10))));
+        assert!(
+            issues[1]
+                .message
+                .contains("10x10 but the artboard 600x2048")
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn repair_clamps_and_moves_next_id_past_every_id() {
+        let mut document = doc_two_artboards();
+        document.next_id = 2;
+        document.artboards[1].size = [9000.0, 300.0];
+        repair(&mut document, 2048);
+        assert_eq!(document.next_id, 5);
+        assert_eq!(document.artboards[1].size, [2048.0, 300.0]);
+        assert_eq!(validate(&document, 2048, &mut unchecked), []);
+    }
+}
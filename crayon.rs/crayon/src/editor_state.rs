@@ -1,7 +1,13 @@
-use crate::renderer::brush::{DEFAULT_BRUSH_SIZE, POINTER_SIZE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document::{Artboard, ArtboardId, BrushTip, Document, LayerId, StrokeMode},
+    renderer::brush::{DEFAULT_BRUSH_HARDNESS, DEFAULT_BRUSH_SIZE, POINTER_SIZE},
+    resources::stroke_state::StrokeTarget,
+};
 
 /// Generalized color representation for editor state
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BrushColor {
     pub r: f32,
     pub g: f32,
@@ -52,19 +58,138 @@ impl From<[u8; 3]> for BrushColor {
 
 pub const DEFAULT_BRUSH_COLOR: BrushColor = BrushColor::new(128.0 / 255.0, 85.0 / 255.0, 1.0, 1.0);
 
-#[derive(Debug, Clone, Copy)]
+/// Shapes pen and touch pressure into the size and opacity of the dabs.
+/// Full pressure, which is all a mouse reports, always gives the plain brush.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureCurve {
+    /// Exponent on the pressure, above 1 takes a firmer press to reach full size, below 1 a lighter one.
+    pub gamma: f32,
+    /// Fraction of the brush size at the lightest touch.
+    pub min_size: f32,
+    /// Dab opacity at the lightest touch.
+    pub min_opacity: f32,
+}
+
+impl PressureCurve {
+    fn response(self, pressure: f32) -> f32 {
+        pressure.clamp(0.0, 1.0).powf(self.gamma)
+    }
+
+    /// Radius of a dab at `pressure` for a brush of `size`.
+    pub fn radius(self, size: f32, pressure: f32) -> f32 {
+        size * (self.min_size + (1.0 - self.min_size) * self.response(pressure))
+    }
+
+    pub fn opacity(self, pressure: f32) -> f32 {
+        self.min_opacity + (1.0 - self.min_opacity) * self.response(pressure)
+    }
+}
+
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            min_size: 0.2,
+            min_opacity: 0.4,
+        }
+    }
+}
+
+/// How each dab of a tip is turned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TipRotation {
+    /// Upright, as the tip image is drawn.
+    #[default]
+    Fixed,
+    /// Along the direction the stroke travels.
+    FollowStroke,
+    /// A different angle for every dab.
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BrushProperties {
     pub color: BrushColor,
     /// matches with the preview, can be scaled via Camera scale
     pub pointer_size: f32,
     /// after multiplying with `POINTER_TO_BRUSH_SIZE_MULTIPLE`
     pub size: f32,
+    /// Sessions recorded before the eraser leave it out, and paint.
+    #[serde(default)]
+    pub mode: StrokeMode,
+    #[serde(default)]
+    pub pressure_curve: PressureCurve,
+    #[serde(default)]
+    pub tip: BrushTip,
+    #[serde(default)]
+    pub tip_rotation: TipRotation,
+}
+
+/// The closest dabs are ever stamped, in pixels, however small the brush.
+const MIN_DAB_STEP: f32 = 0.5;
+
+/// How the brush engine stamps and builds up dabs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BrushSettings {
+    /// Fraction of the round tip's radius at full strength before its edge fades out, 1 is a hard edge.
+    pub hardness: f32,
+    /// Distance between dabs, in percent of the brush diameter.
+    pub spacing: f32,
+    /// Opacity of each dab, overlapping dabs build up towards the stroke's opacity.
+    pub flow: f32,
+    /// The most a stroke covers, however many of its dabs overlap.
+    pub opacity: f32,
+}
+
+impl BrushSettings {
+    /// Distance between dabs, in pixels, for a brush of `size`.
+    pub fn dab_step(self, size: f32) -> f32 {
+        (size * 2.0 * self.spacing / 100.0).max(MIN_DAB_STEP)
+    }
+}
+
+impl Default for BrushSettings {
+    fn default() -> Self {
+        Self {
+            hardness: DEFAULT_BRUSH_HARDNESS,
+            spacing: 5.0,
+            flow: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// An artboard being dragged across the canvas, moved as one step once dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtboardDrag {
+    pub artboard_id: ArtboardId,
+    /// Where the artboard was when grabbed.
+    pub from: [f32; 2],
+    /// World offset of the grab point from the artboard's top left corner.
+    grab: [f32; 2],
+}
+
+impl ArtboardDrag {
+    /// Where the artboard goes with its grab point at `world_position`.
+    pub fn position_at(&self, world_position: cgmath::Point2<f32>) -> [f32; 2] {
+        [
+            world_position.x - self.grab[0],
+            world_position.y - self.grab[1],
+        ]
+    }
 }
 
 /// State pertinent to the editor and painting systems.
 /// UI may rely on some of this.
 pub struct EditorState {
     pub brush_properties: BrushProperties,
+    pub brush_settings: BrushSettings,
+    /// Layer strokes paint on, wherever it moves, see `active_layer` for the one actually used.
+    selected_layer: Option<LayerId>,
+    /// Strokes paint on the artboard under their first dab, switching the active layer to it.
+    pub pick_artboard_at_stroke: bool,
+    pub artboard_drag: Option<ArtboardDrag>,
 }
 
 impl EditorState {
@@ -74,13 +199,94 @@ impl EditorState {
                 color: DEFAULT_BRUSH_COLOR,
                 pointer_size: POINTER_SIZE,
                 size: DEFAULT_BRUSH_SIZE,
+                mode: StrokeMode::Paint,
+                pressure_curve: PressureCurve::default(),
+                tip: BrushTip::Round,
+                tip_rotation: TipRotation::Fixed,
             },
+            brush_settings: BrushSettings::default(),
+            selected_layer: None,
+            pick_artboard_at_stroke: true,
+            artboard_drag: None,
         }
     }
 
     pub fn update_brush(&mut self, brush_properties: BrushProperties) {
         self.brush_properties = brush_properties;
     }
+
+    pub fn update_brush_settings(&mut self, brush_settings: BrushSettings) {
+        self.brush_settings = brush_settings;
+    }
+
+    /// The selected layer while it's still in the document, the top layer of the first artboard otherwise.
+    pub fn active_layer(&self, document: &Document) -> Option<StrokeTarget> {
+        self.selected_layer
+            .and_then(|layer_id| document.find_layer(layer_id))
+            .map(|(artboard_id, layer)| (artboard_id, layer.id))
+            .or_else(|| document.artboards.first().and_then(top_layer))
+    }
+
+    pub fn select_layer(&mut self, layer_id: LayerId) {
+        self.selected_layer = Some(layer_id);
+    }
+
+    /// Moves the active layer `step` layers up the stack of its artboard, stopping at either end.
+    pub fn select_adjacent_layer(&mut self, document: &Document, step: isize) {
+        let Some((artboard_id, layer_id)) = self.active_layer(document) else {
+            return;
+        };
+        let Some(artboard) = document.artboard(artboard_id) else {
+            return;
+        };
+        let layers: Vec<_> = artboard.iter_layers().map(|layer| layer.id).collect();
+        let Some(index) = layers.iter().position(|id| *id == layer_id) else {
+            return;
+        };
+        let index = index.saturating_add_signed(step).min(layers.len() - 1);
+        self.selected_layer = Some(layers[index]);
+    }
+
+    /// Target of a stroke whose first dab lands on `world_position`.
+    /// With `pick_artboard_at_stroke`, a dab on another artboard makes its top layer active.
+    pub fn stroke_target(
+        &mut self,
+        document: &Document,
+        world_position: cgmath::Point2<f32>,
+    ) -> Option<StrokeTarget> {
+        let active = self.active_layer(document);
+        if self.pick_artboard_at_stroke
+            && let Some(artboard_id) = document.hit_test(world_position)
+            && active.map(|(id, _)| id) != Some(artboard_id)
+            && let Some(target) = document.artboard(artboard_id).and_then(top_layer)
+        {
+            self.selected_layer = Some(target.1);
+            return Some(target);
+        }
+        active
+    }
+
+    /// Starts dragging the artboard under `world_position`, if any.
+    pub fn grab_artboard(&mut self, document: &Document, world_position: cgmath::Point2<f32>) {
+        self.artboard_drag = document
+            .hit_test(world_position)
+            .and_then(|artboard_id| document.artboard(artboard_id))
+            .map(|artboard| ArtboardDrag {
+                artboard_id: artboard.id,
+                from: artboard.position,
+                grab: [
+                    world_position.x - artboard.position[0],
+                    world_position.y - artboard.position[1],
+                ],
+            });
+    }
+}
+
+fn top_layer(artboard: &Artboard) -> Option<StrokeTarget> {
+    artboard
+        .iter_layers()
+        .last()
+        .map(|layer| (artboard.id, layer.id))
 }
 
 impl Default for EditorState {
@@ -88,3 +294,143 @@ impl Default for EditorState {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point2;
+
+    use super::*;
+    use crate::testing::fixtures::{blank_layer, doc_two_artboards};
+
+    const LEFT: StrokeTarget = (ArtboardId(1), LayerId(2));
+    const RIGHT: StrokeTarget = (ArtboardId(3), LayerId(4));
+
+    /// `doc_two_artboards` with a second layer above the left artboard's.
+    fn doc_with_two_left_layers() -> Document {
+        let mut document = doc_two_artboards();
+        let upper = document.alloc_layer_id();
+        document.artboards[0]
+            .layers
+            .push(blank_layer(upper.0).into());
+        document
+    }
+
+    #[test]
+    fn pressure_curve_spans_its_minimums_to_the_full_brush() {
+        let curve = PressureCurve {
+            gamma: 2.0,
+            min_size: 0.25,
+            min_opacity: 0.5,
+        };
+        assert!((curve.radius(20.0, 0.0) - 5.0).abs() < 1e-5);
+        assert!((curve.radius(20.0, 1.0) - 20.0).abs() < 1e-5);
+        assert!(
+            (curve.radius(20.0, 0.5) - 8.75).abs() < 1e-5,
+            "gamma bends it"
+        );
+        assert!((curve.opacity(0.0) - 0.5).abs() < 1e-5);
+        assert!(
+            (curve.opacity(2.0) - 1.0).abs() < 1e-5,
+            "pressure is clamped"
+        );
+    }
+
+    #[test]
+    fn dab_step_follows_the_brush_diameter() {
+        let settings = BrushSettings {
+            spacing: 25.0,
+            ..BrushSettings::default()
+        };
+        assert!((settings.dab_step(20.0) - 10.0).abs() < 1e-5);
+        assert!((settings.dab_step(100.0) - 50.0).abs() < 1e-5);
+        assert!(
+            (settings.dab_step(0.5) - MIN_DAB_STEP).abs() < 1e-5,
+            "tiny brushes keep a minimum step"
+        );
+    }
+
+    #[test]
+    fn active_layer_falls_back_to_the_top_of_the_first_artboard() {
+        let document = doc_two_artboards();
+        let mut editor = EditorState::new();
+        assert_eq!(editor.active_layer(&document), Some(LEFT));
+
+        editor.select_layer(RIGHT.1);
+        assert_eq!(editor.active_layer(&document), Some(RIGHT));
+
+        editor.select_layer(LayerId(99));
+        assert_eq!(editor.active_layer(&document), Some(LEFT), "no such layer");
+        assert_eq!(
+            editor.active_layer(&Document {
+                artboards: vec![],
+                ..document
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn adjacent_layers_stay_within_the_artboard() {
+        let document = doc_with_two_left_layers();
+        let upper = (ArtboardId(1), LayerId(5));
+        let mut editor = EditorState::new();
+        assert_eq!(editor.active_layer(&document), Some(upper));
+
+        editor.select_adjacent_layer(&document, 1);
+        assert_eq!(
+            editor.active_layer(&document),
+            Some(upper),
+            "already on top"
+        );
+        editor.select_adjacent_layer(&document, -1);
+        assert_eq!(editor.active_layer(&document), Some(LEFT));
+        editor.select_adjacent_layer(&document, -1);
+        assert_eq!(
+            editor.active_layer(&document),
+            Some(LEFT),
+            "already at the bottom"
+        );
+    }
+
+    #[test]
+    fn strokes_pick_the_artboard_under_their_first_dab() {
+        let document = doc_with_two_left_layers();
+        let mut editor = EditorState::new();
+        editor.select_layer(LEFT.1);
+
+        let on_left = Point2::new(10.0, 10.0);
+        let on_right = Point2::new(800.0, 200.0);
+        let between = Point2::new(650.0, 200.0);
+        assert_eq!(
+            editor.stroke_target(&document, on_left),
+            Some(LEFT),
+            "keeps the selection"
+        );
+        assert_eq!(editor.stroke_target(&document, on_right), Some(RIGHT));
+        assert_eq!(
+            editor.active_layer(&document),
+            Some(RIGHT),
+            "becomes active"
+        );
+        assert_eq!(editor.stroke_target(&document, between), Some(RIGHT));
+
+        editor.pick_artboard_at_stroke = false;
+        editor.select_layer(LEFT.1);
+        assert_eq!(editor.stroke_target(&document, on_right), Some(LEFT));
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn dragged_artboards_follow_their_grab_point() {
+        let document = doc_two_artboards();
+        let mut editor = EditorState::new();
+        editor.grab_artboard(&document, Point2::new(650.0, 200.0));
+        assert_eq!(editor.artboard_drag, None, "nothing between the artboards");
+
+        editor.grab_artboard(&document, Point2::new(750.0, 150.0));
+        let drag = editor.artboard_drag.unwrap();
+        assert_eq!(drag.artboard_id, RIGHT.0);
+        assert_eq!(drag.from, [700.0, 100.0]);
+        assert_eq!(drag.position_at(Point2::new(800.0, 300.0)), [750.0, 250.0]);
+    }
+}
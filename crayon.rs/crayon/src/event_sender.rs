@@ -8,7 +8,49 @@ impl From<ControllerEvent> for CustomEvent {
             ControllerEvent::CameraMove { position } => CustomEvent::CameraMove { position },
             ControllerEvent::CameraZoom { delta, .. } => CustomEvent::CameraZoom { delta },
             ControllerEvent::ClearCanvas => CustomEvent::ClearCanvas,
+            ControllerEvent::Undo => CustomEvent::Undo,
+            ControllerEvent::Redo => CustomEvent::Redo,
+            ControllerEvent::SaveDocument => CustomEvent::SaveDocument,
+            ControllerEvent::ExportArtboard(options) => CustomEvent::ExportArtboard(options),
+            ControllerEvent::ExportOpenRaster => CustomEvent::ExportOpenRaster,
+            ControllerEvent::ExportPsd => CustomEvent::ExportPsd,
+            ControllerEvent::RestoreInterruptedSession => CustomEvent::RestoreInterruptedSession,
+            ControllerEvent::DiscardInterruptedSession => CustomEvent::DiscardInterruptedSession,
             ControllerEvent::UpdateBrush(properties) => CustomEvent::UpdateBrush(properties),
+            ControllerEvent::UpdateBrushSettings(settings) => {
+                CustomEvent::UpdateBrushSettings(settings)
+            }
+            ControllerEvent::SaveBrushPreset(name) => CustomEvent::SaveBrushPreset(name),
+            ControllerEvent::DeleteBrushPreset(name) => CustomEvent::DeleteBrushPreset(name),
+            ControllerEvent::ImportBrushPresets(path) => CustomEvent::ImportBrushPresets(path),
+            ControllerEvent::ExportBrushPresets(path) => CustomEvent::ExportBrushPresets(path),
+            ControllerEvent::SelectLayer(layer_id) => CustomEvent::SelectLayer(layer_id),
+            ControllerEvent::SelectLayerAbove => CustomEvent::SelectLayerAbove,
+            ControllerEvent::SelectLayerBelow => CustomEvent::SelectLayerBelow,
+            ControllerEvent::PickArtboardAtStroke(pick) => CustomEvent::PickArtboardAtStroke(pick),
+            ControllerEvent::AddLayer => CustomEvent::AddLayer,
+            ControllerEvent::AddVectorLayer => CustomEvent::AddVectorLayer,
+            ControllerEvent::DuplicateNode(node) => CustomEvent::DuplicateNode(node),
+            ControllerEvent::DeleteNode(node) => CustomEvent::DeleteNode(node),
+            ControllerEvent::RenameNode { node, name } => CustomEvent::RenameNode { node, name },
+            ControllerEvent::SetNodeVisible { node, visible } => {
+                CustomEvent::SetNodeVisible { node, visible }
+            }
+            ControllerEvent::SetNodeLocked { node, locked } => {
+                CustomEvent::SetNodeLocked { node, locked }
+            }
+            ControllerEvent::MoveNode {
+                node,
+                artboard_id,
+                parent,
+                index,
+            } => CustomEvent::MoveNode {
+                node,
+                artboard_id,
+                parent,
+                index,
+            },
+            ControllerEvent::Artboard(event) => CustomEvent::Artboard(event),
             ControllerEvent::StrokeStart => CustomEvent::StrokeStart,
             ControllerEvent::StrokeEnd => CustomEvent::StrokeEnd,
         }
@@ -18,24 +60,34 @@ impl From<ControllerEvent> for CustomEvent {
 #[cfg(not(target_arch = "wasm32"))]
 pub type ControllerEventSender = std::sync::mpsc::Sender<ControllerEvent>;
 
+/// Where the relay thread writes the events it passes on, once `EventSender::start_recording` sets it.
+#[cfg(not(target_arch = "wasm32"))]
+type SharedRecorder = std::sync::Arc<std::sync::Mutex<Option<crate::session::Recorder>>>;
+
 #[derive(Clone)]
 pub struct EventSender {
     #[cfg(target_arch = "wasm32")]
     proxy: winit::event_loop::EventLoopProxy<CustomEvent>,
     #[cfg(not(target_arch = "wasm32"))]
     channel: ControllerEventSender,
+    #[cfg(not(target_arch = "wasm32"))]
+    recorder: SharedRecorder,
 }
 
 impl EventSender {
     pub fn new(event_loop_proxy: winit::event_loop::EventLoopProxy<CustomEvent>) -> Self {
         #[cfg(not(target_arch = "wasm32"))]
         let (tx, rx) = std::sync::mpsc::channel::<ControllerEvent>();
+        #[cfg(not(target_arch = "wasm32"))]
+        let recorder = SharedRecorder::default();
 
         #[cfg(not(target_arch = "wasm32"))]
         {
             let proxy_clone = event_loop_proxy.clone();
+            let recorder = recorder.clone();
             std::thread::spawn(move || {
                 while let Ok(event) = rx.recv() {
+                    record(&recorder, &event);
                     let _ = proxy_clone.send_event(event.into());
                 }
             });
@@ -46,6 +98,16 @@ impl EventSender {
             proxy: event_loop_proxy,
             #[cfg(not(target_arch = "wasm32"))]
             channel: tx,
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder,
+        }
+    }
+
+    /// Records every event relayed from now on, see `session`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_recording(&self, recorder: crate::session::Recorder) {
+        if let Ok(mut slot) = self.recorder.lock() {
+            *slot = Some(recorder);
         }
     }
 
@@ -71,7 +133,27 @@ impl EventSender {
     #[cfg(all(test, not(target_arch = "wasm32")))]
     pub fn capturing() -> (Self, std::sync::mpsc::Receiver<ControllerEvent>) {
         let (tx, rx) = std::sync::mpsc::channel();
-        (Self { channel: tx }, rx)
+        (
+            Self {
+                channel: tx,
+                recorder: SharedRecorder::default(),
+            },
+            rx,
+        )
+    }
+}
+
+/// Appends `event` to the session being recorded, if any. Recording stops at the first failed write.
+#[cfg(not(target_arch = "wasm32"))]
+fn record(recorder: &SharedRecorder, event: &ControllerEvent) {
+    let Ok(mut slot) = recorder.lock() else {
+        return;
+    };
+    if let Some(recorder) = slot.as_mut()
+        && let Err(error) = recorder.record(event)
+    {
+        log::error!("stopped recording the session: {error:#}");
+        *slot = None;
     }
 }
 
@@ -1,12 +1,19 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use batteries::prelude::Dot2D;
+use serde::{Deserialize, Serialize};
 
-use crate::{editor_state::BrushProperties, renderer::render_context::RenderContext};
+use crate::{
+    document::{
+        ArtboardId, GroupId, LayerId, NodeId, artboard_size::Anchor, export::ExportOptions,
+    },
+    editor_state::{BrushProperties, BrushSettings},
+    renderer::render_context::RenderContext,
+};
 
 /// Controller events are created to add an indirection so the events can be replayed.
-/// This is intended to build the undo/redo functionality in the future.
-#[derive(Debug, Clone)]
+/// Serializable so a session of them can be recorded, see `session`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ControllerEvent {
     BrushPoint {
         dot: Dot2D,
@@ -20,11 +27,117 @@ pub enum ControllerEvent {
         _position: cgmath::Point2<f32>,
     },
     ClearCanvas,
+    /// Take back the latest stroke.
+    Undo,
+    /// Put back the latest undone stroke.
+    Redo,
+    SaveDocument,
+    /// Flatten the artboard in view to a PNG.
+    ExportArtboard(ExportOptions),
+    /// Write every artboard to an OpenRaster file.
+    ExportOpenRaster,
+    /// Write every artboard to a layered PSD.
+    ExportPsd,
+    /// Reopen the interrupted session the launch found, see `InterruptedSession`.
+    RestoreInterruptedSession,
+    /// Delete the interrupted session the launch found.
+    DiscardInterruptedSession,
     UpdateBrush(BrushProperties),
+    UpdateBrushSettings(BrushSettings),
+    /// Keep the brush and its settings as a preset of this name, replacing one already named so.
+    SaveBrushPreset(String),
+    DeleteBrushPreset(String),
+    /// Add the presets of a presets file, replacing those of the same names.
+    ImportBrushPresets(PathBuf),
+    /// Write every preset to a presets file.
+    ExportBrushPresets(PathBuf),
+    /// Make the layer the one strokes paint on.
+    SelectLayer(LayerId),
+    /// Move the active layer one up its artboard's stack.
+    SelectLayerAbove,
+    /// Move the active layer one down its artboard's stack.
+    SelectLayerBelow,
+    /// Whether strokes paint on the artboard under their first dab.
+    PickArtboardAtStroke(bool),
+    /// Add a blank layer above the active one, and make it active.
+    AddLayer,
+    /// Add an empty vector layer above the active one, and make it active.
+    AddVectorLayer,
+    /// Copy a layer or group above itself, `None` for the active layer.
+    DuplicateNode(Option<NodeId>),
+    /// Delete a layer or group, `None` for the active layer.
+    DeleteNode(Option<NodeId>),
+    RenameNode {
+        node: NodeId,
+        name: String,
+    },
+    SetNodeVisible {
+        node: NodeId,
+        visible: bool,
+    },
+    /// Lock a layer or group against painting, clearing, moving, renaming and deleting.
+    SetNodeLocked {
+        node: NodeId,
+        locked: bool,
+    },
+    /// Move a layer or group to `index` among `parent`'s children in `artboard_id`, see `Command::MoveNode`.
+    MoveNode {
+        node: NodeId,
+        artboard_id: ArtboardId,
+        parent: Option<GroupId>,
+        index: usize,
+    },
+    Artboard(ArtboardEvent),
     StrokeStart,
     StrokeEnd,
 }
 
+impl ControllerEvent {
+    /// Whether handling the event saves, exports or otherwise writes files besides the document in view.
+    /// Sessions leave these out, so replaying one never overwrites what the user has on disk.
+    /// Preset edits are not among them: later events pick from the presets they leave,
+    /// and a replay keeps them in memory, see `BrushPresets::in_memory`.
+    pub fn writes_files(&self) -> bool {
+        matches!(
+            self,
+            Self::SaveDocument
+                | Self::ExportArtboard(_)
+                | Self::ExportOpenRaster
+                | Self::ExportPsd
+                | Self::RestoreInterruptedSession
+                | Self::DiscardInterruptedSession
+                | Self::ExportBrushPresets(_)
+        )
+    }
+}
+
+/// Creating, moving and sizing artboards.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArtboardEvent {
+    /// Add an artboard with a blank layer right of the others, and make its layer active.
+    Add {
+        size: [u32; 2],
+    },
+    Delete(ArtboardId),
+    /// Resize around `anchor`, see `Command::ResizeArtboard`.
+    Resize {
+        artboard_id: ArtboardId,
+        size: [u32; 2],
+        anchor: Anchor,
+    },
+    /// Shrink to the bounds of the non-transparent pixels of every layer.
+    CropToContent(ArtboardId),
+    /// Grab the artboard under the screen `position`.
+    DragStart {
+        position: cgmath::Point2<f32>,
+    },
+    DragMove {
+        position: cgmath::Point2<f32>,
+    },
+    /// Drop the grabbed artboard, moving it as one undoable step.
+    DragEnd,
+}
+
 pub enum CustomEvent {
     BrushPoint {
         dot: Dot2D,
@@ -42,7 +155,67 @@ pub enum CustomEvent {
         delta: f32,
     },
     ClearCanvas,
+    /// Take back the latest stroke.
+    Undo,
+    /// Put back the latest undone stroke.
+    Redo,
+    SaveDocument,
+    /// Flatten the artboard in view to a PNG.
+    ExportArtboard(ExportOptions),
+    /// Write every artboard to an OpenRaster file.
+    ExportOpenRaster,
+    /// Write every artboard to a layered PSD.
+    ExportPsd,
+    /// Reopen the interrupted session the launch found, see `InterruptedSession`.
+    RestoreInterruptedSession,
+    /// Delete the interrupted session the launch found.
+    DiscardInterruptedSession,
     UpdateBrush(BrushProperties),
+    UpdateBrushSettings(BrushSettings),
+    /// Keep the brush and its settings as a preset of this name, replacing one already named so.
+    SaveBrushPreset(String),
+    DeleteBrushPreset(String),
+    /// Add the presets of a presets file, replacing those of the same names.
+    ImportBrushPresets(PathBuf),
+    /// Write every preset to a presets file.
+    ExportBrushPresets(PathBuf),
+    /// Make the layer the one strokes paint on.
+    SelectLayer(LayerId),
+    /// Move the active layer one up its artboard's stack.
+    SelectLayerAbove,
+    /// Move the active layer one down its artboard's stack.
+    SelectLayerBelow,
+    /// Whether strokes paint on the artboard under their first dab.
+    PickArtboardAtStroke(bool),
+    /// Add a blank layer above the active one, and make it active.
+    AddLayer,
+    /// Add an empty vector layer above the active one, and make it active.
+    AddVectorLayer,
+    /// Copy a layer or group above itself, `None` for the active layer.
+    DuplicateNode(Option<NodeId>),
+    /// Delete a layer or group, `None` for the active layer.
+    DeleteNode(Option<NodeId>),
+    RenameNode {
+        node: NodeId,
+        name: String,
+    },
+    SetNodeVisible {
+        node: NodeId,
+        visible: bool,
+    },
+    /// Lock a layer or group against painting, clearing, moving, renaming and deleting.
+    SetNodeLocked {
+        node: NodeId,
+        locked: bool,
+    },
+    /// Move a layer or group to `index` among `parent`'s children in `artboard_id`, see `Command::MoveNode`.
+    MoveNode {
+        node: NodeId,
+        artboard_id: ArtboardId,
+        parent: Option<GroupId>,
+        index: usize,
+    },
+    Artboard(ArtboardEvent),
     StrokeStart,
     StrokeEnd,
 }
@@ -1,8 +1,11 @@
 #![warn(clippy::pedantic)]
 
 mod app;
+mod artboard_controller;
 mod brush_controller;
 mod camera_controller;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
 mod constants;
 #[allow(dead_code)]
 mod document;
@@ -12,6 +15,8 @@ mod events;
 mod renderer;
 mod resource;
 mod resources;
+#[cfg(not(target_arch = "wasm32"))]
+mod session;
 mod state;
 mod system;
 mod systems;
@@ -29,6 +34,7 @@ use crate::app::App;
 use crate::renderer::ui::hello_widget::HelloResource;
 use crate::resource::ResourceContext;
 use crate::resources::brush_point_queue::BrushPointQueue;
+use crate::resources::brush_presets::BrushPresets;
 use crate::resources::brush_preview_state::BrushPreviewState;
 use crate::resources::frame_time::FrameTime;
 use crate::resources::launch_options::LaunchOptions;
@@ -40,6 +46,7 @@ use crate::systems::frame_acquire_system::FrameAcquireSystem;
 use crate::systems::frame_present_system::FramePresentSystem;
 use crate::systems::frame_time_update::FrameTimeUpdateSystem;
 use crate::systems::paint_system::PaintSystem;
+use crate::systems::thumbnail_system::ThumbnailSystem;
 use crate::systems::tools_system::ToolsSystem;
 
 pub fn run() -> anyhow::Result<()> {
@@ -53,6 +60,24 @@ pub fn run() -> anyhow::Result<()> {
         console_log::init_with_level(log::Level::Info).unwrap_throw();
     }
 
+    let launch_options = LaunchOptions::from_args();
+    // subcommands never open a window
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(command) = &launch_options.command {
+        return cli::run(command);
+    }
+
+    // a replay edits brush presets as they were recorded, without writing them back
+    #[cfg(not(target_arch = "wasm32"))]
+    let brush_presets = {
+        let presets = BrushPresets::load(crate::resources::brush_presets::presets_path());
+        if launch_options.replay.is_some() {
+            presets.in_memory()
+        } else {
+            presets
+        }
+    };
+
     let event_loop = EventLoop::with_user_event().build()?;
     let event_loop_proxy = event_loop.create_proxy();
     let mut app = App::new(event_loop_proxy);
@@ -62,7 +87,11 @@ pub fn run() -> anyhow::Result<()> {
         .insert_resource(HelloResource::new())
         .insert_resource(BrushPreviewState::new())
         .insert_resource(StrokeState::new())
-        .insert_resource(LaunchOptions::from_args());
+        .insert_resource(launch_options);
+    #[cfg(not(target_arch = "wasm32"))]
+    app.insert_resource(brush_presets);
+    #[cfg(target_arch = "wasm32")]
+    app.insert_resource(BrushPresets::new());
 
     app.add_system(Schedule::PreUpdate, FrameAcquireSystem)
         .add_system(Schedule::Update, FrameTimeUpdateSystem)
@@ -70,7 +99,13 @@ pub fn run() -> anyhow::Result<()> {
         .add_system(Schedule::Update, PaintSystem)
         .add_system(Schedule::Update, CanvasRenderSystem)
         .add_system(Schedule::Update, ToolsSystem::new())
-        .add_system(Schedule::PostUpdate, FramePresentSystem);
+        .add_system(Schedule::PostUpdate, FramePresentSystem)
+        .add_system(Schedule::PostUpdate, ThumbnailSystem);
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_system(
+        Schedule::PostUpdate,
+        crate::systems::autosave_system::AutosaveSystem,
+    );
 
     event_loop.run_app(&mut app)?;
 
@@ -1,3 +1,64 @@
+use crate::document::BrushTip;
+
 pub const POINTER_SIZE: f32 = 20.0;
 
 pub const DEFAULT_BRUSH_SIZE: f32 = POINTER_SIZE;
+
+/// Where the round tip starts fading out, as a fraction of its radius.
+pub const DEFAULT_BRUSH_HARDNESS: f32 = 0.4;
+
+/// The bundled grayscale image a tip stamps, `None` for the procedural round tip.
+pub fn tip_png(tip: BrushTip) -> Option<&'static [u8]> {
+    match tip {
+        BrushTip::Round => None,
+        BrushTip::Chalk => Some(include_bytes!("../../assets/brushes/chalk.png")),
+        BrushTip::Pencil => Some(include_bytes!("../../assets/brushes/pencil.png")),
+        BrushTip::Bristle => Some(include_bytes!("../../assets/brushes/bristle.png")),
+    }
+}
+
+/// Decodes a tip image to its luma and every halved mip level below it, down to 1x1.
+/// Dabs shrink far below the image size, unfiltered they would sparkle.
+pub fn decode_tip_mips(png: &[u8]) -> anyhow::Result<Vec<image::GrayImage>> {
+    let tip = image::load_from_memory_with_format(png, image::ImageFormat::Png)?.to_luma8();
+    let mut mips = vec![tip];
+    while let Some(last) = mips.last()
+        && (last.width() > 1 || last.height() > 1)
+    {
+        let (width, height) = ((last.width() / 2).max(1), (last.height() / 2).max(1));
+        let mip =
+            image::imageops::resize(last, width, height, image::imageops::FilterType::Triangle);
+        mips.push(mip);
+    }
+    Ok(mips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_bundled_tip_decodes_down_to_a_texel() {
+        for tip in BrushTip::ALL {
+            let Some(png) = tip_png(tip) else {
+                continue;
+            };
+            let mips = decode_tip_mips(png).unwrap();
+            let sizes: Vec<_> = mips.iter().map(image::GrayImage::dimensions).collect();
+            assert_eq!(sizes.first(), Some(&(128, 128)), "{tip:?}");
+            assert_eq!(sizes.last(), Some(&(1, 1)), "{tip:?}");
+            assert_eq!(sizes.len(), 8, "{tip:?}");
+            // tips fade out before the edge of the dab
+            let full = &mips[0];
+            assert!(
+                full.get_pixel(0, 0)[0] < 16 && full.get_pixel(127, 127)[0] < 16,
+                "{tip:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn broken_tips_are_errors() {
+        assert!(decode_tip_mips(b"not a png").is_err());
+    }
+}
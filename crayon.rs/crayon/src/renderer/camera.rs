@@ -48,6 +48,16 @@ impl Camera2D {
         }
     }
 
+    /// Frames the world rect at `origin` exactly, `scale` viewport pixels per world unit.
+    /// For offscreen renders, so the scale is not clamped to the interactive zoom range.
+    pub fn framing(origin: [f32; 2], size: [f32; 2], scale: f32) -> Self {
+        Self {
+            scale,
+            viewport: (size[0] * scale, size[1] * scale),
+            translation: Point2::new(origin[0] + size[0] / 2.0, origin[1] + size[1] / 2.0),
+        }
+    }
+
     /// Adjusts the scale to maintain constant visual zoom when window size changes.
     fn adjust_scale_for_resize(&mut self, new_width: f32) {
         let old_width = self.viewport.0;
@@ -61,6 +71,11 @@ impl Camera2D {
         self.translation = world_position;
     }
 
+    /// Viewport pixels per world unit.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
     pub fn zoom_by(&mut self, delta: f32) {
         self.scale = clamp::clamp_zoom(self.scale, delta);
     }
@@ -0,0 +1,38 @@
+/// Scene format of windowless rendering, the sRGB format a window's surface is picked with.
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Device + queue for rendering without a window, as `testing::gpu::headless_gpu` gets for tests.
+///
+/// The adapter is requested with `compatible_surface: None`, so nothing needs a display.
+/// Without a hardware adapter, or with `software`, the software fallback adapter is used.
+pub fn headless_device(software: bool) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let request = |force_fallback_adapter| {
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter,
+        }))
+    };
+    let adapter = if software {
+        request(true)?
+    } else {
+        match request(false) {
+            Ok(adapter) => adapter,
+            Err(error) => {
+                log::warn!("no hardware adapter ({error}), falling back to software");
+                request(true)?
+            }
+        }
+    };
+    let info = adapter.get_info();
+    log::info!("rendering on {} ({:?})", info.name, info.backend);
+
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: Some("Headless Device"),
+        required_limits:
+            wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        ..Default::default()
+    }))?;
+    Ok((device, queue))
+}
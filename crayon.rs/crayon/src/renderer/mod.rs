@@ -2,6 +2,8 @@ pub mod brush;
 pub mod camera;
 pub mod egui_context;
 pub mod frame_context;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
 pub mod pipeline;
 pub mod render_context;
 pub mod ui;
@@ -0,0 +1,109 @@
+// Blends a layer onto an artboard composite with a mode fixed-function blending can't express.
+// The vertex stage matches quad.wgsl. The backdrop is a copy of the composite so far,
+// read texel for texel at the fragment position, so the target must be the same size.
+
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0) var t: texture_2d<f32>;
+@group(1) @binding(1) var s: sampler;
+
+@group(2) @binding(0) var backdrop: texture_2d<f32>;
+
+// Matches `blend_mode_index` in scene_renderer.rs
+const MULTIPLY: u32 = 1u;
+const SCREEN: u32 = 2u;
+const OVERLAY: u32 = 3u;
+const ADD: u32 = 4u;
+const DARKEN: u32 = 5u;
+const LIGHTEN: u32 = 6u;
+
+struct VsOut {
+    @builtin(position) clip: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) opacity: f32,
+    @location(2) @interpolate(flat) blend_mode: u32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) origin: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) uv_rect: vec4<f32>,
+    @location(3) opacity: f32,
+    @location(4) blend_mode: u32,
+) -> VsOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+
+    let corner01 = corners[vertex_index];
+    let world = origin + corner01 * size;
+
+    var out: VsOut;
+    out.clip = camera.view_projection * vec4<f32>(world, 0.0, 1.0);
+    out.uv = mix(uv_rect.xy, uv_rect.zw, corner01);
+    out.opacity = opacity;
+    out.blend_mode = blend_mode;
+    return out;
+}
+
+fn screen(cb: vec3<f32>, cs: vec3<f32>) -> vec3<f32> {
+    return cb + cs - cb * cs;
+}
+
+// Separable blend functions on straight-alpha colors, cb backdrop and cs source.
+fn blend(cb: vec3<f32>, cs: vec3<f32>, mode: u32) -> vec3<f32> {
+    switch mode {
+        case MULTIPLY: {
+            return cb * cs;
+        }
+        case SCREEN: {
+            return screen(cb, cs);
+        }
+        case OVERLAY: {
+            // hard light with the layers swapped
+            return select(screen(cs, 2.0 * cb - 1.0), 2.0 * cb * cs, cb <= vec3<f32>(0.5));
+        }
+        case ADD: {
+            return min(cb + cs, vec3<f32>(1.0));
+        }
+        case DARKEN: {
+            return min(cb, cs);
+        }
+        case LIGHTEN: {
+            return max(cb, cs);
+        }
+        default: {
+            return cs;
+        }
+    }
+}
+
+fn unpremultiply(color: vec4<f32>) -> vec3<f32> {
+    if color.a <= 0.0 {
+        return vec3<f32>(0.0);
+    }
+    return color.rgb / color.a;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let src = textureSample(t, s, in.uv) * in.opacity;
+    let dst = textureLoad(backdrop, vec2<i32>(in.clip.xy), 0);
+
+    // source-over, with the blended color where source and backdrop overlap
+    let mixed = blend(unpremultiply(dst), unpremultiply(src), in.blend_mode);
+    let rgb = src.rgb * (1.0 - dst.a) + dst.rgb * (1.0 - src.a) + src.a * dst.a * mixed;
+    let alpha = src.a + dst.a * (1.0 - src.a);
+    return vec4<f32>(rgb, alpha);
+}
@@ -12,24 +12,36 @@
 // incorrectly and leave color fringes at point edges.
 
 struct PointUniform {
-    color: vec4<f32>,
     // active layer size
-    layer_size: vec2<f32>
+    layer_size: vec2<f32>,
+    // non-zero to stamp the tip texture instead of the procedural circle
+    has_tip: u32,
+    // fraction of the circle's radius at full strength
+    hardness: f32,
 };
 
 @group(0) @binding(0) var<uniform> point: PointUniform;
+@group(1) @binding(0) var tip_texture: texture_2d<f32>;
+@group(1) @binding(1) var tip_sampler: sampler;
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) local: vec2<f32>,
+    @location(1) opacity: f32,
+    @location(2) color: vec4<f32>,
 };
 
-const SHARPNESS: f32 = 0.4;
+// keeps the fade of the hardest circle from collapsing into a step
+const MAX_HARDNESS: f32 = 0.99;
 
 @vertex
 fn vs_main(
     @builtin(vertex_index) vertex_index: u32,
-    @location(0) instance: vec3<f32>,
+    // center.xy, radius_px, opacity
+    @location(0) instance: vec4<f32>,
+    // straight alpha
+    @location(1) color: vec4<f32>,
+    @location(2) rotation: f32,
 ) -> VertexOutput {
     var corners = array<vec2<f32>, 6>(
         vec2<f32>(-1.0, -1.0),
@@ -44,12 +56,20 @@ fn vs_main(
     let center = instance.xy;
     let radius_px = instance.z;
 
+    // rotated in pixels, before the per-axis clip conversion, so it doesn't shear.
+    // clockwise on the layer is counter-clockwise in y-up clip space
+    let c = cos(-rotation);
+    let s = sin(-rotation);
+    let rotated = vec2<f32>(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
+
     // per-axis clip conversion keeps points round on non-square layers.
-    let clip_offset = corner * radius_px * vec2<f32>(2.0 / point.layer_size.x, 2.0 / point.layer_size.y);
+    let clip_offset = rotated * radius_px * vec2<f32>(2.0 / point.layer_size.x, 2.0 / point.layer_size.y);
 
     var out: VertexOutput;
     out.clip_position = vec4<f32>(center + clip_offset, 0.0, 1.0);
     out.local = corner;
+    out.opacity = instance.w;
+    out.color = color;
 
     return out;
 }
@@ -57,8 +77,11 @@ fn vs_main(
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let distance = length(in.local);
-    let strength = 1.0 - smoothstep(SHARPNESS, 1.0, distance);
-    let coverage = strength * point.color.a;
+    let circle = 1.0 - smoothstep(min(point.hardness, MAX_HARDNESS), 1.0, distance);
+    // sampled in uniform control flow, the image's top row at the top of the dab
+    let tip = textureSample(tip_texture, tip_sampler, vec2<f32>(in.local.x * 0.5 + 0.5, 0.5 - in.local.y * 0.5)).r;
+    let strength = select(circle, tip, point.has_tip != 0u);
+    let coverage = strength * in.color.a * in.opacity;
 
-    return vec4<f32>(point.color.rgb * coverage, coverage);
+    return vec4<f32>(in.color.rgb * coverage, coverage);
 }
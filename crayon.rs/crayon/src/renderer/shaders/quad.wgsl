@@ -10,6 +10,7 @@ struct CameraUniform {
 struct VsOut {
     @builtin(position) clip: vec4<f32>,
     @location(0) uv: vec2<f32>,
+    @location(1) opacity: f32,
 };
 
 @vertex
@@ -18,6 +19,7 @@ fn vs_main(
     @location(0) origin: vec2<f32>,
     @location(1) size: vec2<f32>,
     @location(2) uv_rect: vec4<f32>,
+    @location(3) opacity: f32,
 ) -> VsOut {
     var corners = array<vec2<f32>, 6>(
         vec2<f32>(0.0, 0.0),
@@ -35,11 +37,13 @@ fn vs_main(
     var out: VsOut;
     out.clip = camera.view_projection * vec4<f32>(world, 0.0, 1.0);
     out.uv = mix(uv_rect.xy, uv_rect.zw, corner01);
+    out.opacity = opacity;
     return out;
 }
 
 @fragment
 fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
-    return textureSample(t, s, in.uv);
+    // premultiplied, so opacity scales every channel
+    return textureSample(t, s, in.uv) * in.opacity;
 }
 
@@ -0,0 +1,81 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::ui::{drawable::Drawable, theme::widgets::GLOBAL_PADDING},
+    resource::ResourceContext,
+    resources::document_state::DocumentState,
+    state::State,
+};
+
+/// Picks the layer strokes paint on.
+pub struct ActiveLayerWidget;
+
+impl ActiveLayerWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for ActiveLayerWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(state), Some(doc), Some(event_sender)) = (
+            app.read::<State>(),
+            app.read::<DocumentState>(),
+            app.read::<EventSender>(),
+        ) else {
+            return;
+        };
+
+        let document = &doc.document;
+        let active = state.editor.active_layer(document);
+        let label = |(artboard_id, layer_id)| {
+            let artboard = document.artboard(artboard_id)?;
+            let layer = artboard.layer(layer_id)?;
+            Some(format!("{} › {}", artboard.name, layer.name))
+        };
+
+        egui::Window::new("Active Layer")
+            .anchor(
+                egui::Align2::RIGHT_BOTTOM,
+                egui::vec2(-GLOBAL_PADDING, -GLOBAL_PADDING),
+            )
+            .movable(false)
+            .resizable(false)
+            .title_bar(false)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(TOOLS_BG_COLOR)
+                    .shadow(egui::epaint::Shadow::NONE),
+            )
+            .show(ctx, |ui| {
+                egui::ComboBox::from_id_salt("active_layer")
+                    .selected_text(active.and_then(label).unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for artboard in &document.artboards {
+                            // top of the stack first, as it's drawn
+                            for layer in
+                                artboard.iter_layers().collect::<Vec<_>>().into_iter().rev()
+                            {
+                                let target = (artboard.id, layer.id);
+                                let text = format!("{} › {}", artboard.name, layer.name);
+                                if ui.selectable_label(active == Some(target), text).clicked() {
+                                    event_sender.send(ControllerEvent::SelectLayer(layer.id));
+                                }
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Layer to paint on, ⌘[ and ⌘] step through it");
+
+                let mut pick = state.editor.pick_artboard_at_stroke;
+                if ui
+                    .checkbox(&mut pick, "Paint on the artboard under the brush")
+                    .changed()
+                {
+                    event_sender.send(ControllerEvent::PickArtboardAtStroke(pick));
+                }
+            });
+    }
+}
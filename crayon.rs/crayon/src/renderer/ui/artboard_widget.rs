@@ -0,0 +1,169 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    document::{
+        Artboard, ArtboardId,
+        artboard_size::{ARTBOARD_PRESETS, Anchor},
+    },
+    event_sender::EventSender,
+    events::{ArtboardEvent, ControllerEvent},
+    renderer::ui::{drawable::Drawable, theme::widgets::GLOBAL_PADDING},
+    resource::ResourceContext,
+    resources::document_state::DocumentState,
+    state::State,
+};
+
+const MAX_FIELD_SIZE: u32 = 16384;
+const DEFAULT_CUSTOM_SIZE: [u32; 2] = [800, 600];
+const ACTIVE_LAYER_CLEARANCE: f32 = 96.0;
+
+/// What the resize fields hold for the artboard of the active layer, between frames.
+#[derive(Clone, Copy)]
+struct ResizeForm {
+    artboard_id: ArtboardId,
+    /// The artboard's size the form was filled in from, it starts over when that changes.
+    from: (u32, u32),
+    size: [u32; 2],
+    anchor: Anchor,
+}
+
+/// Adds artboards, and resizes, crops or deletes the one holding the active layer.
+pub struct ArtboardWidget;
+
+impl ArtboardWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for ArtboardWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(state), Some(doc), Some(event_sender)) = (
+            app.read::<State>(),
+            app.read::<DocumentState>(),
+            app.read::<EventSender>(),
+        ) else {
+            return;
+        };
+
+        let document = &doc.document;
+        let active = state
+            .editor
+            .active_layer(document)
+            .and_then(|(artboard_id, _)| document.artboard(artboard_id));
+        let send = |event| event_sender.send(ControllerEvent::Artboard(event));
+
+        // above the active layer picker
+        egui::Window::new("Artboard")
+            .anchor(
+                egui::Align2::RIGHT_BOTTOM,
+                egui::vec2(-GLOBAL_PADDING, -ACTIVE_LAYER_CLEARANCE),
+            )
+            .movable(false)
+            .resizable(false)
+            .default_open(false)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(TOOLS_BG_COLOR)
+                    .shadow(egui::epaint::Shadow::NONE),
+            )
+            .show(ctx, |ui| {
+                ui.label("Alt-drag an artboard to move it");
+                add_artboard_ui(ui, &send);
+                if let Some(artboard) = active {
+                    ui.separator();
+                    active_artboard_ui(ui, artboard, &send);
+                }
+            });
+    }
+}
+
+fn add_artboard_ui(ui: &mut egui::Ui, send: &impl Fn(ArtboardEvent)) {
+    let id = egui::Id::new("artboard_custom_size");
+    let mut size = ui.data(|data| data.get_temp(id).unwrap_or(DEFAULT_CUSTOM_SIZE));
+
+    ui.horizontal(|ui| {
+        ui.menu_button("New from preset", |ui| {
+            for preset in ARTBOARD_PRESETS {
+                let [width, height] = preset.size;
+                if ui
+                    .button(format!("{} {width}×{height}", preset.name))
+                    .clicked()
+                {
+                    send(ArtboardEvent::Add { size: preset.size });
+                    ui.close();
+                }
+            }
+        });
+    });
+    ui.horizontal(|ui| {
+        size_fields(ui, &mut size);
+        if ui.button("Add").clicked() {
+            send(ArtboardEvent::Add { size });
+        }
+    });
+
+    ui.data_mut(|data| data.insert_temp(id, size));
+}
+
+fn active_artboard_ui(ui: &mut egui::Ui, artboard: &Artboard, send: &impl Fn(ArtboardEvent)) {
+    let id = egui::Id::new("artboard_resize_form");
+    let from = artboard.pixel_size();
+    let mut form = ui
+        .data(|data| data.get_temp::<ResizeForm>(id))
+        .filter(|form| form.artboard_id == artboard.id && form.from == from)
+        .unwrap_or(ResizeForm {
+            artboard_id: artboard.id,
+            from,
+            size: [from.0, from.1],
+            anchor: Anchor::default(),
+        });
+
+    ui.label(egui::RichText::new(&artboard.name).strong());
+    ui.horizontal(|ui| size_fields(ui, &mut form.size));
+    egui::Grid::new("artboard_anchor").show(ui, |ui| {
+        for (index, anchor) in Anchor::ALL.into_iter().enumerate() {
+            let mark = if form.anchor == anchor { "●" } else { "○" };
+            ui.selectable_value(&mut form.anchor, anchor, mark)
+                .on_hover_text(format!("Resize around {anchor:?}"));
+            if index % 3 == 2 {
+                ui.end_row();
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Resize").clicked() {
+            send(ArtboardEvent::Resize {
+                artboard_id: artboard.id,
+                size: form.size,
+                anchor: form.anchor,
+            });
+        }
+        if ui
+            .button("Crop to content")
+            .on_hover_text("Shrink to the pixels painted on any layer")
+            .clicked()
+        {
+            send(ArtboardEvent::CropToContent(artboard.id));
+        }
+        if ui.button("Delete").clicked() {
+            send(ArtboardEvent::Delete(artboard.id));
+        }
+    });
+
+    ui.data_mut(|data| data.insert_temp(id, form));
+}
+
+fn size_fields(ui: &mut egui::Ui, size: &mut [u32; 2]) {
+    ui.add(
+        egui::DragValue::new(&mut size[0])
+            .range(1..=MAX_FIELD_SIZE)
+            .suffix(" px"),
+    );
+    ui.label("×");
+    ui.add(
+        egui::DragValue::new(&mut size[1])
+            .range(1..=MAX_FIELD_SIZE)
+            .suffix(" px"),
+    );
+}
@@ -0,0 +1,146 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::ui::{drawable::Drawable, theme::widgets::GLOBAL_PADDING},
+    resource::ResourceContext,
+    resources::brush_presets::BrushPresets,
+    state::State,
+};
+
+const NAME_FIELD_WIDTH: f32 = 120.0;
+const PATH_FIELD_WIDTH: f32 = 240.0;
+
+/// What is typed into the save and import/export menus, kept across frames.
+#[derive(Clone)]
+struct PresetsForm {
+    name: String,
+    path: String,
+}
+
+/// Toolbar of the brush presets: a click paints with one,
+/// right-click updates or deletes it, and the menus save, import and export them.
+pub struct BrushPresetsWidget;
+
+impl BrushPresetsWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for BrushPresetsWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(state), Some(presets), Some(event_sender)) = (
+            app.read::<State>(),
+            app.read::<BrushPresets>(),
+            app.read::<EventSender>(),
+        ) else {
+            return;
+        };
+
+        let (brush, settings) = (state.editor.brush_properties, state.editor.brush_settings);
+        egui::Window::new("Brush presets")
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, GLOBAL_PADDING))
+            .movable(false)
+            .resizable(false)
+            .title_bar(false)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(TOOLS_BG_COLOR)
+                    .shadow(egui::epaint::Shadow::NONE),
+            )
+            .show(ctx, |ui| {
+                let id = egui::Id::new("brush_presets_form");
+                let mut form = ui.data(|data| data.get_temp(id)).unwrap_or(PresetsForm {
+                    name: String::new(),
+                    path: default_exchange_path(),
+                });
+
+                ui.horizontal(|ui| {
+                    for preset in presets.presets() {
+                        let selected = preset.matches(&brush, &settings);
+                        let response = ui.add(egui::Button::selectable(selected, &preset.name));
+                        if response.clicked() {
+                            // two events rather than one, so recorded sessions replay presets as they do the sliders
+                            event_sender.send(ControllerEvent::UpdateBrush(preset.brush));
+                            event_sender
+                                .send(ControllerEvent::UpdateBrushSettings(preset.settings));
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Update to the current brush").clicked() {
+                                event_sender
+                                    .send(ControllerEvent::SaveBrushPreset(preset.name.clone()));
+                                ui.close();
+                            }
+                            if ui.button("Delete").clicked() {
+                                event_sender
+                                    .send(ControllerEvent::DeleteBrushPreset(preset.name.clone()));
+                                ui.close();
+                            }
+                        });
+                    }
+
+                    ui.separator();
+                    ui.menu_button("+", |ui| {
+                        ui.label("Save the current brush as");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut form.name)
+                                    .desired_width(NAME_FIELD_WIDTH)
+                                    .hint_text("Name"),
+                            );
+                            let name = form.name.trim();
+                            if ui
+                                .add_enabled(!name.is_empty(), egui::Button::new("Save"))
+                                .clicked()
+                            {
+                                event_sender.send(ControllerEvent::SaveBrushPreset(name.into()));
+                                form.name.clear();
+                                ui.close();
+                            }
+                        });
+                    });
+                    ui.menu_button("…", |ui| {
+                        ui.label("Presets file");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut form.path)
+                                .desired_width(PATH_FIELD_WIDTH),
+                        );
+                        ui.horizontal(|ui| {
+                            let path = std::path::PathBuf::from(form.path.trim());
+                            if ui
+                                .button("Import")
+                                .on_hover_text("Add its presets, replacing those of the same names")
+                                .clicked()
+                            {
+                                event_sender
+                                    .send(ControllerEvent::ImportBrushPresets(path.clone()));
+                                ui.close();
+                            }
+                            if ui.button("Export").clicked() {
+                                event_sender.send(ControllerEvent::ExportBrushPresets(path));
+                                ui.close();
+                            }
+                        });
+                    });
+                });
+
+                ui.data_mut(|data| data.insert_temp(id, form));
+            });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_exchange_path() -> String {
+    crate::document::loader::asset_dir()
+        .join("exports")
+        .join("brush-presets.json")
+        .display()
+        .to_string()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_exchange_path() -> String {
+    "brush-presets.json".to_string()
+}
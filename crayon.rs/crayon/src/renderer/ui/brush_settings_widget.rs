@@ -0,0 +1,130 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    document::BrushTip,
+    editor_state::{BrushProperties, TipRotation},
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::ui::{
+        drawable::Drawable,
+        theme::widgets::{GLOBAL_PADDING, StyledSlider},
+    },
+    resource::ResourceContext,
+    state::State,
+};
+
+/// Keeps the window above the clear and export buttons.
+const BOTTOM_BUTTONS_CLEARANCE: f32 = 64.0;
+
+const SLIDER_LENGTH: f32 = 140.0;
+const MIN_SPACING: f32 = 1.0;
+const MAX_SPACING: f32 = 200.0;
+/// Flow and opacity stop short of zero, a stroke always leaves a mark.
+const MIN_BUILD_UP: f32 = 0.01;
+
+const TIP_ROTATIONS: [(TipRotation, &str); 3] = [
+    (TipRotation::Fixed, "Fixed"),
+    (TipRotation::FollowStroke, "Follow stroke"),
+    (TipRotation::Random, "Random"),
+];
+
+/// Picks the tip the brush stamps and how every dab turns it,
+/// and tunes how dabs are spaced and build up.
+pub struct BrushSettingsWidget;
+
+impl BrushSettingsWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for BrushSettingsWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(state), Some(event_sender)) = (app.read::<State>(), app.read::<EventSender>())
+        else {
+            return;
+        };
+
+        let (brush, settings) = (state.editor.brush_properties, state.editor.brush_settings);
+        egui::Window::new("Brush")
+            .anchor(
+                egui::Align2::LEFT_BOTTOM,
+                egui::vec2(GLOBAL_PADDING, -BOTTOM_BUTTONS_CLEARANCE),
+            )
+            .movable(false)
+            .resizable(false)
+            .default_open(false)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(TOOLS_BG_COLOR)
+                    .shadow(egui::epaint::Shadow::NONE),
+            )
+            .show(ctx, |ui| {
+                let (mut tip, mut tip_rotation) = (brush.tip, brush.tip_rotation);
+                ui.label("Tip");
+                ui.horizontal(|ui| {
+                    for option in BrushTip::ALL {
+                        ui.selectable_value(&mut tip, option, format!("{option:?}"));
+                    }
+                });
+                ui.label("Rotation");
+                ui.add_enabled_ui(tip != BrushTip::Round, |ui| {
+                    ui.horizontal(|ui| {
+                        for (option, name) in TIP_ROTATIONS {
+                            ui.selectable_value(&mut tip_rotation, option, name);
+                        }
+                    });
+                });
+
+                if (tip, tip_rotation) != (brush.tip, brush.tip_rotation) {
+                    event_sender.send(ControllerEvent::UpdateBrush(BrushProperties {
+                        tip,
+                        tip_rotation,
+                        ..brush
+                    }));
+                }
+
+                ui.separator();
+                let mut edited = settings;
+                egui::Grid::new("brush_settings").show(ui, |ui| {
+                    slider_row(ui, "Hardness", &mut edited.hardness, 0.0..=1.0, percent);
+                    slider_row(
+                        ui,
+                        "Spacing",
+                        &mut edited.spacing,
+                        MIN_SPACING..=MAX_SPACING,
+                        |spacing| format!("{spacing:.0}%"),
+                    );
+                    slider_row(ui, "Flow", &mut edited.flow, MIN_BUILD_UP..=1.0, percent);
+                    slider_row(
+                        ui,
+                        "Opacity",
+                        &mut edited.opacity,
+                        MIN_BUILD_UP..=1.0,
+                        percent,
+                    );
+                });
+                if edited != settings {
+                    event_sender.send(ControllerEvent::UpdateBrushSettings(edited));
+                }
+            });
+    }
+}
+
+/// A labelled slider and its value, as a row of the settings grid.
+fn slider_row(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut f32,
+    range: std::ops::RangeInclusive<f32>,
+    format: impl Fn(f32) -> String,
+) {
+    ui.label(label);
+    ui.add(StyledSlider::new(value, range).length(SLIDER_LENGTH));
+    ui.label(format(*value));
+    ui.end_row();
+}
+
+fn percent(fraction: f32) -> String {
+    format!("{:.0}%", fraction * 100.0)
+}
@@ -3,10 +3,14 @@ use cgmath::Point2;
 use crate::{
     app::App,
     constants::TOOLS_BG_COLOR,
+    document::StrokeMode,
     editor_state::BrushProperties,
     event_sender::EventSender,
     events::ControllerEvent,
-    renderer::ui::{drawable::Drawable, theme::widgets::StyledSlider},
+    renderer::ui::{
+        drawable::Drawable,
+        theme::widgets::{IconToggle, StyledSlider},
+    },
     resource::ResourceContext,
     resources::brush_preview_state::BrushPreviewState,
     state::State,
@@ -67,6 +71,23 @@ impl Drawable for BrushSizeWidget {
                         ..state.editor.brush_properties
                     }));
                 }
+
+                let erasing = state.editor.brush_properties.mode == StrokeMode::Erase;
+                let eraser = egui::include_image!("../../../assets/icons/eraser.svg");
+                if ui
+                    .add(IconToggle::new(eraser, erasing))
+                    .on_hover_text(if erasing { "Paint" } else { "Erase" })
+                    .clicked()
+                {
+                    event_sender.send(ControllerEvent::UpdateBrush(BrushProperties {
+                        mode: if erasing {
+                            StrokeMode::Paint
+                        } else {
+                            StrokeMode::Erase
+                        },
+                        ..state.editor.brush_properties
+                    }));
+                }
             });
     }
 }
@@ -0,0 +1,79 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    document::export::ExportOptions,
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::ui::{
+        drawable::Drawable,
+        theme::widgets::{GLOBAL_PADDING, IconButton},
+    },
+    resource::ResourceContext,
+};
+
+const MAX_EXPORT_SCALE: u32 = 8;
+
+/// Export button, whose popup picks the scale and background before exporting the artboard in view.
+pub struct ExportWidget;
+
+impl ExportWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for ExportWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let Some(event_sender) = app.read::<EventSender>() else {
+            return;
+        };
+
+        let height = ctx.content_rect().height();
+
+        // right of the clear button
+        egui::Window::new("Export")
+            .fixed_pos(egui::pos2(GLOBAL_PADDING + 74.0, height - 56.0))
+            .movable(false)
+            .resizable(false)
+            .title_bar(false)
+            .frame(egui::Frame::NONE)
+            .show(ctx, |ui| {
+                let export_icon = egui::include_image!("../../../assets/icons/download.svg");
+                let response = ui
+                    .add(IconButton::new(export_icon))
+                    .on_hover_text("Export artboard as PNG");
+
+                egui::Popup::from_toggle_button_response(&response)
+                    .frame(egui::Frame::window(ui.style()).fill(TOOLS_BG_COLOR))
+                    .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside)
+                    .show(|ui| {
+                        if let Some(options) = export_options_ui(ui) {
+                            event_sender.send(ControllerEvent::ExportArtboard(options));
+                            egui::Popup::close_all(ui.ctx());
+                        }
+                    });
+            });
+    }
+}
+
+/// The options picked so far, kept across frames, returned once "Export" is clicked.
+fn export_options_ui(ui: &mut egui::Ui) -> Option<ExportOptions> {
+    let id = egui::Id::new("export_options");
+    let mut options: ExportOptions = ui.data(|data| data.get_temp(id).unwrap_or_default());
+
+    ui.horizontal(|ui| {
+        ui.label("Scale");
+        ui.add(
+            egui::DragValue::new(&mut options.scale)
+                .range(1..=MAX_EXPORT_SCALE)
+                .suffix("×"),
+        );
+    });
+    let mut transparent = !options.background;
+    ui.checkbox(&mut transparent, "Transparent background");
+    options.background = !transparent;
+    let export = ui.button("Export PNG").clicked();
+
+    ui.data_mut(|data| data.insert_temp(id, options));
+    export.then_some(options)
+}
@@ -1,4 +1,4 @@
-use batteries::prelude::Dot2D;
+use batteries::prelude::{Dot2D, FULL_PRESSURE};
 use cgmath::Point2;
 
 use crate::{
@@ -42,6 +42,9 @@ impl HelloResource {
     }
 }
 
+/// Writes "hello" by sending `HELLO_POINTS` as one stroke, a few points a frame.
+/// The points are played from the frame loop rather than as a recorded session through `session::replay`,
+/// as the web has neither the replay thread nor the session files.
 pub struct HelloWidget;
 
 impl HelloWidget {
@@ -87,6 +90,7 @@ impl Drawable for HelloWidget {
                         // points are in screen-space
                         position: point,
                         radius: DEFAULT_BRUSH_SIZE,
+                        pressure: FULL_PRESSURE,
                     },
                 });
                 emitted = true;
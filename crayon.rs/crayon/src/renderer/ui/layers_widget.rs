@@ -0,0 +1,336 @@
+use crate::{
+    app::App,
+    constants::TOOLS_BG_COLOR,
+    document::{Artboard, Document, LayerId, LayerNode, NodeId},
+    event_sender::EventSender,
+    events::ControllerEvent,
+    renderer::ui::{
+        drawable::Drawable,
+        theme::{
+            DEFAULT_THEME,
+            widgets::{GLOBAL_PADDING, IconToggle},
+        },
+    },
+    resource::ResourceContext,
+    resources::{document_state::DocumentState, layer_thumbnails::LayerThumbnails},
+    state::State,
+};
+
+const THUMBNAIL_SIDE: f32 = 28.0;
+const INDENT: f32 = 14.0;
+const NAME_WIDTH: f32 = 140.0;
+const MAX_LIST_HEIGHT: f32 = 360.0;
+const RENAMING_ID: &str = "layers_renaming";
+
+/// The node being renamed and the name typed so far, between frames.
+#[derive(Clone)]
+struct Renaming {
+    node: NodeId,
+    name: String,
+    /// The field grabs the keyboard once, losing it afterwards commits the name.
+    focused: bool,
+}
+
+/// Lists the artboards and their layer stacks to select, show, lock, reorder and edit layers.
+pub struct LayersWidget;
+
+impl LayersWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for LayersWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(state), Some(doc), Some(event_sender)) = (
+            app.read::<State>(),
+            app.read::<DocumentState>(),
+            app.read::<EventSender>(),
+        ) else {
+            return;
+        };
+        let thumbnails = app.read::<LayerThumbnails>();
+
+        let panel = LayersPanel {
+            document: &doc.document,
+            active: state
+                .editor
+                .active_layer(&doc.document)
+                .map(|(_, layer_id)| layer_id),
+            thumbnails: thumbnails.as_deref(),
+            event_sender: &event_sender,
+        };
+
+        // below the FPS counter
+        egui::Window::new("Layers")
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-GLOBAL_PADDING, 56.0))
+            .movable(false)
+            .resizable(false)
+            .frame(
+                egui::Frame::window(&ctx.style())
+                    .fill(TOOLS_BG_COLOR)
+                    .shadow(egui::epaint::Shadow::NONE),
+            )
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(MAX_LIST_HEIGHT)
+                    .show(ui, |ui| {
+                        for artboard in &panel.document.artboards {
+                            panel.artboard_ui(ui, artboard);
+                        }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("New layer").clicked() {
+                        panel.send(ControllerEvent::AddLayer);
+                    }
+                    if ui
+                        .button("New vector layer")
+                        .on_hover_text("Keeps its strokes, redrawn sharp at any zoom")
+                        .clicked()
+                    {
+                        panel.send(ControllerEvent::AddVectorLayer);
+                    }
+                });
+            });
+    }
+}
+
+struct LayersPanel<'a> {
+    document: &'a Document,
+    active: Option<LayerId>,
+    thumbnails: Option<&'a LayerThumbnails>,
+    event_sender: &'a EventSender,
+}
+
+impl LayersPanel<'_> {
+    fn send(&self, event: ControllerEvent) {
+        self.event_sender.send(event);
+    }
+
+    fn artboard_ui(&self, ui: &mut egui::Ui, artboard: &Artboard) {
+        let header = ui.label(egui::RichText::new(&artboard.name).strong());
+        // dropped on the header, the node goes on top of the artboard's stack
+        if let Some(dragged) = header.dnd_release_payload::<NodeId>() {
+            self.send(ControllerEvent::MoveNode {
+                node: *dragged,
+                artboard_id: artboard.id,
+                parent: None,
+                index: usize::MAX,
+            });
+        }
+        if header.dnd_hover_payload::<NodeId>().is_some() {
+            drop_marker(ui, header.rect.bottom(), header.rect.x_range());
+        }
+        self.nodes_ui(ui, &artboard.layers, 0);
+    }
+
+    /// Top of the stack first, as it's drawn, groups followed by their children.
+    fn nodes_ui(&self, ui: &mut egui::Ui, nodes: &[LayerNode], depth: u8) {
+        for node in nodes.iter().rev() {
+            self.node_row(ui, node, depth);
+            if let LayerNode::Group(group) = node {
+                self.nodes_ui(ui, &group.children, depth + 1);
+            }
+        }
+    }
+
+    fn node_row(&self, ui: &mut egui::Ui, node: &LayerNode, depth: u8) {
+        let id = node.id();
+        let (name, visible, locked) = match node {
+            LayerNode::Layer(layer) => (&layer.name, layer.visible, layer.locked),
+            LayerNode::Group(group) => (&group.name, group.visible, group.locked),
+        };
+
+        let row_ui = |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                let eye = egui::include_image!("../../../assets/icons/eye.svg");
+                if ui
+                    .add(IconToggle::new(eye, visible))
+                    .on_hover_text(if visible { "Hide" } else { "Show" })
+                    .clicked()
+                {
+                    self.send(ControllerEvent::SetNodeVisible {
+                        node: id,
+                        visible: !visible,
+                    });
+                }
+                let lock = egui::include_image!("../../../assets/icons/lock.svg");
+                if ui
+                    .add(IconToggle::new(lock, locked))
+                    .on_hover_text(if locked { "Unlock" } else { "Lock" })
+                    .clicked()
+                {
+                    self.send(ControllerEvent::SetNodeLocked {
+                        node: id,
+                        locked: !locked,
+                    });
+                }
+                ui.add_space(f32::from(depth) * INDENT);
+                self.thumbnail_ui(ui, node);
+                self.name_ui(ui, node, name, (visible, locked));
+            })
+        };
+        // locked nodes stay where they are, though others can still be dropped around them
+        let row = if self.document.is_node_locked(id) {
+            row_ui(ui).response
+        } else {
+            ui.dnd_drag_source(egui::Id::new(("layer_row", id)), id, row_ui)
+                .response
+        };
+
+        // dropped on a row, the node goes right above it
+        if let Some(dragged) = row.dnd_release_payload::<NodeId>()
+            && let Some((artboard_id, parent, index)) = self.document.slot_above(*dragged, id)
+        {
+            self.send(ControllerEvent::MoveNode {
+                node: *dragged,
+                artboard_id,
+                parent,
+                index,
+            });
+        }
+        if row
+            .dnd_hover_payload::<NodeId>()
+            .is_some_and(|dragged| *dragged != id)
+        {
+            drop_marker(ui, row.rect.top(), row.rect.x_range());
+        }
+    }
+
+    fn thumbnail_ui(&self, ui: &mut egui::Ui, node: &LayerNode) {
+        let (rect, _) =
+            ui.allocate_exact_size(egui::Vec2::splat(THUMBNAIL_SIDE), egui::Sense::hover());
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+        ui.painter()
+            .rect_filled(rect, 2.0, DEFAULT_THEME.surface_variant);
+
+        let LayerNode::Layer(layer) = node else {
+            return;
+        };
+        let Some(texture) = self
+            .thumbnails
+            .and_then(|thumbnails| thumbnails.thumbnail(layer.id))
+        else {
+            return;
+        };
+        // fitted, thumbhash previews are not square
+        let scale = (THUMBNAIL_SIDE / texture.size.x).min(THUMBNAIL_SIDE / texture.size.y);
+        let image_rect = egui::Rect::from_center_size(rect.center(), texture.size * scale);
+        egui::Image::from_texture(texture).paint_at(ui, image_rect);
+    }
+
+    fn name_ui(&self, ui: &mut egui::Ui, node: &LayerNode, name: &str, flags: (bool, bool)) {
+        let id = node.id();
+        let renaming_id = egui::Id::new(RENAMING_ID);
+        let renaming = ui
+            .data(|data| data.get_temp::<Renaming>(renaming_id))
+            .filter(|renaming| renaming.node == id);
+
+        if let Some(mut renaming) = renaming {
+            let edit =
+                ui.add(egui::TextEdit::singleline(&mut renaming.name).desired_width(NAME_WIDTH));
+            if !renaming.focused {
+                edit.request_focus();
+                renaming.focused = true;
+            } else if edit.lost_focus() {
+                let is_cancelled = ui.input(|input| input.key_pressed(egui::Key::Escape));
+                let new_name = renaming.name.trim();
+                if !is_cancelled && !new_name.is_empty() && new_name != name {
+                    self.send(ControllerEvent::RenameNode {
+                        node: id,
+                        name: new_name.to_owned(),
+                    });
+                }
+                ui.data_mut(|data| data.remove::<Renaming>(renaming_id));
+                return;
+            }
+            ui.data_mut(|data| data.insert_temp(renaming_id, renaming));
+            return;
+        }
+
+        let text = match node {
+            LayerNode::Layer(_) => egui::RichText::new(name),
+            LayerNode::Group(_) => egui::RichText::new(name).strong(),
+        };
+        let is_active = matches!(id, NodeId::Layer(layer_id) if Some(layer_id) == self.active);
+        let label = ui.add_sized(
+            [NAME_WIDTH, THUMBNAIL_SIDE],
+            egui::Button::selectable(is_active, text),
+        );
+        if label.clicked()
+            && let NodeId::Layer(layer_id) = id
+        {
+            self.send(ControllerEvent::SelectLayer(layer_id));
+        }
+        if label.double_clicked() && !self.document.is_node_locked(id) {
+            start_renaming(ui, renaming_id, id, name);
+        }
+        label.context_menu(|ui| self.context_menu(ui, id, name, flags));
+    }
+
+    fn context_menu(
+        &self,
+        ui: &mut egui::Ui,
+        node: NodeId,
+        name: &str,
+        (visible, locked): (bool, bool),
+    ) {
+        // renaming, moving and deleting are refused for locked nodes, see `DocumentState::apply_unless_locked`
+        let is_held = self.document.is_node_locked(node);
+        if ui
+            .add_enabled(!is_held, egui::Button::new("Rename"))
+            .clicked()
+        {
+            start_renaming(ui, egui::Id::new(RENAMING_ID), node, name);
+            ui.close();
+        }
+        if ui.button("Duplicate").clicked() {
+            self.send(ControllerEvent::DuplicateNode(Some(node)));
+            ui.close();
+        }
+        if ui.button(if locked { "Unlock" } else { "Lock" }).clicked() {
+            self.send(ControllerEvent::SetNodeLocked {
+                node,
+                locked: !locked,
+            });
+            ui.close();
+        }
+        if ui.button(if visible { "Hide" } else { "Show" }).clicked() {
+            self.send(ControllerEvent::SetNodeVisible {
+                node,
+                visible: !visible,
+            });
+            ui.close();
+        }
+        ui.separator();
+        if ui
+            .add_enabled(!is_held, egui::Button::new("Delete"))
+            .clicked()
+        {
+            self.send(ControllerEvent::DeleteNode(Some(node)));
+            ui.close();
+        }
+    }
+}
+
+fn start_renaming(ui: &egui::Ui, renaming_id: egui::Id, node: NodeId, name: &str) {
+    ui.data_mut(|data| {
+        data.insert_temp(
+            renaming_id,
+            Renaming {
+                node,
+                name: name.to_owned(),
+                focused: false,
+            },
+        );
+    });
+}
+
+/// The line showing where a dragged node lands.
+fn drop_marker(ui: &egui::Ui, y: f32, x: egui::Rangef) {
+    ui.painter()
+        .hline(x, y, egui::Stroke::new(2.0, DEFAULT_THEME.primary));
+}
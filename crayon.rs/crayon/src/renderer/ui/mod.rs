@@ -1,9 +1,16 @@
+pub mod active_layer_widget;
+pub mod artboard_widget;
+pub mod brush_presets_widget;
 pub mod brush_preview_widget;
+pub mod brush_settings_widget;
 pub mod brush_size_widget;
 pub mod clear_screen_widget;
 pub mod color_picker_widget;
 pub mod drawable;
+pub mod export_widget;
 pub mod fps_widget;
+pub mod layers_widget;
+pub mod recovery_widget;
 pub mod theme;
 
 mod hello_points;
@@ -0,0 +1,42 @@
+use crate::{
+    app::App, event_sender::EventSender, events::ControllerEvent, renderer::ui::drawable::Drawable,
+    resource::ResourceContext, resources::launch_options::InterruptedSession,
+};
+
+/// Asks whether to restore or discard the interrupted session the launch found, see `InterruptedSession`.
+pub struct RecoveryWidget;
+
+impl RecoveryWidget {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Drawable for RecoveryWidget {
+    fn draw(&self, ctx: &egui::Context, app: &App) {
+        let (Some(session), Some(event_sender)) =
+            (app.read::<InterruptedSession>(), app.read::<EventSender>())
+        else {
+            return;
+        };
+        let Some(document_name) = &session.0 else {
+            return;
+        };
+
+        egui::Modal::new(egui::Id::new("recovery_prompt")).show(ctx, |ui| {
+            ui.heading("Restore the interrupted session?");
+            ui.label(format!(
+                "'{document_name}' was closed before its latest changes were saved."
+            ));
+            ui.label("Restoring reopens them, discarding deletes them for good.");
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    event_sender.send(ControllerEvent::RestoreInterruptedSession);
+                }
+                if ui.button("Discard").clicked() {
+                    event_sender.send(ControllerEvent::DiscardInterruptedSession);
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,49 @@
+use egui::{Image, ImageSource, Response, Sense, Ui, Vec2, Widget};
+
+use crate::renderer::ui::theme::DEFAULT_THEME;
+
+const SIZE: f32 = 20.0;
+const ICON_SIZE: f32 = 14.0;
+
+/// A small square icon button showing an on/off state, bright when on and faded when off
+pub struct IconToggle<'a> {
+    icon: ImageSource<'a>,
+    on: bool,
+}
+
+impl<'a> IconToggle<'a> {
+    pub fn new(icon: impl Into<ImageSource<'a>>, on: bool) -> Self {
+        Self {
+            icon: icon.into(),
+            on,
+        }
+    }
+}
+
+impl Widget for IconToggle<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(Vec2::splat(SIZE), Sense::click());
+
+        if ui.is_rect_visible(rect) {
+            let theme = &DEFAULT_THEME;
+
+            if response.hovered() {
+                ui.painter()
+                    .rect_filled(rect, rect.height() / 4.0, theme.primary_container);
+            }
+
+            let tint = match (self.on, response.hovered()) {
+                (_, true) => theme.on_primary_container,
+                (true, false) => theme.primary,
+                (false, false) => theme.outline_variant,
+            };
+            let icon_rect = egui::Rect::from_center_size(rect.center(), Vec2::splat(ICON_SIZE));
+            Image::new(self.icon)
+                .tint(tint)
+                .fit_to_exact_size(icon_rect.size())
+                .paint_at(ui, icon_rect);
+        }
+
+        response
+    }
+}
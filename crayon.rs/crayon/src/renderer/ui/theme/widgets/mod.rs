@@ -1,10 +1,12 @@
 mod color_picker;
 mod constants;
 mod icon_button;
+mod icon_toggle;
 mod pill_button;
 mod slider;
 
 pub use color_picker::CircularColorPicker;
 pub use constants::*;
 pub use icon_button::IconButton;
+pub use icon_toggle::IconToggle;
 pub use slider::StyledSlider;
@@ -39,7 +39,6 @@ impl<'a> StyledSlider<'a> {
         self
     }
 
-    #[allow(dead_code)]
     pub fn length(mut self, length: f32) -> Self {
         self.length = length;
         self
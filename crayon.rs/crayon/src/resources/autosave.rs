@@ -0,0 +1,287 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    document::{
+        Document, LayerId,
+        recovery::{Journal, discard_journal},
+    },
+    resource::Resource,
+    resources::scene_renderer::SceneRenderer,
+};
+
+/// Periodic crash-recovery snapshots of the open document into its `Journal`.
+/// Layers are copied out on the frame loop, without waiting for the GPU.
+/// A background thread reads the copies back once they are mapped, then encodes and writes them.
+pub struct Autosave {
+    pub journal: Journal,
+    interval: Duration,
+    last_snapshot: Instant,
+    /// The snapshot being written, at most one at a time.
+    pending: Option<PendingSnapshot>,
+}
+
+/// A snapshot on its writer thread, with the device whose polls map its copies.
+struct PendingSnapshot {
+    writer: JoinHandle<anyhow::Result<Vec<LayerId>>>,
+    device: wgpu::Device,
+}
+
+impl Autosave {
+    pub fn new(journal: Journal, interval: Duration) -> Self {
+        Self {
+            journal,
+            interval,
+            last_snapshot: Instant::now(),
+            pending: None,
+        }
+    }
+
+    /// Snapshots `document` if the interval passed and the previous snapshot is written.
+    /// The copies are mapped as the frame loop's submits poll the device, this never waits on the GPU.
+    /// `dirty` is drained only when a snapshot is taken.
+    pub fn tick(
+        &mut self,
+        now: Instant,
+        document: &Document,
+        dirty: &mut HashSet<LayerId>,
+        scene: &SceneRenderer,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        if self.is_writing() || now.duration_since(self.last_snapshot) < self.interval {
+            return;
+        }
+        self.last_snapshot = now;
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Autosave Encoder"),
+        });
+        let Some(mut snapshot) = self
+            .journal
+            .capture(document, dirty, scene, device, &mut encoder)
+        else {
+            return;
+        };
+        dirty.clear();
+        queue.submit([encoder.finish()]);
+        snapshot.map();
+        self.pending = Some(PendingSnapshot {
+            writer: std::thread::spawn(move || snapshot.write()),
+            device: device.clone(),
+        });
+    }
+
+    /// Collects a finished write, true while one is still running.
+    fn is_writing(&mut self) -> bool {
+        match self.pending.take() {
+            Some(pending) if !pending.writer.is_finished() => {
+                self.pending = Some(pending);
+                true
+            }
+            Some(pending) => {
+                self.collect(pending);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Blocks until the snapshot being written, if any, is on disk.
+    /// Polls the device for its copies, in case no frame is submitted meanwhile.
+    pub fn finish(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        if let Err(error) = pending.device.poll(wgpu::PollType::wait_indefinitely()) {
+            log::error!("device poll failed while finishing the autosave: {error}");
+        }
+        self.collect(pending);
+    }
+
+    /// Takes in the outcome of a write.
+    /// A failed write resets the journal so the next snapshot is complete again.
+    fn collect(&mut self, pending: PendingSnapshot) {
+        let result = pending
+            .writer
+            .join()
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("autosave writer panicked")));
+        match result {
+            Ok(blank) => self.journal.written_blank(&blank),
+            Err(error) => {
+                log::error!(
+                    "autosave to {} failed: {error:#}",
+                    self.journal.dir().display()
+                );
+                self.journal.reset();
+            }
+        }
+    }
+
+    /// Carries on journaling in `dir`, moving the journal written so far over whatever `dir` held.
+    pub fn move_to(&mut self, dir: PathBuf) {
+        self.finish();
+        if let Err(error) = discard_journal(&dir) {
+            log::error!("failed to discard the recovery journal: {error:#}");
+        }
+        if self.journal.dir().exists()
+            && let Err(error) = std::fs::rename(self.journal.dir(), &dir)
+        {
+            log::error!(
+                "failed to move the recovery journal to {}: {error}",
+                dir.display()
+            );
+        }
+        self.journal = Journal::new(dir);
+    }
+
+    /// Carries on journaling over the journal in `dir`, deleting the one written so far.
+    /// The next snapshot is complete, so `dir` holds the previous journal until then.
+    pub fn take_over(&mut self, dir: PathBuf) {
+        self.finish();
+        if let Err(error) = discard_journal(self.journal.dir()) {
+            log::error!("failed to discard the recovery journal: {error:#}");
+        }
+        self.journal = Journal::new(dir);
+    }
+
+    /// Deletes the journal once the session is saved or closed cleanly.
+    /// Autosaving carries on, starting from a complete snapshot.
+    pub fn discard(&mut self) {
+        self.finish();
+        if let Err(error) = discard_journal(self.journal.dir()) {
+            log::error!("failed to discard the recovery journal: {error:#}");
+        }
+        self.journal.reset();
+    }
+}
+
+impl Resource for Autosave {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::recovery::{has_journal, load_journal};
+    use crate::testing::fixtures::{hydrated_scene, scratch_dir};
+
+    #[test]
+    fn snapshots_once_the_interval_passes() {
+        let (device, queue, scene, document) = hydrated_scene([0, 0, 255, 255]);
+        let dir = scratch_dir("autosave-interval");
+        let mut autosave = Autosave::new(Journal::new(dir.clone()), Duration::from_secs(30));
+        let start = autosave.last_snapshot;
+        let mut dirty = HashSet::from([LayerId(2)]);
+
+        autosave.tick(start, &document, &mut dirty, &scene, &device, &queue);
+        assert!(!dirty.is_empty(), "not due yet");
+
+        let later = start + Duration::from_secs(31);
+        autosave.tick(later, &document, &mut dirty, &scene, &device, &queue);
+        assert!(dirty.is_empty());
+        autosave.finish();
+        assert!(has_journal(&dir));
+
+        autosave.discard();
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn layer_cleared_since_the_last_snapshot_stays_blank() {
+        let (device, queue, mut scene, mut document) = hydrated_scene([0, 0, 255, 255]);
+        let dir = scratch_dir("autosave-cleared");
+        let mut autosave = Autosave::new(Journal::new(dir.clone()), Duration::ZERO);
+        let mut dirty = HashSet::new();
+        let mut snapshot =
+            |document: &Document, dirty: &mut HashSet<LayerId>, scene: &SceneRenderer| {
+                autosave.tick(Instant::now(), document, dirty, scene, &device, &queue);
+                autosave.finish();
+                load_journal(&dir, 2048).unwrap().unwrap()
+            };
+        assert!(
+            snapshot(&document, &mut dirty, &scene)
+                .layer_pixels
+                .contains_key(&LayerId(2))
+        );
+
+        scene.clear_layer(&device, &queue, LayerId(2));
+        dirty.insert(LayerId(2));
+        snapshot(&document, &mut dirty, &scene);
+        // a later snapshot leaving the layer untouched must not bring back its old PNG
+        document.artboards[0].name = "Renamed".to_string();
+        let recovered = snapshot(&document, &mut dirty, &scene);
+        assert_eq!(recovered.document.artboards[0].name, "Renamed");
+        assert!(!recovered.layer_pixels.contains_key(&LayerId(2)));
+    }
+
+    #[test]
+    fn failed_write_makes_the_next_snapshot_complete() {
+        let (device, queue, scene, document) = hydrated_scene([0, 0, 255, 255]);
+        // a file where the journal dir should be
+        let dir = scratch_dir("autosave-failed").join("journal");
+        std::fs::write(&dir, "").unwrap();
+        let mut autosave = Autosave::new(Journal::new(dir), Duration::ZERO);
+        let mut dirty = HashSet::new();
+
+        autosave.tick(
+            Instant::now(),
+            &document,
+            &mut dirty,
+            &scene,
+            &device,
+            &queue,
+        );
+        autosave.finish();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let snapshot = autosave
+            .journal
+            .capture(&document, &dirty, &scene, &device, &mut encoder)
+            .expect("the failed snapshot is not remembered");
+        assert_eq!(snapshot.layer_count(), 2, "every layer is read back again");
+    }
+
+    #[test]
+    fn journals_move_between_dirs() {
+        let (device, queue, scene, mut document) = hydrated_scene([0, 0, 255, 255]);
+        let root = scratch_dir("autosave-move");
+        let (interrupted, next) = (root.join("doc"), root.join("doc.next"));
+        let mut dirty = HashSet::new();
+        let mut journal_to = |dir: &PathBuf, name: &str, autosave: &mut Autosave| {
+            document.artboards[0].name = name.to_string();
+            autosave.tick(
+                Instant::now(),
+                &document,
+                &mut dirty,
+                &scene,
+                &device,
+                &queue,
+            );
+            autosave.finish();
+            assert_eq!(autosave.journal.dir(), dir);
+        };
+        let mut earlier = Autosave::new(Journal::new(interrupted.clone()), Duration::ZERO);
+        journal_to(&interrupted, "Interrupted", &mut earlier);
+
+        // discarding the interrupted session moves the one journaled meanwhile in its place
+        let mut autosave = Autosave::new(Journal::new(next.clone()), Duration::ZERO);
+        journal_to(&next, "Meanwhile", &mut autosave);
+        autosave.move_to(interrupted.clone());
+        assert!(!next.exists());
+        let moved = load_journal(&interrupted, 2048).unwrap().unwrap();
+        assert_eq!(moved.document.artboards[0].name, "Meanwhile");
+
+        // restoring it drops the one journaled meanwhile and carries on over it, in full
+        let mut autosave = Autosave::new(Journal::new(next.clone()), Duration::ZERO);
+        journal_to(&next, "Meanwhile", &mut autosave);
+        autosave.take_over(interrupted.clone());
+        assert!(!next.exists());
+        journal_to(&interrupted, "Restored", &mut autosave);
+        let restored = load_journal(&interrupted, 2048).unwrap().unwrap();
+        assert_eq!(restored.document.artboards[0].name, "Restored");
+        assert!(restored.layer_pixels.contains_key(&LayerId(2)));
+    }
+}
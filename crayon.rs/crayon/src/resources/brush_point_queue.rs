@@ -8,6 +8,8 @@ const BRUSH_POINT_QUEUE_SIZE: usize = 500;
 #[derive(Clone, Copy)]
 pub struct BrushPointData {
     pub dot: Dot2D,
+    /// Straight-alpha RGBA the dab is stamped with.
+    pub color: [f32; 4],
     pub camera: Camera2D,
     pub target: Option<StrokeTarget>,
 }
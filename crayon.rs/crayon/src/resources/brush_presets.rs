@@ -0,0 +1,417 @@
+//! Named brushes, each the full `BrushProperties` and `BrushSettings` to paint with.
+//!
+//! Presets live in a JSON file in the user's config dir, see `presets_path`, so they outlast the session.
+//! The same format is exported and imported to share presets between machines.
+//! Without a presets file the built-in ones are offered.
+
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document::{BrushTip, StrokeMode},
+    editor_state::{BrushColor, BrushProperties, BrushSettings, PressureCurve, TipRotation},
+    resource::Resource,
+};
+
+pub const PRESETS_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct BrushPreset {
+    pub name: String,
+    pub brush: BrushProperties,
+    pub settings: BrushSettings,
+}
+
+impl BrushPreset {
+    /// Whether painting with `brush` and `settings` is painting with this preset.
+    pub fn matches(&self, brush: &BrushProperties, settings: &BrushSettings) -> bool {
+        self.brush == *brush && self.settings == *settings
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PresetsFile {
+    version: u32,
+    presets: Vec<BrushPreset>,
+}
+
+/// The presets offered, in the order they are shown.
+pub struct BrushPresets {
+    presets: Vec<BrushPreset>,
+    /// Where edits are written back to, `None` keeps them in memory.
+    path: Option<std::path::PathBuf>,
+}
+
+impl BrushPresets {
+    /// The built-in presets, kept in memory, as the web has no presets file.
+    #[cfg(any(test, target_arch = "wasm32"))]
+    pub fn new() -> Self {
+        Self {
+            presets: builtin_presets(),
+            path: None,
+        }
+    }
+
+    /// The presets of the file at `path`, written back there on every edit.
+    /// The built-in presets stand in for a missing or unreadable file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: std::path::PathBuf) -> Self {
+        let presets = if path.is_file() {
+            read_presets(&path).unwrap_or_else(|error| {
+                log::error!("failed to load brush presets, using the built-in ones: {error:#}");
+                builtin_presets()
+            })
+        } else {
+            builtin_presets()
+        };
+        Self {
+            presets,
+            path: Some(path),
+        }
+    }
+
+    /// The same presets, no longer written back on edits.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn in_memory(self) -> Self {
+        Self { path: None, ..self }
+    }
+
+    pub fn presets(&self) -> &[BrushPreset] {
+        &self.presets
+    }
+
+    /// Adds `preset`, replacing the one of the same name in place.
+    pub fn insert(&mut self, preset: BrushPreset) {
+        match self
+            .presets
+            .iter_mut()
+            .find(|existing| existing.name == preset.name)
+        {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Whether a preset was named `name`.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.presets.len();
+        self.presets.retain(|preset| preset.name != name);
+        self.presets.len() != count
+    }
+
+    /// Writes the presets back to their file, if they have one.
+    pub fn persist(&self) -> anyhow::Result<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.path {
+            write_presets(path, &self.presets)?;
+        }
+        Ok(())
+    }
+}
+
+impl Resource for BrushPresets {}
+
+/// Pencil, ink, marker, airbrush and eraser.
+pub fn builtin_presets() -> Vec<BrushPreset> {
+    let preset =
+        |name: &str, size: f32, brush: BrushProperties, settings: BrushSettings| BrushPreset {
+            name: name.to_string(),
+            brush: BrushProperties {
+                pointer_size: size,
+                size,
+                ..brush
+            },
+            settings,
+        };
+    let brush = BrushProperties {
+        color: BrushColor::new(0.1, 0.1, 0.12, 1.0),
+        pointer_size: 0.0,
+        size: 0.0,
+        mode: StrokeMode::Paint,
+        pressure_curve: PressureCurve::default(),
+        tip: BrushTip::Round,
+        tip_rotation: TipRotation::Fixed,
+    };
+    let settings = BrushSettings::default();
+
+    vec![
+        preset(
+            "Pencil",
+            5.0,
+            BrushProperties {
+                color: BrushColor::new(0.25, 0.25, 0.28, 1.0),
+                tip: BrushTip::Pencil,
+                tip_rotation: TipRotation::Random,
+                ..brush
+            },
+            BrushSettings {
+                spacing: 10.0,
+                flow: 0.8,
+                ..settings
+            },
+        ),
+        preset(
+            "Ink",
+            6.0,
+            BrushProperties {
+                pressure_curve: PressureCurve {
+                    gamma: 1.5,
+                    min_size: 0.1,
+                    min_opacity: 1.0,
+                },
+                ..brush
+            },
+            BrushSettings {
+                hardness: 0.95,
+                ..settings
+            },
+        ),
+        preset(
+            "Marker",
+            16.0,
+            BrushProperties {
+                color: BrushColor::new(0.95, 0.35, 0.2, 1.0),
+                ..brush
+            },
+            BrushSettings {
+                hardness: 0.8,
+                opacity: 0.6,
+                ..settings
+            },
+        ),
+        preset(
+            "Airbrush",
+            45.0,
+            brush,
+            BrushSettings {
+                hardness: 0.0,
+                flow: 0.08,
+                ..settings
+            },
+        ),
+        preset(
+            "Eraser",
+            24.0,
+            BrushProperties {
+                mode: StrokeMode::Erase,
+                ..brush
+            },
+            BrushSettings {
+                hardness: 0.6,
+                ..settings
+            },
+        ),
+    ]
+}
+
+/// The presets file holding `presets`.
+pub fn presets_to_json(presets: &[BrushPreset]) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(&PresetsFile {
+        version: PRESETS_VERSION,
+        presets: presets.to_vec(),
+    })?)
+}
+
+/// Parses a presets file, refusing presets that could not be painted with.
+pub fn presets_from_json(json: &str) -> anyhow::Result<Vec<BrushPreset>> {
+    let file: PresetsFile = serde_json::from_str(json)?;
+    if file.version > PRESETS_VERSION {
+        bail!(
+            "brush presets version {} is newer than the supported {PRESETS_VERSION}",
+            file.version
+        );
+    }
+    for preset in &file.presets {
+        validate_preset(preset).with_context(|| format!("brush preset '{}'", preset.name))?;
+    }
+    Ok(file.presets)
+}
+
+fn validate_preset(preset: &BrushPreset) -> anyhow::Result<()> {
+    let BrushPreset {
+        name,
+        brush,
+        settings,
+    } = preset;
+    ensure!(!name.trim().is_empty(), "the name is empty");
+    ensure!(
+        brush.size.is_finite() && brush.size > 0.0,
+        "size {} is not positive",
+        brush.size
+    );
+    let curve = brush.pressure_curve;
+    let fractions = [
+        ("hardness", settings.hardness),
+        ("flow", settings.flow),
+        ("opacity", settings.opacity),
+        ("alpha", brush.color.a),
+        ("pressure min size", curve.min_size),
+        ("pressure min opacity", curve.min_opacity),
+    ];
+    for (what, fraction) in fractions {
+        ensure!(
+            (0.0..=1.0).contains(&fraction),
+            "{what} {fraction} is not in 0..=1"
+        );
+    }
+    ensure!(
+        curve.gamma.is_finite() && curve.gamma > 0.0,
+        "pressure gamma {} is not positive",
+        curve.gamma
+    );
+    ensure!(
+        settings.spacing.is_finite() && settings.spacing > 0.0,
+        "spacing {} is not positive",
+        settings.spacing
+    );
+    Ok(())
+}
+
+/// `crayon/brush-presets.json` under the user's config dir, so edits stay out of the asset dir.
+/// Falls back to the asset dir when the environment names no config dir.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn presets_path() -> std::path::PathBuf {
+    config_dir().map_or_else(
+        || crate::document::loader::asset_dir().join("brush-presets.json"),
+        |dir| dir.join("crayon").join("brush-presets.json"),
+    )
+}
+
+/// `%APPDATA%` on Windows, `~/Library/Application Support` on macOS,
+/// and `$XDG_CONFIG_HOME` or `~/.config` elsewhere.
+#[cfg(not(target_arch = "wasm32"))]
+fn config_dir() -> Option<std::path::PathBuf> {
+    let from_env = |name| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(std::path::PathBuf::from)
+    };
+    if cfg!(windows) {
+        from_env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        from_env("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        from_env("XDG_CONFIG_HOME").or_else(|| from_env("HOME").map(|home| home.join(".config")))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_presets(path: &std::path::Path) -> anyhow::Result<Vec<BrushPreset>> {
+    let json =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    presets_from_json(&json).with_context(|| format!("parsing {}", path.display()))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_presets(path: &std::path::Path, presets: &[BrushPreset]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    crate::document::recovery::write_replacing(path, presets_to_json(presets)?.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_are_the_named_brushes() {
+        let presets = builtin_presets();
+        let names: Vec<_> = presets.iter().map(|preset| preset.name.as_str()).collect();
+        assert_eq!(names, ["Pencil", "Ink", "Marker", "Airbrush", "Eraser"]);
+        for preset in &presets {
+            validate_preset(preset).unwrap();
+        }
+        assert_eq!(presets[4].brush.mode, StrokeMode::Erase);
+        assert!(
+            presets[..4]
+                .iter()
+                .all(|p| p.brush.mode == StrokeMode::Paint)
+        );
+    }
+
+    #[test]
+    fn presets_round_trip_through_json() {
+        let presets = builtin_presets();
+        let json = presets_to_json(&presets).unwrap();
+        assert_eq!(presets_from_json(&json).unwrap(), presets);
+    }
+
+    #[test]
+    fn inserting_a_taken_name_replaces_that_preset() {
+        let mut presets = BrushPresets::new();
+        let count = presets.presets().len();
+        let mut ink = presets.presets()[1].clone();
+        ink.settings.flow = 0.5;
+        presets.insert(ink.clone());
+        assert_eq!(presets.presets().len(), count);
+        assert_eq!(presets.presets()[1], ink);
+
+        presets.insert(BrushPreset {
+            name: "Ink, wet".to_string(),
+            ..ink
+        });
+        assert_eq!(presets.presets().len(), count + 1);
+        assert!(presets.remove("Ink, wet"));
+        assert!(!presets.remove("Ink, wet"));
+        assert_eq!(presets.presets().len(), count);
+    }
+
+    #[test]
+    fn unusable_presets_are_refused() {
+        let newer = serde_json::json!({ "version": PRESETS_VERSION + 1, "presets": [] });
+        assert!(presets_from_json(&newer.to_string()).is_err());
+
+        let mut presets = builtin_presets();
+        presets[2].settings.opacity = 1.5;
+        let error = presets_from_json(&presets_to_json(&presets).unwrap()).unwrap_err();
+        assert!(format!("{error:#}").contains("Marker"), "{error:#}");
+
+        let mut presets = builtin_presets();
+        presets[0].name = " ".to_string();
+        assert!(presets_from_json(&presets_to_json(&presets).unwrap()).is_err());
+
+        for curve in [
+            PressureCurve {
+                gamma: 0.0,
+                ..PressureCurve::default()
+            },
+            PressureCurve {
+                min_size: f32::NAN,
+                ..PressureCurve::default()
+            },
+            PressureCurve {
+                min_opacity: 1.5,
+                ..PressureCurve::default()
+            },
+        ] {
+            let mut ink = builtin_presets().swap_remove(1);
+            ink.brush.pressure_curve = curve;
+            let error = validate_preset(&ink).unwrap_err();
+            assert!(error.to_string().contains("pressure"), "{error}");
+        }
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn edits_are_written_back_to_the_presets_file() {
+        let path = crate::testing::fixtures::scratch_dir("brush-presets").join("presets.json");
+        let mut presets = BrushPresets::load(path.clone());
+        assert_eq!(presets.presets(), builtin_presets(), "no file yet");
+
+        assert!(presets.remove("Airbrush"));
+        presets.persist().unwrap();
+        let reloaded = BrushPresets::load(path.clone());
+        assert_eq!(reloaded.presets(), presets.presets());
+        assert!(reloaded.presets().iter().all(|p| p.name != "Airbrush"));
+
+        let mut replayed = reloaded.in_memory();
+        assert!(replayed.remove("Ink"));
+        replayed.persist().unwrap();
+        assert_eq!(
+            BrushPresets::load(path).presets(),
+            presets.presets(),
+            "in-memory edits stay off the file"
+        );
+    }
+}
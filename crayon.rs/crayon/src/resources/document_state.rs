@@ -1,9 +1,24 @@
-use crate::document::{Document, LayerId};
+use std::collections::HashSet;
+
+use crate::document::{Document, LayerId, NodeId, command::Command};
 use crate::resource::Resource;
+use crate::resources::scene_renderer::SceneRenderer;
 
 pub struct DocumentState {
     pub document: Document,
     pub gpu_dirty: Vec<GpuOp>,
+    /// Layers whose pixels changed since the last autosave snapshot.
+    pub dirty_layers: HashSet<LayerId>,
+    /// Layers whose pixels changed since their layers panel thumbnail was drawn.
+    pub stale_thumbnails: HashSet<LayerId>,
+    /// Edits applied since `History` last recorded them.
+    pub edits: Vec<Edit>,
+}
+
+/// An applied `Command`, with what reverts it and the `GpuOp`s it still has to run.
+pub struct Edit {
+    pub revert: Command,
+    pub gpu_ops: Vec<GpuOp>,
 }
 
 impl DocumentState {
@@ -11,12 +26,181 @@ impl DocumentState {
         Self {
             document,
             gpu_dirty: Vec::new(),
+            dirty_layers: HashSet::new(),
+            stale_thumbnails: HashSet::new(),
+            edits: Vec::new(),
+        }
+    }
+
+    /// Applies an undoable edit to the document.
+    /// Its `GpuOp`s are held back until `History` has kept the pixels they destroy.
+    pub fn apply(&mut self, command: Command) {
+        let mut gpu_ops = Vec::new();
+        if let Some(revert) = command.apply(&mut self.document, &mut gpu_ops) {
+            self.edits.push(Edit { revert, gpu_ops });
+        }
+    }
+
+    /// Applies `command`, an edit of `node` that locks forbid, unless `node` is locked, see `Document::is_node_locked`.
+    pub fn apply_unless_locked(&mut self, node: NodeId, command: Command) {
+        if self.document.is_node_locked(node) {
+            log::warn!("{node:?} is locked");
+            return;
+        }
+        self.apply(command);
+    }
+
+    /// Applies a command without recording it, as undo and redo do. Returns what reverts it.
+    pub fn execute(&mut self, command: Command) -> Option<Command> {
+        command.apply(&mut self.document, &mut self.gpu_dirty)
+    }
+
+    /// Marks the layer's pixels as changed, for autosave and its thumbnail.
+    pub fn mark_dirty(&mut self, layer_id: LayerId) {
+        self.dirty_layers.insert(layer_id);
+        self.stale_thumbnails.insert(layer_id);
+    }
+
+    /// Runs `op` on the scene, marking the layer it changed.
+    pub fn run_gpu_op(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        op: GpuOp,
+    ) {
+        scene.apply_gpu_op(device, queue, op);
+        if let GpuOp::DeleteLayer { layer_id } = op {
+            self.dirty_layers.remove(&layer_id);
+            self.stale_thumbnails.remove(&layer_id);
+        } else {
+            self.mark_dirty(op.layer_id());
+        }
+    }
+
+    /// Runs the `GpuOp`s queued on `gpu_dirty`.
+    pub fn flush_gpu_ops(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+    ) {
+        for op in std::mem::take(&mut self.gpu_dirty) {
+            self.run_gpu_op(device, queue, scene, op);
         }
     }
 }
 
+/// The GPU side of a structural edit, keeping layer textures in step with the document.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum GpuOp {
-    ClearLayer { layer_id: LayerId },
+    ClearLayer {
+        layer_id: LayerId,
+    },
+    /// Allocates a blank texture for a new layer.
+    CreateLayer {
+        layer_id: LayerId,
+        size: (u32, u32),
+    },
+    /// Frees the layer's texture.
+    DeleteLayer {
+        layer_id: LayerId,
+    },
+    /// Allocates a texture for `layer_id` holding a copy of `source`'s pixels.
+    CopyLayer {
+        source: LayerId,
+        layer_id: LayerId,
+    },
+    /// Reallocates the layer's texture at `size`, keeping the pixels that still fit
+    /// with the old top left corner moved to `shift`.
+    ResizeLayer {
+        layer_id: LayerId,
+        size: (u32, u32),
+        shift: [i32; 2],
+    },
+}
+
+impl GpuOp {
+    /// The layer whose texture the op changes.
+    pub fn layer_id(self) -> LayerId {
+        match self {
+            Self::ClearLayer { layer_id }
+            | Self::CreateLayer { layer_id, .. }
+            | Self::DeleteLayer { layer_id }
+            | Self::CopyLayer { layer_id, .. }
+            | Self::ResizeLayer { layer_id, .. } => layer_id,
+        }
+    }
+
+    /// Whether the op loses pixels `History` has to keep to undo it.
+    pub fn destroys_pixels(self) -> bool {
+        match self {
+            Self::ClearLayer { .. } | Self::DeleteLayer { .. } | Self::ResizeLayer { .. } => true,
+            Self::CreateLayer { .. } | Self::CopyLayer { .. } => false,
+        }
+    }
 }
 
 impl Resource for DocumentState {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{document::ArtboardId, testing::fixtures::doc_two_artboards};
+
+    const LOCKED: NodeId = NodeId::Layer(LayerId(2));
+
+    fn locked_state() -> DocumentState {
+        let mut doc = DocumentState::new(doc_two_artboards());
+        doc.apply(Command::SetLocked {
+            node: LOCKED,
+            locked: true,
+        });
+        doc.edits.clear();
+        doc
+    }
+
+    #[test]
+    fn locked_layers_are_not_deleted() {
+        let mut doc = locked_state();
+        doc.apply_unless_locked(LOCKED, Command::RemoveNode { node: LOCKED });
+        assert!(doc.document.find_layer(LayerId(2)).is_some());
+        assert!(doc.edits.is_empty());
+
+        let unlocked = NodeId::Layer(LayerId(4));
+        doc.apply_unless_locked(unlocked, Command::RemoveNode { node: unlocked });
+        assert!(doc.document.find_layer(LayerId(4)).is_none());
+    }
+
+    #[test]
+    fn locked_layers_are_not_moved() {
+        let mut doc = locked_state();
+        doc.apply_unless_locked(
+            LOCKED,
+            Command::MoveNode {
+                artboard_id: ArtboardId(3),
+                node: LOCKED,
+                parent: None,
+                index: usize::MAX,
+            },
+        );
+        assert_eq!(doc.document.locate(LOCKED), Some((ArtboardId(1), None, 0)));
+        assert!(doc.edits.is_empty());
+    }
+
+    #[test]
+    fn locked_layers_are_not_renamed() {
+        let mut doc = locked_state();
+        let name = doc.document.find_layer(LayerId(2)).unwrap().1.name.clone();
+        doc.apply_unless_locked(
+            LOCKED,
+            Command::Rename {
+                node: LOCKED,
+                name: "Renamed".to_owned(),
+            },
+        );
+        assert_eq!(doc.document.find_layer(LayerId(2)).unwrap().1.name, name);
+        assert!(doc.edits.is_empty());
+    }
+}
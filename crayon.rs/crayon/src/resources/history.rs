@@ -0,0 +1,911 @@
+use std::collections::VecDeque;
+
+use crate::{
+    document::{LayerId, command::Command},
+    resource::Resource,
+    resources::{
+        document_state::{DocumentState, Edit, GpuOp},
+        scene_renderer::SceneRenderer,
+    },
+};
+
+/// Default `History` budget, see `LaunchOptions::history_budget_mb`.
+pub const DEFAULT_HISTORY_BUDGET_MB: u64 = 256;
+
+/// Texel rect of a layer, top-left origin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TexelRect {
+    pub origin: [u32; 2],
+    pub size: [u32; 2],
+}
+
+impl TexelRect {
+    /// Texels touched by dabs within `bounds` (`[min_x, min_y, max_x, max_y]` in layer pixels),
+    /// padded for antialiasing and clipped to the layer. `None` when nothing of the layer is touched.
+    pub fn covering(bounds: [f32; 4], (width, height): (u32, u32)) -> Option<Self> {
+        #[allow(clippy::cast_precision_loss)]
+        let (width_px, height_px) = (width as f32, height as f32);
+        let min_x = (bounds[0] - 1.0).floor().clamp(0.0, width_px);
+        let min_y = (bounds[1] - 1.0).floor().clamp(0.0, height_px);
+        let max_x = (bounds[2] + 1.0).ceil().clamp(0.0, width_px);
+        let max_y = (bounds[3] + 1.0).ceil().clamp(0.0, height_px);
+        if max_x <= min_x || max_y <= min_y {
+            return None;
+        }
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Some(Self {
+            origin: [min_x as u32, min_y as u32],
+            size: [(max_x - min_x) as u32, (max_y - min_y) as u32],
+        })
+    }
+
+    fn bytes(self) -> u64 {
+        u64::from(self.size[0]) * u64::from(self.size[1]) * 4
+    }
+}
+
+/// A region of `layer_id` as it looked on the other side of the undo.
+struct Region {
+    layer_id: LayerId,
+    rect: TexelRect,
+    texture: wgpu::Texture,
+}
+
+/// One undoable edit: the pixels it changed, and the command reverting the rest.
+struct Step {
+    regions: Vec<Region>,
+    revert: Option<Command>,
+}
+
+impl Step {
+    fn bytes(&self) -> u64 {
+        self.regions.iter().map(|region| region.rect.bytes()).sum()
+    }
+
+    /// Swaps the regions and applies the revert, leaving the step ready to go the other way.
+    ///
+    /// The revert's `GpuOp`s run around the swap: layers it brings back are allocated first,
+    /// at the size their regions were kept at, and the others run once the regions hold what they destroy.
+    fn swap(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        doc: &mut DocumentState,
+    ) {
+        let mut gpu_ops = Vec::new();
+        self.revert = self
+            .revert
+            .take()
+            .and_then(|revert| revert.apply(&mut doc.document, &mut gpu_ops));
+        let (restoring, destroying): (Vec<GpuOp>, Vec<GpuOp>) =
+            gpu_ops.into_iter().partition(|op| self.restores(*op));
+
+        for op in restoring {
+            doc.run_gpu_op(device, queue, scene, op);
+        }
+        for region in &mut self.regions {
+            swap_region(device, queue, scene, region);
+            doc.mark_dirty(region.layer_id);
+        }
+        for op in destroying {
+            let layer_id = op.layer_id();
+            if op.destroys_pixels() && !self.regions.iter().any(|r| r.layer_id == layer_id) {
+                // first time round, as when undoing a new layer
+                let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("History Capture Encoder"),
+                });
+                self.regions
+                    .extend(capture_layer(device, &mut encoder, scene, layer_id));
+                queue.submit([encoder.finish()]);
+            }
+            doc.run_gpu_op(device, queue, scene, op);
+        }
+    }
+
+    /// Whether `op` brings back a layer for the regions to swap into.
+    fn restores(&self, op: GpuOp) -> bool {
+        match op {
+            GpuOp::CreateLayer { .. } | GpuOp::CopyLayer { .. } => true,
+            GpuOp::ResizeLayer { layer_id, size, .. } => self
+                .regions
+                .iter()
+                .any(|region| region.layer_id == layer_id && region.rect.size == [size.0, size.1]),
+            GpuOp::ClearLayer { .. } | GpuOp::DeleteLayer { .. } => false,
+        }
+    }
+}
+
+/// Undo/redo of strokes and structural edits, in one timeline.
+///
+/// Before a stroke merges into its layer, the region it covers is copied aside on the GPU,
+/// as is the whole layer before an edit clears it. Undo and redo swap that copy with what the layer holds there,
+/// so each step costs only its regions. The document side of a step is a `Command` that reverts it.
+/// Steps past the memory budget are evicted oldest first, redo steps included:
+/// undoing can keep more than was done, as when it takes a layer back out.
+pub struct History {
+    undo: VecDeque<Step>,
+    redo: Vec<Step>,
+    budget_bytes: u64,
+    used_bytes: u64,
+}
+
+impl History {
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            budget_bytes,
+            used_bytes: 0,
+        }
+    }
+
+    /// Forgets every step, as when another document replaces the one they were taken on.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.used_bytes = 0;
+    }
+
+    /// Copies `rect` of the layer aside before a stroke merges into it, on the encoder the merge is recorded on.
+    /// `revert` undoes the stroke's document side, such as the stroke kept on a vector layer.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &SceneRenderer,
+        layer_id: LayerId,
+        rect: Option<TexelRect>,
+        revert: Option<Command>,
+    ) {
+        let regions = rect
+            .and_then(|rect| capture(device, encoder, scene, layer_id, rect))
+            .into_iter()
+            .collect();
+        self.push(Step { regions, revert });
+    }
+
+    /// Turns the edits applied on `doc` into steps, keeping whole the layers their `GpuOp`s are about to destroy.
+    /// The `GpuOp`s then go on `doc.gpu_dirty`.
+    pub fn record_edits(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &SceneRenderer,
+        doc: &mut DocumentState,
+    ) {
+        if doc.edits.is_empty() {
+            return;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("History Capture Encoder"),
+        });
+        for Edit { revert, gpu_ops } in std::mem::take(&mut doc.edits) {
+            let regions = gpu_ops
+                .iter()
+                .filter(|op| op.destroys_pixels())
+                .filter_map(|op| capture_layer(device, &mut encoder, scene, op.layer_id()))
+                .collect();
+            self.push(Step {
+                regions,
+                revert: Some(revert),
+            });
+            doc.gpu_dirty.extend(gpu_ops);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Forgets everything that could be redone, and evicts what no longer fits the budget.
+    fn push(&mut self, step: Step) {
+        if step.regions.is_empty() && step.revert.is_none() {
+            return;
+        }
+        for dropped in self.redo.drain(..) {
+            self.used_bytes -= dropped.bytes();
+        }
+        self.used_bytes += step.bytes();
+        self.undo.push_back(step);
+        self.evict();
+    }
+
+    /// Drops the oldest undo steps, then the redo steps furthest from being redone, until the budget fits.
+    fn evict(&mut self) {
+        while self.used_bytes > self.budget_bytes {
+            let evicted = match self.undo.pop_front() {
+                Some(step) => step,
+                None if !self.redo.is_empty() => self.redo.remove(0),
+                None => break,
+            };
+            self.used_bytes -= evicted.bytes();
+        }
+    }
+
+    /// Takes back the latest step, false when there is none.
+    pub fn undo(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        doc: &mut DocumentState,
+    ) -> bool {
+        let Some(mut step) = self.undo.pop_back() else {
+            return false;
+        };
+        self.used_bytes -= step.bytes();
+        step.swap(device, queue, scene, doc);
+        self.used_bytes += step.bytes();
+        self.redo.push(step);
+        self.evict();
+        true
+    }
+
+    /// Puts back the latest undone step, false when there is none.
+    pub fn redo(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        doc: &mut DocumentState,
+    ) -> bool {
+        let Some(mut step) = self.redo.pop() else {
+            return false;
+        };
+        self.used_bytes -= step.bytes();
+        step.swap(device, queue, scene, doc);
+        self.used_bytes += step.bytes();
+        self.undo.push_back(step);
+        self.evict();
+        true
+    }
+
+    #[cfg(test)]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    #[cfg(test)]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// GPU memory held by undo and redo steps.
+    #[cfg(test)]
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes
+    }
+}
+
+impl Resource for History {}
+
+/// Copies `rect` of the layer into a region of its own.
+fn capture(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    scene: &SceneRenderer,
+    layer_id: LayerId,
+    rect: TexelRect,
+) -> Option<Region> {
+    let layer = scene.layers.get(&layer_id)?;
+    let texture = region_texture(device, scene.format(), rect);
+    copy_region(
+        encoder,
+        &layer.texture.texture,
+        rect.origin,
+        &texture,
+        [0, 0],
+        rect,
+    );
+    Some(Region {
+        layer_id,
+        rect,
+        texture,
+    })
+}
+
+/// Copies the whole layer into a region of its own.
+fn capture_layer(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    scene: &SceneRenderer,
+    layer_id: LayerId,
+) -> Option<Region> {
+    let (width, height) = scene.layers.get(&layer_id)?.size;
+    let whole = TexelRect {
+        origin: [0, 0],
+        size: [width, height],
+    };
+    capture(device, encoder, scene, layer_id, whole)
+}
+
+/// Exchanges the region with the layer's, the layer's current content becoming the region.
+/// A vector layer's display no longer matches and is dropped.
+fn swap_region(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut SceneRenderer,
+    region: &mut Region,
+) {
+    let Some(layer) = scene.layers.get(&region.layer_id) else {
+        return;
+    };
+    let current = region_texture(device, scene.format(), region.rect);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("History Swap Encoder"),
+    });
+    let origin = region.rect.origin;
+    copy_region(
+        &mut encoder,
+        &layer.texture.texture,
+        origin,
+        &current,
+        [0, 0],
+        region.rect,
+    );
+    copy_region(
+        &mut encoder,
+        &region.texture,
+        [0, 0],
+        &layer.texture.texture,
+        origin,
+        region.rect,
+    );
+    queue.submit([encoder.finish()]);
+    region.texture = current;
+    scene.invalidate_vector_display(region.layer_id);
+}
+
+fn region_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    rect: TexelRect,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("History Region"),
+        size: wgpu::Extent3d {
+            width: rect.size[0],
+            height: rect.size[1],
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn copy_region(
+    encoder: &mut wgpu::CommandEncoder,
+    source: &wgpu::Texture,
+    source_origin: [u32; 2],
+    destination: &wgpu::Texture,
+    destination_origin: [u32; 2],
+    rect: TexelRect,
+) {
+    let at = |texture, [x, y]: [u32; 2]| wgpu::TexelCopyTextureInfo {
+        texture,
+        mip_level: 0,
+        origin: wgpu::Origin3d { x, y, z: 0 },
+        aspect: wgpu::TextureAspect::All,
+    };
+    encoder.copy_texture_to_texture(
+        at(source, source_origin),
+        at(destination, destination_origin),
+        wgpu::Extent3d {
+            width: rect.size[0],
+            height: rect.size[1],
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::document::{ArtboardId, BrushTip, NodeId, StrokeMode, VectorPoint, VectorStroke};
+    use crate::editor_state::DEFAULT_BRUSH_COLOR;
+    use crate::resources::scene_renderer::PointInstance;
+    use crate::testing::fixtures;
+    use crate::testing::probe::sample;
+
+    const LAYER: LayerId = LayerId(2);
+    const LAYER_SIZE: (u32, u32) = (600, 400);
+
+    /// `fixtures::hydrated_scene` with the left layer solid green.
+    fn hydrated_scene() -> (wgpu::Device, wgpu::Queue, SceneRenderer, DocumentState) {
+        let (device, queue, scene, document) = fixtures::hydrated_scene([0, 255, 0, 255]);
+        (device, queue, scene, DocumentState::new(document))
+    }
+
+    /// Merges a dab of `radius` at layer pixel `center` as `PaintSystem` does, recording it first.
+    fn stamp(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        history: &mut History,
+        center: [f32; 2],
+        radius: f32,
+    ) {
+        stamp_with(device, queue, scene, history, center, radius, None);
+    }
+
+    /// `stamp`, with `revert` undoing the stroke's document side.
+    fn stamp_with(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        history: &mut History,
+        center: [f32; 2],
+        radius: f32,
+        revert: Option<Command>,
+    ) {
+        #[allow(clippy::cast_precision_loss)]
+        let (width, height) = (LAYER_SIZE.0 as f32, LAYER_SIZE.1 as f32);
+        scene.begin_points().push(PointInstance {
+            center: [
+                center[0] / (width * 0.5) - 1.0,
+                1.0 - center[1] / (height * 0.5),
+            ],
+            radius_px: radius,
+            opacity: 1.0,
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            rotation: 0.0,
+        });
+        let count = scene.upload_points(queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("History Test Encoder"),
+        });
+        scene.accumulate_stroke(queue, &mut encoder, true, count, LAYER_SIZE);
+        let bounds = [
+            center[0] - radius,
+            center[1] - radius,
+            center[0] + radius,
+            center[1] + radius,
+        ];
+        let rect = TexelRect::covering(bounds, LAYER_SIZE);
+        assert!(rect.is_some());
+        history.record(device, &mut encoder, scene, LAYER, rect, revert);
+        scene.merge_stroke_into_layer(queue, &mut encoder, LAYER);
+        queue.submit([encoder.finish()]);
+    }
+
+    fn layer_pixels(device: &wgpu::Device, queue: &wgpu::Queue, scene: &SceneRenderer) -> Vec<u8> {
+        scene
+            .read_layer_pixels(device, queue, LAYER)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn covering_pads_and_clips_to_the_layer() {
+        let rect = TexelRect::covering([10.2, 20.7, 30.5, 40.0], (600, 400)).unwrap();
+        assert_eq!(rect.origin, [9, 19]);
+        assert_eq!(rect.size, [23, 22]);
+
+        let clipped = TexelRect::covering([-50.0, 390.0, 10.0, 450.0], (600, 400)).unwrap();
+        assert_eq!(clipped.origin, [0, 389]);
+        assert_eq!(clipped.size, [11, 11]);
+
+        assert_eq!(
+            TexelRect::covering([700.0, 10.0, 720.0, 20.0], (600, 400)),
+            None
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_stroke_region() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [300.0, 200.0],
+            20.0,
+        );
+        let painted = layer_pixels(&device, &queue, &scene);
+        assert_ne!(sample(&painted, LAYER_SIZE, 300, 200), [0, 255, 0, 255]);
+        assert_eq!(
+            history.used_bytes(),
+            42 * 42 * 4,
+            "only the dab's region is kept"
+        );
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+        assert!(!history.can_undo());
+
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == painted);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn strokes_undo_in_reverse_order() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [300.0, 200.0],
+            20.0,
+        );
+        let first = layer_pixels(&device, &queue, &scene);
+        // overlaps the first dab
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [315.0, 200.0],
+            20.0,
+        );
+
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert!(layer_pixels(&device, &queue, &scene) == first);
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+        assert!(!history.undo(&device, &queue, &mut scene, &mut doc));
+    }
+
+    #[test]
+    fn a_new_stroke_drops_the_redo_entries() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [100.0, 100.0],
+            10.0,
+        );
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert!(history.can_redo());
+
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [200.0, 100.0],
+            10.0,
+        );
+        assert!(!history.can_redo());
+        assert_eq!(history.used_bytes(), 22 * 22 * 4);
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted_over_budget() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let entry_bytes = 22 * 22 * 4;
+        let mut history = History::new(2 * entry_bytes);
+        for x in [100.0, 200.0, 300.0] {
+            stamp(&device, &queue, &mut scene, &mut history, [x, 100.0], 10.0);
+        }
+        assert_eq!(history.used_bytes(), 2 * entry_bytes);
+
+        let mut undone = 0;
+        while history.undo(&device, &queue, &mut scene, &mut doc) {
+            undone += 1;
+        }
+        assert_eq!(undone, 2);
+        let pixels = layer_pixels(&device, &queue, &scene);
+        assert_ne!(
+            sample(&pixels, LAYER_SIZE, 100, 100),
+            [0, 255, 0, 255],
+            "evicted stroke stays"
+        );
+        assert_eq!(sample(&pixels, LAYER_SIZE, 300, 100), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn steps_kept_on_the_way_out_count_against_the_budget() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let layer_bytes = 600 * 400 * 4;
+        let mut history = History::new(layer_bytes);
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [100.0, 100.0],
+            10.0,
+        );
+        let duplicate = Command::DuplicateNode {
+            node: NodeId::Layer(LAYER),
+        };
+        apply_edit(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            &mut doc,
+            duplicate,
+        );
+
+        // keeps the copy's pixels for the redo, pushing the stroke out
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert_eq!(history.used_bytes(), layer_bytes);
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn undo_takes_strokes_off_vector_layers() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let stroke = VectorStroke {
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            mode: StrokeMode::Paint,
+            tip: BrushTip::Round,
+            hardness: 0.4,
+            opacity: 1.0,
+            points: vec![VectorPoint {
+                position: [300.0, 200.0],
+                radius: 20.0,
+                opacity: 1.0,
+                rotation: 0.0,
+            }],
+        };
+        doc.document.find_layer_mut(LAYER).unwrap().strokes = Some(Vec::new());
+        let revert = doc.execute(Command::InsertStroke {
+            layer_id: LAYER,
+            index: 0,
+            stroke: stroke.clone(),
+        });
+        stamp_with(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [300.0, 200.0],
+            20.0,
+            revert,
+        );
+        let strokes =
+            |doc: &DocumentState| doc.document.find_layer(LAYER).unwrap().1.strokes.clone();
+
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert_eq!(strokes(&doc), Some(Vec::new()));
+        history.redo(&device, &queue, &mut scene, &mut doc);
+        assert_eq!(strokes(&doc), Some(vec![stroke]));
+    }
+
+    #[test]
+    fn clearing_every_layer_is_undone_from_the_kept_pixels() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        doc.apply(Command::ClearLayers(vec![LAYER, LayerId(4)]));
+        history.record_edits(&device, &queue, &scene, &mut doc);
+        assert!(doc.edits.is_empty());
+        assert_eq!(
+            doc.gpu_dirty.len(),
+            2,
+            "the clears run once the layers are kept"
+        );
+        doc.flush_gpu_ops(&device, &queue, &mut scene);
+        assert_eq!(history.used_bytes(), (600 * 400 + 400 * 300) * 4);
+        let cleared = layer_pixels(&device, &queue, &scene);
+        assert_eq!(sample(&cleared, LAYER_SIZE, 10, 10), [0, 0, 0, 0]);
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+        assert!(doc.dirty_layers.contains(&LAYER));
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == cleared);
+        assert!(doc.gpu_dirty.is_empty(), "redone from the kept pixels too");
+    }
+
+    #[test]
+    fn strokes_and_structural_edits_share_one_timeline() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [300.0, 200.0],
+            20.0,
+        );
+        let painted = layer_pixels(&device, &queue, &scene);
+        doc.apply(Command::Rename {
+            node: NodeId::Layer(LAYER),
+            name: "Ink".to_string(),
+        });
+        history.record_edits(&device, &queue, &scene, &mut doc);
+        let name = |doc: &DocumentState| doc.document.find_layer(LAYER).unwrap().1.name.clone();
+        let renamed = name(&doc);
+
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert_ne!(name(&doc), renamed);
+        assert!(
+            layer_pixels(&device, &queue, &scene) == painted,
+            "the stroke stays"
+        );
+        history.undo(&device, &queue, &mut scene, &mut doc);
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+
+        history.redo(&device, &queue, &mut scene, &mut doc);
+        history.redo(&device, &queue, &mut scene, &mut doc);
+        assert_eq!(name(&doc), renamed);
+        assert!(layer_pixels(&device, &queue, &scene) == painted);
+    }
+
+    /// Applies `command` as the app does: recorded, then its `GpuOp`s run.
+    fn apply_edit(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        history: &mut History,
+        doc: &mut DocumentState,
+        command: Command,
+    ) {
+        doc.apply(command);
+        history.record_edits(device, queue, scene, doc);
+        doc.flush_gpu_ops(device, queue, scene);
+    }
+
+    #[test]
+    fn deleted_layers_come_back_with_their_pixels() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        let delete = Command::RemoveNode {
+            node: NodeId::Layer(LAYER),
+        };
+        apply_edit(&device, &queue, &mut scene, &mut history, &mut doc, delete);
+        assert!(!scene.layers.contains_key(&LAYER), "texture freed");
+        assert!(doc.document.find_layer(LAYER).is_none());
+        assert_eq!(history.used_bytes(), 600 * 400 * 4);
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(doc.document.find_layer(LAYER).is_some());
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert!(!scene.layers.contains_key(&LAYER));
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == before);
+    }
+
+    #[test]
+    fn duplicates_copy_the_pixels_and_redo_from_the_kept_ones() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+        let copy = LayerId(doc.document.next_id);
+        let copy_pixels = |scene: &SceneRenderer| {
+            scene
+                .read_layer_pixels(&device, &queue, copy)
+                .unwrap()
+                .unwrap()
+        };
+
+        let duplicate = Command::DuplicateNode {
+            node: NodeId::Layer(LAYER),
+        };
+        apply_edit(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            &mut doc,
+            duplicate,
+        );
+        assert!(copy_pixels(&scene) == before);
+        assert_eq!(history.used_bytes(), 0, "nothing destroyed");
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(!scene.layers.contains_key(&copy));
+        assert_eq!(history.used_bytes(), 600 * 400 * 4, "kept on the way out");
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert_eq!(
+            doc.document.find_layer(copy).unwrap().1.name,
+            "Layer 1 copy"
+        );
+        assert!(copy_pixels(&scene) == before);
+    }
+
+    #[test]
+    fn layers_moved_to_another_artboard_are_resized_and_moved_back_whole() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        let before = layer_pixels(&device, &queue, &scene);
+
+        let to_the_right = Command::MoveNode {
+            artboard_id: ArtboardId(3),
+            node: NodeId::Layer(LAYER),
+            parent: None,
+            index: usize::MAX,
+        };
+        apply_edit(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            &mut doc,
+            to_the_right,
+        );
+        assert_eq!(doc.document.find_layer(LAYER).unwrap().0, ArtboardId(3));
+        assert_eq!(scene.layers[&LAYER].size, (400, 300));
+        let moved = layer_pixels(&device, &queue, &scene);
+        assert_eq!(sample(&moved, (400, 300), 399, 299), [0, 255, 0, 255]);
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert_eq!(doc.document.find_layer(LAYER).unwrap().0, ArtboardId(1));
+        assert_eq!(scene.layers[&LAYER].size, LAYER_SIZE);
+        assert!(
+            layer_pixels(&device, &queue, &scene) == before,
+            "the cropped pixels come back"
+        );
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert_eq!(scene.layers[&LAYER].size, (400, 300));
+        assert!(layer_pixels(&device, &queue, &scene) == moved);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn anchored_resizes_shift_the_pixels_and_undo_whole() {
+        let (device, queue, mut scene, mut doc) = hydrated_scene();
+        let mut history = History::new(u64::MAX);
+        stamp(
+            &device,
+            &queue,
+            &mut scene,
+            &mut history,
+            [150.0, 150.0],
+            10.0,
+        );
+        let before = layer_pixels(&device, &queue, &scene);
+        let dab = sample(&before, LAYER_SIZE, 150, 150);
+        assert_ne!(dab, [0, 255, 0, 255]);
+
+        let crop = Command::ResizeArtboard {
+            artboard_id: ArtboardId(1),
+            size: [300.0, 200.0],
+            shift: [-100, -100],
+        };
+        apply_edit(&device, &queue, &mut scene, &mut history, &mut doc, crop);
+        assert_eq!(scene.layers[&LAYER].size, (300, 200));
+        let cropped = layer_pixels(&device, &queue, &scene);
+        assert_eq!(sample(&cropped, (300, 200), 50, 50), dab);
+
+        let grow = Command::ResizeArtboard {
+            artboard_id: ArtboardId(1),
+            size: [400.0, 200.0],
+            shift: [100, 0],
+        };
+        apply_edit(&device, &queue, &mut scene, &mut history, &mut doc, grow);
+        let grown = layer_pixels(&device, &queue, &scene);
+        assert_eq!(sample(&grown, (400, 200), 50, 50), [0, 0, 0, 0], "blank");
+        assert_eq!(sample(&grown, (400, 200), 150, 50), dab);
+
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == cropped);
+        assert!(history.undo(&device, &queue, &mut scene, &mut doc));
+        assert_eq!(scene.layers[&LAYER].size, LAYER_SIZE);
+        assert!(
+            layer_pixels(&device, &queue, &scene) == before,
+            "the cropped pixels come back"
+        );
+        assert_eq!(doc.document.artboards[0].position, [0.0, 0.0]);
+
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert!(history.redo(&device, &queue, &mut scene, &mut doc));
+        assert!(layer_pixels(&device, &queue, &scene) == grown);
+    }
+}
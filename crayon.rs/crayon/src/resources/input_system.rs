@@ -1,34 +1,47 @@
-use winit::event::WindowEvent;
+use winit::{event::WindowEvent, keyboard::ModifiersState};
 
 use crate::{
-    brush_controller::BrushController, camera_controller::CameraController,
-    event_sender::EventSender, resource::Resource,
+    artboard_controller::ArtboardController,
+    brush_controller::BrushController,
+    camera_controller::CameraController,
+    editor_state::{BrushProperties, BrushSettings},
+    event_sender::EventSender,
+    resource::Resource,
 };
 
 pub struct InputSystem {
     brush_controller: BrushController,
     camera_controller: CameraController,
-    is_super_pressed: bool,
+    artboard_controller: ArtboardController,
+    modifiers: ModifiersState,
 }
 
 impl InputSystem {
     pub fn new(event_sender: EventSender) -> Self {
         Self {
             brush_controller: BrushController::new(event_sender.clone()),
-            camera_controller: CameraController::new(event_sender),
-            is_super_pressed: false,
+            camera_controller: CameraController::new(event_sender.clone()),
+            artboard_controller: ArtboardController::new(event_sender),
+            modifiers: ModifiersState::empty(),
         }
     }
 
-    pub fn process_event(&mut self, event: &WindowEvent, brush_size: f32) {
+    pub fn process_event(
+        &mut self,
+        event: &WindowEvent,
+        brush: &BrushProperties,
+        settings: &BrushSettings,
+    ) {
         if let WindowEvent::ModifiersChanged(modifiers) = event {
-            self.is_super_pressed = modifiers.state().super_key();
+            self.modifiers = modifiers.state();
         }
 
         self.brush_controller
-            .process_event(event, brush_size, self.is_super_pressed);
+            .process_event(event, brush, settings, self.modifiers);
         self.camera_controller
-            .process_event(event, self.is_super_pressed);
+            .process_event(event, self.modifiers.super_key());
+        self.artboard_controller
+            .process_event(event, self.modifiers.alt_key());
     }
 }
 
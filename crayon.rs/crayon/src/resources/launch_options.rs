@@ -1,16 +1,105 @@
-use crate::resource::Resource;
-use clap::Parser;
+use crate::{resource::Resource, resources::history::DEFAULT_HISTORY_BUDGET_MB};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
-/// Which `assets/documents/<name>.json` to open, from the `--doc <name>` dev flag.
+/// Which `assets/documents/<name>.crayon` (or `<name>.json`) to open, from the `--doc <name>` dev flag,
+/// and what to do with a session of it that was interrupted, from `--recovery`.
+/// Input sessions are recorded with `--record` and played back with `--replay`.
+/// Without a subcommand the editor opens, subcommands run without a window and exit.
 #[derive(Parser)]
 #[command(name = "crayon")]
 pub struct LaunchOptions {
+    #[command(subcommand)]
+    pub command: Option<LaunchCommand>,
+
     /// Document name under `assets/documents/` to open on launch.
     #[allow(dead_code)]
     #[arg(long = "doc", default_value = "default")]
     pub document: String,
+
+    /// What to do with an interrupted session of the document.
+    #[arg(long, value_enum, default_value_t = Recovery::Ask)]
+    pub recovery: Recovery,
+
+    /// GPU memory undo history may hold, in MiB.
+    #[arg(long = "history-mb", default_value_t = DEFAULT_HISTORY_BUDGET_MB)]
+    pub history_budget_mb: u64,
+
+    /// Record the session's input events to this file.
+    #[allow(dead_code)]
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Play back the input events recorded in this file.
+    #[allow(dead_code)]
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum LaunchCommand {
+    /// Flatten an artboard of a document to a PNG, without a window.
+    Render(RenderArgs),
+    /// Report every problem of a document, failing on errors.
+    Check(CheckArgs),
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct CheckArgs {
+    /// Document file (`.crayon`, `.json` or `.ora`), or a document name under `assets/documents/`.
+    pub document: String,
+
+    /// Texture size limit artboards are checked against, in pixels.
+    #[arg(long, default_value_t = 8192)]
+    pub max_texture_dim: u32,
+
+    /// Fail on warnings too.
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+#[derive(Args, Debug, PartialEq)]
+pub struct RenderArgs {
+    /// Document file (`.crayon`, `.json` or `.ora`), or a document name under `assets/documents/`.
+    #[arg(long = "doc")]
+    pub document: String,
+
+    /// Name of the artboard to render, the first one by default.
+    #[arg(long)]
+    pub artboard: Option<String>,
+
+    /// Output pixels per artboard pixel.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: u32,
+
+    /// Keep the layers' transparency instead of flattening onto the white artboard background.
+    #[arg(long)]
+    pub transparent: bool,
+
+    /// Render on the software fallback adapter even when a GPU is available.
+    #[arg(long)]
+    pub software: bool,
+
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Recovery {
+    /// Open the saved document and ask whether to restore or discard the recovered session, see `InterruptedSession`.
+    Ask,
+    /// Reopen the recovered session.
+    Restore,
+    /// Delete the recovered session.
+    Discard,
 }
 
+/// Name of the document whose interrupted session `Recovery::Ask` found,
+/// `None` once the user restored or discarded it.
+pub struct InterruptedSession(pub Option<String>);
+
+impl Resource for InterruptedSession {}
+
 impl LaunchOptions {
     pub fn from_args() -> Self {
         Self::parse()
@@ -33,6 +122,89 @@ mod tests {
     fn defaults_without_flag() {
         let opts = LaunchOptions::try_parse_from(["crayon"]).unwrap();
         assert_eq!(opts.document, "default");
+        assert_eq!(opts.recovery, Recovery::Ask);
+        assert_eq!(opts.history_budget_mb, DEFAULT_HISTORY_BUDGET_MB);
+        assert_eq!(opts.record, None);
+        assert_eq!(opts.replay, None);
+        assert_eq!(opts.command, None);
+    }
+
+    #[test]
+    fn parses_render_subcommand() {
+        let opts = LaunchOptions::try_parse_from([
+            "crayon",
+            "render",
+            "--doc",
+            "foo.json",
+            "--artboard",
+            "Artboard 1",
+            "--scale",
+            "2",
+            "--out",
+            "foo.png",
+        ])
+        .unwrap();
+        assert_eq!(
+            opts.command,
+            Some(LaunchCommand::Render(RenderArgs {
+                document: "foo.json".to_string(),
+                artboard: Some("Artboard 1".to_string()),
+                scale: 2,
+                transparent: false,
+                software: false,
+                out: PathBuf::from("foo.png"),
+            }))
+        );
+
+        let missing_out = ["crayon", "render", "--doc", "foo.json"];
+        assert!(LaunchOptions::try_parse_from(missing_out).is_err());
+        let zero_scale = [
+            "crayon", "render", "--doc", "foo", "--scale", "0", "--out", "foo.png",
+        ];
+        assert!(LaunchOptions::try_parse_from(zero_scale).is_err());
+    }
+
+    #[test]
+    fn parses_check_subcommand() {
+        let opts = LaunchOptions::try_parse_from(["crayon", "check", "art/foo.crayon"]).unwrap();
+        assert_eq!(
+            opts.command,
+            Some(LaunchCommand::Check(CheckArgs {
+                document: "art/foo.crayon".to_string(),
+                max_texture_dim: 8192,
+                deny_warnings: false,
+            }))
+        );
+        assert!(LaunchOptions::try_parse_from(["crayon", "check"]).is_err());
+    }
+
+    #[test]
+    fn parses_session_flags() {
+        let opts = LaunchOptions::try_parse_from([
+            "crayon",
+            "--record",
+            "next.session",
+            "--replay",
+            "bug.session",
+        ])
+        .unwrap();
+        assert_eq!(opts.record, Some(PathBuf::from("next.session")));
+        assert_eq!(opts.replay, Some(PathBuf::from("bug.session")));
+        assert!(LaunchOptions::try_parse_from(["crayon", "--replay"]).is_err());
+    }
+
+    #[test]
+    fn parses_history_budget() {
+        let opts = LaunchOptions::try_parse_from(["crayon", "--history-mb", "64"]).unwrap();
+        assert_eq!(opts.history_budget_mb, 64);
+        assert!(LaunchOptions::try_parse_from(["crayon", "--history-mb", "-1"]).is_err());
+    }
+
+    #[test]
+    fn parses_recovery_flag() {
+        let opts = LaunchOptions::try_parse_from(["crayon", "--recovery", "restore"]).unwrap();
+        assert_eq!(opts.recovery, Recovery::Restore);
+        assert!(LaunchOptions::try_parse_from(["crayon", "--recovery", "maybe"]).is_err());
     }
 
     #[test]
@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    document::{LayerId, thumbhash::thumbhash_preview},
+    renderer::{egui_context::EguiContext, render_context::RenderContext},
+    resource::Resource,
+    resources::{document_state::DocumentState, scene_renderer::SceneRenderer},
+};
+
+/// Edge length in pixels of the square live thumbnails are fitted in.
+pub const THUMBNAIL_SIZE: u32 = 48;
+
+enum Thumbnail {
+    /// The layer's stored thumbhash, decoded, until the layer changes.
+    Preview(egui::TextureHandle),
+    /// Downscaled from the layer texture.
+    Live {
+        texture: wgpu::Texture,
+        id: egui::TextureId,
+    },
+}
+
+/// The layers panel thumbnail of every layer, as egui textures.
+pub struct LayerThumbnails {
+    thumbnails: HashMap<LayerId, Thumbnail>,
+}
+
+impl LayerThumbnails {
+    pub fn new() -> Self {
+        Self {
+            thumbnails: HashMap::new(),
+        }
+    }
+
+    /// The layer's thumbnail at its own size, previews keep the thumbhash's aspect ratio.
+    pub fn thumbnail(&self, layer_id: LayerId) -> Option<egui::load::SizedTexture> {
+        self.thumbnails
+            .get(&layer_id)
+            .map(|thumbnail| match thumbnail {
+                Thumbnail::Preview(handle) => egui::load::SizedTexture::from_handle(handle),
+                #[allow(clippy::cast_precision_loss)]
+                Thumbnail::Live { id, .. } => {
+                    egui::load::SizedTexture::new(*id, egui::Vec2::splat(THUMBNAIL_SIZE as f32))
+                }
+            })
+    }
+
+    /// Brings every layer's thumbnail up to date, taking the document's stale ones.
+    /// New layers start from their thumbhash, or are drawn right away without one.
+    pub fn update(
+        &mut self,
+        render_ctx: &RenderContext,
+        egui: &mut EguiContext,
+        scene: &SceneRenderer,
+        doc: &mut DocumentState,
+    ) {
+        let (device, queue) = (&render_ctx.device, &render_ctx.queue);
+        let egui_renderer = &mut egui.egui_renderer;
+        let (document, stale) = (&doc.document, &mut doc.stale_thumbnails);
+        let mut live_layers = HashSet::new();
+        for layer in document
+            .artboards
+            .iter()
+            .flat_map(|artboard| artboard.iter_layers())
+        {
+            live_layers.insert(layer.id);
+            if !stale.contains(&layer.id) && self.thumbnails.contains_key(&layer.id) {
+                continue;
+            }
+            let preview = layer
+                .thumbhash
+                .as_deref()
+                .filter(|_| !stale.contains(&layer.id))
+                .and_then(|hash| load_preview(&egui.egui_ctx, layer.id, hash));
+            match preview {
+                Some(handle) => {
+                    self.thumbnails.insert(layer.id, Thumbnail::Preview(handle));
+                }
+                None => self.draw_live(device, queue, egui_renderer, scene, layer.id),
+            }
+        }
+        stale.clear();
+
+        self.thumbnails.retain(|layer_id, thumbnail| {
+            let keep = live_layers.contains(layer_id);
+            if !keep && let Thumbnail::Live { id, .. } = thumbnail {
+                egui_renderer.free_texture(id);
+            }
+            keep
+        });
+    }
+
+    fn draw_live(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        egui_renderer: &mut egui_wgpu::Renderer,
+        scene: &SceneRenderer,
+        layer_id: LayerId,
+    ) {
+        // egui samples the texture itself, redrawing it is enough
+        if let Some(Thumbnail::Live { texture, .. }) = self.thumbnails.get(&layer_id) {
+            scene.draw_layer_thumbnail(device, queue, layer_id, texture);
+            return;
+        }
+
+        let texture = scene.create_thumbnail_texture(device, (THUMBNAIL_SIZE, THUMBNAIL_SIZE));
+        if !scene.draw_layer_thumbnail(device, queue, layer_id, &texture) {
+            return;
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let id = egui_renderer.register_native_texture(device, &view, wgpu::FilterMode::Linear);
+        self.thumbnails
+            .insert(layer_id, Thumbnail::Live { texture, id });
+    }
+}
+
+impl Resource for LayerThumbnails {}
+
+/// The thumbhash as an egui texture, `None` when it does not decode.
+fn load_preview(
+    egui_ctx: &egui::Context,
+    layer_id: LayerId,
+    hash: &str,
+) -> Option<egui::TextureHandle> {
+    let (width, height, pixels) = thumbhash_preview(hash)
+        .inspect_err(|err| log::warn!("layer {} thumbhash: {err:#}", layer_id.0))
+        .ok()?;
+    let image = egui::ColorImage::from_rgba_unmultiplied([width, height], &pixels);
+    Some(egui_ctx.load_texture(
+        format!("layer_thumbhash_{}", layer_id.0),
+        image,
+        egui::TextureOptions::LINEAR,
+    ))
+}
@@ -1,8 +1,13 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod autosave;
 pub mod brush_point_queue;
+pub mod brush_presets;
 pub mod brush_preview_state;
 pub mod document_state;
 pub mod frame_time;
+pub mod history;
 pub mod input_system;
 pub mod launch_options;
+pub mod layer_thumbnails;
 pub mod scene_renderer;
 pub mod stroke_state;
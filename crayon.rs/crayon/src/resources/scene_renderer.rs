@@ -1,19 +1,24 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
 use batteries::prelude::AABB;
 use cgmath::Point2;
 use wgpu::util::DeviceExt;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::texture::{TextureReadback, read_texture_rgba};
 use crate::{
     constants::{CLEAR_COLOR, WHITE},
-    document::{Document, LayerId, loader::LoadedDocument},
-    editor_state::DEFAULT_BRUSH_COLOR,
+    document::{
+        Artboard, ArtboardId, BlendMode, BrushTip, Document, Layer, LayerGroup, LayerId, LayerNode,
+        StrokeMode, VectorStroke, loader::LoadedDocument,
+    },
     renderer::{
+        brush::{DEFAULT_BRUSH_HARDNESS, decode_tip_mips, tip_png},
         camera::{Camera2D, CameraUniform},
         pipeline::CRRenderPipeline,
     },
     resource::Resource,
-    resources::stroke_state::StrokeTarget,
+    resources::{document_state::GpuOp, stroke_state::StrokeTarget},
     texture::CRTexture,
 };
 
@@ -32,6 +37,21 @@ const MAX_POINTS_PER_FRAME: usize = 1024;
 /// The two fixed quads of the merge pass: layer content, then stroke on top.
 const MERGE_QUAD_COUNT: usize = 2;
 
+/// Destination-out: scales every premultiplied channel of the layer by the stroke's missing coverage,
+/// so partially erased pixels stay premultiplied.
+const ERASE_BLEND: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct PointInstance {
@@ -40,10 +60,17 @@ pub struct PointInstance {
     pub center: [f32; 2],
     /// World space
     pub radius_px: f32,
+    /// Scales the dab's coverage, the flow of a single dab.
+    pub opacity: f32,
+    /// Straight-alpha RGBA.
+    pub color: [f32; 4],
+    /// Of the dab's tip around its center, clockwise in layer pixels, in radians.
+    pub rotation: f32,
 }
 
 impl PointInstance {
-    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -58,9 +85,11 @@ impl PointInstance {
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct PointUniform {
-    color: [f32; 4],
     layer_size: [f32; 2],
-    _padding: [f32; 2],
+    /// Non-zero when dabs sample the bound tip texture rather than the procedural circle.
+    has_tip: u32,
+    /// Where the procedural circle starts fading out, as a fraction of its radius.
+    hardness: f32,
 }
 
 /// Per-instance data for the quad compositor.
@@ -72,13 +101,29 @@ pub struct QuadInstance {
     pub size: [f32; 2],
     /// uv min.xy, max.xy, up to the scratch texture size.
     pub uv_rect: [f32; 4],
+    /// Scales the sampled premultiplied color.
+    pub opacity: f32,
+    /// `blend_mode_index`, only read by the composite pipeline.
+    pub blend_mode: u32,
 }
 
 impl QuadInstance {
     pub const FULL_UV: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
 
-    const ATTRIBS: [wgpu::VertexAttribute; 3] =
-        wgpu::vertex_attr_array![0 => Float32x2, 1=> Float32x2, 2 => Float32x4];
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x2, 1=> Float32x2, 2 => Float32x4, 3 => Float32, 4 => Uint32
+    ];
+
+    /// Opaque quad with normal blending.
+    pub fn new(origin: [f32; 2], size: [f32; 2], uv_rect: [f32; 4]) -> Self {
+        Self {
+            origin,
+            size,
+            uv_rect,
+            opacity: 1.0,
+            blend_mode: blend_mode_index(BlendMode::Normal),
+        }
+    }
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -94,8 +139,23 @@ enum QuadBinding {
     /// 1x1 opaque white artboard background.
     Background,
     Layer(LayerId),
-    /// In-progress stroke.
-    Stroke,
+    /// The layer under the in-progress stroke, composed with it in `merge_scratch`.
+    StrokePreview,
+    /// An isolated artboard composite.
+    Composite(ArtboardId),
+    /// An isolated group, flattened into the group scratch at this depth.
+    Group(usize),
+}
+
+/// Draw order inside an artboard composite.
+#[derive(Clone, Copy)]
+enum CompositeOp {
+    /// Draws the quad at this index onto the current target.
+    Quad(u32),
+    /// Targets a cleared group scratch one level deeper.
+    PushGroup,
+    /// Targets the parent again, the group's own quad follows.
+    PopGroup,
 }
 
 /// An artboard's contiguous collection of quads and scissor rect.
@@ -105,10 +165,60 @@ struct ArtboardBatch {
     count: u32,
 }
 
+/// An artboard's range of `composite_ops`, drawn into its isolated texture before the scene pass.
+struct CompositeBatch {
+    artboard: ArtboardId,
+    start_op: usize,
+    op_count: usize,
+    /// Composite onto white rather than transparent.
+    background: bool,
+}
+
+/// Transparent target an isolated group is flattened into, one per nesting depth.
+struct GroupScratch {
+    texture: CRTexture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// An artboard flattened in its own pixel space.
+/// Only kept for artboards with a blend mode that has to read what is below it.
+struct ArtboardComposite {
+    texture: CRTexture,
+    bind_group: wgpu::BindGroup,
+    _camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+}
+
 pub struct LayerGpuResources {
     pub texture: CRTexture,
     pub bind_group: wgpu::BindGroup,
     pub size: (u32, u32),
+    /// Set while a vector layer is shown above 1:1, see `set_vector_display_scale`.
+    pub display: Option<VectorDisplay>,
+}
+
+/// A vector layer rasterized above 1:1, composited in place of the layer texture so zoomed and scaled views stay crisp.
+/// The layer texture stays the 1:1 source of truth that strokes merge into and saves read back.
+pub struct VectorDisplay {
+    #[allow(unused)]
+    pub texture: CRTexture,
+    pub bind_group: wgpu::BindGroup,
+    /// Texels per artboard pixel.
+    pub scale: u32,
+}
+
+/// A tip image and its mips, bound as group 1 of the accumulate pipeline.
+struct TipTexture {
+    _texture: CRTexture,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Where vector strokes are accumulated one at a time while re-rasterizing, apart from the live stroke.
+struct VectorScratch {
+    texture: CRTexture,
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32),
 }
 
 /// GPU side of the document
@@ -124,6 +234,7 @@ pub struct SceneRenderer {
     _background_texture: CRTexture,
     background_bind_group: wgpu::BindGroup,
     pub layers: HashMap<LayerId, LayerGpuResources>,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     format: wgpu::TextureFormat,
 
@@ -131,9 +242,32 @@ pub struct SceneRenderer {
     quad_scratch: Vec<QuadInstance>,
     binding_scratch: Vec<QuadBinding>,
     batch_scratch: Vec<ArtboardBatch>,
+    composite_scratch: Vec<CompositeBatch>,
+    composite_ops: Vec<CompositeOp>,
+
+    // isolated artboard compositing
+    composite_pipeline: wgpu::RenderPipeline,
+    composites: HashMap<ArtboardId, ArtboardComposite>,
+    group_scratch: Vec<GroupScratch>,
+
+    erase_pipeline: wgpu::RenderPipeline,
+    /// How the live stroke lands in its layer, see `set_stroke_mode`.
+    stroke_mode: StrokeMode,
+
+    // Layer thumbnails
+    thumbnail_pipeline: wgpu::RenderPipeline,
+    thumbnail_sampler: wgpu::Sampler,
 
     // point accumulation
     accumulate_pipeline: wgpu::RenderPipeline,
+    /// Tips whose image loaded, the others stamp the procedural circle.
+    tips: HashMap<BrushTip, TipTexture>,
+    /// The tip of the live stroke, see `set_brush_tip`.
+    brush_tip: BrushTip,
+    /// The hardness of the live stroke's round tip, see `set_brush_hardness`.
+    brush_hardness: f32,
+    /// How much of the live stroke lands in its layer, see `set_stroke_opacity`.
+    stroke_opacity: f32,
     point_uniform: PointUniform,
     point_uniform_buffer: wgpu::Buffer,
     point_uniform_bind_group: wgpu::BindGroup,
@@ -144,7 +278,11 @@ pub struct SceneRenderer {
     stroke_scratch: CRTexture,
     stroke_bind_group: wgpu::BindGroup,
     merge_scratch: CRTexture,
+    merge_bind_group: wgpu::BindGroup,
+    backdrop_scratch: CRTexture,
+    backdrop_bind_group: wgpu::BindGroup,
     scratch_size: (u32, u32),
+    vector_scratch: Option<VectorScratch>,
 
     // merge pass
     merge_camera_uniform: CameraUniform,
@@ -230,6 +368,7 @@ impl SceneRenderer {
             &white_texture,
             "Background",
         );
+        let tips = load_tips(device, queue, &texture_bind_group_layout);
 
         let quad_instance_buffer = Self::create_quad_buffer(device, INITIAL_QUAD_CAPACITY);
 
@@ -248,10 +387,66 @@ impl SceneRenderer {
             "Quad Pipeline",
         );
 
+        // takes the stroke's coverage out of the layer instead of painting over it
+        let CRRenderPipeline {
+            pipeline: erase_pipeline,
+            ..
+        } = CRRenderPipeline::new(
+            device,
+            &[&camera_bind_group_layout, &texture_bind_group_layout],
+            &quad_shader,
+            format,
+            &[QuadInstance::desc()],
+            Some(ERASE_BLEND),
+            "Erase Pipeline",
+        );
+
+        // writes the blended result itself, over the backdrop it read
+        let composite_shader =
+            device.create_shader_module(wgpu::include_wgsl!("../renderer/shaders/composite.wgsl"));
+        let CRRenderPipeline {
+            pipeline: composite_pipeline,
+            ..
+        } = CRRenderPipeline::new(
+            device,
+            &[
+                &camera_bind_group_layout,
+                &texture_bind_group_layout,
+                &texture_bind_group_layout,
+            ],
+            &composite_shader,
+            format,
+            &[QuadInstance::desc()],
+            Some(wgpu::BlendState::REPLACE),
+            "Composite Pipeline",
+        );
+
+        // layer thumbnails go to egui, which wants gamma encoded rgba
+        let thumbnail_format = thumbnail_render_format(format);
+        let CRRenderPipeline {
+            pipeline: thumbnail_pipeline,
+            ..
+        } = CRRenderPipeline::new(
+            device,
+            &[&camera_bind_group_layout, &texture_bind_group_layout],
+            &quad_shader,
+            thumbnail_format,
+            &[QuadInstance::desc()],
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            "Thumbnail Pipeline",
+        );
+        // every halving step averages 2x2 texels
+        let thumbnail_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Thumbnail Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
         let point_uniform = PointUniform {
-            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
             layer_size: [1.0, 1.0],
-            _padding: [0.0, 0.0],
+            has_tip: 0,
+            hardness: DEFAULT_BRUSH_HARDNESS,
         };
         let point_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Point Uniform Buffer"),
@@ -262,7 +457,7 @@ impl SceneRenderer {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    // the vertex stage reads layer_size, the fragment stage color
+                    // the vertex stage reads layer_size, the fragment stage has_tip
                     visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
@@ -303,7 +498,7 @@ impl SceneRenderer {
             ..
         } = CRRenderPipeline::new(
             device,
-            &[&point_uniform_bind_group_layout],
+            &[&point_uniform_bind_group_layout, &texture_bind_group_layout],
             &point_shader,
             format,
             &[PointInstance::desc()],
@@ -323,6 +518,20 @@ impl SceneRenderer {
         );
         let merge_scratch =
             CRTexture::create_render_texture(device, (1, 1), format, "Merge Scratch");
+        let merge_bind_group = Self::texture_bind_group(
+            device,
+            &texture_bind_group_layout,
+            &merge_scratch,
+            "Merge Scratch",
+        );
+        let backdrop_scratch =
+            CRTexture::create_render_texture(device, (1, 1), format, "Backdrop Scratch");
+        let backdrop_bind_group = Self::texture_bind_group(
+            device,
+            &texture_bind_group_layout,
+            &backdrop_scratch,
+            "Backdrop Scratch",
+        );
 
         let merge_camera_uniform = CameraUniform::new();
         let merge_camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -355,12 +564,26 @@ impl SceneRenderer {
             _background_texture: white_texture,
             background_bind_group,
             layers: HashMap::new(),
+            camera_bind_group_layout,
             texture_bind_group_layout,
             format,
             quad_scratch: Vec::new(),
             binding_scratch: Vec::new(),
             batch_scratch: Vec::new(),
+            composite_scratch: Vec::new(),
+            composite_ops: Vec::new(),
+            composite_pipeline,
+            composites: HashMap::new(),
+            group_scratch: Vec::new(),
+            erase_pipeline,
+            stroke_mode: StrokeMode::Paint,
+            thumbnail_pipeline,
+            thumbnail_sampler,
             accumulate_pipeline,
+            tips,
+            brush_tip: BrushTip::Round,
+            brush_hardness: DEFAULT_BRUSH_HARDNESS,
+            stroke_opacity: 1.0,
             point_uniform,
             point_uniform_buffer,
             point_uniform_bind_group,
@@ -369,11 +592,15 @@ impl SceneRenderer {
             stroke_scratch,
             stroke_bind_group,
             merge_scratch,
+            merge_bind_group,
+            backdrop_scratch,
+            backdrop_bind_group,
             scratch_size: (1, 1),
             merge_camera_uniform,
             merge_camera_buffer,
             merge_camera_bind_group,
             merge_quad_buffer,
+            vector_scratch: None,
         }
     }
 
@@ -383,13 +610,14 @@ impl SceneRenderer {
         for artboard in &loaded.document.artboards {
             let size = artboard.pixel_size();
             max_size = (max_size.0.max(size.0), max_size.1.max(size.1));
-            for layer in &artboard.layers {
+            for layer in artboard.iter_layers() {
                 self.create_layer_resources(device, layer.id, size);
                 if let Some(pixels) = loaded.layer_pixels.get(&layer.id) {
                     let layer_gpu = &self.layers[&layer.id];
+                    let pixels = texture_byte_order(self.format, pixels);
                     queue.write_texture(
                         layer_gpu.texture.texture.as_image_copy(),
-                        pixels,
+                        &pixels,
                         wgpu::TexelCopyBufferLayout {
                             offset: 0,
                             bytes_per_row: Some(4 * size.0),
@@ -402,6 +630,7 @@ impl SceneRenderer {
                         },
                     );
                 }
+                self.rasterize_vector_layer(device, queue, layer);
             }
         }
         self.ensure_scratch(device, max_size);
@@ -426,6 +655,22 @@ impl SceneRenderer {
         );
         self.merge_scratch =
             CRTexture::create_render_texture(device, size, self.format, "Merge Scratch");
+        self.merge_bind_group = Self::texture_bind_group(
+            device,
+            &self.texture_bind_group_layout,
+            &self.merge_scratch,
+            "Merge Scratch",
+        );
+        self.backdrop_scratch =
+            CRTexture::create_render_texture(device, size, self.format, "Backdrop Scratch");
+        self.backdrop_bind_group = Self::texture_bind_group(
+            device,
+            &self.texture_bind_group_layout,
+            &self.backdrop_scratch,
+            "Backdrop Scratch",
+        );
+        // reallocated at the new size on demand
+        self.group_scratch.clear();
         self.scratch_size = size;
     }
 
@@ -444,8 +689,216 @@ impl SceneRenderer {
                 texture,
                 bind_group,
                 size,
+                display: None,
+            },
+        );
+    }
+
+    /// Reads a layer texture back as premultiplied RGBA8, `None` for unknown layers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_layer_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: LayerId,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(layer) = self.layers.get(&id) else {
+            return Ok(None);
+        };
+        self.read_pixels(device, queue, &layer.texture.texture, layer.size)
+            .map(Some)
+    }
+
+    /// Reads back a texture in this renderer's format as RGBA8, whatever the format's byte order.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        size: (u32, u32),
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut pixels = read_texture_rgba(device, queue, texture, size)?;
+        if is_bgra(self.format) {
+            swap_red_blue(&mut pixels);
+        }
+        Ok(pixels)
+    }
+
+    /// Records a copy of a layer texture on `encoder`, to be read without waiting on the GPU here,
+    /// see `LayerReadback`. `None` for unknown layers.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn copy_layer(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        id: LayerId,
+    ) -> Option<LayerReadback> {
+        let layer = self.layers.get(&id)?;
+        Some(LayerReadback {
+            readback: TextureReadback::record(device, encoder, &layer.texture.texture, layer.size),
+            bgra: is_bgra(self.format),
+        })
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// A texture for `draw_layer_thumbnail` to draw into, in the `Rgba8Unorm` egui expects.
+    pub fn create_thumbnail_texture(
+        &self,
+        device: &wgpu::Device,
+        size: (u32, u32),
+    ) -> wgpu::Texture {
+        let render_format = thumbnail_render_format(self.format);
+        device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            label: Some("Layer Thumbnail"),
+            view_formats: if render_format == wgpu::TextureFormat::Rgba8Unorm {
+                &[]
+            } else {
+                &[wgpu::TextureFormat::Rgba8UnormSrgb]
             },
+        })
+    }
+
+    /// Draws the layer fitted and centered into `target`, made by `create_thumbnail_texture`.
+    /// Halves the layer through transient textures first, so every texel still counts at the end.
+    /// `false` for unknown layers.
+    pub fn draw_layer_thumbnail(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        id: LayerId,
+        target: &wgpu::Texture,
+    ) -> bool {
+        let Some(layer) = self.layers.get(&id) else {
+            return false;
+        };
+        let render_format = thumbnail_render_format(self.format);
+        let target_size = (target.width(), target.height());
+        let fitted = fit_within(layer.size, target_size);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Thumbnail Encoder"),
+        });
+
+        let mut source = self.thumbnail_source(device, &layer.texture.view);
+        let mut source_size = layer.size;
+        while source_size.0 > fitted.0 * 2 || source_size.1 > fitted.1 * 2 {
+            let half = (
+                source_size.0.div_ceil(2).max(fitted.0),
+                source_size.1.div_ceil(2).max(fitted.1),
+            );
+            let step =
+                CRTexture::create_render_texture(device, half, render_format, "Thumbnail Step");
+            #[allow(clippy::cast_precision_loss)]
+            let quad = QuadInstance::new(
+                [0.0, 0.0],
+                [half.0 as f32, half.1 as f32],
+                QuadInstance::FULL_UV,
+            );
+            self.thumbnail_pass(device, &mut encoder, &source, (&step.view, half), quad);
+            source = self.thumbnail_source(device, &step.view);
+            source_size = half;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let quad = QuadInstance::new(
+            [
+                (target_size.0 - fitted.0) as f32 / 2.0,
+                (target_size.1 - fitted.1) as f32 / 2.0,
+            ],
+            [fitted.0 as f32, fitted.1 as f32],
+            QuadInstance::FULL_UV,
         );
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Layer Thumbnail Render View"),
+            format: Some(render_format),
+            ..Default::default()
+        });
+        self.thumbnail_pass(device, &mut encoder, &source, (&view, target_size), quad);
+        queue.submit([encoder.finish()]);
+        true
+    }
+
+    fn thumbnail_source(&self, device: &wgpu::Device, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.thumbnail_sampler),
+                },
+            ],
+            label: Some("Thumbnail Source Bind Group"),
+        })
+    }
+
+    /// Clears `target` and draws `source` over `quad`, in the target's pixel space.
+    fn thumbnail_pass(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::BindGroup,
+        (target, target_size): (&wgpu::TextureView, (u32, u32)),
+        quad: QuadInstance,
+    ) {
+        // every pass needs its own camera and quad, they are all written before the submit
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Camera Buffer"),
+            contents: bytemuck::cast_slice(&[pixel_space_uniform(target_size)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("Thumbnail Camera Bind Group"),
+        });
+        let quad_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Thumbnail Quad Buffer"),
+            contents: bytemuck::cast_slice(&[quad]),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Thumbnail Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.thumbnail_pipeline);
+        pass.set_bind_group(0, &camera_bind_group, &[]);
+        pass.set_bind_group(1, source, &[]);
+        pass.set_vertex_buffer(0, quad_buffer.slice(..));
+        pass.draw(0..6, 0..1);
     }
 
     /// Clears the reusable point staging buffer.
@@ -468,9 +921,42 @@ impl SceneRenderer {
         u32::try_from(count).unwrap_or(0)
     }
 
-    pub fn update_brush(&mut self, queue: &wgpu::Queue, color: [f32; 4]) {
-        self.point_uniform.color = color;
-        self.write_point_uniform(queue);
+    /// The tip the live stroke's dabs stamp.
+    pub fn set_brush_tip(&mut self, tip: BrushTip) {
+        self.brush_tip = tip;
+    }
+
+    /// Where the live stroke's round dabs start fading out, as a fraction of their radius.
+    pub fn set_brush_hardness(&mut self, hardness: f32) {
+        self.brush_hardness = hardness;
+    }
+
+    /// Scales the live stroke's built up coverage, in the merge and in the live preview.
+    pub fn set_stroke_opacity(&mut self, opacity: f32) {
+        self.stroke_opacity = opacity;
+    }
+
+    /// The tip texture to bind, and whether the shader samples it.
+    /// Tips without a loaded image bind the background and stamp the procedural circle.
+    fn tip_binding(&self, tip: BrushTip) -> (&wgpu::BindGroup, bool) {
+        self.tips
+            .get(&tip)
+            .map_or((&self.background_bind_group, false), |tip| {
+                (&tip.bind_group, true)
+            })
+    }
+
+    /// Whether the stroke scratch paints over its layer or erases it, in the merge and in the live preview.
+    pub fn set_stroke_mode(&mut self, mode: StrokeMode) {
+        self.stroke_mode = mode;
+    }
+
+    /// The pipeline that lands a stroke's coverage in its layer.
+    fn stroke_pipeline(&self, mode: StrokeMode) -> &wgpu::RenderPipeline {
+        match mode {
+            StrokeMode::Paint => &self.quad_pipeline,
+            StrokeMode::Erase => &self.erase_pipeline,
+        }
     }
 
     /// Stamps the frame's points into the stroke scratch in a single pass,
@@ -487,8 +973,29 @@ impl SceneRenderer {
         #[allow(clippy::cast_precision_loss)]
         let layer_size = [layer_size.0 as f32, layer_size.1 as f32];
         self.point_uniform.layer_size = layer_size;
+        self.point_uniform.has_tip = u32::from(self.tip_binding(self.brush_tip).1);
+        self.point_uniform.hardness = self.brush_hardness;
         self.write_point_uniform(queue);
 
+        self.stamp_points(
+            encoder,
+            &self.stroke_scratch.view,
+            self.tip_binding(self.brush_tip).0,
+            clear,
+            (instance_count, layer_size),
+        );
+    }
+
+    /// Draws the uploaded points into `target`, whose top-left `layer_size` texels map to the layer,
+    /// with the tip texture of `tip_bind_group`.
+    fn stamp_points(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        tip_bind_group: &wgpu::BindGroup,
+        clear: bool,
+        (instance_count, layer_size): (u32, [f32; 2]),
+    ) {
         let load = if clear {
             wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
         } else {
@@ -498,7 +1005,7 @@ impl SceneRenderer {
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Accumulate Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.stroke_scratch.view,
+                view: target,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load,
@@ -515,6 +1022,7 @@ impl SceneRenderer {
             pass.set_viewport(0.0, 0.0, layer_size[0], layer_size[1], 0.0, 1.0);
             pass.set_pipeline(&self.accumulate_pipeline);
             pass.set_bind_group(0, &self.point_uniform_bind_group, &[]);
+            pass.set_bind_group(1, tip_bind_group, &[]);
             pass.set_vertex_buffer(0, self.point_instance_buffer.slice(..));
             pass.draw(0..6, 0..instance_count);
         }
@@ -522,14 +1030,62 @@ impl SceneRenderer {
 
     /// Composites the layer and the stroke scratch into `merge_scratch`,
     /// copies the result back into the layer texture, then clears the scratch.
+    ///
+    /// The stroke lands in the layer's own pixels with normal blending at full strength,
+    /// or erases them, see `set_stroke_mode`.
+    /// Layer opacity and blend mode stay non-destructive and apply when the layer is composited,
+    /// exactly as they did to the live preview in `render`.
     pub fn merge_stroke_into_layer(
         &mut self,
         queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
         id: LayerId,
     ) {
-        let Some(layer) = self.layers.get(&id) else {
+        if !self.compose_stroke(queue, encoder, id) {
             return;
+        }
+        let layer = &self.layers[&id];
+        let (width, height) = layer.size;
+
+        // this is okay as the texture formats and extends are identical, and the destination is never sampled in the same pass.
+        encoder.copy_texture_to_texture(
+            self.merge_scratch.texture.as_image_copy(),
+            layer.texture.texture.as_image_copy(),
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let _clear = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Stroke Scratch Clear Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.stroke_scratch.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+    }
+
+    /// Draws the layer and the stroke scratch on top into `merge_scratch`, in the layer's pixel space.
+    /// An erasing stroke takes its coverage out of the layer instead.
+    /// `false` for unknown layers.
+    fn compose_stroke(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        id: LayerId,
+    ) -> bool {
+        let Some(layer) = self.layers.get(&id) else {
+            return false;
         };
         let (width, height) = layer.size;
         #[allow(clippy::cast_precision_loss)]
@@ -537,10 +1093,7 @@ impl SceneRenderer {
         #[allow(clippy::cast_precision_loss)]
         let (scratch_w, scratch_h) = (self.scratch_size.0 as f32, self.scratch_size.1 as f32);
 
-        // Pixel-to-NDC ortho over the layer's own pixel space.
-        let mut ortho = Camera2D::with_viewport(w, h);
-        ortho.center_on(Point2::new(w / 2.0, h / 2.0));
-        self.merge_camera_uniform.update_view_projection(&ortho);
+        self.merge_camera_uniform = pixel_space_uniform(layer.size);
         queue.write_buffer(
             &self.merge_camera_buffer,
             0,
@@ -550,15 +1103,10 @@ impl SceneRenderer {
         // Layer content, then the stroke on top.
         // Scratch texels map 1:1 to layer texels but the scratch may be larger.
         let merge_quads = [
+            QuadInstance::new([0.0, 0.0], [w, h], QuadInstance::FULL_UV),
             QuadInstance {
-                origin: [0.0, 0.0],
-                size: [w, h],
-                uv_rect: QuadInstance::FULL_UV,
-            },
-            QuadInstance {
-                origin: [0.0, 0.0],
-                size: [w, h],
-                uv_rect: [0.0, 0.0, w / scratch_w, h / scratch_h],
+                opacity: self.stroke_opacity,
+                ..QuadInstance::new([0.0, 0.0], [w, h], [0.0, 0.0, w / scratch_w, h / scratch_h])
             },
         ];
         queue.write_buffer(
@@ -567,47 +1115,10 @@ impl SceneRenderer {
             bytemuck::cast_slice(&merge_quads),
         );
 
-        {
-            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Merge Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.merge_scratch.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                        store: wgpu::StoreOp::Store,
-                    },
-                    depth_slice: None,
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-            pass.set_viewport(0.0, 0.0, w, h, 0.0, 1.0);
-            pass.set_pipeline(&self.quad_pipeline);
-            pass.set_bind_group(0, &self.merge_camera_bind_group, &[]);
-            pass.set_vertex_buffer(0, self.merge_quad_buffer.slice(..));
-            pass.set_bind_group(1, &layer.bind_group, &[]);
-            pass.draw(0..6, 0..1);
-            pass.set_bind_group(1, &self.stroke_bind_group, &[]);
-            pass.draw(0..6, 1..2);
-        }
-
-        // this is okay as the texture formats and extends are identical, and the destination is never sampled in the same pass.
-        encoder.copy_texture_to_texture(
-            self.merge_scratch.texture.as_image_copy(),
-            layer.texture.texture.as_image_copy(),
-            wgpu::Extent3d {
-                width,
-                height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        let _clear = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Stroke Scratch Clear Pass"),
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Merge Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &self.stroke_scratch.view,
+                view: &self.merge_scratch.view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
@@ -619,31 +1130,176 @@ impl SceneRenderer {
             occlusion_query_set: None,
             timestamp_writes: None,
         });
+        pass.set_viewport(0.0, 0.0, w, h, 0.0, 1.0);
+        pass.set_pipeline(&self.quad_pipeline);
+        pass.set_bind_group(0, &self.merge_camera_bind_group, &[]);
+        pass.set_vertex_buffer(0, self.merge_quad_buffer.slice(..));
+        pass.set_bind_group(1, &layer.bind_group, &[]);
+        pass.draw(0..6, 0..1);
+        pass.set_pipeline(self.stroke_pipeline(self.stroke_mode));
+        pass.set_bind_group(1, &self.stroke_bind_group, &[]);
+        pass.draw(0..6, 1..2);
+        true
     }
 
-    fn write_point_uniform(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(
-            &self.point_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.point_uniform]),
-        );
+    /// Redraws a vector layer's texture from its strokes at 1:1. Raster layers are left alone.
+    pub fn rasterize_vector_layer(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layer: &Layer,
+    ) {
+        let (Some(strokes), Some(layer_gpu)) = (&layer.strokes, self.layers.get(&layer.id)) else {
+            return;
+        };
+        let target = layer_gpu.texture.view.clone();
+        let size = layer_gpu.size;
+        self.draw_strokes(device, queue, &target, size, 1, strokes);
     }
 
-    pub fn clear_layer(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, id: LayerId) {
-        let Some(layer) = self.layers.get(&id) else {
+    /// Shows a vector layer rasterized at `scale` texels per artboard pixel, re-rasterizing only when the scale changes.
+    /// Scale 1 falls back to the layer texture, which merges keep current.
+    pub fn set_vector_display_scale(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layer: &Layer,
+        scale: u32,
+    ) {
+        let Some(strokes) = &layer.strokes else {
             return;
         };
-        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Clear Layer Encoder"),
-        });
+        let Some(layer_gpu) = self.layers.get_mut(&layer.id) else {
+            return;
+        };
+        if scale <= 1 {
+            layer_gpu.display = None;
+            return;
+        }
+        if layer_gpu
+            .display
+            .as_ref()
+            .is_some_and(|display| display.scale == scale)
         {
-            let _pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Clear Layer Pass"),
+            return;
+        }
+
+        let size = (layer_gpu.size.0 * scale, layer_gpu.size.1 * scale);
+        let label = format!("Layer {} Display", layer.id.0);
+        let texture = CRTexture::create_render_texture(device, size, self.format, &label);
+        let bind_group =
+            Self::texture_bind_group(device, &self.texture_bind_group_layout, &texture, &label);
+        let target = texture.view.clone();
+        layer_gpu.display = Some(VectorDisplay {
+            texture,
+            bind_group,
+            scale,
+        });
+        self.draw_strokes(device, queue, &target, size, scale, strokes);
+    }
+
+    /// Texels per artboard pixel the layer is shown at.
+    pub fn vector_display_scale(&self, id: LayerId) -> u32 {
+        self.layers
+            .get(&id)
+            .and_then(|layer| layer.display.as_ref())
+            .map_or(1, |display| display.scale)
+    }
+
+    /// Drops a vector layer's display after its strokes changed, so it shows the layer texture until rasterized again.
+    pub fn invalidate_vector_display(&mut self, id: LayerId) {
+        if let Some(layer) = self.layers.get_mut(&id) {
+            layer.display = None;
+        }
+    }
+
+    /// Clears `target` and replays `strokes` into it in order.
+    /// Each stroke is accumulated on its own and then composited over the ones before, as a live stroke merges into its layer,
+    /// with positions and radii multiplied by `scale`.
+    ///
+    /// Submits as it goes, since every batch of points reuses the point instance buffer.
+    fn draw_strokes(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target: &wgpu::TextureView,
+        size: (u32, u32),
+        scale: u32,
+        strokes: &[VectorStroke],
+    ) {
+        let scratch_uv = self.ensure_vector_scratch(device, size);
+        #[allow(clippy::cast_precision_loss)]
+        let (width, height, scale) = (size.0 as f32, size.1 as f32, scale as f32);
+        self.point_uniform.layer_size = [width, height];
+
+        self.merge_camera_uniform = pixel_space_uniform(size);
+        queue.write_buffer(
+            &self.merge_camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.merge_camera_uniform]),
+        );
+
+        let new_encoder = || {
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Vector Raster Encoder"),
+            })
+        };
+        let mut encoder = new_encoder();
+        clear_texture(&mut encoder, target, "Vector Raster Clear Pass");
+
+        for stroke in strokes.iter().filter(|stroke| !stroke.points.is_empty()) {
+            let has_tip = self.tip_binding(stroke.tip).1;
+            self.point_uniform.has_tip = u32::from(has_tip);
+            self.point_uniform.hardness = stroke.hardness;
+            self.write_point_uniform(queue);
+            // submitted with the stroke's composite below, before the next stroke rewrites it
+            queue.write_buffer(
+                &self.merge_quad_buffer,
+                0,
+                bytemuck::cast_slice(&[QuadInstance {
+                    opacity: stroke.opacity,
+                    ..QuadInstance::new([0.0, 0.0], [width, height], scratch_uv)
+                }]),
+            );
+            for (batch, points) in stroke.points.chunks(MAX_POINTS_PER_FRAME).enumerate() {
+                let instances: Vec<PointInstance> = points
+                    .iter()
+                    .map(|point| PointInstance {
+                        center: [
+                            point.position[0] * scale / (width * 0.5) - 1.0,
+                            1.0 - point.position[1] * scale / (height * 0.5),
+                        ],
+                        radius_px: point.radius * scale,
+                        opacity: point.opacity,
+                        color: stroke.color,
+                        rotation: point.rotation,
+                    })
+                    .collect();
+                queue.write_buffer(
+                    &self.point_instance_buffer,
+                    0,
+                    bytemuck::cast_slice(&instances),
+                );
+                let scratch = self.vector_scratch.as_ref().expect("ensured above");
+                #[allow(clippy::cast_possible_truncation)]
+                self.stamp_points(
+                    &mut encoder,
+                    &scratch.texture.view,
+                    self.tip_binding(stroke.tip).0,
+                    batch == 0,
+                    (instances.len() as u32, [width, height]),
+                );
+                queue.submit([std::mem::replace(&mut encoder, new_encoder()).finish()]);
+            }
+
+            let scratch = self.vector_scratch.as_ref().expect("ensured above");
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Vector Stroke Composite Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &layer.texture.view,
+                    view: target,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        load: wgpu::LoadOp::Load,
                         store: wgpu::StoreOp::Store,
                     },
                     depth_slice: None,
@@ -652,10 +1308,143 @@ impl SceneRenderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
+            pass.set_viewport(0.0, 0.0, width, height, 0.0, 1.0);
+            pass.set_pipeline(self.stroke_pipeline(stroke.mode));
+            pass.set_bind_group(0, &self.merge_camera_bind_group, &[]);
+            pass.set_vertex_buffer(0, self.merge_quad_buffer.slice(..));
+            pass.set_bind_group(1, &scratch.bind_group, &[]);
+            pass.draw(0..6, 0..1);
+            drop(pass);
+            queue.submit([std::mem::replace(&mut encoder, new_encoder()).finish()]);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Grows the vector scratch to hold `size`, returning the uv rect of its top-left `size` texels.
+    fn ensure_vector_scratch(&mut self, device: &wgpu::Device, size: (u32, u32)) -> [f32; 4] {
+        let current = self
+            .vector_scratch
+            .as_ref()
+            .map_or((0, 0), |scratch| scratch.size);
+        if current.0 < size.0 || current.1 < size.1 {
+            let grown = (current.0.max(size.0), current.1.max(size.1));
+            let texture =
+                CRTexture::create_render_texture(device, grown, self.format, "Vector Scratch");
+            let bind_group = Self::texture_bind_group(
+                device,
+                &self.texture_bind_group_layout,
+                &texture,
+                "Vector Scratch",
+            );
+            self.vector_scratch = Some(VectorScratch {
+                texture,
+                bind_group,
+                size: grown,
+            });
         }
+        let scratch_size = self
+            .vector_scratch
+            .as_ref()
+            .map_or(size, |scratch| scratch.size);
+        #[allow(clippy::cast_precision_loss)]
+        [
+            0.0,
+            0.0,
+            size.0 as f32 / scratch_size.0 as f32,
+            size.1 as f32 / scratch_size.1 as f32,
+        ]
+    }
+
+    fn write_point_uniform(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.point_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.point_uniform]),
+        );
+    }
+
+    pub fn clear_layer(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, id: LayerId) {
+        let Some(layer) = self.layers.get_mut(&id) else {
+            return;
+        };
+        layer.display = None;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Clear Layer Encoder"),
+        });
+        clear_texture(&mut encoder, &layer.texture.view, "Clear Layer Pass");
         queue.submit(std::iter::once(encoder.finish()));
     }
 
+    /// Runs a structural change on the layer textures. Ops on layers it doesn't have are ignored.
+    pub fn apply_gpu_op(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, op: GpuOp) {
+        match op {
+            GpuOp::ClearLayer { layer_id } => self.clear_layer(device, queue, layer_id),
+            GpuOp::CreateLayer { layer_id, size } => {
+                self.ensure_scratch(device, size);
+                self.create_layer_resources(device, layer_id, size);
+            }
+            GpuOp::DeleteLayer { layer_id } => {
+                self.layers.remove(&layer_id);
+            }
+            GpuOp::CopyLayer { source, layer_id } => {
+                let Some(size) = self.layers.get(&source).map(|layer| layer.size) else {
+                    return;
+                };
+                self.create_layer_resources(device, layer_id, size);
+                self.copy_layer_pixels(device, queue, source, layer_id);
+            }
+            GpuOp::ResizeLayer {
+                layer_id,
+                size,
+                shift,
+            } => {
+                let Some(previous) = self.layers.remove(&layer_id) else {
+                    return;
+                };
+                if previous.size == size && shift == [0, 0] {
+                    self.layers.insert(layer_id, previous);
+                    return;
+                }
+                self.ensure_scratch(device, size);
+                self.create_layer_resources(device, layer_id, size);
+                let resized = &self.layers[&layer_id];
+                copy_texture_shifted(
+                    device,
+                    queue,
+                    (&previous.texture.texture, previous.size),
+                    (&resized.texture.texture, size),
+                    shift,
+                );
+            }
+        }
+    }
+
+    fn copy_layer_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source: LayerId,
+        destination: LayerId,
+    ) {
+        let (Some(source), Some(destination)) =
+            (self.layers.get(&source), self.layers.get(&destination))
+        else {
+            return;
+        };
+        copy_texture_shifted(
+            device,
+            queue,
+            (&source.texture.texture, source.size),
+            (&destination.texture.texture, destination.size),
+            [0, 0],
+        );
+    }
+
+    /// Draws every visible artboard through `camera`.
+    ///
+    /// Artboards whose layers all blend normally are drawn straight into `target`.
+    /// Any other blend mode needs the pixels below it, and isolated groups need flattening on their own,
+    /// so such an artboard is first composited into its own texture, see `draw_composite`.
     pub fn render(
         &mut self,
         device: &wgpu::Device,
@@ -666,6 +1455,77 @@ impl SceneRenderer {
         document: &Document,
         camera: &Camera2D,
         active_stroke: Option<StrokeTarget>,
+    ) {
+        // Culled artboards keep their composite so panning back doesn't reallocate.
+        self.composites.retain(|id, _| {
+            document
+                .artboard(*id)
+                .is_some_and(|artboard| needs_isolation(&artboard.layers))
+        });
+
+        // The stroke is previewed inside its layer, so the layer's opacity and blend mode apply to both.
+        let preview_layer = active_stroke
+            .map(|(_, layer_id)| layer_id)
+            .filter(|layer_id| self.compose_stroke(queue, encoder, *layer_id));
+
+        self.draw_artboards(
+            device,
+            queue,
+            encoder,
+            target,
+            target_size,
+            &document.artboards,
+            camera,
+            preview_layer,
+            true,
+        );
+    }
+
+    /// Renders `artboard` alone into a `target` of its pixel size times `scale`, e.g. for exports.
+    /// Without `background` the white artboard fill is left out, leaving the layers' own alpha.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_artboard(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        artboard: &Artboard,
+        scale: u32,
+        background: bool,
+    ) {
+        let (width, height) = artboard.pixel_size();
+        #[allow(clippy::cast_precision_loss)]
+        let camera = Camera2D::framing(
+            artboard.position,
+            [width as f32, height as f32],
+            scale as f32,
+        );
+        self.draw_artboards(
+            device,
+            queue,
+            encoder,
+            target,
+            (width * scale, height * scale),
+            std::slice::from_ref(artboard),
+            &camera,
+            None,
+            background,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_artboards(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        target_size: (u32, u32),
+        artboards: &[Artboard],
+        camera: &Camera2D,
+        preview_layer: Option<LayerId>,
+        background: bool,
     ) {
         self.camera_uniform.update_view_projection(camera);
         queue.write_buffer(
@@ -677,9 +1537,11 @@ impl SceneRenderer {
         self.quad_scratch.clear();
         self.binding_scratch.clear();
         self.batch_scratch.clear();
+        self.composite_scratch.clear();
+        self.composite_ops.clear();
 
         let visible = camera.viewport_world_rect();
-        for artboard in &document.artboards {
+        for artboard in artboards {
             let rect = AABB::from_origin_and_size(artboard.position, artboard.size);
             if !visible.intersects(&rect) {
                 continue;
@@ -689,49 +1551,60 @@ impl SceneRenderer {
                 continue;
             };
 
+            if needs_isolation(&artboard.layers) {
+                self.ensure_composite(device, artboard);
+                let mut ops = std::mem::take(&mut self.composite_ops);
+                let start_op = ops.len();
+                self.push_composite_quads(
+                    device,
+                    &artboard.layers,
+                    artboard.size,
+                    preview_layer,
+                    &mut ops,
+                    0,
+                );
+                self.composite_scratch.push(CompositeBatch {
+                    artboard: artboard.id,
+                    start_op,
+                    op_count: ops.len() - start_op,
+                    background,
+                });
+                self.composite_ops = ops;
+
+                #[allow(clippy::cast_possible_truncation)]
+                let start_idx = self.quad_scratch.len() as u32;
+                self.quad_scratch.push(QuadInstance::new(
+                    artboard.position,
+                    artboard.size,
+                    QuadInstance::FULL_UV,
+                ));
+                self.binding_scratch
+                    .push(QuadBinding::Composite(artboard.id));
+                self.batch_scratch.push(ArtboardBatch {
+                    scissor,
+                    start_idx,
+                    count: 1,
+                });
+                continue;
+            }
+
             #[allow(clippy::cast_possible_truncation)]
             let start_idx = self.quad_scratch.len() as u32;
 
-            self.quad_scratch.push(QuadInstance {
-                origin: artboard.position,
-                size: artboard.size,
-                uv_rect: QuadInstance::FULL_UV,
-            });
-            self.binding_scratch.push(QuadBinding::Background);
-
-            // Bottom-to-top
-            for layer in artboard.layers.iter().filter(|layer| layer.visible) {
-                let Some(layer_gpu) = self.layers.get(&layer.id) else {
-                    continue;
-                };
-                let origin = [
-                    artboard.position[0] + layer.offset[0],
-                    artboard.position[1] + layer.offset[1],
-                ];
-
-                self.quad_scratch.push(QuadInstance {
-                    origin,
-                    size: artboard.size,
-                    uv_rect: QuadInstance::FULL_UV,
-                });
-                self.binding_scratch.push(QuadBinding::Layer(layer.id));
-
-                if active_stroke == Some((artboard.id, layer.id)) {
-                    let uv_max = (
-                        layer_gpu.size.0 as f32 / self.scratch_size.0 as f32,
-                        layer_gpu.size.1 as f32 / self.scratch_size.1 as f32,
-                    );
-                    self.quad_scratch.push(QuadInstance {
-                        origin: [
-                            artboard.position[0] + layer.offset[0],
-                            artboard.position[1] + layer.offset[1],
-                        ],
-                        size: artboard.size,
-                        uv_rect: [0.0, 0.0, uv_max.0, uv_max.1],
-                    });
-                    self.binding_scratch.push(QuadBinding::Stroke);
-                };
+            if background {
+                self.quad_scratch.push(QuadInstance::new(
+                    artboard.position,
+                    artboard.size,
+                    QuadInstance::FULL_UV,
+                ));
+                self.binding_scratch.push(QuadBinding::Background);
             }
+            self.push_layer_quads(
+                &artboard.layers,
+                artboard.size,
+                artboard.position,
+                preview_layer,
+            );
 
             #[allow(clippy::cast_possible_truncation)]
             let count = self.quad_scratch.len() as u32 - start_idx;
@@ -744,13 +1617,31 @@ impl SceneRenderer {
 
         self.upload_quads(device, queue);
 
+        for batch in &self.composite_scratch {
+            self.draw_composite(encoder, batch);
+        }
+        let clear = if background {
+            CLEAR_COLOR
+        } else {
+            wgpu::Color::TRANSPARENT
+        };
+        self.draw_scene(encoder, target, clear);
+    }
+
+    /// Draws the artboard batches onto `target` with their scissors.
+    fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        clear: wgpu::Color,
+    ) {
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Scene Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view: target,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(CLEAR_COLOR),
+                    load: wgpu::LoadOp::Clear(clear),
                     store: wgpu::StoreOp::Store,
                 },
                 depth_slice: None,
@@ -768,17 +1659,298 @@ impl SceneRenderer {
             let (x, y, width, height) = batch.scissor;
             pass.set_scissor_rect(x, y, width, height);
             for index in batch.start_idx..batch.start_idx + batch.count {
-                let bind_group = match &self.binding_scratch[index as usize] {
-                    QuadBinding::Background => &self.background_bind_group,
-                    QuadBinding::Layer(id) => &self.layers[id].bind_group,
-                    QuadBinding::Stroke => &self.stroke_bind_group,
-                };
-                pass.set_bind_group(1, bind_group, &[]);
+                pass.set_bind_group(1, self.quad_bind_group(index), &[]);
                 pass.draw(0..6, index..index + 1);
             }
         }
     }
 
+    /// Bottom-to-top quads for the drawn layers of `nodes`, offset from `origin`.
+    /// Only for stacks without isolation, so groups pass their children straight through.
+    fn push_layer_quads(
+        &mut self,
+        nodes: &[LayerNode],
+        size: [f32; 2],
+        origin: [f32; 2],
+        preview_layer: Option<LayerId>,
+    ) {
+        for node in nodes {
+            match node {
+                LayerNode::Layer(layer) if is_drawn(layer) => {
+                    self.push_layer_quad(layer, size, origin, preview_layer);
+                }
+                LayerNode::Group(group) if is_group_drawn(group) => {
+                    self.push_layer_quads(&group.children, size, origin, preview_layer);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Like `push_layer_quads` in artboard pixel space, recording the order to draw them in `ops`.
+    /// Isolated groups are flattened into the group scratch one level below `depth`,
+    /// then drawn onto their parent as a single quad.
+    fn push_composite_quads(
+        &mut self,
+        device: &wgpu::Device,
+        nodes: &[LayerNode],
+        size: [f32; 2],
+        preview_layer: Option<LayerId>,
+        ops: &mut Vec<CompositeOp>,
+        depth: usize,
+    ) {
+        for node in nodes {
+            match node {
+                LayerNode::Layer(layer) if is_drawn(layer) => {
+                    if let Some(index) =
+                        self.push_layer_quad(layer, size, [0.0, 0.0], preview_layer)
+                    {
+                        ops.push(CompositeOp::Quad(index));
+                    }
+                }
+                LayerNode::Group(group) if is_group_drawn(group) && group.is_isolated() => {
+                    self.ensure_group_scratch(device, depth);
+                    ops.push(CompositeOp::PushGroup);
+                    self.push_composite_quads(
+                        device,
+                        &group.children,
+                        size,
+                        preview_layer,
+                        ops,
+                        depth + 1,
+                    );
+                    ops.push(CompositeOp::PopGroup);
+
+                    #[allow(clippy::cast_possible_truncation)]
+                    let index = self.quad_scratch.len() as u32;
+                    self.quad_scratch.push(QuadInstance {
+                        origin: [0.0, 0.0],
+                        size,
+                        uv_rect: self.scratch_uv(size),
+                        opacity: group.opacity.clamp(0.0, 1.0),
+                        blend_mode: blend_mode_index(group.blend_mode),
+                    });
+                    self.binding_scratch.push(QuadBinding::Group(depth));
+                    ops.push(CompositeOp::Quad(index));
+                }
+                LayerNode::Group(group) if is_group_drawn(group) => {
+                    self.push_composite_quads(
+                        device,
+                        &group.children,
+                        size,
+                        preview_layer,
+                        ops,
+                        depth,
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Quad for one layer, `None` when it has no GPU resources.
+    fn push_layer_quad(
+        &mut self,
+        layer: &Layer,
+        size: [f32; 2],
+        origin: [f32; 2],
+        preview_layer: Option<LayerId>,
+    ) -> Option<u32> {
+        let layer_gpu = self.layers.get(&layer.id)?;
+
+        let (uv_rect, binding) = if preview_layer == Some(layer.id) {
+            #[allow(clippy::cast_precision_loss)]
+            let layer_size = [layer_gpu.size.0 as f32, layer_gpu.size.1 as f32];
+            (self.scratch_uv(layer_size), QuadBinding::StrokePreview)
+        } else {
+            (QuadInstance::FULL_UV, QuadBinding::Layer(layer.id))
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let index = self.quad_scratch.len() as u32;
+        self.quad_scratch.push(QuadInstance {
+            origin: [origin[0] + layer.offset[0], origin[1] + layer.offset[1]],
+            size,
+            uv_rect,
+            opacity: layer.opacity.clamp(0.0, 1.0),
+            blend_mode: blend_mode_index(layer.blend_mode),
+        });
+        self.binding_scratch.push(binding);
+        Some(index)
+    }
+
+    /// uv rect of the top-left `size` texels of a scratch texture.
+    fn scratch_uv(&self, size: [f32; 2]) -> [f32; 4] {
+        #[allow(clippy::cast_precision_loss)]
+        let (scratch_w, scratch_h) = (self.scratch_size.0 as f32, self.scratch_size.1 as f32);
+        [0.0, 0.0, size[0] / scratch_w, size[1] / scratch_h]
+    }
+
+    /// Makes sure groups nested `depth` deep have a scratch to flatten into.
+    fn ensure_group_scratch(&mut self, device: &wgpu::Device, depth: usize) {
+        while self.group_scratch.len() <= depth {
+            let label = format!("Group Scratch {}", self.group_scratch.len());
+            let texture =
+                CRTexture::create_render_texture(device, self.scratch_size, self.format, &label);
+            let bind_group =
+                Self::texture_bind_group(device, &self.texture_bind_group_layout, &texture, &label);
+            self.group_scratch.push(GroupScratch {
+                texture,
+                bind_group,
+            });
+        }
+    }
+
+    /// (Re)allocates the artboard's composite texture when missing or resized.
+    fn ensure_composite(&mut self, device: &wgpu::Device, artboard: &Artboard) {
+        let size = artboard.pixel_size();
+        if self
+            .composites
+            .get(&artboard.id)
+            .is_some_and(|composite| composite.size == size)
+        {
+            return;
+        }
+
+        let label = format!("Artboard {} Composite", artboard.id.0);
+        let texture = CRTexture::create_render_texture(device, size, self.format, &label);
+        let bind_group =
+            Self::texture_bind_group(device, &self.texture_bind_group_layout, &texture, &label);
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label} Camera Buffer")),
+            contents: bytemuck::cast_slice(&[pixel_space_uniform(size)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some(&format!("{label} Camera Bind Group")),
+        });
+        self.composites.insert(
+            artboard.id,
+            ArtboardComposite {
+                texture,
+                bind_group,
+                _camera_buffer: camera_buffer,
+                camera_bind_group,
+                size,
+            },
+        );
+    }
+
+    /// Flattens the batch onto white, or transparent, in the artboard's composite texture.
+    ///
+    /// Normal quads blend in fixed function. Every other quad ends the pass, the target so far
+    /// is copied into the backdrop scratch, and a new pass blends the quad over it in the shader.
+    /// Isolated groups switch the target to a cleared group scratch until they are popped.
+    fn draw_composite(&self, encoder: &mut wgpu::CommandEncoder, batch: &CompositeBatch) {
+        let composite = &self.composites[&batch.artboard];
+        let (width, height) = composite.size;
+        #[allow(clippy::cast_precision_loss)]
+        let (w, h) = (width as f32, height as f32);
+        let target = |depth: usize| match depth {
+            0 => &composite.texture,
+            _ => &self.group_scratch[depth - 1].texture,
+        };
+
+        let ops = &self.composite_ops[batch.start_op..batch.start_op + batch.op_count];
+        let normal = blend_mode_index(BlendMode::Normal);
+        let mut next = 0;
+        let mut depth = 0;
+        let mut load = wgpu::LoadOp::Clear(if batch.background {
+            wgpu::Color::WHITE
+        } else {
+            wgpu::Color::TRANSPARENT
+        });
+        let mut backdrop_current = false;
+
+        loop {
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Artboard Composite Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target(depth).view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+                // group scratch can be larger than the artboard
+                pass.set_viewport(0.0, 0.0, w, h, 0.0, 1.0);
+                pass.set_bind_group(0, &composite.camera_bind_group, &[]);
+                pass.set_vertex_buffer(0, self.quad_instance_buffer.slice(..));
+
+                while let Some(CompositeOp::Quad(index)) = ops.get(next) {
+                    let index = *index;
+                    if self.quad_scratch[index as usize].blend_mode == normal {
+                        pass.set_pipeline(&self.quad_pipeline);
+                    } else if backdrop_current {
+                        backdrop_current = false;
+                        pass.set_pipeline(&self.composite_pipeline);
+                        pass.set_bind_group(2, &self.backdrop_bind_group, &[]);
+                    } else {
+                        break;
+                    }
+                    pass.set_bind_group(1, self.quad_bind_group(index), &[]);
+                    pass.draw(0..6, index..index + 1);
+                    next += 1;
+                }
+            }
+
+            match ops.get(next) {
+                None => break,
+                Some(CompositeOp::Quad(_)) => {
+                    encoder.copy_texture_to_texture(
+                        target(depth).texture.as_image_copy(),
+                        self.backdrop_scratch.texture.as_image_copy(),
+                        wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                    backdrop_current = true;
+                    load = wgpu::LoadOp::Load;
+                }
+                Some(CompositeOp::PushGroup) => {
+                    depth += 1;
+                    load = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+                    next += 1;
+                }
+                Some(CompositeOp::PopGroup) => {
+                    depth -= 1;
+                    load = wgpu::LoadOp::Load;
+                    next += 1;
+                }
+            }
+        }
+    }
+
+    fn quad_bind_group(&self, index: u32) -> &wgpu::BindGroup {
+        match &self.binding_scratch[index as usize] {
+            QuadBinding::Background => &self.background_bind_group,
+            QuadBinding::Layer(id) => {
+                let layer = &self.layers[id];
+                layer
+                    .display
+                    .as_ref()
+                    .map_or(&layer.bind_group, |display| &display.bind_group)
+            }
+            QuadBinding::StrokePreview => &self.merge_bind_group,
+            QuadBinding::Composite(id) => &self.composites[id].bind_group,
+            QuadBinding::Group(depth) => &self.group_scratch[*depth].bind_group,
+        }
+    }
+
     fn upload_quads(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
         if self.quad_scratch.is_empty() {
             return;
@@ -828,6 +2000,281 @@ impl SceneRenderer {
 
 impl Resource for SceneRenderer {}
 
+/// Index of `mode` in `composite.wgsl`.
+/// Uploads every bundled tip image with its mips, as single channel textures.
+/// A tip that fails to decode is left out and stamps the procedural circle instead.
+fn load_tips(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> HashMap<BrushTip, TipTexture> {
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Brush Tip Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let mut tips = HashMap::new();
+    for tip in BrushTip::ALL {
+        let Some(png) = tip_png(tip) else {
+            continue;
+        };
+        let mips = match decode_tip_mips(png) {
+            Ok(mips) => mips,
+            Err(err) => {
+                log::warn!("brush tip {tip:?} falls back to round: {err:#}");
+                continue;
+            }
+        };
+        let (width, height) = mips[0].dimensions();
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(&format!("Brush Tip {tip:?}")),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            #[allow(clippy::cast_possible_truncation)]
+            mip_level_count: mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (level, mip) in (0..).zip(&mips) {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                mip.as_raw(),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(mip.width()),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d {
+                    width: mip.width(),
+                    height: mip.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        let texture = CRTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler: sampler.clone(),
+            texture,
+        };
+        let bind_group = SceneRenderer::texture_bind_group(
+            device,
+            layout,
+            &texture,
+            &format!("Brush Tip {tip:?}"),
+        );
+        tips.insert(
+            tip,
+            TipTexture {
+                _texture: texture,
+                bind_group,
+            },
+        );
+    }
+    tips
+}
+
+fn blend_mode_index(mode: BlendMode) -> u32 {
+    match mode {
+        BlendMode::Normal => 0,
+        BlendMode::Multiply => 1,
+        BlendMode::Screen => 2,
+        BlendMode::Overlay => 3,
+        BlendMode::Add => 4,
+        BlendMode::Darken => 5,
+        BlendMode::Lighten => 6,
+    }
+}
+
+fn is_drawn(layer: &Layer) -> bool {
+    layer.visible && layer.opacity > 0.0
+}
+
+fn is_group_drawn(group: &LayerGroup) -> bool {
+    group.visible && group.opacity > 0.0
+}
+
+/// Whether a drawn layer blends with something other than source-over, or a drawn group is isolated.
+fn needs_isolation(nodes: &[LayerNode]) -> bool {
+    nodes.iter().any(|node| match node {
+        LayerNode::Layer(layer) => is_drawn(layer) && layer.blend_mode != BlendMode::Normal,
+        LayerNode::Group(group) => {
+            is_group_drawn(group) && (group.is_isolated() || needs_isolation(&group.children))
+        }
+    })
+}
+
+/// Clears `target` to transparent.
+fn clear_texture(encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, label: &str) {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+            depth_slice: None,
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+}
+
+/// Pixel-to-NDC ortho over a `size` texture, origin top-left.
+/// Copies what overlaps of two textures, both anchored at their top left corner.
+/// Copies `source` into `destination` with its top left corner at `shift`, clipped to both textures.
+fn copy_texture_shifted(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    (source, source_size): (&wgpu::Texture, (u32, u32)),
+    (destination, destination_size): (&wgpu::Texture, (u32, u32)),
+    shift: [i32; 2],
+) {
+    // (source start, destination start, length) of the overlap along one axis
+    let overlap = |shift: i32, source: u32, destination: u32| {
+        let source_start = shift.min(0).unsigned_abs();
+        let destination_start = shift.max(0).unsigned_abs();
+        let length = source
+            .saturating_sub(source_start)
+            .min(destination.saturating_sub(destination_start));
+        (source_start, destination_start, length)
+    };
+    let (source_x, destination_x, width) = overlap(shift[0], source_size.0, destination_size.0);
+    let (source_y, destination_y, height) = overlap(shift[1], source_size.1, destination_size.1);
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Copy Layer Encoder"),
+    });
+    encoder.copy_texture_to_texture(
+        wgpu::TexelCopyTextureInfo {
+            origin: wgpu::Origin3d {
+                x: source_x,
+                y: source_y,
+                z: 0,
+            },
+            ..source.as_image_copy()
+        },
+        wgpu::TexelCopyTextureInfo {
+            origin: wgpu::Origin3d {
+                x: destination_x,
+                y: destination_y,
+                z: 0,
+            },
+            ..destination.as_image_copy()
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+fn pixel_space_uniform(size: (u32, u32)) -> CameraUniform {
+    #[allow(clippy::cast_precision_loss)]
+    let (w, h) = (size.0 as f32, size.1 as f32);
+    let mut ortho = Camera2D::with_viewport(w, h);
+    ortho.center_on(Point2::new(w / 2.0, h / 2.0));
+    let mut uniform = CameraUniform::new();
+    uniform.update_view_projection(&ortho);
+    uniform
+}
+
+/// What thumbnails are rendered as: srgb encoded like the scene is, so egui reads gamma values either way.
+fn thumbnail_render_format(format: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    if format.is_srgb() {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    }
+}
+
+/// `size` scaled down to fit within `bounds`, keeping its aspect ratio, never upscaled.
+fn fit_within(size: (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    #[allow(clippy::cast_precision_loss)]
+    let scale = (bounds.0 as f32 / size.0 as f32)
+        .min(bounds.1 as f32 / size.1 as f32)
+        .min(1.0);
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let fit = |length: u32| ((length as f32 * scale).round() as u32).max(1);
+    (fit(size.0), fit(size.1))
+}
+
+/// A layer copied by `SceneRenderer::copy_layer`, read back as premultiplied RGBA8 once the copy has run.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct LayerReadback {
+    readback: TextureReadback,
+    bgra: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LayerReadback {
+    /// See `TextureReadback::map`.
+    pub fn map(&mut self) {
+        self.readback.map();
+    }
+
+    /// See `TextureReadback::into_pixels`.
+    pub fn into_pixels(self) -> anyhow::Result<Vec<u8>> {
+        let mut pixels = self.readback.into_pixels()?;
+        if self.bgra {
+            swap_red_blue(&mut pixels);
+        }
+        Ok(pixels)
+    }
+}
+
+fn is_bgra(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// RGBA8 `pixels` in the byte order of `format`, only copies for BGRA targets.
+fn texture_byte_order(format: wgpu::TextureFormat, pixels: &[u8]) -> Cow<'_, [u8]> {
+    if is_bgra(format) {
+        let mut swapped = pixels.to_vec();
+        swap_red_blue(&mut swapped);
+        Cow::Owned(swapped)
+    } else {
+        Cow::Borrowed(pixels)
+    }
+}
+
+/// Swaps the red and blue channels in place, converting RGBA8 to BGRA8 and back.
+fn swap_red_blue(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+}
+
 /// WGPU scissor rect.
 type ScissorRect = (RectLeft, RectTop, RectWidth, RectHeight);
 
@@ -864,9 +2311,10 @@ mod tests {
 
     use super::*;
     use crate::constants::{CLEAR_COLOR, RED};
-    use crate::document::ArtboardId;
     use crate::document::loader::LoadedDocument;
-    use crate::testing::fixtures::{doc_two_artboards, solid_layer_pixels};
+    use crate::document::{GroupId, VectorPoint};
+    use crate::editor_state::DEFAULT_BRUSH_COLOR;
+    use crate::testing::fixtures::{doc_single_layer, doc_two_artboards, solid_layer_pixels};
     use crate::testing::gpu::{headless_gpu, readback_rgba};
     use crate::testing::probe::{assert_pixel, sample};
 
@@ -955,6 +2403,9 @@ mod tests {
         scene.begin_points().push(PointInstance {
             center: [0.0, 0.0],
             radius_px,
+            opacity: 1.0,
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            rotation: 0.0,
         });
         let count = scene.upload_points(queue);
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -967,6 +2418,95 @@ mod tests {
         queue.submit([encoder.finish()]);
     }
 
+    /// One dab of `radius` at `position` in the default brush color, as the paint system records it.
+    fn dab_stroke(position: [f32; 2], radius: f32) -> VectorStroke {
+        VectorStroke {
+            color: DEFAULT_BRUSH_COLOR.to_rgba_array(),
+            mode: StrokeMode::Paint,
+            tip: BrushTip::Round,
+            hardness: DEFAULT_BRUSH_HARDNESS,
+            opacity: 1.0,
+            points: vec![VectorPoint {
+                position,
+                radius,
+                opacity: 1.0,
+                rotation: 0.0,
+            }],
+        }
+    }
+
+    #[test]
+    fn vector_layer_rasterizes_like_the_painted_stroke() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        // lands on the center of the 400x300 right layer
+        stamp_point(&device, &queue, &mut scene, LayerId(4), 30.0, true);
+        let painted = scene
+            .read_layer_pixels(&device, &queue, LayerId(4))
+            .unwrap()
+            .unwrap();
+        assert!(painted.chunks_exact(4).any(|px| px[3] == 255));
+
+        let stroke = dab_stroke([200.0, 150.0], 30.0);
+        document.find_layer_mut(LayerId(4)).unwrap().strokes = Some(vec![stroke]);
+        scene.clear_layer(&device, &queue, LayerId(4));
+        scene.rasterize_vector_layer(&device, &queue, document.find_layer(LayerId(4)).unwrap().1);
+        let rasterized = scene
+            .read_layer_pixels(&device, &queue, LayerId(4))
+            .unwrap()
+            .unwrap();
+        assert!(painted == rasterized);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn later_vector_strokes_draw_over_earlier_ones() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let strokes = vec![
+            dab_stroke([100.0, 100.0], 20.0),
+            VectorStroke {
+                color: [0.0, 0.0, 1.0, 0.5],
+                ..dab_stroke([100.0, 100.0], 10.0)
+            },
+        ];
+        document.find_layer_mut(LayerId(4)).unwrap().strokes = Some(strokes);
+        scene.rasterize_vector_layer(&device, &queue, document.find_layer(LayerId(4)).unwrap().1);
+
+        let pixels = scene
+            .read_layer_pixels(&device, &queue, LayerId(4))
+            .unwrap()
+            .unwrap();
+        let center = sample(&pixels, (400, 300), 100, 100);
+        assert_eq!(center[3], 255);
+        assert!(center[2] > 100, "half-blue dab over the first: {center:?}");
+    }
+
+    #[test]
+    fn vector_display_follows_scale_until_invalidated() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        document.find_layer_mut(LayerId(4)).unwrap().strokes =
+            Some(vec![dab_stroke([50.0, 50.0], 8.0)]);
+        let vector = document.find_layer(LayerId(4)).unwrap().1;
+
+        scene.set_vector_display_scale(&device, &queue, vector, 3);
+        assert_eq!(scene.vector_display_scale(LayerId(4)), 3);
+        let display = &scene.layers[&LayerId(4)].display.as_ref().unwrap().texture;
+        assert_eq!(
+            (display.texture.width(), display.texture.height()),
+            (1200, 900)
+        );
+
+        scene.invalidate_vector_display(LayerId(4));
+        assert_eq!(scene.vector_display_scale(LayerId(4)), 1);
+        scene.set_vector_display_scale(&device, &queue, vector, 2);
+        scene.set_vector_display_scale(&device, &queue, vector, 1);
+        assert_eq!(scene.vector_display_scale(LayerId(4)), 1);
+
+        // raster layers only ever show their texture
+        let raster = document.find_layer(LayerId(2)).unwrap().1;
+        scene.set_vector_display_scale(&device, &queue, raster, 2);
+        assert_eq!(scene.vector_display_scale(LayerId(2)), 1);
+    }
+
     /// Camera showing the whole two-artboard world (0..1100 x -50..450)
     /// in a 220x100 target.
     fn overview_camera(size: (u32, u32)) -> Camera2D {
@@ -1013,7 +2553,7 @@ mod tests {
         let size = (220, 100);
         let camera = overview_camera(size);
 
-        document.artboards[0].layers[0].visible = false;
+        document.find_layer_mut(LayerId(2)).unwrap().visible = false;
         let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
         // The red layer is hidden; the white artboard background shows.
         assert_world_pixel(&pixels, size, &camera, (300.0, 200.0), WHITE);
@@ -1027,7 +2567,7 @@ mod tests {
 
         // Drag the red layer half an artboard to the right: content now spans
         // world x 300..900, but the artboard ends at 600.
-        document.artboards[0].layers[0].offset = [300.0, 0.0];
+        document.find_layer_mut(LayerId(2)).unwrap().offset = [300.0, 0.0];
         let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
 
         // Inside the artboard, over the moved layer: red.
@@ -1113,6 +2653,231 @@ mod tests {
         );
     }
 
+    /// Stamps `dabs` in a single batch into the blank right layer (400x300) and reads it back.
+    fn stamp_batch(dabs: &[PointInstance]) -> Vec<u8> {
+        stamp_styled_batch(|_| {}, dabs)
+    }
+
+    /// `stamp_batch` with the live stroke styled by `style` first.
+    fn stamp_styled_batch(
+        style: impl FnOnce(&mut SceneRenderer),
+        dabs: &[PointInstance],
+    ) -> Vec<u8> {
+        let (device, queue, mut scene, _document) = scene_with_red_left_layer();
+        let layer = LayerId(4);
+        let size = scene.layers[&layer].size;
+        style(&mut scene);
+        scene.begin_points().extend_from_slice(dabs);
+        let count = scene.upload_points(&queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Batch Encoder"),
+        });
+        scene.accumulate_stroke(&queue, &mut encoder, true, count, size);
+        scene.merge_stroke_into_layer(&queue, &mut encoder, layer);
+        queue.submit([encoder.finish()]);
+        readback_rgba(&device, &queue, &scene.layers[&layer].texture.texture, size)
+    }
+
+    fn dab(center: [f32; 2], color: [f32; 4], rotation: f32) -> PointInstance {
+        PointInstance {
+            center,
+            radius_px: 30.0,
+            opacity: 1.0,
+            color,
+            rotation,
+        }
+    }
+
+    #[test]
+    fn dabs_keep_their_own_color_within_a_batch() {
+        let pixels = stamp_batch(&[
+            dab([-0.5, 0.0], [1.0, 0.0, 0.0, 1.0], 0.0),
+            dab([0.5, 0.0], [0.0, 0.0, 1.0, 1.0], 0.0),
+        ]);
+        let size = (400, 300);
+        assert_eq!(sample(&pixels, size, 100, 150), [255, 0, 0, 255]);
+        assert_eq!(sample(&pixels, size, 300, 150), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rotating_a_round_dab_leaves_it_unchanged() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let straight = stamp_batch(&[dab([0.0, 0.0], color, 0.0)]);
+        let rotated = stamp_batch(&[dab([0.0, 0.0], color, 0.7)]);
+        for (x, y) in [(200, 150), (225, 150), (200, 170), (218, 168), (240, 150)] {
+            assert_pixel(
+                &rotated,
+                (400, 300),
+                x,
+                y,
+                sample(&straight, (400, 300), x, y),
+                1,
+            );
+        }
+    }
+
+    #[test]
+    fn tip_images_stamp_within_the_dab() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let round = stamp_batch(&[dab([0.0, 0.0], color, 0.0)]);
+        for tip in [BrushTip::Chalk, BrushTip::Pencil, BrushTip::Bristle] {
+            let pixels = stamp_styled_batch(
+                |scene| scene.set_brush_tip(tip),
+                &[dab([0.0, 0.0], color, 0.0)],
+            );
+            assert!(pixels != round, "{tip:?} stamps the round dab");
+            assert!(
+                pixels.chunks_exact(4).any(|px| px[3] > 0),
+                "{tip:?} stamps nothing"
+            );
+            // 30 px around (200, 150), the rest of the layer stays blank
+            for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+                let (x, y) = (index % 400, index / 400);
+                if x.abs_diff(200) > 31 || y.abs_diff(150) > 31 {
+                    assert_eq!(pixel[3], 0, "{tip:?} at ({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotating_a_tip_turns_it_clockwise() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let bristle = |scene: &mut SceneRenderer| scene.set_brush_tip(BrushTip::Bristle);
+        let straight = stamp_styled_batch(bristle, &[dab([0.0, 0.0], color, 0.0)]);
+        let rotated = stamp_styled_batch(
+            bristle,
+            &[dab([0.0, 0.0], color, std::f32::consts::FRAC_PI_2)],
+        );
+        assert!(straight != rotated, "bristle streaks turn with the dab");
+        // a quarter turn around the pixel corner at (200, 150) takes pixel (200 + i, 150 + j) to (199 - j, 150 + i)
+        for ((x, y), (turned_x, turned_y)) in [
+            ((200, 150), (199, 150)),
+            ((210, 143), (206, 160)),
+            ((180, 155), (194, 130)),
+            ((203, 172), (177, 153)),
+            ((186, 136), (213, 136)),
+        ] {
+            let expected = sample(&straight, (400, 300), x, y);
+            assert_pixel(&rotated, (400, 300), turned_x, turned_y, expected, 2);
+        }
+    }
+
+    /// Paints the single dab of `stroke`, centered on the blank right layer, live with `style`,
+    /// then replays it as a vector stroke, returning both layers.
+    fn paint_and_replay(
+        style: impl FnOnce(&mut SceneRenderer),
+        stroke: VectorStroke,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let layer = LayerId(4);
+        let size = scene.layers[&layer].size;
+        style(&mut scene);
+        let point = stroke.points[0];
+        scene.begin_points().push(PointInstance {
+            radius_px: point.radius,
+            opacity: point.opacity,
+            ..dab([0.0, 0.0], stroke.color, point.rotation)
+        });
+        let count = scene.upload_points(&queue);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Replay Encoder"),
+        });
+        scene.accumulate_stroke(&queue, &mut encoder, true, count, size);
+        scene.merge_stroke_into_layer(&queue, &mut encoder, layer);
+        queue.submit([encoder.finish()]);
+        let painted = scene
+            .read_layer_pixels(&device, &queue, layer)
+            .unwrap()
+            .unwrap();
+
+        document.find_layer_mut(layer).unwrap().strokes = Some(vec![stroke]);
+        scene.clear_layer(&device, &queue, layer);
+        scene.rasterize_vector_layer(&device, &queue, document.find_layer(layer).unwrap().1);
+        let rasterized = scene
+            .read_layer_pixels(&device, &queue, layer)
+            .unwrap()
+            .unwrap();
+        (painted, rasterized)
+    }
+
+    #[test]
+    fn vector_strokes_replay_their_tip() {
+        let mut stroke = VectorStroke {
+            tip: BrushTip::Chalk,
+            ..dab_stroke([200.0, 150.0], 30.0)
+        };
+        stroke.points[0].rotation = 0.4;
+        let (painted, rasterized) =
+            paint_and_replay(|scene| scene.set_brush_tip(BrushTip::Chalk), stroke);
+        assert!(painted == rasterized);
+    }
+
+    #[test]
+    fn vector_strokes_replay_their_hardness_and_opacity() {
+        let stroke = VectorStroke {
+            hardness: 0.9,
+            opacity: 0.5,
+            ..dab_stroke([200.0, 150.0], 30.0)
+        };
+        let (painted, rasterized) = paint_and_replay(
+            |scene| {
+                scene.set_brush_hardness(0.9);
+                scene.set_stroke_opacity(0.5);
+            },
+            stroke,
+        );
+        assert!(painted == rasterized);
+        let center = sample(&painted, (400, 300), 200, 150);
+        assert!(center[3].abs_diff(128) <= 1, "{center:?}");
+    }
+
+    #[test]
+    fn hardness_moves_where_round_dabs_fade() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let dabs = [dab([0.0, 0.0], color, 0.0)];
+        let soft = stamp_styled_batch(|scene| scene.set_brush_hardness(0.0), &dabs);
+        let hard = stamp_styled_batch(|scene| scene.set_brush_hardness(1.0), &dabs);
+        // 25 px out of the 30 px radius
+        let (soft_edge, hard_edge) = (
+            sample(&soft, (400, 300), 225, 150)[3],
+            sample(&hard, (400, 300), 225, 150)[3],
+        );
+        assert_eq!(hard_edge, 255);
+        assert!(soft_edge < 32, "{soft_edge}");
+        assert_eq!(sample(&hard, (400, 300), 232, 150)[3], 0, "past the radius");
+    }
+
+    #[test]
+    fn stroke_opacity_caps_overlapping_dabs() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let dabs = [
+            dab([0.0, 0.0], color, 0.0),
+            dab([0.01, 0.0], color, 0.0),
+            dab([0.02, 0.0], color, 0.0),
+        ];
+        let full = stamp_batch(&dabs);
+        let capped = stamp_styled_batch(|scene| scene.set_stroke_opacity(0.25), &dabs);
+        assert_eq!(sample(&full, (400, 300), 202, 150)[3], 255);
+        let alpha = sample(&capped, (400, 300), 202, 150)[3];
+        assert!(alpha.abs_diff(64) <= 1, "{alpha}");
+    }
+
+    #[test]
+    fn dab_opacity_scales_its_coverage() {
+        let color = DEFAULT_BRUSH_COLOR.to_rgba_array();
+        let pixels = stamp_batch(&[
+            dab([-0.5, 0.0], color, 0.0),
+            PointInstance {
+                opacity: 0.5,
+                ..dab([0.5, 0.0], color, 0.0)
+            },
+        ]);
+        assert_eq!(sample(&pixels, (400, 300), 100, 150)[3], 255);
+        let half = sample(&pixels, (400, 300), 300, 150)[3];
+        assert!(half.abs_diff(128) <= 1, "half opacity dab: {half}");
+    }
+
     #[test]
     fn merge_composites_over_existing_layer_content() {
         let (device, queue, mut scene, _document) = scene_with_red_left_layer();
@@ -1169,4 +2934,459 @@ mod tests {
         scene.merge_stroke_into_layer(&queue, &mut encoder, LayerId(999));
         queue.submit([encoder.finish()]);
     }
+
+    #[test]
+    fn thumbnails_fit_the_layer_centered() {
+        let (device, queue, scene, _document) = scene_with_red_left_layer();
+        let size = (48, 48);
+        let thumbnail = scene.create_thumbnail_texture(&device, size);
+        // 600x400 halves three times down to 75x50 before the last pass
+        assert!(scene.draw_layer_thumbnail(&device, &queue, LayerId(2), &thumbnail));
+        let pixels = readback_rgba(&device, &queue, &thumbnail, size);
+
+        // fitted to 48x32, leaving 8 transparent rows above and below
+        assert_pixel(&pixels, size, 24, 24, [255, 0, 0, 255], 1);
+        assert_pixel(&pixels, size, 0, 8, [255, 0, 0, 255], 1);
+        assert_pixel(&pixels, size, 47, 39, [255, 0, 0, 255], 1);
+        assert_pixel(&pixels, size, 24, 4, [0, 0, 0, 0], 0);
+        assert_pixel(&pixels, size, 24, 44, [0, 0, 0, 0], 0);
+
+        assert!(!scene.draw_layer_thumbnail(&device, &queue, LayerId(999), &thumbnail));
+    }
+
+    // ---- layer opacity + blend modes ----
+
+    const BOTTOM: LayerId = LayerId(2);
+    const TOP: LayerId = LayerId(3);
+
+    /// An 8x8 artboard with an opaque `backdrop` layer (`BOTTOM`) under a `source` layer (`TOP`), viewed 1:1.
+    fn blend_scene(
+        backdrop: [u8; 4],
+        source: [u8; 4],
+    ) -> (wgpu::Device, wgpu::Queue, SceneRenderer, Document, Camera2D) {
+        let (device, queue) = headless_gpu();
+        let mut scene = SceneRenderer::new(&device, &queue, wgpu::TextureFormat::Rgba8Unorm);
+        let mut document = doc_single_layer();
+        document.artboards[0].size = [8.0, 8.0];
+        let mut top = document.find_layer(BOTTOM).unwrap().1.clone();
+        top.id = document.alloc_layer_id();
+        assert_eq!(top.id, TOP);
+        document.artboards[0].layers.push(top.into());
+
+        let mut layer_pixels = HashMap::new();
+        layer_pixels.insert(BOTTOM, solid_layer_pixels((8, 8), backdrop));
+        layer_pixels.insert(TOP, solid_layer_pixels((8, 8), source));
+        scene.hydrate(
+            &device,
+            &queue,
+            &LoadedDocument {
+                document: document.clone(),
+                layer_pixels,
+            },
+        );
+
+        let mut camera = Camera2D::with_viewport(8.0, 8.0);
+        camera.center_on(Point2::new(4.0, 4.0));
+        (device, queue, scene, document, camera)
+    }
+
+    /// Straight-alpha blend functions from the W3C compositing spec, channels in 0..=1.
+    fn reference_blend(mode: BlendMode, cb: f32, cs: f32) -> f32 {
+        match mode {
+            BlendMode::Normal => cs,
+            BlendMode::Multiply => cb * cs,
+            BlendMode::Screen => cb + cs - cb * cs,
+            BlendMode::Overlay if cb <= 0.5 => 2.0 * cb * cs,
+            BlendMode::Overlay => 1.0 - 2.0 * (1.0 - cb) * (1.0 - cs),
+            BlendMode::Add => (cb + cs).min(1.0),
+            BlendMode::Darken => cb.min(cs),
+            BlendMode::Lighten => cb.max(cs),
+        }
+    }
+
+    /// `source` at `opacity` blended over the opaque `backdrop`, from the premultiplied texels the GPU sees.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn reference_pixel(
+        mode: BlendMode,
+        backdrop: [u8; 4],
+        source: [u8; 4],
+        opacity: f32,
+    ) -> [u8; 4] {
+        let premultiplied = solid_layer_pixels((1, 1), source);
+        let source_alpha = f32::from(premultiplied[3]) / 255.0;
+        let alpha = source_alpha * opacity;
+        let mut out = [255; 4];
+        for channel in 0..3 {
+            let cb = f32::from(backdrop[channel]) / 255.0;
+            let cs = f32::from(premultiplied[channel]) / 255.0 / source_alpha;
+            let blended = cb * (1.0 - alpha) + alpha * reference_blend(mode, cb, cs);
+            out[channel] = (blended * 255.0).round() as u8;
+        }
+        out
+    }
+
+    #[test]
+    fn every_blend_mode_matches_the_reference() {
+        // backdrop channels on both sides of 0.5 exercise both overlay branches
+        let backdrop = [200, 100, 50, 255];
+        let source = [60, 160, 230, 191];
+        let opacity = 0.8;
+        let (device, queue, mut scene, mut document, camera) = blend_scene(backdrop, source);
+
+        for mode in BlendMode::ALL {
+            let top = document.find_layer_mut(TOP).unwrap();
+            top.blend_mode = mode;
+            top.opacity = opacity;
+            let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, (8, 8));
+            let expect = reference_pixel(mode, backdrop, source, opacity);
+            let got = sample(&pixels, (8, 8), 4, 4);
+            assert!(
+                got.iter()
+                    .zip(expect)
+                    .all(|(got, expect)| got.abs_diff(expect) <= 2),
+                "{mode:?}: got {got:?}, expected {expect:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn multiply_scales_the_backdrop() {
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene([200, 100, 50, 255], [128, 255, 0, 255]);
+        document.find_layer_mut(TOP).unwrap().blend_mode = BlendMode::Multiply;
+        let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, (8, 8));
+        assert_pixel(&pixels, (8, 8), 4, 4, [100, 100, 0, 255], 1);
+    }
+
+    #[test]
+    fn transparent_source_leaves_the_backdrop() {
+        let backdrop = [200, 100, 50, 255];
+        let (device, queue, mut scene, mut document, camera) = blend_scene(backdrop, [0, 0, 0, 0]);
+        for mode in BlendMode::ALL {
+            document.find_layer_mut(TOP).unwrap().blend_mode = mode;
+            let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, (8, 8));
+            assert_pixel(&pixels, (8, 8), 4, 4, backdrop, 1);
+        }
+    }
+
+    #[test]
+    fn layer_opacity_fades_normal_layers() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let size = (220, 100);
+        let camera = overview_camera(size);
+
+        document.find_layer_mut(LayerId(2)).unwrap().opacity = 0.5;
+        let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
+        // half red over the white background
+        assert_world_pixel(&pixels, size, &camera, (300.0, 200.0), [255, 128, 128, 255]);
+    }
+
+    #[test]
+    fn isolated_artboard_keeps_its_place_and_bounds() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let size = (220, 100);
+        let camera = overview_camera(size);
+
+        let layer = document.find_layer_mut(LayerId(2)).unwrap();
+        layer.blend_mode = BlendMode::Multiply;
+        layer.offset = [300.0, 0.0];
+        let pixels = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
+
+        // red multiplied over the white background is red
+        assert_world_pixel(&pixels, size, &camera, (450.0, 200.0), RED);
+        assert_world_pixel(&pixels, size, &camera, (100.0, 200.0), WHITE);
+        assert_world_pixel(&pixels, size, &camera, (650.0, 200.0), clear_color_bytes());
+        // the other artboard still takes the direct path
+        assert_world_pixel(&pixels, size, &camera, (900.0, 250.0), WHITE);
+        assert_eq!(scene.composites.len(), 1);
+
+        document.find_layer_mut(LayerId(2)).unwrap().blend_mode = BlendMode::Normal;
+        render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
+        assert!(
+            scene.composites.is_empty(),
+            "composite kept after it was needed"
+        );
+    }
+
+    /// The live preview must show exactly what the merge will produce once the layer's opacity
+    /// and blend mode apply, rather than the raw stroke on top.
+    #[test]
+    fn live_stroke_preview_matches_the_merged_result() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let target = (ArtboardId(1), LayerId(2));
+        let layer = document.find_layer_mut(LayerId(2)).unwrap();
+        layer.opacity = 0.5;
+        layer.blend_mode = BlendMode::Multiply;
+        stamp_point(&device, &queue, &mut scene, target.1, 40.0, false);
+
+        let size = (220, 100);
+        let camera = overview_camera(size);
+        // Dab center in world px: left artboard (0,0) + layer center (300,200).
+        let dab = camera.world_to_screen(Point2::new(300.0, 200.0));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (x, y) = (dab.x as u32, dab.y as u32);
+
+        let live = render_offscreen_with_stroke(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            &camera,
+            size,
+            Some(target),
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Preview Merge Encoder"),
+        });
+        scene.merge_stroke_into_layer(&queue, &mut encoder, target.1);
+        queue.submit([encoder.finish()]);
+        let merged = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
+
+        let untouched = sample(&merged, size, 5, 50);
+        assert_ne!(sample(&live, size, x, y), untouched, "stroke must show");
+        assert_pixel(&live, size, x, y, sample(&merged, size, x, y), 1);
+    }
+
+    #[test]
+    fn erasing_keeps_partial_pixels_premultiplied() {
+        let (device, queue) = headless_gpu();
+        let mut scene = SceneRenderer::new(&device, &queue, wgpu::TextureFormat::Rgba8Unorm);
+        let mut layer_pixels = HashMap::new();
+        // premultiplies to [200, 100, 0, 200]
+        layer_pixels.insert(
+            LayerId(2),
+            solid_layer_pixels((600, 400), [255, 128, 0, 200]),
+        );
+        scene.hydrate(
+            &device,
+            &queue,
+            &LoadedDocument {
+                document: doc_two_artboards(),
+                layer_pixels,
+            },
+        );
+        scene.set_stroke_mode(StrokeMode::Erase);
+        stamp_point(&device, &queue, &mut scene, LayerId(2), 40.0, true);
+
+        let size = scene.layers[&LayerId(2)].size;
+        let pixels = readback_rgba(
+            &device,
+            &queue,
+            &scene.layers[&LayerId(2)].texture.texture,
+            size,
+        );
+        assert_eq!(sample(&pixels, size, 300, 200), [0, 0, 0, 0]);
+        assert_eq!(sample(&pixels, size, 50, 50), [200, 100, 0, 200]);
+
+        // on the soft edge every channel scales with alpha
+        let [r, g, b, a] = sample(&pixels, size, 330, 200);
+        assert!(a > 20 && a < 180, "on the falloff: {a}");
+        assert!(r.abs_diff(a) <= 1, "red {r} vs alpha {a}");
+        assert!(g.abs_diff(a / 2) <= 1, "green {g} vs alpha {a}");
+        assert_eq!(b, 0);
+    }
+
+    #[test]
+    fn live_erase_preview_shows_what_lies_beneath() {
+        let (device, queue, mut scene, document) = scene_with_red_left_layer();
+        let target = (ArtboardId(1), LayerId(2));
+        scene.set_stroke_mode(StrokeMode::Erase);
+        stamp_point(&device, &queue, &mut scene, target.1, 40.0, false);
+
+        let size = (220, 100);
+        let camera = overview_camera(size);
+        // Dab center in world px: left artboard (0,0) + layer center (300,200).
+        let dab = camera.world_to_screen(Point2::new(300.0, 200.0));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (x, y) = (dab.x as u32, dab.y as u32);
+        let edge = camera.world_to_screen(Point2::new(330.0, 200.0));
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let edge = (edge.x as u32, edge.y as u32);
+
+        let live = render_offscreen_with_stroke(
+            &device,
+            &queue,
+            &mut scene,
+            &document,
+            &camera,
+            size,
+            Some(target),
+        );
+        assert_pixel(&live, size, x, y, WHITE, 1);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Erase Merge Encoder"),
+        });
+        scene.merge_stroke_into_layer(&queue, &mut encoder, target.1);
+        queue.submit([encoder.finish()]);
+        let merged = render_offscreen(&device, &queue, &mut scene, &document, &camera, size);
+        assert_pixel(&merged, size, x, y, WHITE, 1);
+        assert_pixel(
+            &live,
+            size,
+            edge.0,
+            edge.1,
+            sample(&merged, size, edge.0, edge.1),
+            1,
+        );
+    }
+
+    #[test]
+    fn vector_erase_strokes_replay_as_erasers() {
+        let (device, queue, mut scene, mut document) = scene_with_red_left_layer();
+        let mut painted = dab_stroke([300.0, 200.0], 200.0);
+        painted.color = [0.0, 0.0, 1.0, 1.0];
+        let erased = VectorStroke {
+            mode: StrokeMode::Erase,
+            ..dab_stroke([300.0, 200.0], 30.0)
+        };
+        let layer = document.find_layer_mut(LayerId(2)).unwrap();
+        layer.strokes = Some(vec![painted, erased]);
+        scene.clear_layer(&device, &queue, LayerId(2));
+        scene.rasterize_vector_layer(&device, &queue, document.find_layer(LayerId(2)).unwrap().1);
+
+        let pixels = scene
+            .read_layer_pixels(&device, &queue, LayerId(2))
+            .unwrap()
+            .unwrap();
+        let size = scene.layers[&LayerId(2)].size;
+        assert_eq!(sample(&pixels, size, 300, 200), [0, 0, 0, 0]);
+        // past the eraser's reach the painted stroke is untouched
+        assert_eq!(sample(&pixels, size, 360, 200), [0, 0, 255, 255]);
+    }
+
+    // ---- layer groups ----
+
+    /// Moves `TOP` into nested groups, outermost first, each with `(opacity, blend_mode)`.
+    fn group_top_layer(document: &mut Document, groups: &[(f32, BlendMode)]) {
+        let layers = &mut document.artboards[0].layers;
+        let mut node = layers.pop().unwrap();
+        for &(opacity, blend_mode) in groups.iter().rev() {
+            node = LayerNode::Group(LayerGroup {
+                id: GroupId(document.next_id),
+                name: "Group".to_string(),
+                visible: true,
+                locked: false,
+                opacity,
+                blend_mode,
+                children: vec![node],
+            });
+            document.next_id += 1;
+        }
+        document.artboards[0].layers.push(node);
+    }
+
+    fn render_8x8(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &mut SceneRenderer,
+        document: &Document,
+        camera: &Camera2D,
+    ) -> [u8; 4] {
+        let pixels = render_offscreen(device, queue, scene, document, camera, (8, 8));
+        sample(&pixels, (8, 8), 4, 4)
+    }
+
+    #[test]
+    fn pass_through_group_draws_its_children_directly() {
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene([200, 100, 50, 255], [128, 255, 0, 255]);
+        document.find_layer_mut(TOP).unwrap().blend_mode = BlendMode::Multiply;
+        let ungrouped = render_8x8(&device, &queue, &mut scene, &document, &camera);
+
+        group_top_layer(&mut document, &[(1.0, BlendMode::Normal)]);
+        let grouped = render_8x8(&device, &queue, &mut scene, &document, &camera);
+        assert_eq!(
+            grouped, ungrouped,
+            "the multiply still reaches the backdrop"
+        );
+
+        document.find_layer_mut(TOP).unwrap().blend_mode = BlendMode::Normal;
+        render_8x8(&device, &queue, &mut scene, &document, &camera);
+        assert!(scene.composites.is_empty(), "nothing to isolate");
+    }
+
+    #[test]
+    fn hidden_group_hides_its_children() {
+        let backdrop = [200, 100, 50, 255];
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene(backdrop, [128, 255, 0, 255]);
+        group_top_layer(&mut document, &[(1.0, BlendMode::Normal)]);
+        let LayerNode::Group(group) = &mut document.artboards[0].layers[1] else {
+            unreachable!()
+        };
+        group.visible = false;
+        let got = render_8x8(&device, &queue, &mut scene, &document, &camera);
+        assert!(
+            got.iter()
+                .zip(backdrop)
+                .all(|(got, expect)| got.abs_diff(expect) <= 1)
+        );
+    }
+
+    /// Group opacity fades the flattened group: overlapping children don't show through each other.
+    #[test]
+    fn group_opacity_applies_after_flattening() {
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene([255, 0, 0, 255], [0, 255, 0, 255]);
+        // both layers in one half-transparent group, over the white background
+        let layers = std::mem::take(&mut document.artboards[0].layers);
+        let id = document.alloc_group_id();
+        document.artboards[0].layers.push(
+            LayerGroup {
+                id,
+                name: "Group".to_string(),
+                visible: true,
+                locked: false,
+                opacity: 0.5,
+                blend_mode: BlendMode::Normal,
+                children: layers,
+            }
+            .into(),
+        );
+        let got = render_8x8(&device, &queue, &mut scene, &document, &camera);
+        let expect = [128, 255, 128, 255];
+        assert!(
+            got.iter()
+                .zip(expect)
+                .all(|(got, expect)| got.abs_diff(expect) <= 1),
+            "got {got:?}"
+        );
+    }
+
+    /// Children of an isolated group blend against the group, not the backdrop below it.
+    #[test]
+    fn isolated_group_blends_as_one_layer() {
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene([200, 100, 50, 255], [128, 255, 0, 255]);
+        document.find_layer_mut(TOP).unwrap().blend_mode = BlendMode::Screen;
+        group_top_layer(&mut document, &[(1.0, BlendMode::Multiply)]);
+        let got = render_8x8(&device, &queue, &mut scene, &document, &camera);
+        assert!(
+            got.iter()
+                .zip([100, 100, 0, 255])
+                .all(|(got, expect)| got.abs_diff(expect) <= 1),
+            "got {got:?}"
+        );
+    }
+
+    #[test]
+    fn nested_isolated_groups_composite_inside_out() {
+        let (device, queue, mut scene, mut document, camera) =
+            blend_scene([200, 100, 50, 255], [128, 255, 0, 255]);
+        group_top_layer(
+            &mut document,
+            &[(0.5, BlendMode::Normal), (1.0, BlendMode::Multiply)],
+        );
+        // the multiply group sits on the transparent outer group, which fades over the backdrop
+        let got = render_8x8(&device, &queue, &mut scene, &document, &camera);
+        let expect = [164, 178, 25, 255];
+        assert!(
+            got.iter()
+                .zip(expect)
+                .all(|(got, expect)| got.abs_diff(expect) <= 2),
+            "got {got:?}"
+        );
+        assert_eq!(scene.group_scratch.len(), 2, "one scratch per depth");
+    }
 }
@@ -1,16 +1,44 @@
+use std::f32::consts::TAU;
+
 use crate::{
-    document::{ArtboardId, LayerId},
+    document::{ArtboardId, BrushTip, LayerId, StrokeMode, VectorPoint},
+    editor_state::{BrushSettings, TipRotation},
     resource::Resource,
 };
 
 pub type StrokeTarget = (ArtboardId, LayerId);
 
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum Phase {
+    #[default]
+    Idle,
+    /// Begun, its target is picked where the first dab lands.
+    AwaitingFirstDab,
+    Active,
+}
+
 #[derive(Default)]
 pub struct StrokeState {
-    active: bool,
+    phase: Phase,
     needs_clear: bool,
     needs_merge: bool,
     pub target: Option<StrokeTarget>,
+    /// Fixed when the stroke starts, switching tools mid-stroke waits for the next one.
+    pub mode: StrokeMode,
+    /// Fixed when the stroke starts, like `mode`.
+    pub tip: BrushTip,
+    /// Fixed when the stroke starts, like `mode`.
+    pub settings: BrushSettings,
+    /// Where the latest dab landed, in layer pixels.
+    last_dab: Option<[f32; 2]>,
+    /// Direction the stroke last moved in, clockwise from +x in layer pixels.
+    heading: f32,
+    /// Dabs of the current stroke, kept when it paints a vector layer.
+    points: Vec<VectorPoint>,
+    /// Color of the latest kept dab, vector strokes keep a single one.
+    pub color: [f32; 4],
+    /// `[min_x, min_y, max_x, max_y]` the current stroke's dabs reach, in layer pixels.
+    bounds: Option<[f32; 4]>,
 }
 
 impl StrokeState {
@@ -18,21 +46,95 @@ impl StrokeState {
         Self::default()
     }
 
+    /// A stroke is coming, `start` it on the target its first dab picks.
+    pub fn begin(&mut self) {
+        self.phase = Phase::AwaitingFirstDab;
+    }
+
+    pub fn awaiting_first_dab(&self) -> bool {
+        self.phase == Phase::AwaitingFirstDab
+    }
+
     // The next accumulate pass clears the stroke layer.
-    pub fn start(&mut self, target: StrokeTarget) {
-        self.active = true;
+    pub fn start(
+        &mut self,
+        target: StrokeTarget,
+        mode: StrokeMode,
+        tip: BrushTip,
+        settings: BrushSettings,
+    ) {
+        self.phase = Phase::Active;
         self.needs_clear = true;
         self.target = Some(target);
+        self.mode = mode;
+        self.tip = tip;
+        self.settings = settings;
+        self.last_dab = None;
+        self.heading = 0.0;
+        self.points.clear();
+        self.bounds = None;
+    }
+
+    /// Grows the stroke's bounds to cover a dab.
+    pub fn cover(&mut self, [x, y]: [f32; 2], radius: f32) {
+        let dab = [x - radius, y - radius, x + radius, y + radius];
+        self.bounds = Some(self.bounds.map_or(dab, |bounds| {
+            [
+                bounds[0].min(dab[0]),
+                bounds[1].min(dab[1]),
+                bounds[2].max(dab[2]),
+                bounds[3].max(dab[3]),
+            ]
+        }));
+    }
+
+    /// How the tip of a dab landing at `position`, in layer pixels, is turned, clockwise in radians.
+    /// Following the stroke, dabs keep the heading of the last move until the stroke moves again.
+    pub fn dab_rotation(&mut self, position: [f32; 2], rotation: TipRotation) -> f32 {
+        let previous = self.last_dab.replace(position);
+        match rotation {
+            TipRotation::Fixed => 0.0,
+            TipRotation::FollowStroke => {
+                if let Some([x, y]) = previous {
+                    let (dx, dy) = (position[0] - x, position[1] - y);
+                    if dx.hypot(dy) > f32::EPSILON {
+                        self.heading = dy.atan2(dx);
+                    }
+                }
+                self.heading
+            }
+            TipRotation::Random => random_angle(position),
+        }
+    }
+
+    /// Takes the bounds covered since `start`, `None` if no dab landed.
+    pub fn take_bounds(&mut self) -> Option<[f32; 4]> {
+        self.bounds.take()
+    }
+
+    pub fn record(&mut self, point: VectorPoint) {
+        self.points.push(point);
+    }
+
+    /// Takes the dabs recorded since `start`.
+    pub fn take_points(&mut self) -> Vec<VectorPoint> {
+        std::mem::take(&mut self.points)
     }
 
     pub fn active_target(&self) -> Option<StrokeTarget> {
-        if self.active { self.target } else { None }
+        if self.phase == Phase::Active {
+            self.target
+        } else {
+            None
+        }
     }
 
     // The stroke layer is merged into the canvas next frame.
     pub fn end(&mut self) {
-        if self.active {
-            self.needs_merge = true;
+        match self.phase {
+            Phase::Active => self.needs_merge = true,
+            Phase::AwaitingFirstDab => self.phase = Phase::Idle,
+            Phase::Idle => {}
         }
     }
 
@@ -44,8 +146,8 @@ impl StrokeState {
     /// Consumes the pending-merge flag, ending the active stroke.
     pub fn take_needs_merge(&mut self) -> bool {
         let merge = std::mem::take(&mut self.needs_merge);
-        if merge {
-            self.active = false;
+        if merge && self.phase == Phase::Active {
+            self.phase = Phase::Idle;
         }
         merge
     }
@@ -53,6 +155,19 @@ impl StrokeState {
 
 impl Resource for StrokeState {}
 
+/// An angle in `[0, TAU)` scattered by hashing the dab's position.
+fn random_angle([x, y]: [f32; 2]) -> f32 {
+    // splitmix64's finalizer
+    let mut z =
+        (u64::from(x.to_bits()) << 32 | u64::from(y.to_bits())).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    #[allow(clippy::cast_precision_loss)]
+    let unit = (z >> 40) as f32 / (1u64 << 24) as f32;
+    unit * TAU
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,7 +179,12 @@ mod tests {
         let mut stroke = StrokeState::new();
         assert_eq!(stroke.active_target(), None);
 
-        stroke.start(TARGET);
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
         assert_eq!(stroke.active_target(), Some(TARGET));
         assert!(stroke.take_needs_clear());
         assert!(!stroke.take_needs_clear(), "clear consumed once");
@@ -75,6 +195,142 @@ mod tests {
         assert_eq!(stroke.target, Some(TARGET), "target stays for the merge");
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn dabs_turn_along_the_stroke() {
+        let mut stroke = StrokeState::new();
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Bristle,
+            BrushSettings::default(),
+        );
+        let follow = TipRotation::FollowStroke;
+        assert_eq!(stroke.dab_rotation([10.0, 10.0], follow), 0.0);
+        assert_eq!(stroke.dab_rotation([20.0, 10.0], follow), 0.0);
+        let down = stroke.dab_rotation([20.0, 20.0], follow);
+        assert!((down - std::f32::consts::FRAC_PI_2).abs() < 1e-6, "{down}");
+        assert_eq!(
+            stroke.dab_rotation([20.0, 20.0], follow),
+            down,
+            "a dab in place keeps the heading"
+        );
+        assert_eq!(stroke.dab_rotation([0.0, 20.0], TipRotation::Fixed), 0.0);
+
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Bristle,
+            BrushSettings::default(),
+        );
+        assert_eq!(
+            stroke.dab_rotation([0.0, 0.0], follow),
+            0.0,
+            "reset by start"
+        );
+    }
+
+    #[test]
+    fn random_rotations_spread_over_the_circle() {
+        let mut stroke = StrokeState::new();
+        let angles: Vec<f32> = (0..64u8)
+            .map(|i| stroke.dab_rotation([f32::from(i) * 1.5, 20.0], TipRotation::Random))
+            .collect();
+        assert!(angles.iter().all(|angle| (0.0..TAU).contains(angle)));
+        // every quarter turn gets some
+        for quarter in 0..4u8 {
+            let range = f32::from(quarter) * TAU / 4.0..f32::from(quarter + 1) * TAU / 4.0;
+            assert!(
+                angles.iter().any(|angle| range.contains(angle)),
+                "{range:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn start_discards_points_of_the_previous_stroke() {
+        let mut stroke = StrokeState::new();
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
+        stroke.record(VectorPoint {
+            position: [1.0, 2.0],
+            radius: 3.0,
+            opacity: 1.0,
+            rotation: 0.0,
+        });
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
+        assert!(stroke.take_points().is_empty());
+
+        stroke.record(VectorPoint {
+            position: [4.0, 5.0],
+            radius: 6.0,
+            opacity: 1.0,
+            rotation: 0.0,
+        });
+        assert_eq!(stroke.take_points().len(), 1);
+        assert!(stroke.take_points().is_empty(), "points are taken once");
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn bounds_cover_every_dab_of_the_stroke() {
+        let mut stroke = StrokeState::new();
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
+        assert_eq!(stroke.take_bounds(), None);
+
+        stroke.cover([10.0, 20.0], 5.0);
+        stroke.cover([40.0, 12.0], 2.0);
+        assert_eq!(stroke.take_bounds(), Some([5.0, 10.0, 42.0, 25.0]));
+        assert_eq!(stroke.take_bounds(), None, "bounds are taken once");
+
+        stroke.cover([1.0, 1.0], 1.0);
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
+        assert_eq!(stroke.take_bounds(), None, "a new stroke starts empty");
+    }
+
+    #[test]
+    fn begun_strokes_wait_for_their_first_dab() {
+        let mut stroke = StrokeState::new();
+        stroke.begin();
+        assert!(stroke.awaiting_first_dab());
+        assert_eq!(stroke.active_target(), None);
+
+        stroke.start(
+            TARGET,
+            StrokeMode::Paint,
+            BrushTip::Round,
+            BrushSettings::default(),
+        );
+        assert!(!stroke.awaiting_first_dab());
+        assert_eq!(stroke.active_target(), Some(TARGET));
+
+        stroke.end();
+        assert!(stroke.take_needs_merge());
+        stroke.begin();
+        stroke.end();
+        assert!(!stroke.awaiting_first_dab(), "a stroke without dabs ends");
+        assert!(!stroke.take_needs_merge());
+    }
+
     #[test]
     fn end_without_start_does_not_merge() {
         let mut stroke = StrokeState::new();
@@ -0,0 +1,415 @@
+//! Recorded `ControllerEvent` sessions, from `--record` and played back by `--replay`.
+//!
+//! A session is JSON lines: a `SessionHeader`, then one `RecordedEvent` per event in the order they were sent.
+//! Events are written as they are relayed, so the log of a session that crashed is readable up to its last line.
+//!
+//! Events that write files, see `ControllerEvent::writes_files`, are neither recorded nor replayed.
+//!
+//! Playback sends the events through the same `EventSender` as live input, at the times they were recorded.
+//! Brush points are in screen space, so the same pixels come back when the session is replayed
+//! on the document and window size it was recorded with.
+
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{event_sender::EventSender, events::ControllerEvent};
+
+pub const SESSION_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SessionHeader {
+    pub version: u32,
+    /// Document the session was recorded on, see `LaunchOptions::document`.
+    pub document: String,
+    /// Inner size of the window, in physical pixels.
+    pub window_size: [u32; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RecordedEvent {
+    /// Since the session started.
+    pub at: Duration,
+    pub event: ControllerEvent,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Session {
+    pub header: SessionHeader,
+    pub events: Vec<RecordedEvent>,
+}
+
+/// Appends every event it is handed to a session log.
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Starts a session log at `path`, replacing any there.
+    pub fn create(path: &Path, header: &SessionHeader) -> anyhow::Result<Self> {
+        let file =
+            std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        Self::new(std::io::BufWriter::new(file), header)
+    }
+
+    pub fn new(out: impl Write + Send + 'static, header: &SessionHeader) -> anyhow::Result<Self> {
+        let mut recorder = Self {
+            out: Box::new(out),
+            started: Instant::now(),
+        };
+        recorder.write_line(header)?;
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, event: &ControllerEvent) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            at: Duration,
+            event: &'a ControllerEvent,
+        }
+        if event.writes_files() {
+            return Ok(());
+        }
+        self.write_line(&Line {
+            at: self.started.elapsed(),
+            event,
+        })
+    }
+
+    /// Flushed line by line, the session may end with the process.
+    fn write_line(&mut self, line: &impl Serialize) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.out, line)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub fn load_session(path: &Path) -> anyhow::Result<Session> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    read_session(std::io::BufReader::new(file))
+        .with_context(|| format!("reading session {}", path.display()))
+}
+
+/// Parses a session log. A last line cut short, as a crash leaves it, is dropped.
+pub fn read_session(reader: impl BufRead) -> anyhow::Result<Session> {
+    let mut lines = reader.lines().enumerate();
+    let Some((_, header)) = lines.next() else {
+        bail!("empty session");
+    };
+    let header: SessionHeader = serde_json::from_str(&header?).context("parsing the header")?;
+    if header.version > SESSION_VERSION {
+        bail!(
+            "session version {} is newer than the supported {SESSION_VERSION}",
+            header.version
+        );
+    }
+
+    let mut events = Vec::new();
+    let mut lines = lines.peekable();
+    while let Some((index, line)) = lines.next() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(event) => events.push(event),
+            Err(error) if lines.peek().is_none() => {
+                log::warn!("dropping the truncated last event of the session: {error}");
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("parsing line {}", index + 1));
+            }
+        }
+    }
+    Ok(Session { header, events })
+}
+
+/// Sends `events` through `sender` at the times they were recorded, from a thread of its own.
+pub fn replay(events: Vec<RecordedEvent>, sender: EventSender) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        let started = Instant::now();
+        // sessions recorded before file writes were left out may still hold some
+        for RecordedEvent { at, event } in events.into_iter().filter(|e| !e.event.writes_files()) {
+            if let Some(wait) = at.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+            sender.send(event);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use batteries::prelude::{Dot2D, FULL_PRESSURE};
+    use cgmath::Point2;
+
+    use super::*;
+    use crate::document::{BrushTip, LayerId, StrokeMode};
+    use crate::editor_state::{
+        BrushProperties, DEFAULT_BRUSH_COLOR, EditorState, PressureCurve, TipRotation,
+    };
+    use crate::renderer::camera::Camera2D;
+    use crate::resources::brush_point_queue::{BrushPointData, BrushPointQueue};
+    use crate::resources::document_state::DocumentState;
+    use crate::resources::stroke_state::StrokeState;
+    use crate::systems::paint_system::paint_frame;
+    use crate::testing::fixtures::hydrated_scene;
+
+    /// `Write` into a buffer the test keeps a handle on.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn header() -> SessionHeader {
+        SessionHeader {
+            version: SESSION_VERSION,
+            document: "default".to_string(),
+            window_size: [1280, 800],
+        }
+    }
+
+    fn sample_events() -> Vec<ControllerEvent> {
+        vec![
+            ControllerEvent::UpdateBrush(BrushProperties {
+                color: DEFAULT_BRUSH_COLOR,
+                pointer_size: 7.5,
+                size: 12.25,
+                mode: StrokeMode::Erase,
+                pressure_curve: PressureCurve {
+                    gamma: 2.0,
+                    min_size: 0.5,
+                    min_opacity: 0.125,
+                },
+                tip: BrushTip::Bristle,
+                tip_rotation: TipRotation::FollowStroke,
+            }),
+            ControllerEvent::StrokeStart,
+            ControllerEvent::BrushPoint {
+                dot: Dot2D {
+                    position: Point2::new(588.0, 970.125),
+                    radius: 0.1,
+                    pressure: 0.75,
+                },
+            },
+            ControllerEvent::BrushPoint {
+                dot: Dot2D {
+                    position: Point2::new(1.0 / 3.0, -2.5),
+                    radius: 5.0,
+                    pressure: FULL_PRESSURE,
+                },
+            },
+            ControllerEvent::StrokeEnd,
+            ControllerEvent::CameraZoom {
+                delta: -0.1,
+                _position: Point2::new(640.0, 400.0),
+            },
+            ControllerEvent::Undo,
+        ]
+    }
+
+    fn recorded(events: &[ControllerEvent]) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let mut recorder = Recorder::new(buffer.clone(), &header()).unwrap();
+        for event in events {
+            recorder.record(event).unwrap();
+        }
+        buffer.0.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn recorded_sessions_read_back_exactly() {
+        let events = sample_events();
+        let session = read_session(recorded(&events).as_slice()).unwrap();
+        assert_eq!(session.header, header());
+        let read: Vec<ControllerEvent> = session.events.into_iter().map(|e| e.event).collect();
+        assert_eq!(read, events, "floats survive bit for bit");
+    }
+
+    #[test]
+    fn file_writes_are_left_out_of_sessions_and_preset_edits_kept() {
+        let events = vec![
+            ControllerEvent::StrokeStart,
+            ControllerEvent::ExportPsd,
+            ControllerEvent::DeleteBrushPreset("Ink".to_string()),
+            ControllerEvent::ExportBrushPresets("/tmp/presets.json".into()),
+            ControllerEvent::StrokeEnd,
+        ];
+        let kept = [
+            ControllerEvent::StrokeStart,
+            ControllerEvent::DeleteBrushPreset("Ink".to_string()),
+            ControllerEvent::StrokeEnd,
+        ];
+        let session = read_session(recorded(&events).as_slice()).unwrap();
+        let read: Vec<ControllerEvent> = session.events.into_iter().map(|e| e.event).collect();
+        assert_eq!(read, kept);
+
+        let (sender, receiver) = EventSender::capturing();
+        let logged = events
+            .into_iter()
+            .map(|event| RecordedEvent {
+                at: Duration::ZERO,
+                event,
+            })
+            .collect();
+        replay(logged, sender).join().unwrap();
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), kept);
+    }
+
+    #[test]
+    fn a_truncated_last_line_is_dropped() {
+        let mut log = recorded(&sample_events());
+        log.truncate(log.len() - 5);
+        let session = read_session(log.as_slice()).unwrap();
+        assert_eq!(session.events.len(), sample_events().len() - 1);
+
+        // anywhere else it's corrupt
+        let log = String::from_utf8(recorded(&sample_events())).unwrap();
+        let mut lines: Vec<&str> = log.lines().collect();
+        lines[2] = "{\"at\":";
+        assert!(read_session(lines.join("\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn newer_sessions_are_refused() {
+        let newer = serde_json::to_string(&SessionHeader {
+            version: SESSION_VERSION + 1,
+            ..header()
+        })
+        .unwrap();
+        assert!(read_session(newer.as_bytes()).is_err());
+        assert!(read_session(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn replay_sends_the_events_in_order_and_on_time() {
+        let events = sample_events();
+        let recorded: Vec<RecordedEvent> = events
+            .iter()
+            .enumerate()
+            .map(|(index, event)| RecordedEvent {
+                at: Duration::from_millis(5 * index as u64),
+                event: event.clone(),
+            })
+            .collect();
+        let (sender, receiver) = EventSender::capturing();
+
+        let started = Instant::now();
+        replay(recorded, sender).join().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(5 * (events.len() as u64 - 1)));
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), events);
+    }
+
+    /// The left layer of the shared fixture after `events`, handled as `App` does and painted a frame at a time,
+    /// with the window showing the left artboard 1:1.
+    fn paint_events(events: impl IntoIterator<Item = ControllerEvent>) -> Vec<u8> {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 255, 255, 255]);
+        let mut doc = DocumentState::new(document);
+        let mut editor = EditorState::new();
+        let camera = Camera2D::framing([0.0, 0.0], [600.0, 400.0], 1.0);
+        let mut stroke_state = StrokeState::new();
+        let mut points = BrushPointQueue::new();
+        for event in events {
+            match event {
+                ControllerEvent::UpdateBrush(brush) => editor.update_brush(brush),
+                ControllerEvent::StrokeStart => stroke_state.begin(),
+                ControllerEvent::BrushPoint { dot } => {
+                    if stroke_state.awaiting_first_dab()
+                        && let Some(target) = editor
+                            .stroke_target(&doc.document, camera.screen_to_world(dot.position))
+                    {
+                        let brush = &editor.brush_properties;
+                        stroke_state.start(target, brush.mode, brush.tip, editor.brush_settings);
+                    }
+                    points.write(BrushPointData {
+                        dot,
+                        color: editor.brush_properties.color.to_rgba_array(),
+                        camera,
+                        target: stroke_state.active_target(),
+                    });
+                }
+                ControllerEvent::StrokeEnd => stroke_state.end(),
+                other => panic!("not a stroke event: {other:?}"),
+            }
+            paint_frame(
+                &device,
+                &queue,
+                &mut scene,
+                &mut doc,
+                &mut points,
+                &mut stroke_state,
+                &editor.brush_properties,
+            );
+        }
+        scene
+            .read_layer_pixels(&device, &queue, LayerId(2))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn replayed_strokes_paint_the_pixels_they_were_recorded_with() {
+        let brush = BrushProperties {
+            color: DEFAULT_BRUSH_COLOR,
+            pointer_size: 14.0,
+            size: 14.0,
+            mode: StrokeMode::Paint,
+            pressure_curve: PressureCurve {
+                gamma: 2.0,
+                min_size: 0.25,
+                min_opacity: 0.5,
+            },
+            tip: BrushTip::Chalk,
+            tip_rotation: TipRotation::FollowStroke,
+        };
+        let mut stroke = vec![
+            ControllerEvent::UpdateBrush(brush),
+            ControllerEvent::StrokeStart,
+        ];
+        stroke.extend((0..24u8).map(|step| {
+            let t = f32::from(step) / 23.0;
+            ControllerEvent::BrushPoint {
+                dot: Dot2D {
+                    position: Point2::new(100.0 + 400.0 * t, 200.0 + 80.0 * (t * 6.0).sin()),
+                    radius: 7.0 * (0.5 + t / 2.0),
+                    pressure: 0.3 + 0.7 * t,
+                },
+            }
+        }));
+        stroke.push(ControllerEvent::StrokeEnd);
+
+        let direct = paint_events(stroke.clone());
+        assert_ne!(
+            direct,
+            paint_events([]),
+            "the stroke paints the layer it is checked on"
+        );
+
+        let session = read_session(recorded(&stroke).as_slice()).unwrap();
+        let (sender, receiver) = EventSender::capturing();
+        replay(session.events, sender).join().unwrap();
+        let replayed = paint_events(receiver.try_iter());
+        assert!(
+            replayed == direct,
+            "replayed pixels differ from the direct render"
+        );
+    }
+}
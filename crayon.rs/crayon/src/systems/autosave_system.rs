@@ -0,0 +1,36 @@
+use std::time::Instant;
+
+use crate::{
+    app::App,
+    renderer::render_context::RenderContext,
+    resource::ResourceContext,
+    resources::{autosave::Autosave, document_state::DocumentState, scene_renderer::SceneRenderer},
+    system::System,
+};
+
+/// Feeds the open document to `Autosave`.
+/// Runs after the frame is submitted so read backs see this frame's merges.
+pub struct AutosaveSystem;
+
+impl System for AutosaveSystem {
+    fn run(&self, app: &App) {
+        let (Some(render_ctx), Some(scene), Some(mut doc), Some(mut autosave)) = (
+            app.read::<RenderContext>(),
+            app.read::<SceneRenderer>(),
+            app.write::<DocumentState>(),
+            app.write::<Autosave>(),
+        ) else {
+            return;
+        };
+
+        let doc = &mut *doc;
+        autosave.tick(
+            Instant::now(),
+            &doc.document,
+            &mut doc.dirty_layers,
+            &scene,
+            &render_ctx.device,
+            &render_ctx.queue,
+        );
+    }
+}
@@ -1,7 +1,10 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod autosave_system;
 pub mod brush_preview_update_system;
 pub mod canvas_render_system;
 pub mod frame_acquire_system;
 pub mod frame_present_system;
 pub mod frame_time_update;
 pub mod paint_system;
+pub mod thumbnail_system;
 pub mod tools_system;
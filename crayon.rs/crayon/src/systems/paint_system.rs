@@ -1,18 +1,26 @@
+use cgmath::Point2;
+
 use crate::{
     app::App,
+    constants::VECTOR_DISPLAY_SCALE_MAX,
+    document::{Document, LayerId, VectorPoint, VectorStroke, command::Command},
+    editor_state::BrushProperties,
     renderer::render_context::RenderContext,
     resource::ResourceContext,
     resources::{
         brush_point_queue::BrushPointQueue,
         brush_preview_state::BrushPreviewState,
-        document_state::{DocumentState, GpuOp},
+        document_state::DocumentState,
+        history::{History, TexelRect},
         scene_renderer::{PointInstance, SceneRenderer},
         stroke_state::StrokeState,
     },
+    state::State,
     system::System,
 };
 
 /// Applies queued structural `GpuOp`s to the `SceneRenderer` before any pass is recorded.
+/// Keeps vector layers rasterized at the current zoom and records the strokes painted into them.
 ///
 // TODO: Stroke accumulation and merge.
 // Until then queued brush points and stroke flags are drained and dropped so painting state can't leak across the stage boundary.
@@ -27,6 +35,8 @@ impl System for PaintSystem {
             Some(mut brush_point_queue),
             Some(mut preview_state),
             Some(mut stroke_state),
+            Some(mut history),
+            Some(state),
         ) = (
             app.write::<RenderContext>(),
             app.write::<SceneRenderer>(),
@@ -34,6 +44,8 @@ impl System for PaintSystem {
             app.write::<BrushPointQueue>(),
             app.write::<BrushPreviewState>(),
             app.write::<StrokeState>(),
+            app.write::<History>(),
+            app.read::<State>(),
         )
         else {
             return;
@@ -41,91 +53,338 @@ impl System for PaintSystem {
         let render_ctx = &mut *render_ctx;
 
         // this avoids mid-stroke allocations
-        for op in doc.gpu_dirty.drain(..) {
-            match op {
-                GpuOp::ClearLayer { layer_id: layer } => {
-                    scene.clear_layer(&render_ctx.device, &render_ctx.queue, layer);
-                }
-            }
-        }
+        let doc = &mut *doc;
+        history.record_edits(&render_ctx.device, &render_ctx.queue, &scene, doc);
+        doc.flush_gpu_ops(&render_ctx.device, &render_ctx.queue, &mut scene);
 
-        let mut last_position = None;
-        {
-            let points = scene.begin_points();
-            while let Some(point) = brush_point_queue.read() {
-                last_position = Some(point.dot.position);
-
-                let Some((artboard_id, layer_id)) = point.target else {
-                    continue;
-                };
-
-                let Some(artboard) = doc.document.artboard(artboard_id) else {
-                    continue;
-                };
-
-                let Some(layer) = artboard.layer(layer_id) else {
-                    continue;
-                };
-
-                let world = point.camera.screen_to_world(point.dot.position);
-                let (local_x, local_y) = (
-                    world.x - artboard.position[0] - layer.offset[0],
-                    world.y - artboard.position[1] - layer.offset[1],
-                );
-
-                let (width, height) = {
-                    let (w, h) = artboard.pixel_size();
-                    (w as f32, h as f32)
-                };
-
-                points.push(PointInstance {
-                    center: [
-                        local_x / (width * 0.5) - 1.0,
-                        1.0 - local_y / (height * 0.5),
-                    ],
-                    radius_px: point.dot.radius,
-                })
-            }
-        }
+        update_vector_displays(render_ctx, &mut scene, &doc.document, state.camera.scale());
+
+        let last_position = stage_points(
+            &mut scene,
+            &doc.document,
+            &mut brush_point_queue,
+            &mut stroke_state,
+            &state.editor.brush_properties,
+        );
 
         if let Some(position) = last_position {
             preview_state.show_at_position(position);
         }
 
-        let needs_clear = stroke_state.take_needs_clear();
-        let needs_merge = stroke_state.take_needs_merge();
+        paint_staged_points(
+            &render_ctx.device,
+            &render_ctx.queue,
+            render_ctx.encoder.as_mut(),
+            &mut scene,
+            doc,
+            &mut stroke_state,
+            &mut history,
+        );
+    }
+}
+
+/// Accumulates the staged dabs into the stroke layer on `encoder`, and merges the stroke once it has ended.
+/// Without an encoder the frame's dabs are dropped.
+fn paint_staged_points(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    encoder: Option<&mut wgpu::CommandEncoder>,
+    scene: &mut SceneRenderer,
+    doc: &mut DocumentState,
+    stroke_state: &mut StrokeState,
+    history: &mut History,
+) {
+    // the canvas previews the stroke the way it will merge
+    scene.set_stroke_mode(stroke_state.mode);
+    scene.set_brush_tip(stroke_state.tip);
+    scene.set_brush_hardness(stroke_state.settings.hardness);
+    scene.set_stroke_opacity(stroke_state.settings.opacity);
 
-        let instance_count = scene.upload_points(&render_ctx.queue);
-        if instance_count == 0 && !needs_clear && !needs_merge {
-            return;
-        }
+    let needs_clear = stroke_state.take_needs_clear();
+    let needs_merge = stroke_state.take_needs_merge();
 
-        let target_layer = stroke_state.target.and_then(|(_, layer_id)| {
-            scene
-                .layers
-                .get(&layer_id)
-                .map(|layer| (layer_id, layer.size))
-        });
-        let Some((layer_id, layer_size)) = target_layer else {
-            return;
+    let instance_count = scene.upload_points(queue);
+    if instance_count == 0 && !needs_clear && !needs_merge {
+        return;
+    }
+
+    let target_layer = stroke_state.target.and_then(|(_, layer_id)| {
+        scene
+            .layers
+            .get(&layer_id)
+            .map(|layer| (layer_id, layer.size))
+    });
+    let Some((layer_id, layer_size)) = target_layer else {
+        return;
+    };
+
+    let Some(encoder) = encoder else {
+        return;
+    };
+
+    if needs_clear || instance_count > 0 {
+        scene.accumulate_stroke(queue, encoder, needs_clear, instance_count, layer_size);
+    }
+
+    if needs_merge {
+        let covered = stroke_state.take_bounds();
+        let rect = covered.and_then(|bounds| TexelRect::covering(bounds, layer_size));
+        let revert = record_merged_stroke(scene, doc, layer_id, stroke_state);
+        history.record(device, encoder, scene, layer_id, rect, revert);
+        scene.merge_stroke_into_layer(queue, encoder, layer_id);
+    }
+}
+
+/// One frame of `PaintSystem` painting the queued brush points, submitted on its own.
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn paint_frame(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scene: &mut SceneRenderer,
+    doc: &mut DocumentState,
+    brush_point_queue: &mut BrushPointQueue,
+    stroke_state: &mut StrokeState,
+    brush: &BrushProperties,
+) {
+    stage_points(scene, &doc.document, brush_point_queue, stroke_state, brush);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Paint Frame Encoder"),
+    });
+    paint_staged_points(
+        device,
+        queue,
+        Some(&mut encoder),
+        scene,
+        doc,
+        stroke_state,
+        &mut History::new(u64::MAX),
+    );
+    queue.submit([encoder.finish()]);
+}
+
+/// Stages queued brush points as dabs in their layer's clip space, returning the last cursor position.
+/// Grows the stroke's bounds, and keeps the dabs of strokes on vector layers.
+/// The dabs' pressure sets their opacity through the brush's pressure curve, scaled by the stroke's flow,
+/// and their tips turn as the brush's tip rotation says.
+fn stage_points(
+    scene: &mut SceneRenderer,
+    document: &Document,
+    brush_point_queue: &mut BrushPointQueue,
+    stroke_state: &mut StrokeState,
+    brush: &BrushProperties,
+) -> Option<Point2<f32>> {
+    let mut last_position = None;
+    let points = scene.begin_points();
+    while let Some(point) = brush_point_queue.read() {
+        last_position = Some(point.dot.position);
+
+        let Some((artboard_id, layer_id)) = point.target else {
+            continue;
         };
 
-        let Some(encoder) = render_ctx.encoder.as_mut() else {
-            return;
+        let Some(artboard) = document.artboard(artboard_id) else {
+            continue;
         };
 
-        if needs_clear || instance_count > 0 {
-            scene.accumulate_stroke(
-                &render_ctx.queue,
-                encoder,
-                needs_clear,
-                instance_count,
-                layer_size,
-            );
+        let Some(layer) = artboard.layer(layer_id) else {
+            continue;
+        };
+
+        let world = point.camera.screen_to_world(point.dot.position);
+        let (local_x, local_y) = (
+            world.x - artboard.position[0] - layer.offset[0],
+            world.y - artboard.position[1] - layer.offset[1],
+        );
+
+        let (width, height) = {
+            let (w, h) = artboard.pixel_size();
+            (w as f32, h as f32)
+        };
+
+        let opacity = brush.pressure_curve.opacity(point.dot.pressure) * stroke_state.settings.flow;
+        let rotation = stroke_state.dab_rotation([local_x, local_y], brush.tip_rotation);
+        points.push(PointInstance {
+            center: [
+                local_x / (width * 0.5) - 1.0,
+                1.0 - local_y / (height * 0.5),
+            ],
+            radius_px: point.dot.radius,
+            opacity,
+            color: point.color,
+            rotation,
+        });
+        let (position, radius) = ([local_x, local_y], point.dot.radius);
+        stroke_state.cover(position, radius);
+        if layer.is_vector() {
+            stroke_state.color = point.color;
+            stroke_state.record(VectorPoint {
+                position,
+                radius,
+                opacity,
+                rotation,
+            });
         }
+    }
+    last_position
+}
 
-        if needs_merge {
-            scene.merge_stroke_into_layer(&render_ctx.queue, encoder, layer_id);
+/// Marks the layer dirty, and keeps the merged stroke's dabs when it is a vector layer.
+/// Returns the command taking the kept stroke back off.
+fn record_merged_stroke(
+    scene: &mut SceneRenderer,
+    doc: &mut DocumentState,
+    layer_id: LayerId,
+    stroke_state: &mut StrokeState,
+) -> Option<Command> {
+    doc.mark_dirty(layer_id);
+    let points = stroke_state.take_points();
+    let is_vector = doc
+        .document
+        .find_layer(layer_id)
+        .is_some_and(|(_, layer)| layer.is_vector());
+    if !is_vector {
+        return None;
+    }
+    // the merge only reached the 1:1 texture, the display catches up next frame
+    scene.invalidate_vector_display(layer_id);
+    if points.is_empty() {
+        return None;
+    }
+    doc.execute(Command::InsertStroke {
+        layer_id,
+        index: usize::MAX,
+        stroke: VectorStroke {
+            color: stroke_state.color,
+            mode: stroke_state.mode,
+            tip: stroke_state.tip,
+            hardness: stroke_state.settings.hardness,
+            opacity: stroke_state.settings.opacity,
+            points,
+        },
+    })
+}
+
+/// Re-rasterizes vector layers whose display no longer matches the zoom.
+/// Runs before the frame's points are uploaded, since rasterizing reuses the point buffer.
+fn update_vector_displays(
+    render_ctx: &RenderContext,
+    scene: &mut SceneRenderer,
+    document: &Document,
+    zoom: f32,
+) {
+    let (device, queue) = (&render_ctx.device, &render_ctx.queue);
+    let max_texture_dim = device.limits().max_texture_dimension_2d;
+    for artboard in &document.artboards {
+        let scale = vector_display_scale(zoom, artboard.pixel_size(), max_texture_dim);
+        for layer in artboard.iter_layers().filter(|layer| layer.is_vector()) {
+            if scene.vector_display_scale(layer.id) != scale {
+                scene.set_vector_display_scale(device, queue, layer, scale);
+            }
         }
     }
 }
+
+/// Whole texels per artboard pixel that cover `zoom` screen pixels per artboard pixel,
+/// within `VECTOR_DISPLAY_SCALE_MAX` and the texture limit.
+fn vector_display_scale(zoom: f32, (width, height): (u32, u32), max_texture_dim: u32) -> u32 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let wanted = zoom.ceil().max(1.0) as u32;
+    let fits = (max_texture_dim / width.max(height).max(1)).max(1);
+    wanted.min(VECTOR_DISPLAY_SCALE_MAX).min(fits)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use batteries::prelude::{Dot2D, FULL_PRESSURE};
+
+    use super::*;
+    use crate::document::{ArtboardId, Layer};
+    use crate::editor_state::EditorState;
+    use crate::renderer::camera::Camera2D;
+    use crate::resources::brush_point_queue::BrushPointData;
+    use crate::testing::fixtures::hydrated_scene;
+    use crate::testing::probe::sample;
+
+    #[test]
+    fn vector_display_scale_follows_zoom_within_limits() {
+        assert_eq!(vector_display_scale(0.25, (600, 400), 8192), 1);
+        assert_eq!(vector_display_scale(1.0, (600, 400), 8192), 1);
+        assert_eq!(vector_display_scale(1.2, (600, 400), 8192), 2);
+        assert_eq!(
+            vector_display_scale(10.0, (600, 400), 8192),
+            VECTOR_DISPLAY_SCALE_MAX
+        );
+        assert_eq!(
+            vector_display_scale(10.0, (3000, 400), 8192),
+            2,
+            "texture limit"
+        );
+        assert_eq!(vector_display_scale(10.0, (9000, 400), 8192), 1);
+    }
+
+    #[test]
+    fn strokes_on_a_new_vector_layer_are_kept_and_re_rasterized() {
+        let (device, queue, mut scene, document) = hydrated_scene([255, 0, 0, 128]);
+        let mut doc = DocumentState::new(document);
+        let artboard_id = ArtboardId(3);
+        let layer_id = doc.document.alloc_layer_id();
+        doc.apply(Command::InsertNode {
+            artboard_id,
+            parent: None,
+            index: usize::MAX,
+            node: Layer::new_vector(layer_id, "Vector 2".to_string()).into(),
+        });
+        let mut history = History::new(u64::MAX);
+        history.record_edits(&device, &queue, &scene, &mut doc);
+        doc.flush_gpu_ops(&device, &queue, &mut scene);
+
+        // one dab in the middle of the right artboard
+        let editor = EditorState::new();
+        let brush = editor.brush_properties;
+        let mut stroke_state = StrokeState::new();
+        stroke_state.start(
+            (artboard_id, layer_id),
+            brush.mode,
+            brush.tip,
+            editor.brush_settings,
+        );
+        let mut points = BrushPointQueue::new();
+        points.write(BrushPointData {
+            dot: Dot2D {
+                position: Point2::new(200.0, 150.0),
+                radius: 20.0,
+                pressure: FULL_PRESSURE,
+            },
+            color: brush.color.to_rgba_array(),
+            camera: Camera2D::framing([700.0, 100.0], [400.0, 300.0], 1.0),
+            target: Some((artboard_id, layer_id)),
+        });
+        stroke_state.end();
+        paint_frame(
+            &device,
+            &queue,
+            &mut scene,
+            &mut doc,
+            &mut points,
+            &mut stroke_state,
+            &brush,
+        );
+        let size = scene.layers[&layer_id].size;
+
+        let painted = scene
+            .read_layer_pixels(&device, &queue, layer_id)
+            .unwrap()
+            .unwrap();
+        assert_ne!(sample(&painted, size, 200, 150)[3], 0);
+        let (_, layer) = doc.document.find_layer(layer_id).unwrap();
+        assert_eq!(layer.strokes.as_ref().map(Vec::len), Some(1));
+
+        scene.clear_layer(&device, &queue, layer_id);
+        scene.rasterize_vector_layer(&device, &queue, layer);
+        let rasterized = scene
+            .read_layer_pixels(&device, &queue, layer_id)
+            .unwrap()
+            .unwrap();
+        assert!(painted == rasterized, "re-rasterized from the kept stroke");
+    }
+}
@@ -0,0 +1,30 @@
+use crate::{
+    app::App,
+    renderer::{egui_context::EguiContext, render_context::RenderContext},
+    resource::ResourceContext,
+    resources::{
+        document_state::DocumentState, layer_thumbnails::LayerThumbnails,
+        scene_renderer::SceneRenderer,
+    },
+    system::System,
+};
+
+/// Redraws the layers panel thumbnails of layers that changed.
+/// Runs after the frame is submitted so they show this frame's merges.
+pub struct ThumbnailSystem;
+
+impl System for ThumbnailSystem {
+    fn run(&self, app: &App) {
+        let (Some(render_ctx), Some(mut egui), Some(scene), Some(mut doc), Some(mut thumbnails)) = (
+            app.read::<RenderContext>(),
+            app.write::<EguiContext>(),
+            app.read::<SceneRenderer>(),
+            app.write::<DocumentState>(),
+            app.write::<LayerThumbnails>(),
+        ) else {
+            return;
+        };
+
+        thumbnails.update(&render_ctx, &mut egui, &scene, &mut doc);
+    }
+}
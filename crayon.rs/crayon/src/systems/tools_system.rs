@@ -2,19 +2,26 @@ use crate::app::{App, WindowResource};
 use crate::renderer::egui_context::EguiContext;
 use crate::renderer::frame_context::FrameContext;
 use crate::renderer::render_context::RenderContext;
+use crate::renderer::ui::active_layer_widget::ActiveLayerWidget;
+use crate::renderer::ui::artboard_widget::ArtboardWidget;
+use crate::renderer::ui::brush_presets_widget::BrushPresetsWidget;
 use crate::renderer::ui::brush_preview_widget::BrushPreviewWidget;
+use crate::renderer::ui::brush_settings_widget::BrushSettingsWidget;
 use crate::renderer::ui::brush_size_widget::BrushSizeWidget;
 use crate::renderer::ui::clear_screen_widget::ClearScreenWidget;
 use crate::renderer::ui::color_picker_widget::ColorPickerWidget;
 use crate::renderer::ui::drawable::Drawable;
+use crate::renderer::ui::export_widget::ExportWidget;
 use crate::renderer::ui::fps_widget::FpsWidget;
 use crate::renderer::ui::hello_widget::HelloWidget;
+use crate::renderer::ui::layers_widget::LayersWidget;
+use crate::renderer::ui::recovery_widget::RecoveryWidget;
 use crate::resource::ResourceContext;
 use crate::system::System;
 
 /// Renders Tools UI
 pub struct ToolsSystem {
-    tools: [Box<dyn Drawable>; 6],
+    tools: [Box<dyn Drawable>; 13],
 }
 
 impl ToolsSystem {
@@ -22,11 +29,19 @@ impl ToolsSystem {
         Self {
             tools: [
                 Box::new(BrushSizeWidget::new()),
+                Box::new(BrushSettingsWidget::new()),
+                Box::new(BrushPresetsWidget::new()),
                 Box::new(ColorPickerWidget::new()),
                 Box::new(ClearScreenWidget::new()),
+                Box::new(ExportWidget::new()),
                 Box::new(FpsWidget::new()),
                 Box::new(HelloWidget::new()),
                 Box::new(BrushPreviewWidget::new()),
+                Box::new(ActiveLayerWidget::new()),
+                Box::new(ArtboardWidget::new()),
+                Box::new(LayersWidget::new()),
+                // last, so it is drawn over the rest
+                Box::new(RecoveryWidget::new()),
             ],
         }
     }
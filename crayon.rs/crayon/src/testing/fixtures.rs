@@ -1,13 +1,20 @@
-use crate::document::{Artboard, ArtboardId, DOCUMENT_VERSION, Document, Layer, LayerId};
+use crate::document::{
+    Artboard, ArtboardId, BlendMode, DOCUMENT_VERSION, Document, Layer, LayerId,
+};
 
-fn blank_layer(id: u32) -> Layer {
+/// Empty raster layer named "Layer 1".
+pub fn blank_layer(id: u32) -> Layer {
     Layer {
         id: LayerId(id),
         name: "Layer 1".to_string(),
         offset: [0.0, 0.0],
         visible: true,
+        locked: false,
+        opacity: 1.0,
+        blend_mode: BlendMode::Normal,
         content_path: None,
         thumbhash: None,
+        strokes: None,
     }
 }
 
@@ -26,14 +33,14 @@ pub fn doc_two_artboards() -> Document {
                 name: "Left".to_string(),
                 position: [0.0, 0.0],
                 size: [600.0, 400.0],
-                layers: vec![blank_layer(2)],
+                layers: vec![blank_layer(2).into()],
             },
             Artboard {
                 id: ArtboardId(3),
                 name: "Right".to_string(),
                 position: [700.0, 100.0],
                 size: [400.0, 300.0],
-                layers: vec![blank_layer(4)],
+                layers: vec![blank_layer(4).into()],
             },
         ],
     }
@@ -46,6 +53,47 @@ pub fn solid_layer_pixels((width, height): (u32, u32), rgba: [u8; 4]) -> Vec<u8>
     pixels
 }
 
+/// `doc_two_artboards` hydrated on a headless GPU, with the left layer a solid `rgba` fill and the right layer blank.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn hydrated_scene(
+    rgba: [u8; 4],
+) -> (
+    wgpu::Device,
+    wgpu::Queue,
+    crate::resources::scene_renderer::SceneRenderer,
+    Document,
+) {
+    let (device, queue) = crate::testing::gpu::headless_gpu();
+    let mut scene = crate::resources::scene_renderer::SceneRenderer::new(
+        &device,
+        &queue,
+        wgpu::TextureFormat::Rgba8Unorm,
+    );
+    let document = doc_two_artboards();
+    let layer_pixels = std::collections::HashMap::from([(
+        LayerId(2),
+        solid_layer_pixels(document.artboards[0].pixel_size(), rgba),
+    )]);
+    scene.hydrate(
+        &device,
+        &queue,
+        &crate::document::loader::LoadedDocument {
+            document: document.clone(),
+            layer_pixels,
+        },
+    );
+    (device, queue, scene, document)
+}
+
+/// Fresh, empty directory under the system temp dir for tests that write files.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn scratch_dir(label: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("crayon-{label}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,7 +104,7 @@ mod tests {
         let document = doc_single_layer();
         assert_eq!(document.artboards.len(), 1);
         assert_eq!(document.artboards[0].layers.len(), 1);
-        assert!(document.artboards[0].layers[0].visible);
+        assert!(document.artboards[0].iter_layers().next().unwrap().visible);
     }
 
     #[test]
@@ -72,7 +120,7 @@ mod tests {
         for artboard in &document.artboards {
             assert!(ids.insert(artboard.id.0), "unique ids");
             assert!(artboard.id.0 < document.next_id);
-            for layer in &artboard.layers {
+            for layer in artboard.iter_layers() {
                 assert!(ids.insert(layer.id.0), "unique ids");
                 assert!(layer.id.0 < document.next_id);
             }
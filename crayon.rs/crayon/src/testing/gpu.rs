@@ -18,67 +18,15 @@ pub fn headless_gpu() -> (wgpu::Device, wgpu::Queue) {
     .expect("headless gpu: adapter refused a device with webgl2 downlevel limits")
 }
 
+/// Panicking `read_texture_rgba` for tests.
 pub fn readback_rgba(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     texture: &wgpu::Texture,
-    (width, height): (u32, u32),
+    size: (u32, u32),
 ) -> Vec<u8> {
-    let unpadded_bytes_per_row = width * 4;
-    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
-        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-
-    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Readback buffer"),
-        size: u64::from(padded_bytes_per_row) * u64::from(height),
-        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-        mapped_at_creation: false,
-    });
-
-    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-        label: Some("Readback Encoder"),
-    });
-
-    encoder.copy_texture_to_buffer(
-        texture.as_image_copy(),
-        wgpu::TexelCopyBufferInfo {
-            buffer: &buffer,
-            layout: wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(padded_bytes_per_row),
-                rows_per_image: None,
-            },
-        },
-        wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        },
-    );
-
-    queue.submit([encoder.finish()]);
-
-    let slice = buffer.slice(..);
-    let (tx, rx) = std::sync::mpsc::channel();
-    slice.map_async(wgpu::MapMode::Read, move |result| {
-        let _ = tx.send(result);
-    });
-    device
-        .poll(wgpu::PollType::wait_indefinitely())
-        .expect("readback_rgba: device poll failed");
-    rx.recv()
-        .expect("readback_rgba: map_async callback never ran")
-        .expect("readback_rgba: buffer mapping failed");
-
-    let data = slice.get_mapped_range();
-    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
-    for row in data.chunks_exact(padded_bytes_per_row as usize) {
-        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
-    }
-
-    drop(data);
-    buffer.unmap();
-    pixels
+    crate::texture::read_texture_rgba(device, queue, texture, size)
+        .expect("readback_rgba: texture readback failed")
 }
 
 #[cfg(test)]
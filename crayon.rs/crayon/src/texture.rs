@@ -56,3 +56,116 @@ impl CRTexture {
         }
     }
 }
+
+/// Copies `texture` into a mappable buffer and blocks until it is readable.
+/// Returns tightly-packed rows, with the `COPY_BYTES_PER_ROW_ALIGNMENT` padding stripped.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_texture_rgba(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: (u32, u32),
+) -> anyhow::Result<Vec<u8>> {
+    use anyhow::Context;
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+    let mut readback = TextureReadback::record(device, &mut encoder, texture, size);
+    queue.submit([encoder.finish()]);
+
+    readback.map();
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .context("device poll failed during readback")?;
+    readback.into_pixels()
+}
+
+/// A texture read back without stalling whoever records it:
+/// `record` the copy, submit it, `map` the buffer, then `into_pixels` wherever the device is polled past the copy.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct TextureReadback {
+    buffer: wgpu::Buffer,
+    size: (u32, u32),
+    padded_bytes_per_row: u32,
+    mapped: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TextureReadback {
+    /// Records the copy of `texture` into a new mappable buffer on `encoder`.
+    pub fn record(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        (width, height): (u32, u32),
+    ) -> Self {
+        let padded_bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback buffer"),
+            size: u64::from(padded_bytes_per_row) * u64::from(height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            buffer,
+            size: (width, height),
+            padded_bytes_per_row,
+            mapped: None,
+        }
+    }
+
+    /// Starts mapping the buffer, once the encoder the copy was recorded on is submitted.
+    pub fn map(&mut self) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = tx.send(result);
+            });
+        self.mapped = Some(rx);
+    }
+
+    /// Blocks until the buffer is mapped, which takes a poll of the device past the copy, and returns its rows.
+    pub fn into_pixels(self) -> anyhow::Result<Vec<u8>> {
+        use anyhow::Context;
+
+        self.mapped
+            .context("readback buffer was never mapped")?
+            .recv()
+            .context("map_async callback never ran")?
+            .context("readback buffer mapping failed")?;
+
+        let (width, height) = self.size;
+        let unpadded_bytes_per_row = width as usize * 4;
+        let data = self.buffer.slice(..).get_mapped_range();
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * height as usize);
+        for row in data.chunks_exact(self.padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+        }
+
+        drop(data);
+        self.buffer.unmap();
+        Ok(pixels)
+    }
+}